@@ -1,14 +1,10 @@
-use crate::state::STATE; use crate::events::Event;
-pub fn get_events(cursor:u128,limit:u128)->Vec<Event>{
-  STATE.with(|s|{ let s=s.borrow(); let st=std::cmp::min(cursor as usize,s.events.len()); let en=std::cmp::min(st+(limit as usize),s.events.len()); s.events[st..en].to_vec() })
+use crate::events::{self, EventV1};
+// 旧接口：按 v1 格式返回，v1 无对应类型的事件（ClaimFee / AdminAction 等）不出现；完整数据见 query_events
+// cursor 即事件 id（单调递增，旧事件搬走后不再偏移）
+pub fn get_events(cursor:u128,limit:u128)->Vec<EventV1>{
+  events::range(cursor.min(u64::MAX as u128) as u64, limit.min(u64::MAX as u128) as u64).iter().filter_map(|e| e.to_v1()).collect()
 }
 
-pub fn get_events_latest(limit: u128) -> Vec<Event> {
-    STATE.with(|s| {
-        let s = s.borrow();
-        let len = s.events.len();
-        let l = limit as usize;
-        let start = if len > l { len - l } else { 0 };
-        s.events[start..len].to_vec()
-    })
+pub fn get_events_latest(limit: u128) -> Vec<EventV1> {
+    events::latest(limit.min(u64::MAX as u128) as u64).iter().filter_map(|e| e.to_v1()).collect()
 }
@@ -3,10 +3,11 @@ use serde::{Serialize, Deserialize};
 
 use crate::{
     types::{
-        Account, AmountE6, TokenId, PoolInfo, QuoteOut, SwapArgs, SubBalance, Position, Available,
+        Account, AmountE6, TokenId, Coin, PoolInfo, QuoteOut, SwapArgs, SubBalance, Position, Available,
         StatsSnapshot, RiskParams, CyclesInfo,
     },
-    assets, explore, swap as swap_mod, positions, events::{self, Event},
+    assets, explore, swap as swap_mod, positions, events::{self, Event, EventV1, EventQuery, EventPage, EventArchiveRange},
+    error::{Error, DepositError},
 };
 
 use ic_cdk::query;
@@ -15,11 +16,33 @@ use ic_cdk::api::call::call as ic_call;
 
 use crate::state::{STATE, now, skey};
 use crate::math::stableswap;
+use crate::math::rates::{self, Rounding};
+use crate::ledger_book::{self, DustBook};
 use num_traits::cast::ToPrimitive;                   // 若缺少请添加
 
 
 
 use crate::icrc::{pool_account, derive_subaccount, icrc1_balance_of, canister_principal};
+use crate::icrc::{
+    Icrc1TransferArg as LpTransferArg, TransferError, ApproveArgs, ApproveError, AllowanceArgs, Allowance,
+    TransferFromArgs, TransferFromError, MetadataValue, StandardRecord,
+};
+use crate::icrc3::{
+    GetBlocksArgs, GetBlocksResult, GetArchivesArgs, Icrc3ArchiveInfo, Icrc3DataCertificate, SupportedBlockType,
+};
+use crate::lp_token::{self, TxCtx};
+use crate::http::{HttpRequest, HttpResponse};
+use crate::metrics;
+use crate::certified::{self, PoolInfoCertified};
+use crate::jobs::{self, JobsConfig, JobsState};
+use crate::reconcile::{self, ReconcileReport, ReconcileCorrection};
+use crate::solvency::{self, SolvencyArgs, SolvencyReport};
+use crate::pause::{self, AutoPauseConfig, PauseOp, PauseState};
+use crate::oracle::{self, OracleConfig, PriceOracle};
+use crate::pools::{self, PoolId, PoolKind, PoolState, CreatePoolArgs, POOL0};
+use crate::router::{self, RouteQuote, RouteSwapArgs};
+use crate::metapool::{self, UnderlyingQuote, UnderlyingSwapArgs};
+use crate::icrc3::{self, Icrc3Value};
 use candid::{Nat, Principal};
 
 
@@ -35,48 +58,7 @@ pub struct TokenMeta {
 }
 
 /* ---------------- 数值换算工具：外部 decimals ↔ 内部 e6 ---------------- */
-
-// === 预计算：根据池状态，确定 add 实际扣款与铸造份额（全部 e6 口径） ===
-fn compute_add_use_amounts(req_usdc_e6: u128, req_usdt_e6: u128) -> (u128, u128, u128) {
-    STATE.with(|s| {
-        let st = s.borrow();
-        let ru = st.pool.reserve_usdc;
-        let rv = st.pool.reserve_usdt;
-        let ts = st.pool.total_shares;
-
-        // 首次建池或任一侧为 0：按请求全额入池（演示/PoC 场景下最直观）
-        if ts == 0 || ru == 0 || rv == 0 {
-            let mint = req_usdc_e6.saturating_add(req_usdt_e6);
-            return (req_usdc_e6, req_usdt_e6, mint);
-        }
-
-        // 常规：按最小可铸份额 min(s1, s2) 决定“实际扣款”
-        let s1   = req_usdc_e6.saturating_mul(ts) / ru;
-        let s2   = req_usdt_e6.saturating_mul(ts) / rv;
-        let mint = s1.min(s2);
-        if mint == 0 {
-            return (0, 0, 0);
-        }
-        let use_u = mint.saturating_mul(ru) / ts;
-        let use_v = mint.saturating_mul(rv) / ts;
-        (use_u, use_v, mint)
-    })
-}
-
-// ------------------- 工具：ext(Nat) -> e6(u128) -------------------
-fn ext_to_e6(n: &Nat, decimals: u8) -> u128 {
-    let raw = n.0.to_u128().unwrap_or(0);
-    if decimals == 6 {
-        raw
-    } else if decimals < 6 {
-        raw.saturating_mul(10u128.pow((6 - decimals) as u32))
-    } else {
-        raw / 10u128.pow((decimals - 6) as u32)
-    }
-}
-
-
-fn pow10u(n: u32) -> u128 { 10u128.saturating_pow(n) }
+// 统一走 math::rates::rescale，取整方向显式给出：付给用户 / 读余额向下，向用户收取向上（偏向池子）
 
 fn nat_to_u128(n: &Nat) -> u128 {
     if let Some(v) = n.0.to_u128() { return v; }
@@ -84,35 +66,44 @@ fn nat_to_u128(n: &Nat) -> u128 {
     s.parse::<u128>().unwrap_or(0u128)
 }
 
-fn u128_to_nat(x: u128) -> Nat { Nat::from(x) }
+// ------------------- 工具：ext(Nat) -> e6(u128)，向下取整 -------------------
+pub(crate) fn ext_to_e6(n: &Nat, decimals: u8) -> u128 {
+    ext_to_e6_dust(n, decimals).0
+}
 
-/// 外部单位（decimals=dec）→ 内部 e6（向下取整，保守）
-fn ext_to_int_e6(amount_ext: &Nat, dec: u8) -> u128 {
-    let x = nat_to_u128(amount_ext);
-    if dec >= 6 {
-        let k = pow10u((dec - 6) as u32);
-        x / k
-    } else {
-        let k = pow10u((6 - dec) as u32);
-        x.saturating_mul(k)
-    }
+/// 外部单位 → 内部 e6（向下取整），同时返回截掉的尾数（外部单位）
+fn ext_to_e6_dust(amount_ext: &Nat, dec: u8) -> (u128, u128) {
+    rates::rescale(nat_to_u128(amount_ext), dec, rates::BOOK_DECIMALS, Rounding::Down)
 }
 
-/// 内部 e6 → 外部单位（Nat，跟随各自 decimals）
-fn int_e6_to_ext(amount_e6: u128, dec: u8) -> Nat {
-    if dec >= 6 {
-        let k = pow10u((dec - 6) as u32);
-        u128_to_nat(amount_e6.saturating_mul(k))
-    } else {
-        let k = pow10u((6 - dec) as u32);
-        u128_to_nat(amount_e6 / k)
-    }
+/// 内部 e6 → 外部单位（Nat，跟随各自 decimals）；decimals < 6 时才会真正取整
+fn e6_to_ext(amount_e6: u128, dec: u8, rounding: Rounding) -> Nat {
+    Nat::from(rates::rescale(amount_e6, rates::BOOK_DECIMALS, dec, rounding).0)
+}
+
+/// 池子付款成功后：e6 → 外部单位向下取整截掉的尾数记入 DustBook（e6，按账本累计）
+fn record_payout_dust(ledgers: &[(Principal, u8)], amounts_e6: &[u128]) {
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        for (&(ledger, dec), &amt) in ledgers.iter().zip(amounts_e6) {
+            let (_, dust) = rates::rescale(amt, rates::BOOK_DECIMALS, dec, Rounding::Down);
+            ledger_book::record_pool_dust(&mut st, ledger, dust);
+        }
+    })
 }
 
 /* ---------------- 代币元信息的设置/读取 ---------------- */
 
 #[ic_cdk::update]
 pub fn set_token_meta(meta: TokenMeta) {
+    let old = get_token_meta();
+    events::push(Event::ParamChange {
+        who: ic_cdk::caller().to_text(),
+        param: "token_meta".into(),
+        old: format!("{:?}", old),
+        new: format!("{:?}", meta),
+        ts: now(),
+    });
     STATE.with(|s| {
         let mut st = s.borrow_mut();
         st.ckusdc = Some(meta.ckusdc);
@@ -165,8 +156,8 @@ pub async fn get_pool_reserves_live() -> PoolReserves {
             return STATE.with(|s| {
                 let st = s.borrow();
                 PoolReserves {
-                    usdc: int_e6_to_ext(st.pool.reserve_usdc, st.dec_usdc.unwrap_or(6)),
-                    usdt: int_e6_to_ext(st.pool.reserve_usdt, st.dec_usdt.unwrap_or(6)),
+                    usdc: e6_to_ext(st.pool.reserve_usdc, st.dec_usdc.unwrap_or(6), Rounding::Down),
+                    usdt: e6_to_ext(st.pool.reserve_usdt, st.dec_usdt.unwrap_or(6), Rounding::Down),
                 }
             });
         }
@@ -184,92 +175,225 @@ pub async fn get_pool_reserves_live() -> PoolReserves {
         _ => STATE.with(|s| {
             let st = s.borrow();
             PoolReserves {
-                usdc: int_e6_to_ext(st.pool.reserve_usdc, du),
-                usdt: int_e6_to_ext(st.pool.reserve_usdt, dt),
+                usdc: e6_to_ext(st.pool.reserve_usdc, du, Rounding::Down),
+                usdt: e6_to_ext(st.pool.reserve_usdt, dt, Rounding::Down),
             }
         }),
     }
 }
 
 
-/// 管理员：从 Ledger 实时余额对齐内部池储备，并写回 state.pool.* （单位：e6）
+/* ---------------- 对账（见 reconcile/mod.rs；不再改写储备 / 缩放份额） ---------------- */
+
+fn require_controller() -> std::result::Result<(), Error> {
+    let caller = ic_cdk::caller();
+    if ic_cdk::api::is_controller(&caller) { Ok(()) } else { Err(Error::Unauthorized(caller)) }
+}
+
+/// 管理员：读取 Ledger 实时余额，生成对账报告（只读，不改任何账），保存为最近一份报告并写 Reconcile 事件
 #[ic_cdk::update]
 pub async fn admin_reconcile_pool_from_live() -> TextResult {
-    // 1) 读取 token meta
-    let meta = match get_token_meta() {
-        Some(m) => m,
-        None => return TextResult::Err("token meta not set".into()),
-    };
+    if let Err(e) = require_controller() { return TextResult::Err(e); }
+    match reconcile::run_report(ic_cdk::caller().to_text(), now()).await {
+        Ok(r) => TextResult::Ok(reconcile::summary(&r)),
+        Err(e) => TextResult::Err(e),
+    }
+}
 
-    // 2) 读取 POOL 子账户的 live 余额
-    let pool_acct = get_pool_account("USDC_USDT".to_string());
-    let (bal_u_res, bal_v_res) = futures::future::join(
-        icrc1_balance_of(meta.ckusdc, pool_acct.clone()),
-        icrc1_balance_of(meta.ckusdt, pool_acct.clone()),
-    ).await;
+/// 已停用：旧实现会把 total_shares 重置为 usdc+usdt 并等比缩放全部 LP。请改用对账报告 + 显式更正
+#[ic_cdk::update]
+pub fn admin_reconcile_from_internal() -> TextResult {
+    TextResult::Err(Error::Internal(
+        "share rescaling removed; use admin_reconcile_pool_from_live and admin_apply_reconcile_correction".into(),
+    ))
+}
 
-    let bal_u = match bal_u_res {
-        Ok(n) => n,
-        Err(e) => return TextResult::Err(format!("ckUSDC balance err: {e}")),
-    };
-    let bal_v = match bal_v_res {
-        Ok(n) => n,
-        Err(e) => return TextResult::Err(format!("ckUSDT balance err: {e}")),
+#[ic_cdk::query]
+pub fn get_last_reconcile_report() -> Option<ReconcileReport> {
+    STATE.with(|s| s.borrow().reconcile.as_ref().and_then(|r| r.last_report.clone()))
+}
+
+/// 管理员：按最近一份报告执行一笔更正（额度以报告为上限），写 Reconcile 事件留痕
+#[ic_cdk::update]
+pub fn admin_apply_reconcile_correction(c: ReconcileCorrection) -> TextResult {
+    if let Err(e) = require_controller() { return TextResult::Err(e); }
+    let res = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let before = events::pool_snapshot(&st);
+        reconcile::apply_correction(&mut st, &c, now()).map(|_| (before, events::pool_snapshot(&st)))
+    });
+    let (before, after) = match res {
+        Ok(t) => t,
+        Err(e) => return TextResult::Err(e),
     };
+    certified::certify();
+    events::push(Event::Reconcile {
+        who: ic_cdk::caller().to_text(),
+        before,
+        after,
+        detail: format!("correction pool {} {:?} {:?} amount_e6={} memo={}", c.pool.unwrap_or(POOL0), c.token, c.kind, c.amount_e6, c.memo),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
+}
 
-    // 3) 外部最小单位 -> 内部 e6，并计算 new_total
-    let usdc_e6 = ext_to_e6(&bal_u, meta.dec_usdc);
-    let usdt_e6 = ext_to_e6(&bal_v, meta.dec_usdt);
-    let new_total = usdc_e6.saturating_add(usdt_e6);
+/* ---------------- 偿付能力（见 solvency/mod.rs） ---------------- */
 
-    // ★ 4) 先按“旧 total_shares”把 user_shares 等比缩放到 new_total（函数内部会写回 total_shares=new_total）
-    positions::admin_rescale_all_shares(new_total);
+/// 复合查询：内部负债 vs 池子子账户 + 本页用户派生子账户的 live 余额；翻页用 next_cursor
+#[ic_cdk::query(composite = true)]
+pub async fn get_solvency_report(args: SolvencyArgs) -> SolvencyReportResult {
+    match solvency::report(args, now()).await {
+        Ok(r) => SolvencyReportResult::Ok(r),
+        Err(e) => SolvencyReportResult::Err(e),
+    }
+}
 
-    // ★ 5) 再写回池子的 internal 储备（避免覆盖上一步已设置的 total_shares）
-    STATE.with(|s| {
+/// 管理员：同上，并把报告写成 Reconcile 事件留档
+#[ic_cdk::update]
+pub async fn admin_record_solvency_report(args: SolvencyArgs) -> SolvencyReportResult {
+    let _m = metrics::measure("admin_record_solvency_report");
+    if let Err(e) = require_controller() { return SolvencyReportResult::Err(e); }
+    match solvency::record(ic_cdk::caller().to_text(), args, now()).await {
+        Ok(r) => SolvencyReportResult::Ok(r),
+        Err(e) => SolvencyReportResult::Err(e),
+    }
+}
+
+/* ---------------- 动态费率 ---------------- */
+
+/// 控制者：设置 offpeg_fee_multiplier（1e10 = 1x；≤ 1x 即固定费率，None 清除）
+#[ic_cdk::update]
+pub fn admin_set_offpeg_fee_multiplier(multiplier: Option<u64>) -> TextResult {
+    if let Err(e) = require_controller() { return TextResult::Err(e); }
+    let old = STATE.with(|s| std::mem::replace(&mut s.borrow_mut().pool.offpeg_fee_multiplier, multiplier));
+    events::push(Event::ParamChange {
+        who: ic_cdk::caller().to_text(),
+        param: "offpeg_fee_multiplier".into(),
+        old: format!("{:?}", old),
+        new: format!("{:?}", multiplier),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
+}
+
+/* ---------------- 紧急暂停（见 pause.rs） ---------------- */
+
+#[ic_cdk::query]
+pub fn get_pause_state() -> PauseState {
+    STATE.with(|s| pause::state(&s.borrow()))
+}
+
+/// 暂停角色 / 控制者：开关单项操作；退出通道（remove_liquidity / withdraw）仅控制者可关
+#[ic_cdk::update]
+pub fn admin_set_pause(op: PauseOp, paused: bool, reason: String) -> TextResult {
+    let caller = ic_cdk::caller();
+    let is_controller = ic_cdk::api::is_controller(&caller);
+    let ev = STATE.with(|s| {
         let mut st = s.borrow_mut();
-        st.pool.reserve_usdc = usdc_e6;
-        st.pool.reserve_usdt = usdt_e6;
+        if !pause::can_set(&st, caller, is_controller, op) { return Err(Error::Unauthorized(caller)); }
+        Ok(pause::set(&mut st, &[op], paused, caller.to_text(), reason, now()))
     });
+    match ev {
+        Ok(ev) => { if let Some(ev) = ev { events::push(ev); } TextResult::Ok("ok".into()) }
+        Err(e) => TextResult::Err(e),
+    }
+}
 
-    TextResult::Ok(format!(
-        "ok: live_e6 {{usdc:{}, usdt:{}}}, total_shares={}",
-        usdc_e6, usdt_e6, new_total
-    ))
+/// 暂停角色 / 控制者：紧急暂停（除退出通道外全部关闭）
+#[ic_cdk::update]
+pub fn admin_pause_all(reason: String) -> TextResult {
+    let caller = ic_cdk::caller();
+    let is_controller = ic_cdk::api::is_controller(&caller);
+    let ev = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        if !pause::can_set(&st, caller, is_controller, PauseOp::Swap) { return Err(Error::Unauthorized(caller)); }
+        Ok(pause::pause_all(&mut st, caller.to_text(), reason, now()))
+    });
+    match ev {
+        Ok(ev) => { if let Some(ev) = ev { events::push(ev); } TextResult::Ok("ok".into()) }
+        Err(e) => TextResult::Err(e),
+    }
 }
 
+/// 控制者：设置暂停角色与自动暂停阈值
 #[ic_cdk::update]
-pub fn admin_reconcile_from_internal() -> TextResult {
-    // 按当前 internal 储备计算 total，并对 LP 份额做等比缩放
-    let (u, v) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.pool.reserve_usdc, st.pool.reserve_usdt)
+pub fn admin_set_pause_config(pausers: Vec<Principal>, auto: AutoPauseConfig) -> TextResult {
+    if let Err(e) = require_controller() { return TextResult::Err(e); }
+    let (old, new) = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let p = st.pause.get_or_insert_with(Default::default);
+        let old = format!("pausers={:?} auto={:?}", p.pausers, p.auto);
+        p.pausers = pausers;
+        p.auto = auto;
+        (old, format!("pausers={:?} auto={:?}", p.pausers, p.auto))
     });
-    let new_total = u.saturating_add(v);
-    positions::admin_rescale_all_shares(new_total);
-    TextResult::Ok(format!("ok: internal_e6 total_shares={}", new_total))
+    events::push(Event::ParamChange { who: ic_cdk::caller().to_text(), param: "pause".into(), old, new, ts: now() });
+    TextResult::Ok("ok".into())
 }
 
+/* ---------------- 价格预言机（见 oracle.rs） ---------------- */
 
+/// pool 0（USDC/USDT）的 EMA 价格；其它池没有预言机，也不受脱锚保护
+#[ic_cdk::query]
+pub fn get_price_oracle() -> PriceOracle {
+    STATE.with(|s| oracle::price_oracle(&s.borrow(), now()))
+}
+
+/// pool 0 内部储备下的现价（1 USDC 可换 USDT，e6，不含手续费）；空池为 None
+#[ic_cdk::query]
+pub fn get_spot_price() -> Option<u128> {
+    STATE.with(|s| oracle::spot_price_e6(&s.borrow()))
+}
+
+/// 控制者：修改 EMA 时间常数与脱锚保护阈值
+#[ic_cdk::update]
+pub fn admin_set_oracle_config(cfg: OracleConfig) -> TextResult {
+    if let Err(e) = require_controller() { return TextResult::Err(e); }
+    let old = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let o = st.oracle.get_or_insert_with(Default::default);
+        std::mem::replace(&mut o.config, cfg.clone())
+    });
+    events::push(Event::ParamChange {
+        who: ic_cdk::caller().to_text(),
+        param: "oracle".into(),
+        old: format!("{:?}", old),
+        new: format!("{:?}", cfg),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
+}
 
 /* ---------------- 通用 Result ---------------- */
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum TextResult {
     #[serde(rename = "ok")] Ok(String),
-    #[serde(rename = "err")] Err(String),
+    #[serde(rename = "err")] Err(Error),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum SolvencyReportResult {
+    #[serde(rename = "ok")] Ok(SolvencyReport),
+    #[serde(rename = "err")] Err(Error),
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum TxResultNat {
     #[serde(rename = "ok")]  Ok(Nat),
-    #[serde(rename = "err")] Err(String),
+    #[serde(rename = "err")] Err(Error),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum DepositResult {
+    #[serde(rename = "ok")]  Ok(Nat),
+    #[serde(rename = "err")] Err(DepositError),
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum PositionResult {
     #[serde(rename = "ok")] Ok(Position),
-    #[serde(rename = "err")] Err(String),
+    #[serde(rename = "err")] Err(Error),
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -278,11 +402,7 @@ pub struct TwoAmounts { pub usdc: AmountE6, pub usdt: AmountE6 }
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum TwoAmountsResult {
     #[serde(rename = "ok")] Ok(TwoAmounts),
-    #[serde(rename = "err")] Err(String),
-}
-
-fn err<T>(e: impl core::fmt::Debug) -> std::result::Result<T, String> {
-    Err(format!("{:?}", e))
+    #[serde(rename = "err")] Err(Error),
 }
 
 /* ---------------- Assets（保留：资产页依赖） ---------------- */
@@ -320,6 +440,10 @@ pub fn get_user_sub_balances(account: Account) -> Vec<SubBalance> {
 #[ic_cdk::query]
 pub fn get_pool_info() -> PoolInfo { explore::get_pool_info() }
 
+/// 带证书与 witness 的池子信息（客户端用 vaultpair_verify 校验）；非 query 调用返回 null
+#[ic_cdk::query]
+pub fn get_pool_info_certified() -> Option<PoolInfoCertified> { certified::pool_info_certified() }
+
 /// 演示/灌池入口 —— 为避免线上干扰，这里改为“无副作用”的空实现：返回当前池信息，不再改写任何状态。
 #[ic_cdk::update]
 pub fn seed_pool_demo(_usdc: AmountE6, _usdt: AmountE6) -> PoolInfo {
@@ -334,310 +458,545 @@ pub struct SwapOk { pub dy_e6: AmountE6 }
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub enum StdResultSwap {
     #[serde(rename = "ok")] Ok(SwapOk),
-    #[serde(rename = "err")] Err(String),
+    #[serde(rename = "err")] Err(Error),
 }
 
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum RouteResult {
+    #[serde(rename = "ok")] Ok(RouteQuote),
+    #[serde(rename = "err")] Err(Error),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum UnderlyingResult {
+    #[serde(rename = "ok")] Ok(UnderlyingQuote),
+    #[serde(rename = "err")] Err(Error),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum QuoteResult {
+    #[serde(rename = "ok")] Ok(QuoteOut),
+    #[serde(rename = "err")] Err(Error),
+}
+
+impl From<crate::error::Result<QuoteOut>> for QuoteResult {
+    fn from(r: crate::error::Result<QuoteOut>) -> Self {
+        match r { Ok(q) => QuoteResult::Ok(q), Err(e) => QuoteResult::Err(e) }
+    }
+}
+
+/// pool 缺省 = 0 号 USDC/USDT 池（swap / swap_live 用 SwapArgs.pool）
 #[ic_cdk::query]
-pub fn quote(token_in: TokenId, token_out: TokenId, dx_e6: AmountE6) -> QuoteOut {
-    swap_mod::quote(token_in, token_out, dx_e6)
+pub fn quote(token_in: Coin, token_out: Coin, dx_e6: AmountE6, pool: Option<PoolId>) -> QuoteResult {
+    swap_mod::quote(pool.unwrap_or(POOL0), token_in, token_out, dx_e6).into()
 }
 
 #[ic_cdk::update]
 pub fn swap(args: SwapArgs) -> StdResultSwap {
+    let _m = metrics::measure("swap");
+    if let Err(e) = pause::check(PauseOp::Swap) { return StdResultSwap::Err(e); }
     match swap_mod::swap(args) {
         Ok(big) => {
+            icrc3::commit();
+            pause::after_swap();
+            metrics::swap_outcome("swap", None);
             let n = big.to_u128().unwrap_or(0);
             StdResultSwap::Ok(SwapOk { dy_e6: n })
         }
-        Err(e) => StdResultSwap::Err(format!("{e:?}")),
+        Err(e) => {
+            metrics::swap_outcome("swap", Some(&e));
+            StdResultSwap::Err(e)
+        }
     }
 }
 
-/* ---------------- Liquidity ---------------- */
-
+/// 多跳路由报价（见 router）：max_hops 缺省 3
 #[ic_cdk::query]
-pub fn get_user_position(account: Account) -> Position {
-    let shares = positions::get_user_position(account);
-    Position { shares }
+pub fn quote_route(token_in: Coin, token_out: Coin, dx_e6: AmountE6, max_hops: Option<u8>) -> RouteResult {
+    match router::quote_route(token_in, token_out, dx_e6, max_hops) {
+        Ok(q) => RouteResult::Ok(q), Err(e) => RouteResult::Err(e),
+    }
 }
 
-/// 只读预览：可领取手续费
+/// 按最优路径在内账逐跳成交，只在终点校验 min_out；任何一跳不成则整笔不成
+#[ic_cdk::update]
+pub fn swap_route(args: RouteSwapArgs) -> RouteResult {
+    let _m = metrics::measure("swap_route");
+    if let Err(e) = pause::check(PauseOp::Swap) { return RouteResult::Err(e); }
+    match router::swap_route(args, ic_cdk::caller()) {
+        Ok(q) => {
+            icrc3::commit();
+            pause::after_swap();
+            metrics::swap_outcome("swap_route", None);
+            RouteResult::Ok(q)
+        }
+        Err(e) => {
+            metrics::swap_outcome("swap_route", Some(&e));
+            RouteResult::Err(e)
+        }
+    }
+}
+
+/// Metapool 与基础池（0 号池）币之间的报价（见 metapool）：新币 ↔ USDC / USDT，或 USDC ↔ USDT
 #[ic_cdk::query]
-pub fn get_unclaimed_fee(account: Account) -> TwoAmounts {
-    match positions::preview_claim_fee(account) {
-        Ok((u, v)) => TwoAmounts { usdc: u, usdt: v },
-        Err(_)     => TwoAmounts { usdc: 0, usdt: 0 },
+pub fn quote_underlying(pool: PoolId, token_in: Coin, token_out: Coin, dx_e6: AmountE6) -> UnderlyingResult {
+    match metapool::quote_underlying(pool, token_in, token_out, dx_e6) {
+        Ok(q) => UnderlyingResult::Ok(q), Err(e) => UnderlyingResult::Err(e),
     }
 }
 
+/// 经基础池一次成交：中间的 LP 只在内账经过，min_dy 只在终点校验；任何一步不成则整笔不成。
+/// 基础池一步另受 add_liquidity / remove_liquidity 暂停开关与脱锚保护约束（见 metapool::execute）
 #[ic_cdk::update]
-pub async fn add_liquidity(account: Account, usdc: AmountE6, usdt: AmountE6) -> PositionResult {
-    // 1) 依据池状态计算实际扣款（多的一侧不扣）
-    let (use_u_e6, use_v_e6, _mint) = compute_add_use_amounts(usdc, usdt);
-    if use_u_e6 == 0 && use_v_e6 == 0 {
-        return PositionResult::Err("amount too small".into());
+pub fn exchange_underlying(args: UnderlyingSwapArgs) -> UnderlyingResult {
+    let _m = metrics::measure("exchange_underlying");
+    if let Err(e) = pause::check(PauseOp::Swap) { return UnderlyingResult::Err(e); }
+    match metapool::exchange_underlying(args, ic_cdk::caller()) {
+        Ok(q) => {
+            icrc3::commit();
+            pause::after_swap();
+            metrics::swap_outcome("exchange_underlying", None);
+            UnderlyingResult::Ok(q)
+        }
+        Err(e) => {
+            metrics::swap_outcome("exchange_underlying", Some(&e));
+            UnderlyingResult::Err(e)
+        }
     }
+}
 
-    // 2) 取元信息与账户
-    let (ckusdc, ckusdt, du, dt) = STATE.with(|s| {
+/* ---------------- Liquidity ---------------- */
+// 以下接口末尾的 pool 参数为池子编号（见 pools）；缺省 = 0 号 USDC/USDT 池。
+// 金额参数 / 返回的 usdc、usdt 字段沿用旧名，非 0 号池按该池 coins 顺序对应第 0 / 1 个币。
+// Metapool 的 LP 一侧不走链上转账（pools::onchain_amounts 置 0），由 positions 在内账里划转份额。
+
+#[ic_cdk::query]
+pub fn get_user_position(account: Account, pool: Option<PoolId>) -> Position {
+    let shares = positions::get_user_position(account, pool.unwrap_or(POOL0));
+    Position { shares }
+}
+
+/// 只读预览：可领取手续费
+#[ic_cdk::query]
+pub fn get_unclaimed_fee(account: Account, pool: Option<PoolId>) -> TwoAmounts {
+    let [usdc, usdt] = positions::preview_claim_fee(account, pool.unwrap_or(POOL0));
+    TwoAmounts { usdc, usdt }
+}
+
+/// 池子两个币的账本与精度（按 coins 顺序）
+fn pool_ledgers(id: PoolId) -> Result<(PoolState, [(Principal, u8); 2]), Error> {
+    STATE.with(|s| {
         let st = s.borrow();
-        (st.ckusdc, st.ckusdt, st.dec_usdc, st.dec_usdt)
-    });
-    let (ckusdc, ckusdt, du, dt) = match (ckusdc, ckusdt, du, dt) {
-        (Some(a), Some(b), Some(du), Some(dt)) => (a, b, du, dt),
-        _ => return PositionResult::Err("token meta not set".into()),
-    };
+        let p = pools::load(&st, id)?;
+        let l0 = pools::token_ledger(&st, p.coins[0]).ok_or(Error::TokenMetaNotSet)?;
+        let l1 = pools::token_ledger(&st, p.coins[1]).ok_or(Error::TokenMetaNotSet)?;
+        Ok((p, [l0, l1]))
+    })
+}
 
-    let pool_acc = get_pool_account("USDC_USDT".to_string());
-    let user_sub = derive_subaccount(account.owner).to_vec();
-    let from_user = Some(user_sub.clone());
-
-    // 3) 先执行实际扣款的链上转账：用户子 → 池子子
-    if use_u_e6 > 0 {
-        let arg_u = Icrc1TransferArg {
-            from_subaccount: from_user.clone(),
-            to: pool_acc.clone(),
-            amount: int_e6_to_ext(use_u_e6, du),
+/// 按 coins 顺序逐笔转账（跳过 0），返回块号；任一笔失败即停止，返回 (已成功的块号, 错误)。
+/// rounding 为 e6 → 外部单位的取整方向：池子付款 Down，向用户收取 Up
+async fn transfer_pair(
+    ledgers: &[(Principal, u8); 2], from_subaccount: Option<Vec<u8>>, to: &Account, amounts: [u128; 2], rounding: Rounding,
+) -> (Vec<Nat>, Option<Error>) {
+    let mut blocks = Vec::new();
+    for (&(ledger, dec), amt) in ledgers.iter().zip(amounts) {
+        if amt == 0 { continue; }
+        let arg = Icrc1TransferArg {
+            from_subaccount: from_subaccount.clone(),
+            to: to.clone(),
+            amount: e6_to_ext(amt, dec, rounding),
             fee: None, memo: None, created_at_time: None,
         };
-        if let Err(e) = do_icrc1_transfer(ckusdc, arg_u).await {
-            return PositionResult::Err(format!("ckUSDC transfer failed: {e}"));
+        match do_icrc1_transfer(ledger, arg).await {
+            Ok(b) => blocks.push(b),
+            Err(e) => return (blocks, Some(e)),
         }
     }
-    if use_v_e6 > 0 {
-        let arg_v = Icrc1TransferArg {
-            from_subaccount: from_user.clone(),
-            to: pool_acc.clone(),
-            amount: int_e6_to_ext(use_v_e6, dt),
-            fee: None, memo: None, created_at_time: None,
-        };
-        if let Err(e) = do_icrc1_transfer(ckusdt, arg_v).await {
-            // 回滚已成功的 USDC 扣款（尽力而为）
-            if use_u_e6 > 0 {
-                let back_to_user = Account {
-                    owner: canister_principal(),
-                    subaccount: Some(user_sub.clone()),
-                };
-                let _ = do_icrc1_transfer(
-                    ckusdc,
-                    Icrc1TransferArg {
-                        from_subaccount: pool_acc.subaccount.clone(),
-                        to: back_to_user,
-                        amount: int_e6_to_ext(use_u_e6, du),
-                        fee: None, memo: None, created_at_time: None,
-                    },
-                ).await;
-            }
-            return PositionResult::Err(format!("ckUSDT transfer failed: {e}"));
-        }
+    (blocks, None)
+}
+
+/// transfer_pair 失败时：已成功的笔数对应的金额（按 coins 顺序跳过 0 计数）
+fn done_amounts(amounts: [u128; 2], n_done: usize) -> [u128; 2] {
+    let mut left = n_done;
+    amounts.map(|a| if a > 0 && left > 0 { left -= 1; a } else { 0 })
+}
+
+#[ic_cdk::update]
+pub async fn add_liquidity(account: Account, usdc: AmountE6, usdt: AmountE6, pool: Option<PoolId>) -> PositionResult {
+    let _m = metrics::measure("add_liquidity");
+    if let Err(e) = pause::check(PauseOp::AddLiquidity) { return PositionResult::Err(e); }
+    match do_add_liquidity(account, [usdc, usdt], pool.unwrap_or(POOL0)).await {
+        Ok(shares) => PositionResult::Ok(Position { shares }),
+        Err(e) => PositionResult::Err(e),
     }
+}
 
-    // 4) 铸造 shares（内部账本）
-    match positions::add_liquidity(account.clone(), use_u_e6, use_v_e6) {
-        Ok(shares) => {
-            // 异步刷新可用额缓存（不阻塞本次返回）
-            ic_cdk::spawn(async move { let _ = do_refresh_available_for(account.owner).await; });
-            let who = format!("{}", account.owner.to_text());
-            events::push(Event::AddLiq { who, usdc: use_u_e6, usdt: use_v_e6, shares, ts: now() });
-            PositionResult::Ok(Position { shares })
-        }
+async fn do_add_liquidity(account: Account, req: [AmountE6; 2], id: PoolId) -> Result<u128, Error> {
+    // 1) 依据池状态计算实际扣款（多的一侧不扣）
+    let (p, ledgers) = pool_ledgers(id)?;
+    let (used, _mint) = positions::mint_amounts(&p, req);
+    if used == [0, 0] { return Err(Error::InvalidInput); }
+    let onchain = pools::onchain_amounts(&p, used);
+
+    let pool_acc = pools::account(id);
+    let user_sub = derive_subaccount(account.owner).to_vec();
+    let back_to_user = Account { owner: canister_principal(), subaccount: Some(user_sub.clone()) };
+
+    // 2) 先执行实际扣款的链上转账：用户子 → 池子子；失败则退回已成功的部分（尽力而为）
+    let (ledger_blocks, err) = transfer_pair(&ledgers, Some(user_sub.clone()), &pool_acc, onchain, Rounding::Up).await;
+    if let Some(e) = err {
+        let done = done_amounts(onchain, ledger_blocks.len());
+        let _ = transfer_pair(&ledgers, pool_acc.subaccount.clone(), &back_to_user, done, Rounding::Up).await;
+        return Err(e);
+    }
+
+    // 3) 铸造 shares（内部账本）；内部失败则回滚链上扣款（尽力而为）
+    let shares = match STATE.with(|s| positions::add_liquidity(&mut s.borrow_mut(), id, &account, used, now())) {
+        Ok(x) => x,
         Err(e) => {
-            // 内部失败则回滚链上扣款（尽力而为）
-            let back_to_user = Account {
-                owner: canister_principal(),
-                subaccount: Some(user_sub.clone()),
-            };
-            if use_u_e6 > 0 {
-                let _ = do_icrc1_transfer(
-                    ckusdc,
-                    Icrc1TransferArg {
-                        from_subaccount: pool_acc.subaccount.clone(),
-                        to: back_to_user.clone(),
-                        amount: int_e6_to_ext(use_u_e6, du),
-                        fee: None, memo: None, created_at_time: None,
-                    },
-                ).await;
-            }
-            if use_v_e6 > 0 {
-                let _ = do_icrc1_transfer(
-                    ckusdt,
-                    Icrc1TransferArg {
-                        from_subaccount: pool_acc.subaccount.clone(),
-                        to: back_to_user,
-                        amount: int_e6_to_ext(use_v_e6, dt),
-                        fee: None, memo: None, created_at_time: None,
-                    },
-                ).await;
-            }
-            PositionResult::Err(format!("{:?}", e))
+            let _ = transfer_pair(&ledgers, pool_acc.subaccount.clone(), &back_to_user, onchain, Rounding::Up).await;
+            return Err(e);
         }
-    }
+    };
+
+    // Metapool 收进的 LP 记 1xfer（用户 → 池子账户）
+    STATE.with(|s| pools::log_lp_moves(&mut s.borrow_mut(), &p, account.owner, used, true));
+    if id != POOL0 { icrc3::commit(); }
+
+    // 异步刷新可用额缓存（不阻塞本次返回）
+    ic_cdk::spawn(async move { let _ = do_refresh_available_for(account.owner).await; });
+    // LP 份额代币（ICRC-1/2）只对应 0 号池，其他池子不写 mint 区块
+    let block = (id == POOL0).then(|| icrc3::log("1mint", vec![
+        ("to", Icrc3Value::account(&account)),
+        ("amt", Icrc3Value::nat(shares)),
+        ("usdc", Icrc3Value::nat(used[0])),
+        ("usdt", Icrc3Value::nat(used[1])),
+    ]));
+    events::push(Event::AddLiq {
+        who: account.owner.to_text(), sub: account.subaccount.clone(),
+        usdc: used[0], usdt: used[1], shares,
+        pool: STATE.with(|s| pools::snapshot(&s.borrow(), id)), pool_id: Some(id),
+        ledger_blocks, icrc3_block: block, ts: now(),
+    });
+    Ok(shares)
 }
 
 
 #[ic_cdk::update]
-pub async fn remove_liquidity(account: Account, shares: AmountE6) -> TwoAmountsResult {
-    if shares == 0 {
-        return TwoAmountsResult::Err("shares is zero".into());
+pub async fn remove_liquidity(account: Account, shares: AmountE6, pool: Option<PoolId>) -> TwoAmountsResult {
+    let _m = metrics::measure("remove_liquidity");
+    if let Err(e) = pause::check(PauseOp::RemoveLiquidity) { return TwoAmountsResult::Err(e); }
+    match do_remove_liquidity(account, shares, pool.unwrap_or(POOL0)).await {
+        Ok([usdc, usdt]) => TwoAmountsResult::Ok(TwoAmounts { usdc, usdt }),
+        Err(e) => TwoAmountsResult::Err(e),
     }
+}
 
-    // 1) 先按内部规则扣减 shares，得到应退 ckUSDC/ckUSDT（均 e6 口径）
-    let (out_u_e6, out_v_e6) = match positions::remove_liquidity(account.clone(), shares) {
-        Ok((u, v)) => (u, v),
-        Err(e) => return TwoAmountsResult::Err(format!("{:?}", e)),
-    };
+async fn do_remove_liquidity(account: Account, shares: AmountE6, id: PoolId) -> Result<[AmountE6; 2], Error> {
+    if shares == 0 { return Err(Error::InvalidInput); }
+    let (p, ledgers) = pool_ledgers(id)?;
 
-    // 2) 元信息 / 账户
-    let (ckusdc, ckusdt, du, dt) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.ckusdc, st.ckusdt, st.dec_usdc, st.dec_usdt)
-    });
-    let (ckusdc, ckusdt, du, dt) = match (ckusdc, ckusdt, du, dt) {
-        (Some(a), Some(b), Some(du), Some(dt)) => (a, b, du, dt),
-        _ => return TwoAmountsResult::Err("token meta not set".into()),
-    };
+    // 1) 先按内部规则扣减 shares，得到应退数量（e6 口径，按 coins 顺序）
+    let out = STATE.with(|s| positions::remove_liquidity(&mut s.borrow_mut(), id, &account, shares))?;
 
-    let pool_acc = get_pool_account("USDC_USDT".to_string());
+    let pool_acc = pools::account(id);
     let user_sub = derive_subaccount(account.owner).to_vec();
     let to_user = Account { owner: canister_principal(), subaccount: Some(user_sub.clone()) };
 
-    // 3) 链上实际转回：池子子 → 用户子
-    // 先转 ckUSDC
-    if out_u_e6 > 0 {
-        let arg_u = Icrc1TransferArg {
-            from_subaccount: pool_acc.subaccount.clone(),
-            to: to_user.clone(),
-            amount: int_e6_to_ext(out_u_e6, du),
-            fee: None, memo: None, created_at_time: None,
-        };
-        if let Err(e) = do_icrc1_transfer(ckusdc, arg_u).await {
-            // 回滚 shares（把刚刚的 remove 复原）
-            let _ = positions::add_liquidity(account.clone(), out_u_e6, out_v_e6);
-            return TwoAmountsResult::Err(format!("ckUSDC transfer back failed: {e}"));
-        }
-    }
-    // 再转 ckUSDT
-    if out_v_e6 > 0 {
-        let arg_v = Icrc1TransferArg {
-            from_subaccount: pool_acc.subaccount.clone(),
-            to: to_user.clone(),
-            amount: int_e6_to_ext(out_v_e6, dt),
-            fee: None, memo: None, created_at_time: None,
-        };
-        if let Err(e) = do_icrc1_transfer(ckusdt, arg_v).await {
-            // 尝试把已转出的 USDC 挪回池子、并复原 shares（尽力而为）
-            if out_u_e6 > 0 {
-                let _ = do_icrc1_transfer(
-                    ckusdc,
-                    Icrc1TransferArg {
-                        from_subaccount: to_user.subaccount.clone(),
-                        to: pool_acc.clone(),
-                        amount: int_e6_to_ext(out_u_e6, du),
-                        fee: None, memo: None, created_at_time: None,
-                    },
-                ).await;
-            }
-            let _ = positions::add_liquidity(account.clone(), out_u_e6, out_v_e6);
-            let who = format!("{}", account.owner.to_text());
-            events::push(Event::RemoveLiq { who, shares, usdc: out_u_e6, usdt: out_v_e6, ts: now() });
-
-            return TwoAmountsResult::Err(format!("ckUSDT transfer back failed: {e}"));
-        }
+    // 2) 链上实际转回：池子子 → 用户子；失败则把已转出的挪回池子、并复原 shares（尽力而为）
+    let onchain = pools::onchain_amounts(&p, out);
+    let (ledger_blocks, err) = transfer_pair(&ledgers, pool_acc.subaccount.clone(), &to_user, onchain, Rounding::Down).await;
+    if let Some(e) = err {
+        let done = done_amounts(onchain, ledger_blocks.len());
+        let _ = transfer_pair(&ledgers, Some(user_sub), &pool_acc, done, Rounding::Down).await;
+        let _ = STATE.with(|s| positions::add_liquidity(&mut s.borrow_mut(), id, &account, out, now()));
+        return Err(e);
     }
+    record_payout_dust(&ledgers, &onchain);
+    STATE.with(|s| pools::log_lp_moves(&mut s.borrow_mut(), &p, account.owner, out, false));
+    if id != POOL0 { icrc3::commit(); }
+
+    let block = (id == POOL0).then(|| icrc3::log("1burn", vec![
+        ("from", Icrc3Value::account(&account)),
+        ("amt", Icrc3Value::nat(shares)),
+        ("usdc", Icrc3Value::nat(out[0])),
+        ("usdt", Icrc3Value::nat(out[1])),
+    ]));
+    events::push(Event::RemoveLiq {
+        who: account.owner.to_text(), sub: account.subaccount.clone(),
+        shares, usdc: out[0], usdt: out[1],
+        pool: STATE.with(|s| pools::snapshot(&s.borrow(), id)), pool_id: Some(id),
+        ledger_blocks, icrc3_block: block, ts: now(),
+    });
 
-    // 4) 刷新 live 可用额度缓存（异步）
+    // 3) 刷新 live 可用额度缓存（异步）
     ic_cdk::spawn(async move { let _ = do_refresh_available_for(account.owner).await; });
-
-    TwoAmountsResult::Ok(TwoAmounts { usdc: out_u_e6, usdt: out_v_e6 })
+    Ok(out)
 }
 
 
 // ------------------- 真实发币的 Claim Fee（POOL → 用户子账户） -------------------
 #[ic_cdk::update]
-pub async fn claim_fee(acct: Account) -> Result<(AmountE6, AmountE6), String> {
-    // 0) 预览可领取（e6）——注意 preview_claim_fee 返回 Result
-    let (usdc_e6, usdt_e6) = positions::preview_claim_fee(acct.clone())
-        .map_err(|e| format!("{:?}", e))?;
-
-    if usdc_e6 == 0 && usdt_e6 == 0 {
-        return Ok((0, 0));
+pub async fn claim_fee(acct: Account, pool: Option<PoolId>) -> TwoAmountsResult {
+    let _m = metrics::measure("claim_fee");
+    if let Err(e) = pause::check(PauseOp::ClaimFees) { return TwoAmountsResult::Err(e); }
+    match do_claim_fee(acct, pool.unwrap_or(POOL0)).await {
+        Ok([usdc, usdt]) => TwoAmountsResult::Ok(TwoAmounts { usdc, usdt }),
+        Err(e) => TwoAmountsResult::Err(e),
     }
+}
+
+async fn do_claim_fee(acct: Account, id: PoolId) -> Result<[AmountE6; 2], Error> {
+    // 0) 预览可领取（e6）
+    let amounts = positions::preview_claim_fee(acct.clone(), id);
+    if amounts == [0, 0] { return Ok(amounts); }
 
-    // 1) 读取 meta 与账户
-    let meta = get_token_meta().ok_or("token meta not set")?;
-    let from_pool = pool_account();
+    // 1) 读取账本与账户
+    let (p, ledgers) = pool_ledgers(id)?;
+    let onchain = pools::onchain_amounts(&p, amounts);
+    let from_pool = pools::account(id);
 
-    // 注意：你的架构中“用户子账户”实际是 canister 作为 owner、sub 为 derive(user)
-    // 保持与 remove_liquidity 中 to_user 的口径一致，避免账户体系混乱
+    // “用户子账户”实际是 canister 作为 owner、sub 为 derive(user)，与 remove_liquidity 中 to_user 的口径一致
     let to_user = Account {
         owner: canister_principal(),
         subaccount: Some(derive_subaccount(acct.owner).to_vec()),
     };
 
     // 2) 余额校验（live），避免半成功
-    let bal_u = icrc1_balance_of(meta.ckusdc, from_pool.clone())
-        .await.map_err(|e| format!("ckUSDC balance err: {e}"))?;
-    let bal_v = icrc1_balance_of(meta.ckusdt, from_pool.clone())
-        .await.map_err(|e| format!("ckUSDT balance err: {e}"))?;
-    if ext_to_e6(&bal_u, meta.dec_usdc) < usdc_e6 || ext_to_e6(&bal_v, meta.dec_usdt) < usdt_e6 {
-        return Err("POOL subaccount insufficient for fee claim".into());
+    for (&(ledger, dec), amt) in ledgers.iter().zip(onchain) {
+        if amt == 0 { continue; }
+        let bal = icrc1_balance_of(ledger, from_pool.clone()).await?;
+        if ext_to_e6(&bal, dec) < amt { return Err(Error::InsufficientLiquidity); }
     }
 
-    // 3) 真实 ICRC-1 转账：POOL 子 → 用户子（两笔都成功后再落账）
-    if usdc_e6 > 0 {
-        let arg_u = Icrc1TransferArg {
-            from_subaccount: from_pool.subaccount.clone(),
-            to: to_user.clone(),
-            amount: int_e6_to_ext(usdc_e6, meta.dec_usdc),
-            fee: None, memo: None, created_at_time: None,
-        };
-        do_icrc1_transfer(meta.ckusdc, arg_u).await
-            .map_err(|e| format!("ckUSDC transfer err: {e}"))?;
-    }
-    if usdt_e6 > 0 {
-        let arg_v = Icrc1TransferArg {
-            from_subaccount: from_pool.subaccount.clone(),
-            to: to_user.clone(),
-            amount: int_e6_to_ext(usdt_e6, meta.dec_usdt),
-            fee: None, memo: None, created_at_time: None,
-        };
-        do_icrc1_transfer(meta.ckusdt, arg_v).await
-            .map_err(|e| format!("ckUSDT transfer err: {e}"))?;
-    }
+    // 3) 真实 ICRC-1 转账：POOL 子 → 用户子（都成功后再落账）
+    let (ledger_blocks, err) = transfer_pair(&ledgers, from_pool.subaccount.clone(), &to_user, onchain, Rounding::Down).await;
+    if let Some(e) = err { return Err(e); }
+    record_payout_dust(&ledgers, &onchain);
 
-    // 4) 两笔都成功 → 正式提交内部结算，并同步调整 internal 储备（使用你的真实字段名）
-    let who = acct.owner.to_text();
+    // 4) 正式提交内部结算：金库扣减、打入用户内账（手续费不在储备里，储备不变）
+    let (owner, sub) = (acct.owner, acct.subaccount.clone());
     STATE.with(|s| {
         let mut st = s.borrow_mut();
-        st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_sub(usdc_e6);
-        st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_sub(usdt_e6);
+        positions::claim_fee(&mut st, id, &acct)?;
+        pools::log_lp_moves(&mut st, &p, owner, amounts, false);
+        Ok::<_, Error>(())
+    })?;
+
+    let mut tx = vec![
+        ("acct", Icrc3Value::account(&acct)),
+        ("usdc", Icrc3Value::nat(amounts[0])),
+        ("usdt", Icrc3Value::nat(amounts[1])),
+    ];
+    if id != POOL0 { tx.push(("pool", Icrc3Value::nat(id))); }
+    let block = icrc3::log(icrc3::BT_CLAIM_FEE, tx);
+    events::push(Event::ClaimFee {
+        who: owner.to_text(), sub, usdc_e6: amounts[0], usdt_e6: amounts[1], pool_id: Some(id),
+        ledger_blocks, icrc3_block: Some(block), ts: now(),
     });
 
-    let _ = positions::claim_fee(acct).map_err(|e| format!("{:?}", e))?;
+    Ok(amounts)
+}
 
-    // 用 Withdraw 记录两条（USDC / USDT），前端可标“Claim Fee”
-    if usdc_e6 > 0 {
-        events::push(Event::Withdraw { who: who.clone(), token: TokenId::USDC, amount: usdc_e6, ts: now() });
-    }
-    if usdt_e6 > 0 {
-        events::push(Event::Withdraw { who: who.clone(), token: TokenId::USDT, amount: usdt_e6, ts: now() });
-    }
+/* ---------------- 多池（见 pools） ---------------- */
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum CreatePoolResult {
+    #[serde(rename = "ok")] Ok(PoolId),
+    #[serde(rename = "err")] Err(Error),
+}
+
+#[ic_cdk::query]
+pub fn get_pools() -> Vec<PoolState> { STATE.with(|s| pools::list(&s.borrow())) }
+
+#[ic_cdk::query]
+pub fn get_pool(pool: PoolId) -> Option<PoolState> { STATE.with(|s| pools::load(&s.borrow(), pool).ok()) }
 
-    Ok((usdc_e6, usdt_e6))
+/// 某池子 24h / 7d 统计；不存在返回 null
+#[ic_cdk::query]
+pub fn get_pool_stats(pool: PoolId) -> Option<StatsSnapshot> {
+    STATE.with(|s| explore::pool_stats_snapshot(&s.borrow(), pool, now()))
+}
+
+/// 控制者：新建池子（同一代币对只能有一个池子）；池子子账户见 pools::subaccount
+#[ic_cdk::update]
+pub fn create_pool(args: CreatePoolArgs) -> CreatePoolResult {
+    if let Err(e) = require_controller() { return CreatePoolResult::Err(e); }
+    let id = match STATE.with(|s| pools::create(&mut s.borrow_mut(), &args, now())) {
+        Ok(id) => id,
+        Err(e) => return CreatePoolResult::Err(e),
+    };
+    events::push(Event::AdminAction {
+        who: ic_cdk::caller().to_text(),
+        action: "create_pool".into(),
+        detail: format!("pool {}: {:?} {:?} A={} fee_bps={}", id, args.coins, args.kind.unwrap_or(PoolKind::StableSwap), args.a_amp, args.fee_bps),
+        ts: now(),
+    });
+    CreatePoolResult::Ok(id)
+}
+
+/* ---------------- LP 份额代币（ICRC-1 / ICRC-2） ---------------- */
+// 份额转移会先结算双方的手续费指数（见 lp_token::move_shares）；
+// 份额与手续费按 lp_token::account_key 记账：非默认子账户持有的份额直接以该 Account 调用 remove_liquidity / claim_fee
+
+#[ic_cdk::query(name = "icrc1_name")]
+pub fn lp_icrc1_name() -> String { lp_token::LP_NAME.to_string() }
+
+#[ic_cdk::query(name = "icrc1_symbol")]
+pub fn lp_icrc1_symbol() -> String { lp_token::LP_SYMBOL.to_string() }
+
+#[ic_cdk::query(name = "icrc1_decimals")]
+pub fn lp_icrc1_decimals() -> u8 { lp_token::LP_DECIMALS }
+
+#[ic_cdk::query(name = "icrc1_fee")]
+pub fn lp_icrc1_fee() -> Nat { Nat::from(lp_token::LP_FEE) }
+
+#[ic_cdk::query(name = "icrc1_metadata")]
+pub fn lp_icrc1_metadata() -> Vec<(String, MetadataValue)> { lp_token::metadata() }
+
+#[ic_cdk::query(name = "icrc1_total_supply")]
+pub fn lp_icrc1_total_supply() -> Nat { STATE.with(|s| Nat::from(s.borrow().pool.total_shares)) }
+
+#[ic_cdk::query(name = "icrc1_minting_account")]
+pub fn lp_icrc1_minting_account() -> Option<Account> { Some(lp_token::minting_account(canister_principal())) }
+
+#[ic_cdk::query(name = "icrc1_supported_standards")]
+pub fn lp_icrc1_supported_standards() -> Vec<StandardRecord> { lp_token::supported_standards() }
+
+#[ic_cdk::query(name = "icrc1_balance_of")]
+pub fn lp_icrc1_balance_of(account: Account) -> Nat {
+    // 池子账户 account(id) 的余额即 Metapool 名下的份额，与 1xfer 区块口径一致
+    STATE.with(|s| Nat::from(pools::lp_balance_of(&s.borrow(), &account, canister_principal())))
+}
+
+#[ic_cdk::update(name = "icrc1_transfer")]
+pub fn lp_icrc1_transfer(arg: LpTransferArg) -> Result<Nat, TransferError> {
+    let _m = metrics::measure("icrc1_transfer");
+    let ctx = TxCtx::current();
+    let res = STATE.with(|s| lp_token::transfer(&mut s.borrow_mut(), &ctx, arg));
+    if res.is_ok() { icrc3::commit(); }
+    res
+}
+
+#[ic_cdk::update(name = "icrc2_approve")]
+pub fn lp_icrc2_approve(args: ApproveArgs) -> Result<Nat, ApproveError> {
+    let _m = metrics::measure("icrc2_approve");
+    let ctx = TxCtx::current();
+    let res = STATE.with(|s| lp_token::approve(&mut s.borrow_mut(), &ctx, args));
+    if res.is_ok() { icrc3::commit(); }
+    res
+}
+
+#[ic_cdk::query(name = "icrc2_allowance")]
+pub fn lp_icrc2_allowance(args: AllowanceArgs) -> Allowance {
+    STATE.with(|s| lp_token::allowance(&s.borrow(), &args, ic_cdk::api::time()))
+}
+
+#[ic_cdk::update(name = "icrc2_transfer_from")]
+pub fn lp_icrc2_transfer_from(args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    let _m = metrics::measure("icrc2_transfer_from");
+    let ctx = TxCtx::current();
+    let res = STATE.with(|s| lp_token::transfer_from(&mut s.borrow_mut(), &ctx, args));
+    if res.is_ok() { icrc3::commit(); }
+    res
+}
+
+/* ---------------- ICRC-3 区块日志 ---------------- */
+
+#[ic_cdk::query]
+pub fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    STATE.with(|s| icrc3::get_blocks(&s.borrow(), args))
+}
+
+#[ic_cdk::query]
+pub fn icrc3_get_archives(args: GetArchivesArgs) -> Vec<Icrc3ArchiveInfo> {
+    STATE.with(|s| icrc3::get_archives(&s.borrow(), args))
+}
+
+#[ic_cdk::query]
+pub fn icrc3_get_tip_certificate() -> Option<Icrc3DataCertificate> { icrc3::tip_certificate() }
+
+#[ic_cdk::query]
+pub fn icrc3_supported_block_types() -> Vec<SupportedBlockType> { icrc3::supported_block_types() }
+
+/// 控制者：设置 ICRC-3 溢出目标 archive canister（见 canisters/vaultpair_archive）；None 停止溢出
+#[ic_cdk::update]
+pub fn admin_set_icrc3_archive(archive: Option<Principal>) -> TextResult {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return TextResult::Err(Error::Unauthorized(ic_cdk::caller()));
+    }
+    STATE.with(|s| {
+        s.borrow_mut().icrc3.get_or_insert_with(Default::default).archive_canister = archive;
+    });
+    events::push(Event::AdminAction {
+        who: ic_cdk::caller().to_text(),
+        action: "set_icrc3_archive".into(),
+        detail: format!("{:?}", archive.map(|p| p.to_text())),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
 }
 
 /* ---------------- Activity ---------------- */
 
 #[ic_cdk::query]
-pub fn get_events(cursor: u128, limit: u128) -> Vec<Event> {
+pub fn get_events(cursor: u128, limit: u128) -> Vec<EventV1> {
     crate::activity::get_events(cursor, limit)
 }
 
 #[ic_cdk::query]
-pub fn get_events_latest(limit: u128) -> Vec<Event> {
+pub fn get_events_latest(limit: u128) -> Vec<EventV1> {
     crate::activity::get_events_latest(limit)
 }
 
+/// 按 id 游标分页，可按 principal / 事件类型 / 时间范围过滤；next 为下一页 from_id
+#[ic_cdk::query]
+pub fn query_events(q: EventQuery) -> EventPage {
+    STATE.with(|s| events::query(&s.borrow(), q))
+}
+
+/// 已搬到 archive 的事件区间（id 闭区间）；archive 上用 get_events(start, length) 读取
+#[ic_cdk::query]
+pub fn get_event_archives() -> Vec<EventArchiveRange> {
+    STATE.with(|s| events::archives(&s.borrow()))
+}
+
+/// 控制者：本地最多保留 max_live 条事件，超出部分搬到 archive；任一为 None 则不搬迁（绝不删除）
+#[ic_cdk::update]
+pub fn admin_set_event_retention(max_live: Option<u64>, archive: Option<Principal>) -> TextResult {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return TextResult::Err(Error::Unauthorized(ic_cdk::caller()));
+    }
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let m = st.event_log.get_or_insert_with(Default::default);
+        m.max_live = max_live;
+        m.archive_canister = archive;
+    });
+    events::push(Event::AdminAction {
+        who: ic_cdk::caller().to_text(),
+        action: "set_event_retention".into(),
+        detail: format!("max_live={:?} archive={:?}", max_live, archive.map(|p| p.to_text())),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
+}
+
+/* ---------------- HTTP（JSON / CSV 导出，见 http/mod.rs） ---------------- */
+
+#[ic_cdk::query]
+pub fn http_request(req: HttpRequest) -> HttpResponse {
+    STATE.with(|s| crate::http::handle(&s.borrow(), now(), &req, false))
+}
+
+/// certified=1 的请求由网关升级到这里：响应经共识返回
+#[ic_cdk::update]
+pub fn http_request_update(req: HttpRequest) -> HttpResponse {
+    let _m = metrics::measure("http_request_update");
+    STATE.with(|s| crate::http::handle(&s.borrow(), now(), &req, true))
+}
+
 /* ---------------- 权威统计 / 风控 / Cycles ---------------- */
 
 #[query]
@@ -650,26 +1009,7 @@ pub fn get_tvl_e6() -> u128 {
 
 #[query]
 pub fn get_stats_snapshot() -> StatsSnapshot {
-    STATE.with(|s| {
-        let st = s.borrow();
-        let n = now();
-        let (vol_24, fee_24, swaps_24) = st.stats.sum_last_hours(n, 24);
-        let (vol_7d, fee_7d, _swaps_7d) = st.stats.sum_last_hours(n, 168);
-        let tvl = st.pool.reserve_usdc.saturating_add(st.pool.reserve_usdt);
-        let apy_bp = if tvl == 0 { 0 } else {
-            ((fee_24.saturating_mul(365) * 10_000) / tvl).min(u128::from(u32::MAX)) as u32
-        };
-        StatsSnapshot {
-            now_sec: n,
-            tvl_e6: tvl,
-            vol_24h_e6: vol_24,
-            vol_7d_e6: vol_7d,
-            fee_24h_e6: fee_24,
-            fee_7d_e6: fee_7d,
-            swaps_24h: swaps_24,
-            apy_24h_bp: apy_bp,
-        }
-    })
+    STATE.with(|s| explore::stats_snapshot(&s.borrow(), now()))
 }
 
 #[query]
@@ -695,23 +1035,61 @@ pub fn get_cycles_info() -> CyclesInfo {
     })
 }
 
+/* ---------------- 后台任务（见 jobs.rs） ---------------- */
+
+#[query]
+pub fn get_jobs_status() -> JobsState {
+    STATE.with(|s| s.borrow().jobs.clone().unwrap_or_default())
+}
+
+/// 控制者：修改定时任务参数并立即重新挂载
+#[ic_cdk::update]
+pub fn admin_set_jobs(cfg: JobsConfig) -> TextResult {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return TextResult::Err(Error::Unauthorized(ic_cdk::caller()));
+    }
+    let old = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let old = jobs::config(&st);
+        st.jobs.get_or_insert_with(Default::default).config = cfg.clone();
+        old
+    });
+    jobs::start();
+    events::push(Event::ParamChange {
+        who: ic_cdk::caller().to_text(),
+        param: "jobs".into(),
+        old: format!("{:?}", old),
+        new: format!("{:?}", cfg),
+        ts: now(),
+    });
+    TextResult::Ok("ok".into())
+}
+
+/// 控制者：立即执行一次对账漂移检查（不等定时器）
+#[ic_cdk::update]
+pub async fn admin_run_reconcile_check() -> TextResult {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        return TextResult::Err(Error::Unauthorized(ic_cdk::caller()));
+    }
+    jobs::run_reconcile().await;
+    let last = STATE.with(|s| s.borrow().jobs.as_ref().and_then(|j| j.last_drift.clone()));
+    TextResult::Ok(format!("{:?}", last))
+}
+
 #[query]
 pub fn get_estimated_24h_volume(window_minutes: u32) -> u128 {
     let w = window_minutes.clamp(1, 120) as u64; // 1~120 分钟
-    STATE.with(|s| {
-        let st = s.borrow();
-        let now_sec = now();
-        let cutoff = now_sec.saturating_sub(w * 60);
-        let mut vol_e6: u128 = 0;
-        for ev in st.events.iter().rev() {
-            if let Event::Swap { dx_e6, dy_e6, ts, .. } = ev {
-                if *ts < cutoff { break; }
-                vol_e6 = vol_e6.saturating_add((*dx_e6 + *dy_e6) / 2);
-            }
+    let cutoff = now().saturating_sub(w * 60);
+    let mut vol_e6: u128 = 0;
+    events::scan_rev(|ev| {
+        if ev.ts() < cutoff { return false; }
+        if let Some((dx_e6, dy_e6)) = ev.swap_amounts() {
+            vol_e6 = vol_e6.saturating_add((dx_e6 + dy_e6) / 2);
         }
-        let scale = (24u128 * 60u128) / (w as u128);
-        vol_e6.saturating_mul(scale)
-    })
+        true
+    });
+    let scale = (24u128 * 60u128) / (w as u128);
+    vol_e6.saturating_mul(scale)
 }
 
 /* ---------------- ICRC 辅助 ---------------- */
@@ -727,14 +1105,25 @@ fn get_my_subaccount() -> Vec<u8> {
 
 #[ic_cdk::update]
 pub async fn withdraw_from_sub(token_canister: String, to: Account, amount: candid::Nat) -> TextResult {
+    let _m = metrics::measure("withdraw_from_sub");
+    if let Err(e) = pause::check(PauseOp::Withdraw) { return TextResult::Err(e); }
     use candid::Principal as P;
     let token = match P::from_text(&token_canister) {
         Ok(p) => p,
-        Err(e) => return TextResult::Err(format!("invalid token canister id: {e:?}")),
+        Err(e) => return TextResult::Err(Error::InvalidPrincipal(e.to_string())),
     };
     let caller = ic_cdk::caller();
-    match crate::icrc::transfer_from_user_sub(token, caller, to, amount).await {
-        Ok(n) => TextResult::Ok(n.to_string()),
+    match crate::icrc::transfer_from_user_sub(token, caller, to.clone(), amount.clone()).await {
+        Ok(n) => {
+            icrc3::log(icrc3::BT_WITHDRAW, vec![
+                ("acct", Icrc3Value::account(&Account { owner: caller, subaccount: None })),
+                ("ledger", Icrc3Value::blob(token.as_slice())),
+                ("to", Icrc3Value::account(&to)),
+                ("amt", Icrc3Value::Nat(amount)),
+                ("ledger_block", Icrc3Value::Nat(n.clone())),
+            ]);
+            TextResult::Ok(n.to_string())
+        }
         Err(e) => TextResult::Err(e),
     }
 }
@@ -747,6 +1136,17 @@ pub fn get_my_deposit_target() -> DepositTarget {
     DepositTarget { owner, sub: sub.to_vec(), ai_hex: crate::icrc::to_hex32(&ai) }
 }
 
+/// ICRC-2 入金：按调用者对本 canister 的授权，从其默认账户代扣到派生子账户，再按 live 余额刷新内账，返回账本块号（见 assets::deposit）
+#[ic_cdk::update]
+pub async fn deposit(token: TokenId, amount_e6: AmountE6) -> DepositResult {
+    let _m = metrics::measure("deposit");
+    if let Err(e) = pause::check(PauseOp::Deposit) { return DepositResult::Err(e.into()); }
+    match assets::deposit(ic_cdk::caller(), token, amount_e6).await {
+        Ok(block) => DepositResult::Ok(block),
+        Err(e) => DepositResult::Err(e),
+    }
+}
+
 #[ic_cdk::query]
 fn get_my_icp_account_id_hex() -> String {
     get_my_deposit_target().ai_hex
@@ -770,6 +1170,7 @@ pub fn get_deposit_target_for(user: Principal) -> DepositTarget {
 /// ICRC-2 授权（保留：提现/代扣前置）
 #[ic_cdk::update]
 pub async fn ensure_allowance_for_user(token: Principal, min_amount: Nat) -> bool {
+    let _m = metrics::measure("ensure_allowance_for_user");
     use serde::Deserialize;
     use candid::CandidType;
 
@@ -815,7 +1216,11 @@ pub async fn ensure_allowance_for_user(token: Principal, min_amount: Nat) -> boo
         ic_cdk::call(token, "icrc2_allowance", (AllowanceArgs { account: from.clone(), spender: spender.clone() },)).await;
     let allowance = match allow_res {
         Ok((res,)) => res.allowance,
-        Err(e) => { ic_cdk::print(format!("[ensure_allowance_for_user] allowance call failed: {:?}", e)); return false; }
+        Err(e) => {
+            metrics::ledger_error(token, "CallRejected");
+            ic_cdk::print(format!("[ensure_allowance_for_user] allowance call failed: {:?}", e));
+            return false;
+        }
     };
     if allowance >= min_amount { return false; }
 
@@ -830,8 +1235,16 @@ pub async fn ensure_allowance_for_user(token: Principal, min_amount: Nat) -> boo
         ic_cdk::call(token, "icrc2_approve", (args,)).await;
     match approve_call {
         Ok((Ok(_txid),)) => true,
-        Ok((Err(e),)) => { ic_cdk::print(format!("[ensure_allowance_for_user] approve Err: {:?}", e)); false }
-        Err(e) => { ic_cdk::print(format!("[ensure_allowance_for_user] approve call failed: {:?}", e)); false }
+        Ok((Err(e),)) => {
+            metrics::ledger_error(token, &metrics::kind_of(&e));
+            ic_cdk::print(format!("[ensure_allowance_for_user] approve Err: {:?}", e));
+            false
+        }
+        Err(e) => {
+            metrics::ledger_error(token, "CallRejected");
+            ic_cdk::print(format!("[ensure_allowance_for_user] approve call failed: {:?}", e));
+            false
+        }
     }
 }
 
@@ -859,6 +1272,12 @@ pub fn get_my_available_balances_live() -> Available {
     get_available_balances_live_for(ic_cdk::caller())
 }
 
+/// 换算到 e6 记账口径时截掉的尾数（见 ledger_book::DustBook）
+#[ic_cdk::query]
+pub fn get_dust() -> DustBook {
+    STATE.with(|s| s.borrow().dust.clone().unwrap_or_default())
+}
+
 /// Liquidity 页“指定用户 live 可用额”（**query**）：读取本地缓存（内部 e6→外部 Nat）
 #[ic_cdk::query]
 pub fn get_available_balances_live_for(who: Principal) -> Available {
@@ -870,8 +1289,8 @@ pub fn get_available_balances_live_for(who: Principal) -> Available {
         let du = st.dec_usdc.unwrap_or(6);
         let dt = st.dec_usdt.unwrap_or(6);
         Available {
-            usdc: int_e6_to_ext(u_e6, du),
-            usdt: int_e6_to_ext(t_e6, dt),
+            usdc: e6_to_ext(u_e6, du, Rounding::Down),
+            usdt: e6_to_ext(t_e6, dt, Rounding::Down),
         }
     })
 }
@@ -880,6 +1299,7 @@ pub fn get_available_balances_live_for(who: Principal) -> Available {
 
 #[ic_cdk::update]
 pub fn refresh_available_for(user: Principal) -> TextResult {
+    if let Err(e) = pause::check(PauseOp::Deposit) { return TextResult::Err(e); }
     ic_cdk::spawn(async move {
         let _ = do_refresh_available_for(user).await;
     });
@@ -889,6 +1309,8 @@ pub fn refresh_available_for(user: Principal) -> TextResult {
 // === 如需“阻塞等待到链上完成”的旧语义，提供一个备用方法 ===
 #[ic_cdk::update]
 pub async fn refresh_available_for_blocking(user: Principal) -> TextResult {
+    let _m = metrics::measure("refresh_available_for_blocking");
+    if let Err(e) = pause::check(PauseOp::Deposit) { return TextResult::Err(e); }
     match do_refresh_available_for(user).await {
         Ok(()) => TextResult::Ok("ok".into()),
         Err(e) => TextResult::Err(e),
@@ -896,7 +1318,7 @@ pub async fn refresh_available_for_blocking(user: Principal) -> TextResult {
 }
 
 // === 实际的刷新实现：并发读取两条账本，写入 e6 缓存 ===
-async fn do_refresh_available_for(user: Principal) -> Result<(), String> {
+async fn do_refresh_available_for(user: Principal) -> Result<(), Error> {
     // 1) 元信息
     let (ckusdc, ckusdt, du, dt) = STATE.with(|s| {
         let st = s.borrow();
@@ -904,7 +1326,7 @@ async fn do_refresh_available_for(user: Principal) -> Result<(), String> {
     });
     let (ckusdc, ckusdt, du, dt) = match (ckusdc, ckusdt, du, dt) {
         (Some(a), Some(b), Some(du), Some(dt)) => (a, b, du, dt),
-        _ => return Err("token meta not set".into()),
+        _ => return Err(Error::TokenMetaNotSet),
     };
 
     let acct = crate::types::Account {
@@ -912,7 +1334,8 @@ async fn do_refresh_available_for(user: Principal) -> Result<(), String> {
         subaccount: Some(derive_subaccount(user).to_vec()),
     };
 
-    // 2) 并发读取（各自仍是 update 流程，但我们同时发出以减少总 wall-time）
+    // 2) 并发读取（各自仍是 update 流程，但我们同时发出以减少总 wall-time）；序号在发出前取，见 assets::apply_live_balance
+    let seq = assets::begin_refresh();
     let (u_res, t_res) = futures::future::join(
         icrc1_balance_of(ckusdc, acct.clone()),
         icrc1_balance_of(ckusdt, acct.clone()),
@@ -920,29 +1343,21 @@ async fn do_refresh_available_for(user: Principal) -> Result<(), String> {
 
     let (u_nat, t_nat) = match (u_res, t_res) {
         (Ok(a), Ok(b)) => (a, b),
-        (ua, tb) => return Err(format!("ledger err: usdc={ua:?}, usdt={tb:?}")),
+        (Err(e), _) | (_, Err(e)) => return Err(e),
     };
 
-    // 3) 写入内部 e6 缓存
-    let u_e6 = ext_to_int_e6(&u_nat, du);
-    let t_e6 = ext_to_int_e6(&t_nat, dt);
-
-
+    // 3) 写入内部 e6 缓存（向下取整，截掉的尾数记入 DustBook）；比已落账读数旧的结果丢弃
     STATE.with(|s| {
         let mut st = s.borrow_mut();
-        let key = skey(&user);
-        st.user_sub_usdc.insert(key.clone(), u_e6);
-        st.user_sub_usdt.insert(key,        t_e6);
-        // 如需让“旧的 get_available_balances(Account)”也一致，可顺便写 ledger_book：
-        // crate::ledger_book::set_available(user, TokenId::USDC, u_e6);
-        // crate::ledger_book::set_available(user, TokenId::USDT, t_e6);
+        assets::apply_live_balance(&mut st, user, TokenId::USDC, seq, &u_nat, du);
+        assets::apply_live_balance(&mut st, user, TokenId::USDT, seq, &t_nat, dt);
     });
 
     Ok(())
 }
 
 
-// ------- ICRC-1 transfer 通用参数/错误，用于直接调用 ledger 的 `icrc1_transfer` -------
+// ------- ICRC-1 transfer 通用参数，用于直接调用 ledger 的 `icrc1_transfer` -------
 #[derive(CandidType, Deserialize)]
 struct Icrc1TransferArg {
     from_subaccount: Option<Vec<u8>>,
@@ -953,54 +1368,20 @@ struct Icrc1TransferArg {
     created_at_time: Option<u64>,
 }
 
-#[derive(CandidType, Deserialize)]
-enum Icrc1TransferError {
-    GenericError { error_code: Nat, message: String },
-    TemporarilyUnavailable,
-    BadBurn { min_burn_amount: Nat },
-    Duplicate { duplicate_of: Nat },
-    BadFee { expected_fee: Nat },
-    CreatedInFuture { ledger_time: Nat },
-    InsufficientFunds { balance: Nat },
-    TooOld,
-}
-
-// ledger 调用封装：返回区块索引（或错误信息）
-
-// 1) 封装的转账调用
+// ledger 调用封装：返回区块索引；账本拒绝/调用失败统一映射为 Error::LedgerError
 async fn do_icrc1_transfer(
     ledger: Principal,
     arg: Icrc1TransferArg,
-) -> std::result::Result<Nat, String> {
-    let (res,): (std::result::Result<Nat, Icrc1TransferError>,) =
+) -> std::result::Result<Nat, Error> {
+    let from_pool = arg.from_subaccount.as_deref().and_then(|sub| STATE.with(|s| pools::pool_of_subaccount(&s.borrow(), sub)));
+    let (res,): (std::result::Result<Nat, crate::icrc::TransferError>,) =
         ic_call(ledger, "icrc1_transfer", (arg,))
             .await
-            .map_err(|(code, msg)| format!("icrc1_transfer call failed: {:?}: {}", code, msg))?;
-
-    match res {
-        Ok(block_idx) => Ok(block_idx),
-        Err(e) => {
-            let msg = match e {
-                Icrc1TransferError::GenericError { error_code, message } =>
-                    format!("GenericError[{}]: {}", error_code, message),
-                Icrc1TransferError::TemporarilyUnavailable =>
-                    "TemporarilyUnavailable".to_string(),
-                Icrc1TransferError::BadBurn { min_burn_amount } =>
-                    format!("BadBurn, min_burn_amount={}", min_burn_amount),
-                Icrc1TransferError::Duplicate { duplicate_of } =>
-                    format!("Duplicate, duplicate_of={}", duplicate_of),
-                Icrc1TransferError::BadFee { expected_fee } =>
-                    format!("BadFee, expected_fee={}", expected_fee),
-                Icrc1TransferError::CreatedInFuture { ledger_time } =>
-                    format!("CreatedInFuture, ledger_time={}", ledger_time),
-                Icrc1TransferError::InsufficientFunds { balance } =>
-                    format!("InsufficientFunds, balance={}", balance),
-                Icrc1TransferError::TooOld =>
-                    "TooOld".to_string(),
-            };
-            Err(msg)
-        }
-    }
+            .map_err(|e| Error::call_rejected(ledger, e))?;
+    let block = res.map_err(|e| Error::ledger(ledger, e.into()))?;
+    // 池子转出另扣的账本 fee 不进内部账，计数供对账估算
+    if let Some(id) = from_pool { reconcile::note_pool_outgoing(id, ledger); }
+    Ok(block)
 }
 
 #[ic_cdk::update]
@@ -1009,12 +1390,14 @@ pub async fn transfer_from_user_sub_to_pool(
     user: Principal,
     amount_e6: AmountE6,
 ) -> TxResultNat {
+    let _m = metrics::measure("transfer_from_user_sub_to_pool");
+    if let Err(e) = pause::check(PauseOp::Deposit) { return TxResultNat::Err(e); }
     if amount_e6 == 0 {
-        return TxResultNat::Err("amount_e6 must be > 0".to_string());
+        return TxResultNat::Err(Error::InvalidInput);
     }
     let ledger = match Principal::from_text(&token_ledger) {
         Ok(p) => p,
-        Err(_) => return TxResultNat::Err("invalid token_ledger principal text".to_string()),
+        Err(_) => return TxResultNat::Err(Error::InvalidPrincipal(token_ledger)),
     };
 
     let user_sub = derive_subaccount(user).to_vec();
@@ -1041,12 +1424,14 @@ pub async fn transfer_from_pool_to_user_sub(
     user: Principal,
     amount_e6: AmountE6,
 ) -> TxResultNat {
+    let _m = metrics::measure("transfer_from_pool_to_user_sub");
+    if let Err(e) = pause::check(PauseOp::Withdraw) { return TxResultNat::Err(e); }
     if amount_e6 == 0 {
-        return TxResultNat::Err("amount_e6 must be > 0".to_string());
+        return TxResultNat::Err(Error::InvalidInput);
     }
     let ledger = match Principal::from_text(&token_ledger) {
         Ok(p) => p,
-        Err(_) => return TxResultNat::Err("invalid token_ledger principal text".to_string()),
+        Err(_) => return TxResultNat::Err(Error::InvalidPrincipal(token_ledger)),
     };
 
     let to = Account {
@@ -1069,269 +1454,222 @@ pub async fn transfer_from_pool_to_user_sub(
 }
 
 
-#[inline]
-fn orient_pair(
-    token_in: &TokenId,
-    token_out: &TokenId,
-    ru_e6: u128,
-    rv_e6: u128,
-) -> Option<(bool /* in=USDC? */, u128 /*rin*/, u128 /*rout*/)> {
-    use crate::types::TokenId::*;
-    match (token_in, token_out) {
-        (USDC, USDT) => Some((true,  ru_e6, rv_e6)),
-        (USDT, USDC) => Some((false, rv_e6, ru_e6)),
-        _ => None,
-    }
-}
-
-
 // 直接基于 internal 储备报价（不做跨 canister 调用）
 #[ic_cdk::query]
-pub fn quote_live(token_in: TokenId, token_out: TokenId, dx_e6: AmountE6) -> QuoteOut {
-    const E6: u128 = 1_000_000;
-    if dx_e6 == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 };
-    }
-
-    // 读内部储备与参数
-    let (ru, rv, a_amp_raw, fee_bps) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.pool.reserve_usdc, st.pool.reserve_usdt, st.pool.a_amp as u128, st.pool.fee_bps as u32)
-    });
-
-    // 方向
-    let ( _is_usdc_in, rin, rout ) = match orient_pair(&token_in, &token_out, ru, rv) {
-        Some(t) => t,
-        None => return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 },
-    };
-    if rin == 0 || rout == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 };
-    }
-
-    // A 归一化 + 报价（与 swap/mod.rs 的公式保持一致）
-    let a_norm = if a_amp_raw < 1_000_000 { a_amp_raw * 1_000_000 } else { a_amp_raw };
-    let (dy, fee_e6) = stableswap::quote_dx_to_dy(a_norm, rin, rout, dx_e6 as u128, fee_bps);
-
-    let price_e6 = if dx_e6 > 0 { dy.saturating_mul(E6) / (dx_e6 as u128) } else { E6 };
-    QuoteOut { dy_e6: dy, fee_e6, price_e6 }
+pub fn quote_live(token_in: Coin, token_out: Coin, dx_e6: AmountE6, pool: Option<PoolId>) -> QuoteResult {
+    // 与 swap/mod.rs 的公式保持一致
+    swap_mod::quote(pool.unwrap_or(POOL0), token_in, token_out, dx_e6).into()
 }
 
-/// —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于内部储备）——
-/// 返回：dx_e6（最小需要投入），fee_e6（基于 dx 的输入侧手续费），price_e6 = dy/dx * 1e6
-#[ic_cdk::query]
-pub fn quote_exact_out(token_in: TokenId, token_out: TokenId, dy_target_e6: AmountE6) -> QuoteOut {
-    if dy_target_e6 == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 };
-    }
-    // 读内部池储备
-    let (ru, rv, a_raw, fee_bps) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.pool.reserve_usdc, st.pool.reserve_usdt, st.pool.a_amp as u128, st.pool.fee_bps as u32)
-    });
-    let (is_usdc_in, rin, rout) = match orient_pair(&token_in, &token_out, ru, rv) {
-        Some(t) => t, None => return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 }
-    };
-    if rin == 0 || rout == 0 { return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 }; }
+/// 池子 live 储备（池子子账户在两条账本上的余额，e6，按 coins 顺序）
+async fn live_reserves(ledgers: &[(Principal, u8); 2], pool_acc: &Account) -> Result<[u128; 2], Error> {
+    let (r0, r1) = futures::future::join(
+        icrc1_balance_of(ledgers[0].0, pool_acc.clone()),
+        icrc1_balance_of(ledgers[1].0, pool_acc.clone()),
+    ).await;
+    Ok([ext_to_e6(&r0?, ledgers[0].1), ext_to_e6(&r1?, ledgers[1].1)])
+}
 
-    let a = if a_raw < 1_000_000 { a_raw * 1_000_000 } else { a_raw };
+/// 反向报价的公共部分：上界扩张 + 二分，求满足 dy >= dy_target 的最小 dx（动态费率与正向报价一致）
+/// 返回的 QuoteOut 复用结构：dy_e6 字段放的是 dx
+fn solve_exact_out(a: u128, rin: u128, rout: u128, dy_target_e6: u128, fee: u128, offpeg: u128)
+    -> crate::error::Result<QuoteOut>
+{
+    let q = |dx: u128| stableswap::quote_dx_to_dy_book(a, rin, rout, dx, fee, offpeg);
+    // 目标不小于对侧储备时必然无解
+    if dy_target_e6 >= rout { return Err(Error::InsufficientLiquidity); }
 
     // 上界扩张
     let mut lo: u128 = 0;
-    let mut hi: u128 = dy_target_e6 as u128; // 乐观起点
+    let mut hi: u128 = dy_target_e6; // 乐观起点
     loop {
-        let (dy_try, _) = stableswap::quote_dx_to_dy(a, rin, rout, hi, fee_bps);
-        if dy_try >= dy_target_e6 as u128 { break; }
+        let (dy_try, _, _) = q(hi)?;
+        if dy_try >= dy_target_e6 { break; }
         hi = hi.saturating_mul(2).saturating_add(1);
         if hi > 10_000_000_000_000u128 { // 保护
-            return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 };
+            return Err(Error::InsufficientLiquidity);
         }
     }
 
     // 二分求最小 dx
     while lo + 1 < hi {
         let mid = (lo + hi) / 2;
-        let (dy_mid, _) = stableswap::quote_dx_to_dy(a, rin, rout, mid, fee_bps);
-        if dy_mid >= dy_target_e6 as u128 { hi = mid; } else { lo = mid; }
+        let (dy_mid, _, _) = q(mid)?;
+        if dy_mid >= dy_target_e6 { hi = mid; } else { lo = mid; }
     }
     let dx = hi;
-    let (dy, fee_e6) = stableswap::quote_dx_to_dy(a, rin, rout, dx, fee_bps);
+    let (dy, fee_e6, rate) = q(dx)?;
     let price_e6 = if dx > 0 { dy.saturating_mul(1_000_000) / dx } else { 1_000_000 };
-    let _ = is_usdc_in;
-    QuoteOut { dy_e6: dx as u128, fee_e6, price_e6 } // 复用结构：这里把 dx 放在 dy_e6 字段返回
+    Ok(QuoteOut { dy_e6: dx, fee_e6, price_e6, fee_rate_e10: rate as u64 })
 }
 
-/// —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于实时 live 储备）——
-#[ic_cdk::query(composite = true)]
-pub async fn quote_live_exact_out(token_in: TokenId, token_out: TokenId, dy_target_e6: AmountE6) -> QuoteOut {
+/// —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于内部储备）——
+/// 返回：dx_e6（最小需要投入），fee_e6（基于 dx 的输入侧手续费），price_e6 = dy/dx * 1e6
+#[ic_cdk::query]
+pub fn quote_exact_out(token_in: Coin, token_out: Coin, dy_target_e6: AmountE6, pool: Option<PoolId>) -> QuoteResult {
     if dy_target_e6 == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 };
+        return QuoteResult::Ok(QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000, ..Default::default() });
     }
-    let meta = if let Some(m) = get_token_meta() { m } else {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 };
-    };
-    let pool_acc = get_pool_account("USDC_USDT".to_string());
-    let (u_res, v_res) = futures::future::join(
-        icrc1_balance_of(meta.ckusdc, pool_acc.clone()),
-        icrc1_balance_of(meta.ckusdt, pool_acc.clone()),
-    ).await;
-    let (u_nat, v_nat) = match (u_res, v_res) {
-        (Ok(a), Ok(b)) => (a, b),
-        _ => return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 },
+    // 读内部池储备
+    let p = match STATE.with(|s| pools::load(&s.borrow(), pool.unwrap_or(POOL0))) {
+        Ok(p) => p, Err(e) => return QuoteResult::Err(e),
     };
-    let ru = ext_to_e6(&u_nat, meta.dec_usdc);
-    let rv = ext_to_e6(&v_nat, meta.dec_usdt);
-
-    let (is_usdc_in, rin, rout) = match orient_pair(&token_in, &token_out, ru, rv) {
-        Some(t) => t, None => return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 }
+    let (i, j) = match p.orient(token_in, token_out) {
+        Some(t) => t, None => return QuoteResult::Err(Error::InvalidInput),
     };
-    if rin == 0 || rout == 0 { return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 }; }
-
-    let (a_raw, fee_bps) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.pool.a_amp as u128, st.pool.fee_bps as u32)
-    });
-    let a = if a_raw < 1_000_000 { a_raw * 1_000_000 } else { a_raw };
+    exact_out_in(&p, i, p.reserves[i], p.reserves[j], dy_target_e6).into()
+}
 
-    // 上界扩张
-    let mut lo: u128 = 0;
-    let mut hi: u128 = dy_target_e6 as u128;
-    loop {
-        let (dy_try, _) = stableswap::quote_dx_to_dy(a, rin, rout, hi, fee_bps);
-        if dy_try >= dy_target_e6 as u128 { break; }
-        hi = hi.saturating_mul(2).saturating_add(1);
-        if hi > 10_000_000_000_000u128 {
-            return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000 };
-        }
+/// StableSwap 的动态费率依赖 dx，只能二分；其余池型直接用 Invariant::get_dx
+fn exact_out_in(p: &PoolState, i: usize, rin: u128, rout: u128, dy_target_e6: u128) -> crate::error::Result<QuoteOut> {
+    let a = swap_mod::normalize_amp(p.a_amp as u128);
+    let fee = stableswap::bps_to_fee(p.fee_bps as u32);
+    if p.kind == PoolKind::StableSwap {
+        return solve_exact_out(a, rin, rout, dy_target_e6, fee, swap_mod::offpeg_multiplier(p));
     }
-    // 二分
-    while lo + 1 < hi {
-        let mid = (lo + hi) / 2;
-        let (dy_mid, _) = stableswap::quote_dx_to_dy(a, rin, rout, mid, fee_bps);
-        if dy_mid >= dy_target_e6 as u128 { hi = mid; } else { lo = mid; }
+    if dy_target_e6 >= rout { return Err(Error::InsufficientLiquidity); }
+    let inv = p.invariant(i);
+    let dx = inv.get_dx(rin, rout, dy_target_e6, fee)?;
+    let (dy, fee_e6) = inv.get_dy(rin, rout, dx, fee)?;
+    let price_e6 = dy.saturating_mul(1_000_000) / dx.max(1);
+    let rate = if p.kind == PoolKind::CryptoSwap { fee_e6.saturating_mul(stableswap::FEE_DENOMINATOR) / dx.max(1) } else { fee };
+    Ok(QuoteOut { dy_e6: dx, fee_e6, price_e6, fee_rate_e10: rate as u64 })
+}
+
+/// —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于实时 live 储备）——
+#[ic_cdk::query(composite = true)]
+pub async fn quote_live_exact_out(token_in: Coin, token_out: Coin, dy_target_e6: AmountE6, pool: Option<PoolId>) -> QuoteResult {
+    if dy_target_e6 == 0 {
+        return QuoteResult::Ok(QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: 1_000_000, ..Default::default() });
     }
-    let dx = hi;
-    let (dy, fee_e6) = stableswap::quote_dx_to_dy(a, rin, rout, dx, fee_bps);
-    let price_e6 = if dx > 0 { dy.saturating_mul(1_000_000) / dx } else { 1_000_000 };
-    let _ = is_usdc_in;
-    QuoteOut { dy_e6: dx as u128, fee_e6, price_e6 } // 复用字段：返回 dx 放在 dy_e6
+    let id = pool.unwrap_or(POOL0);
+    let (p, ledgers) = match pool_ledgers(id) {
+        Ok(t) => t, Err(e) => return QuoteResult::Err(e),
+    };
+    let (i, j) = match p.orient(token_in, token_out) {
+        Some(t) => t, None => return QuoteResult::Err(Error::InvalidInput),
+    };
+    let live = match live_reserves(&ledgers, &pools::account(id)).await {
+        Ok(r) => r, Err(e) => return QuoteResult::Err(e),
+    };
+    exact_out_in(&p, i, live[i], live[j], dy_target_e6).into()
 }
 
 
 //新增实时成交（两笔 ICRC-1 转账 + 内部账本同步 + 刷新缓存）：
 #[ic_cdk::update]
 pub async fn swap_live(args: SwapArgs) -> StdResultSwap {
-    use crate::types::TokenId::*;
+    let _m = metrics::measure("swap_live");
+    let r = do_swap_live(args).await;
+    match &r {
+        StdResultSwap::Ok(_) => metrics::swap_outcome("swap_live", None),
+        StdResultSwap::Err(e) => metrics::swap_outcome("swap_live", Some(e)),
+    }
+    r
+}
+
+async fn do_swap_live(args: SwapArgs) -> StdResultSwap {
+    if let Err(e) = pause::check(PauseOp::Swap) { return StdResultSwap::Err(e); }
 
     if args.dx_e6 == 0 {
-        return StdResultSwap::Err("amountIn=0".into());
+        return StdResultSwap::Err(Error::InvalidInput);
     }
 
-    // ---------- 读元信息 & 读 live 储备，计算成交 ----------
-    let meta = if let Some(m) = get_token_meta() { m } else {
-        return StdResultSwap::Err("token meta not set".into());
+    // ---------- 读账本 & 读 live 储备，计算成交 ----------
+    let id = args.pool.unwrap_or(POOL0);
+    let (p, ledgers) = match pool_ledgers(id) {
+        Ok(t) => t, Err(e) => return StdResultSwap::Err(e),
     };
-
-    let pool_acc = get_pool_account("USDC_USDT".to_string());
-    let (u_res, v_res) = futures::future::join(
-        icrc1_balance_of(meta.ckusdc, pool_acc.clone()),
-        icrc1_balance_of(meta.ckusdt, pool_acc.clone()),
-    ).await;
-
-    let (u_nat, v_nat) = match (u_res, v_res) {
-        (Ok(a), Ok(b)) => (a, b),
-        (ua, vb) => return StdResultSwap::Err(format!("read pool live err: usdc={ua:?}, usdt={vb:?}")),
+    let (i, j) = match p.orient(args.token_in, args.token_out) {
+        Some(t) => t, None => return StdResultSwap::Err(Error::InvalidInput),
+    };
+    let pool_acc = pools::account(id);
+    let live = match live_reserves(&ledgers, &pool_acc).await {
+        Ok(r) => r, Err(e) => return StdResultSwap::Err(e),
     };
-
-    let ru_e6 = ext_to_e6(&u_nat, meta.dec_usdc);
-    let rv_e6 = ext_to_e6(&v_nat, meta.dec_usdt);
-
-    let (is_usdc_in, rin, rout) = if let Some(t) = orient_pair(&args.token_in, &args.token_out, ru_e6, rv_e6) { t }
-    else { return StdResultSwap::Err("unsupported token pair".into()); };
-
-    if rin == 0 || rout == 0 { return StdResultSwap::Err("pool empty".into()); }
-
-    let (a_amp, fee_bps) = STATE.with(|s| {
-        let st = s.borrow();
-        (st.pool.a_amp as u128, st.pool.fee_bps as u32)
-    });
-    let a_norm = if a_amp < 1_000_000 { a_amp * 1_000_000 } else { a_amp };
 
     let dx_e6 = args.dx_e6 as u128;
-    let (dy_e6, fee_e6) = stableswap::quote_dx_to_dy(a_norm, rin, rout, dx_e6, fee_bps);
+    // 按 live 储备计价，含动态费率与脱锚保护（见 swap::price_swap）
+    let quoted = STATE.with(|s| swap_mod::price_swap(&s.borrow(), &p, i, live[i], live[j], dx_e6, now()));
+    let (dy_e6, fee_e6, _rate) = match quoted {
+        Ok(t) => t,
+        Err(e) => return StdResultSwap::Err(e),
+    };
 
-    if dy_e6 == 0 { return StdResultSwap::Err("dy=0".into()); }
-    if dy_e6 < (args.min_dy_e6 as u128) { return StdResultSwap::Err("slippage".into()); }
+    if dy_e6 == 0 { return StdResultSwap::Err(Error::InvalidInput); } // 输入过小，输出取整为 0
+    if dy_e6 < (args.min_dy_e6 as u128) { return StdResultSwap::Err(Error::SlippageExceeded { min: args.min_dy_e6, got: dy_e6 }); }
 
     // ---------- 执行两笔 ICRC-1 转账 ----------
     let user_sub = derive_subaccount(args.account.owner).to_vec();
     let to_user  = Account { owner: canister_principal(), subaccount: Some(user_sub.clone()) };
+    let ((in_ledger, dec_in), (out_ledger, dec_out)) = (ledgers[i], ledgers[j]);
 
     // in: 用户子 -> 池子子
-    let (in_ledger, out_ledger, dec_in, dec_out) = match (&args.token_in, &args.token_out) {
-        (USDC, USDT) => (meta.ckusdc, meta.ckusdt, meta.dec_usdc, meta.dec_usdt),
-        (USDT, USDC) => (meta.ckusdt, meta.ckusdc, meta.dec_usdt, meta.dec_usdc),
-        _ => return StdResultSwap::Err("unsupported token pair".into()),
-    };
-
     let arg_in = Icrc1TransferArg {
         from_subaccount: Some(user_sub.clone()),
         to: pool_acc.clone(),
-        amount: int_e6_to_ext(dx_e6, dec_in),
+        amount: e6_to_ext(dx_e6, dec_in, Rounding::Up),
         fee: None, memo: None, created_at_time: None,
     };
-    if let Err(e) = do_icrc1_transfer(in_ledger, arg_in).await {
-        return StdResultSwap::Err(format!("debit user_sub failed: {e}"));
-    }
+    let block_in = match do_icrc1_transfer(in_ledger, arg_in).await {
+        Ok(b) => b,
+        Err(e) => return StdResultSwap::Err(e),
+    };
 
     // out: 池子子 -> 用户子
     let arg_out = Icrc1TransferArg {
         from_subaccount: pool_acc.subaccount.clone(),
         to: to_user.clone(),
-        amount: int_e6_to_ext(dy_e6, dec_out),
+        amount: e6_to_ext(dy_e6, dec_out, Rounding::Down),
         fee: None, memo: None, created_at_time: None,
     };
-    if let Err(e) = do_icrc1_transfer(out_ledger, arg_out).await {
-        // 尝试退款（尽力而为）
-        let _ = do_icrc1_transfer(
-            in_ledger,
-            Icrc1TransferArg {
-                from_subaccount: pool_acc.subaccount.clone(),
-                to: to_user.clone(),
-                amount: int_e6_to_ext(dx_e6, dec_in),
-                fee: None, memo: None, created_at_time: None,
-            },
-        ).await;
-        return StdResultSwap::Err(format!("credit user_sub failed: {e}"));
-    }
-
-    // ---------- 关键：避免嵌套可变借用 ----------
-    // 1) 先记手续费（内部会单独借用 STATE）
-    positions::accrue_swap_fee(args.token_in.clone(), fee_e6);
+    let block_out = match do_icrc1_transfer(out_ledger, arg_out).await {
+        Ok(b) => b,
+        Err(e) => {
+            // 尝试退款（尽力而为）
+            let _ = do_icrc1_transfer(
+                in_ledger,
+                Icrc1TransferArg {
+                    from_subaccount: pool_acc.subaccount.clone(),
+                    to: to_user.clone(),
+                    amount: e6_to_ext(dx_e6, dec_in, Rounding::Up),
+                    fee: None, memo: None, created_at_time: None,
+                },
+            ).await;
+            return StdResultSwap::Err(e);
+        }
+    };
 
-    // 2) 再单独进入一次 borrow_mut，更新池内储备
-    let dx_net = dx_e6.saturating_sub(fee_e6);
-    STATE.with(|cell| {
+    // ---------- 内部账本：手续费入金库、更新池内储备与统计（转账期间池子可能已变，重新读取） ----------
+    let applied = STATE.with(|cell| {
         let mut st = cell.borrow_mut();
-        if is_usdc_in {
-            st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_add(dx_net);
-            st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_sub(dy_e6);
-        } else {
-            st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_add(dx_net);
-            st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_sub(dy_e6);
-        }
+        let mut p = pools::load(&st, id)?;
+        swap_mod::apply_trade(&mut st, &mut p, (i, j), dx_e6, dy_e6, fee_e6, now())
     });
+    if let Err(e) = applied { return StdResultSwap::Err(e); }
+    record_payout_dust(&ledgers[j..=j], &[dy_e6]);
 
     // 刷新该用户 live 可用额（异步即可；需要强一致可改为 blocking 版本）
     ic_cdk::spawn(async move { let _ = do_refresh_available_for(args.account.owner).await; });
+    let mut tx = vec![
+        ("acct", Icrc3Value::account(&args.account)),
+        ("tin", Icrc3Value::token(args.token_in)),
+        ("tout", Icrc3Value::token(args.token_out)),
+        ("dx", Icrc3Value::nat(dx_e6)),
+        ("dy", Icrc3Value::nat(dy_e6)),
+        ("fee", Icrc3Value::nat(fee_e6)),
+    ];
+    if id != POOL0 { tx.push(("pool", Icrc3Value::nat(id))); }
+    let block = icrc3::log(icrc3::BT_SWAP, tx);
     // 记录 Swap 事件（统一 who = 调用者 principal）
-    let who = format!("{}", args.account.owner.to_text());
     events::push(Event::Swap {
-        who,
-        dx_e6: dx_e6,       // 输入
-        dy_e6: dy_e6,       // 输出
-        ts: now(),
+        who: args.account.owner.to_text(), sub: args.account.subaccount.clone(),
+        token_in: args.token_in, token_out: args.token_out,
+        dx_e6, dy_e6, fee_e6,
+        pool: STATE.with(|s| pools::snapshot(&s.borrow(), id)), pool_id: Some(id),
+        ledger_blocks: vec![block_in, block_out], icrc3_block: Some(block), ts: now(),
     });
-
+    pause::after_swap();
 
     StdResultSwap::Ok(SwapOk { dy_e6 })
 }
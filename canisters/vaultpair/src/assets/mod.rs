@@ -1,10 +1,18 @@
 // canisters/vaultpair/src/assets/mod.rs
 use crate::{
   types::{Account,AmountE6,TokenId,SubBalance},
-  state::{STATE,push_event,now,DEFAULT_SUB_ID},
-  error::{Result,Error},
-  events::Event
+  state::{STATE,push_event,now,skey,DEFAULT_SUB_ID},
+  error::{Result,Error,DepositError},
+  events::Event,
+  icrc3::{self,Icrc3Value},
+  icrc::{self,TransferFromArgs},
+  math::rates::{self,Rounding},
+  pools,
 };
+use candid::{Nat,Principal};
+use num_traits::ToPrimitive;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 
 const AIRDROP_E6:u128 = 100_000 * 1_000_000;    // ✅ 10万（e6）
 
@@ -64,28 +72,29 @@ pub fn deposit_demo(acct:Account,token:TokenId,amount:AmountE6)->Result<()>{
 
   let res:Result<()> = STATE.with(|s|{
     let mut st=s.borrow_mut();
-    let ok = match token{
+    let short = match token{
       TokenId::USDC=>{
         let b=st.user_usdc.entry(who.clone()).or_default();
-        if *b<amount { false } else { *b-=amount; *st.user_sub_usdc.entry(key.clone()).or_default() += amount; true }
+        if *b<amount { Some(*b) } else { *b-=amount; *st.user_sub_usdc.entry(key.clone()).or_default() += amount; None }
       }
       TokenId::USDT=>{
         let b=st.user_usdt.entry(who.clone()).or_default();
-        if *b<amount { false } else { *b-=amount; *st.user_sub_usdt.entry(key.clone()).or_default() += amount; true }
+        if *b<amount { Some(*b) } else { *b-=amount; *st.user_sub_usdt.entry(key.clone()).or_default() += amount; None }
       }
       TokenId::BOB =>{
         let b=st.user_bob.entry(who.clone()).or_default();
-        if *b<amount { false } else { *b-=amount; *st.user_sub_bob.entry(key.clone()).or_default()  += amount; true }
+        if *b<amount { Some(*b) } else { *b-=amount; *st.user_sub_bob.entry(key.clone()).or_default()  += amount; None }
       }
       TokenId::ICP =>{
         let b=st.user_icp.entry(who.clone()).or_default();
-        if *b<amount { false } else { *b-=amount; *st.user_sub_icp.entry(key.clone()).or_default()  += amount; true }
+        if *b<amount { Some(*b) } else { *b-=amount; *st.user_sub_icp.entry(key.clone()).or_default()  += amount; None }
       }
     };
-    if ok { Ok(()) } else { Err(Error::BalanceTooLow) }
+    match short { None=>Ok(()), Some(have)=>Err(Error::InsufficientBalance{token,have,need:amount}) }
   });
   res?;
-  push_event(Event::Deposit{who,token,amount,ts:now()});
+  let block=icrc3::log(icrc3::BT_DEPOSIT, vec![("acct", Icrc3Value::account(&acct)), ("tok", Icrc3Value::token(token)), ("amt", Icrc3Value::nat(amount))]);
+  push_event(Event::Deposit{who,sub:acct.subaccount.clone(),token,amount,ledger_block:None,icrc3_block:Some(block),ts:now()});
   Ok(())
 }
 
@@ -97,27 +106,143 @@ pub fn withdraw_demo(acct:Account,token:TokenId,amount:AmountE6)->Result<()>{
 
   let res:Result<()> = STATE.with(|s|{
     let mut st=s.borrow_mut();
-    let ok = match token{
+    let short = match token{
       TokenId::USDC=>{
         let sb=st.user_sub_usdc.entry(key.clone()).or_default();
-        if *sb<amount { false } else { *sb-=amount; *st.user_usdc.entry(who.clone()).or_default() += amount; true }
+        if *sb<amount { Some(*sb) } else { *sb-=amount; *st.user_usdc.entry(who.clone()).or_default() += amount; None }
       }
       TokenId::USDT=>{
         let sb=st.user_sub_usdt.entry(key.clone()).or_default();
-        if *sb<amount { false } else { *sb-=amount; *st.user_usdt.entry(who.clone()).or_default() += amount; true }
+        if *sb<amount { Some(*sb) } else { *sb-=amount; *st.user_usdt.entry(who.clone()).or_default() += amount; None }
       }
       TokenId::BOB =>{
         let sb=st.user_sub_bob.entry(key.clone()).or_default();
-        if *sb<amount { false } else { *sb-=amount; *st.user_bob.entry(who.clone()).or_default()  += amount; true }
+        if *sb<amount { Some(*sb) } else { *sb-=amount; *st.user_bob.entry(who.clone()).or_default()  += amount; None }
       }
       TokenId::ICP =>{
         let sb=st.user_sub_icp.entry(key.clone()).or_default();
-        if *sb<amount { false } else { *sb-=amount; *st.user_icp.entry(who.clone()).or_default()  += amount; true }
+        if *sb<amount { Some(*sb) } else { *sb-=amount; *st.user_icp.entry(who.clone()).or_default()  += amount; None }
       }
     };
-    if ok { Ok(()) } else { Err(Error::BalanceTooLow) }
+    match short { None=>Ok(()), Some(have)=>Err(Error::InsufficientBalance{token,have,need:amount}) }
   });
   res?;
-  push_event(Event::Withdraw{who,token,amount,ts:now()});
+  let block=icrc3::log(icrc3::BT_WITHDRAW, vec![("acct", Icrc3Value::account(&acct)), ("tok", Icrc3Value::token(token)), ("amt", Icrc3Value::nat(amount))]);
+  push_event(Event::Withdraw{who,sub:acct.subaccount.clone(),token,amount,ledger_block:None,icrc3_block:Some(block),ts:now()});
   Ok(())
 }
+
+// ---------- 子账户内账 = 派生子账户的链上余额 ----------
+// refresh_available_for 与 deposit 共用一条落账路径：读 live 余额后整体覆盖内账，不在内账上另行加减，
+// 因此并发的刷新与入金不会重复记账。每次读余额前取一个递增序号，落账时只接受比该账户已落账序号更新的读数：
+// 先发出、后落地的读数（可能读到转账之前的余额）直接丢弃，不会把已落账的入金冲掉。
+// 序号只在飞行中的调用之间有意义，升级前 canister 已停止、没有未完成的调用，所以不进 State。
+
+/// (最近发出的序号, 各 (用户, 币种) 已落账的序号)
+type RefreshSeq=(u64,BTreeMap<(Principal,TokenId),u64>);
+
+thread_local!{
+  static REFRESH_SEQ:RefCell<RefreshSeq> = const { RefCell::new((0,BTreeMap::new())) };
+}
+
+/// 发出余额读取前调用，返回本次读数的序号
+pub(crate) fn begin_refresh()->u64{
+  REFRESH_SEQ.with(|r|{ let mut r=r.borrow_mut(); r.0+=1; r.0 })
+}
+
+/// 落账 user 派生子账户上 token 的 live 余额（外部单位，向下取整到 e6，尾数记入 DustBook）；
+/// 该账户已有更新的读数落账时丢弃本次读数，返回是否落账
+pub(crate) fn apply_live_balance(st:&mut crate::state::State,user:Principal,token:TokenId,seq:u64,balance_ext:&Nat,dec:u8)->bool{
+  let fresh=REFRESH_SEQ.with(|r|{
+    let mut r=r.borrow_mut();
+    let last=r.1.entry((user,token)).or_default();
+    if seq<=*last { return false; }
+    *last=seq;
+    true
+  });
+  if !fresh { return false; }
+  let (e6,dust)=rates::rescale(balance_ext.0.to_u128().unwrap_or(u128::MAX),dec,rates::BOOK_DECIMALS,Rounding::Down);
+  crate::ledger_book::record_user_dust(st,user,token,dust);
+  let book = match token{
+    TokenId::USDC=>&mut st.user_sub_usdc,
+    TokenId::USDT=>&mut st.user_sub_usdt,
+    TokenId::BOB =>&mut st.user_sub_bob,
+    TokenId::ICP =>&mut st.user_sub_icp,
+  };
+  book.insert(skey(&user),e6);
+  true
+}
+
+// ---------- ICRC-2 代扣入金（用户默认账户 -> 派生子账户） ----------
+// 用户先对本 canister 做 icrc2_approve（额度需覆盖 amount + 账本 fee），一次调用完成入金，无需再轮询 refresh_available_for
+
+/// 从 user 的默认账户代扣 amount（e6，换成链上单位向上取整）到其派生子账户（即 get_my_deposit_target），返回账本块号。
+/// 代扣成功后按上面的口径刷新该币的子账户内账（不直接加 amount）；刷新读取失败时由下一次 refresh_available_for 补上。
+/// 授权不足返回 DepositError::InsufficientAllowance
+pub async fn deposit(user:Principal,token:TokenId,amount:AmountE6)->core::result::Result<Nat,DepositError>{
+  if amount==0 {return Err(Error::InvalidInput.into())}
+  let (ledger,dec)=STATE.with(|s| pools::token_ledger(&s.borrow(),token.into())).ok_or(Error::TokenMetaNotSet)?;
+  let (amount_ext,_)=rates::rescale(amount,rates::BOOK_DECIMALS,dec,Rounding::Up);
+  let to=Account{owner:icrc::canister_principal(),subaccount:Some(icrc::derive_subaccount(user).to_vec())};
+  let arg=TransferFromArgs{
+    spender_subaccount:None,
+    from:Account{owner:user,subaccount:None},
+    to:to.clone(),
+    amount:Nat::from(amount_ext),
+    fee:None,memo:None,created_at_time:None,
+  };
+  let ledger_block=icrc::icrc2_transfer_from(ledger,arg).await?;
+
+  // 读数在代扣执行之后发出，必然包含这笔入金
+  let seq=begin_refresh();
+  if let Ok(bal)=icrc::icrc1_balance_of(ledger,to).await {
+    STATE.with(|s| apply_live_balance(&mut s.borrow_mut(),user,token,seq,&bal,dec));
+  }
+  let acct=Account{owner:user,subaccount:None};
+  let block=icrc3::log(icrc3::BT_DEPOSIT, vec![
+    ("acct", Icrc3Value::account(&acct)), ("tok", Icrc3Value::token(token)), ("amt", Icrc3Value::nat(amount)),
+    ("blk", Icrc3Value::nat(ledger_block.clone())),
+  ]);
+  push_event(Event::Deposit{who:user.to_text(),sub:None,token,amount,ledger_block:Some(ledger_block.clone()),icrc3_block:Some(block),ts:now()});
+  Ok(ledger_block)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::state::State;
+  use crate::error::LedgerErrorCode;
+  use crate::icrc::TransferFromError;
+
+  fn p(n:u8)->Principal{ Principal::from_slice(&[n;29]) }
+
+  /// 入金与刷新都以 live 余额覆盖内账：先发出、后落地的读数被丢弃，同一笔入金既不重复记账也不丢失
+  #[test]
+  fn deposit_and_refresh_interleavings_credit_exactly_once(){
+    let mut st=State::default();
+    let (user,key)=(p(1),skey(&p(1)));
+    // 刷新 A 在代扣之前读到 0；代扣（100，8 位小数）执行后入金回调读到 100
+    let a=begin_refresh();
+    let d=begin_refresh();
+    assert!(apply_live_balance(&mut st,user,TokenId::ICP,d,&Nat::from(10_000_000_000u64),8));
+    assert!(!apply_live_balance(&mut st,user,TokenId::ICP,a,&Nat::from(0u8),8));
+    assert_eq!(pools::sub_balance(&st,&key,TokenId::ICP.into()),100_000_000);
+    // 刷新 B 在代扣之后发出、先于入金回调落地：入金回调不再叠加
+    let d2=begin_refresh();
+    let b=begin_refresh();
+    assert!(apply_live_balance(&mut st,user,TokenId::ICP,b,&Nat::from(20_000_000_099u64),8));
+    assert!(!apply_live_balance(&mut st,user,TokenId::ICP,d2,&Nat::from(20_000_000_099u64),8));
+    assert_eq!(pools::sub_balance(&st,&key,TokenId::ICP.into()),200_000_000);
+    assert_eq!(st.dust.as_ref().and_then(|d| d.user.get(&(user,TokenId::ICP)).copied()),Some(99));
+    // 序号按账户与币种分别比较
+    assert!(apply_live_balance(&mut st,user,TokenId::USDT,a,&Nat::from(5u8),6));
+    assert!(st.user_shares.is_empty());
+  }
+
+  /// 授权不足不落进 LedgerErrorCode，带回额度另行表达
+  #[test]
+  fn allowance_error_is_typed(){
+    assert_eq!(LedgerErrorCode::try_from(TransferFromError::InsufficientAllowance{allowance:Nat::from(3u8)}),Err(Nat::from(3u8)));
+    assert_eq!(LedgerErrorCode::try_from(TransferFromError::TooOld),Ok(LedgerErrorCode::TooOld));
+  }
+}
@@ -0,0 +1,114 @@
+// canisters/vaultpair/src/certified.rs
+// certified data 只有一个 32 字节槽位，由 ICRC-3 tip 与池子状态共用一棵 hash tree：
+//   root = fork( fork(last_block_hash, last_block_index),  pool/{a_amp, fee_bps, reserve_usdc, reserve_usdt, total_shares, virtual_price_e6} )
+// 标签按字节序排列；叶子均为 LEB128 编码的 Nat。
+// 各读接口只返回自己那一支，另一支剪枝为摘要（pruned），根摘要不变，客户端可对照证书中的 certified_data 校验。
+// 客户端校验见 canisters/vaultpair_verify。
+use candid::{CandidType, Nat};
+use ic_certification::{fork, labeled, leaf, pruned, HashTree};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    explore,
+    state::{State, STATE},
+    types::PoolInfo,
+};
+
+/// get_pool_info_certified 的返回：证书 + 池子一支的 witness（CBOR hash tree）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PoolInfoCertified {
+    pub pool: PoolInfo,
+    pub certificate: serde_bytes::ByteBuf,
+    pub witness: serde_bytes::ByteBuf,
+}
+
+fn leb(n: impl Into<Nat>) -> HashTree {
+    let mut out = Vec::new();
+    n.into().encode(&mut out).expect("leb128");
+    leaf(out)
+}
+
+fn pool_tree(p: &PoolInfo) -> HashTree {
+    labeled("pool", fork(
+        fork(
+            fork(labeled("a_amp", leb(p.a_amp)), labeled("fee_bps", leb(p.fee_bps))),
+            fork(labeled("reserve_usdc", leb(p.reserve_usdc)), labeled("reserve_usdt", leb(p.reserve_usdt))),
+        ),
+        fork(labeled("total_shares", leb(p.total_shares)), labeled("virtual_price_e6", leb(p.virtual_price_e6))),
+    ))
+}
+
+/// (ICRC-3 tip 一支, 池子一支)；尚无区块时 tip 一支为空
+fn branches(st: &State) -> (HashTree, HashTree) {
+    let tip = st.icrc3.as_ref().and_then(|l| l.tip())
+        .map(|(idx, hash)| crate::icrc3::tip_tree(idx, hash))
+        .unwrap_or_else(ic_certification::empty);
+    (tip, pool_tree(&explore::pool_info(st)))
+}
+
+pub fn root_hash(st: &State) -> [u8; 32] {
+    let (tip, pool) = branches(st);
+    fork(tip, pool).digest()
+}
+
+/// ICRC-3 tip 证书用：池子一支剪枝
+pub fn tip_witness(st: &State) -> HashTree {
+    let (tip, pool) = branches(st);
+    fork(tip, pruned(pool.digest()))
+}
+
+/// 池子证书用：tip 一支剪枝
+pub fn pool_witness(st: &State) -> HashTree {
+    let (tip, pool) = branches(st);
+    fork(pruned(tip.digest()), pool)
+}
+
+/// 重新计算根摘要并写入 certified data。凡改动池子参数 / 储备 / 份额或追加 ICRC-3 区块的 update 都要调用
+/// （icrc3::commit 已包含）；不可在 STATE 借用期间调用
+pub fn certify() {
+    let root = STATE.with(|s| root_hash(&s.borrow()));
+    ic_cdk::api::set_certified_data(&root);
+}
+
+/// 仅 query 调用可拿到证书；update 中返回 None
+pub fn pool_info_certified() -> Option<PoolInfoCertified> {
+    let certificate = ic_cdk::api::data_certificate()?;
+    STATE.with(|s| {
+        let st = s.borrow();
+        let witness = serde_cbor::to_vec(&pool_witness(&st)).ok()?;
+        Some(PoolInfoCertified {
+            pool: explore::pool_info(&st),
+            certificate: serde_bytes::ByteBuf::from(certificate),
+            witness: serde_bytes::ByteBuf::from(witness),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witnesses_share_the_certified_root() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 1_000;
+        st.pool.total_shares = 2_000;
+        let before = root_hash(&st);
+        assert_eq!(pool_witness(&st).digest(), before);
+        assert_eq!(tip_witness(&st).digest(), before);
+
+        crate::icrc3::append(&mut st, crate::icrc3::BT_SWAP, 1, vec![]);
+        let root = root_hash(&st);
+        assert_ne!(root, before);
+        assert_eq!(pool_witness(&st).digest(), root);
+        assert_eq!(tip_witness(&st).digest(), root);
+
+        // 客户端侧：从 witness 还原并校验池子字段
+        let cbor = serde_cbor::to_vec(&pool_witness(&st)).unwrap();
+        let got = vaultpair_verify::verify_witness(&root, &cbor).unwrap();
+        assert_eq!((got.reserve_usdc, got.total_shares), (1_000, 2_000));
+
+        st.pool.reserve_usdt = 1;
+        assert!(vaultpair_verify::verify_witness(&root_hash(&st), &cbor).is_err());
+    }
+}
@@ -1,19 +1,71 @@
-use candid::CandidType;
+use candid::{CandidType, Nat, Principal};
 use serde::{Deserialize, Serialize};
 use core::fmt;
+use crate::math::stableswap::MathError;
+use crate::pause::PauseOp;
+use crate::pools::PoolId;
+use crate::types::{AmountE6, Coin, TokenId};
+
+/// 账本（ICRC-1/2）调用失败的具体原因：镜像 ICRC TransferError / TransferFromError，另加跨 canister 调用被拒
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum LedgerErrorCode {
+    CallRejected { code: i32, message: String },
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    TemporarilyUnavailable,
+    Duplicate { duplicate_of: Nat },
+    GenericError { error_code: Nat, message: String },
+}
 
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+#[allow(clippy::enum_variant_names)] // LedgerError 为对外 candid 名称，保持不变
 pub enum Error {
     InvalidInput,
     InsufficientLiquidity,
-    BalanceTooLow,
-    SlippageExceeded,
+    /// 内账余额不足（e6 口径）
+    InsufficientBalance { token: TokenId, have: AmountE6, need: AmountE6 },
+    InsufficientShares { have: AmountE6, need: AmountE6 },
+    /// 实际成交量低于 min_dy（e6 口径）
+    SlippageExceeded { min: AmountE6, got: AmountE6 },
     PriceImpactTooHigh,
     DInvariantBroken,
+    UnsupportedPair { token_in: TokenId, token_out: TokenId },
+    /// set_token_meta 尚未调用
+    TokenMetaNotSet,
+    InvalidPrincipal(String),
+    /// 调用者无权执行该操作（非 controller / 非暂停角色 / 非账户本人）
+    Unauthorized(Principal),
+    /// 该操作已被紧急暂停（见 pause.rs）
+    Paused { op: PauseOp },
+    /// 池子编号不存在
+    PoolNotFound(PoolId),
+    /// 该代币对已有池子（返回已有的池子编号）
+    PoolExists(PoolId),
+    /// 还没有对账报告：先调 admin_reconcile_pool_from_live
+    NoReconcileReport,
+    /// 最近一份对账报告已过期（report_ts 为生成时间，秒）：重新生成后再更正
+    StaleReconcileReport { report_ts: u64 },
+    /// 账本调用失败：ledger 为对应 ICRC 账本 canister
+    LedgerError { ledger: Principal, code: LedgerErrorCode },
+    Math(MathError),
     Internal(String),
 }
 pub type Result<T> = core::result::Result<T, Error>;
 
+/// deposit 专用错误：授权不足单列（LedgerErrorCode 是已发布类型，不再加分支），其余沿用 Error
+#[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
+pub enum DepositError {
+    /// 用户给本 canister 的授权不足：allowance 为当前授权额（最小单位）
+    InsufficientAllowance { ledger: Principal, allowance: Nat },
+    Failed(Error),
+}
+impl From<Error> for DepositError {
+    fn from(e: Error) -> Self { DepositError::Failed(e) }
+}
+
 // 把 &str / String 统一映射到 Internal(String)
 impl From<&'static str> for Error {
     fn from(s: &'static str) -> Self { Error::Internal(s.to_string()) }
@@ -21,6 +73,35 @@ impl From<&'static str> for Error {
 impl From<String> for Error {
     fn from(s: String) -> Self { Error::Internal(s) }
 }
+impl From<MathError> for Error {
+    fn from(e: MathError) -> Self { Error::Math(e) }
+}
+
+impl Error {
+    /// 所有账本调用失败都经此构造，顺带计入 /metrics 的 ledger_errors
+    pub fn ledger(ledger: Principal, code: LedgerErrorCode) -> Self {
+        crate::metrics::ledger_error(ledger, &crate::metrics::kind_of(&code));
+        Error::LedgerError { ledger, code }
+    }
+    /// 池内币余额不足：LP 份额不在对外 TokenId 里，报 InsufficientShares
+    pub fn insufficient(coin: Coin, have: AmountE6, need: AmountE6) -> Self {
+        match coin.token() {
+            Some(token) => Error::InsufficientBalance { token, have, need },
+            None => Error::InsufficientShares { have, need },
+        }
+    }
+    /// 池内币对不支持：含 LP 时无法用 TokenId 表达，报 InvalidInput
+    pub fn unsupported(token_in: Coin, token_out: Coin) -> Self {
+        match (token_in.token(), token_out.token()) {
+            (Some(token_in), Some(token_out)) => Error::UnsupportedPair { token_in, token_out },
+            _ => Error::InvalidInput,
+        }
+    }
+    /// ic_cdk 跨 canister 调用被拒 → LedgerError::CallRejected
+    pub fn call_rejected(ledger: Principal, (code, message): (ic_cdk::api::call::RejectionCode, String)) -> Self {
+        Error::ledger(ledger, LedgerErrorCode::CallRejected { code: code as i32, message })
+    }
+}
 
 // 可选：便于日志输出
 impl fmt::Display for Error {
@@ -1,10 +1,20 @@
-use crate::types::{AmountE6, TokenId};
-use crate::state::STATE;
-use candid::CandidType;
+// canisters/vaultpair/src/events.rs
+// 事件日志：写入 stable memory（StableBTreeMap<id, Event>），id 单调递增、永不复用。
+// 本地不再按条数裁剪；配置保留策略后，超出部分按批搬到 archive canister（成功后才本地删除）。
+// 存储格式带版本标签（StoredEvent::V2）；无标签的旧字节按 v1 解码为 Event::Legacy。
+use crate::types::{AmountE6, Coin, TokenId};
+use crate::pools::PoolId;
+use crate::state::{State, STATE};
+use crate::memory::{self, VMem};
+use crate::math::stableswap;
+use candid::{CandidType, Decode, Encode, Nat, Principal};
+use ic_stable_structures::{storable::Bound, StableBTreeMap, Storable};
 use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, cell::{Cell, RefCell}};
 
+/// v1 事件（旧格式）：旧 stable 数据按此解码；get_events / get_events_latest 仍按此返回
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
-pub enum Event {
+pub enum EventV1 {
     Swap      { who: String, dx_e6: AmountE6, dy_e6: AmountE6, ts: u64 },
     AddLiq    { who: String, usdc: AmountE6, usdt: AmountE6, shares: AmountE6, ts: u64 },
     RemoveLiq { who: String, shares: AmountE6, usdc: AmountE6, usdt: AmountE6, ts: u64 },
@@ -12,16 +22,467 @@ pub enum Event {
     Withdraw  { who: String, token: TokenId, amount: AmountE6, ts: u64 },
 }
 
-pub const MAX_EVENTS: usize = 2000;
+/// 操作完成后的池子状态（e6）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolSnapshot {
+    pub reserve_usdc: AmountE6,
+    pub reserve_usdt: AmountE6,
+    pub total_shares: AmountE6,
+    pub virtual_price_e6: u128,
+}
+
+/// 事件（v2）。sub 为调用方传入的子账户；ledger_blocks 为 ckUSDC/ckUSDT 账本上的转账块号（按发生顺序），
+/// icrc3_block 为本 canister ICRC-3 日志中对应的区块号。
+/// pool_id 为所在池子（None = 多池之前的事件，即 0 号池）；非 0 号池的 usdc / usdt 与 pool 快照按该池 coins 顺序记第 0 / 1 个币
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum Event {
+    Swap {
+        who: String, sub: Option<Vec<u8>>,
+        token_in: Coin, token_out: Coin,
+        dx_e6: AmountE6, dy_e6: AmountE6, fee_e6: AmountE6,
+        pool: PoolSnapshot, pool_id: Option<PoolId>,
+        ledger_blocks: Vec<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    AddLiq {
+        who: String, sub: Option<Vec<u8>>,
+        usdc: AmountE6, usdt: AmountE6, shares: AmountE6,
+        pool: PoolSnapshot, pool_id: Option<PoolId>,
+        ledger_blocks: Vec<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    RemoveLiq {
+        who: String, sub: Option<Vec<u8>>,
+        shares: AmountE6, usdc: AmountE6, usdt: AmountE6,
+        pool: PoolSnapshot, pool_id: Option<PoolId>,
+        ledger_blocks: Vec<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    Deposit {
+        who: String, sub: Option<Vec<u8>>, token: TokenId, amount: AmountE6,
+        ledger_block: Option<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    Withdraw {
+        who: String, sub: Option<Vec<u8>>, token: TokenId, amount: AmountE6,
+        ledger_block: Option<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    /// 领取 LP 手续费（此前记为两条 Withdraw）
+    ClaimFee {
+        who: String, sub: Option<Vec<u8>>, usdc_e6: AmountE6, usdt_e6: AmountE6, pool_id: Option<PoolId>,
+        ledger_blocks: Vec<Nat>, icrc3_block: Option<u64>, ts: u64,
+    },
+    /// 控制者操作（设置 archive、保留策略等）
+    AdminAction { who: String, action: String, detail: String, ts: u64 },
+    /// 储备 / 份额对账
+    Reconcile { who: String, before: PoolSnapshot, after: PoolSnapshot, detail: String, ts: u64 },
+    /// 池子 / 代币参数变更
+    ParamChange { who: String, param: String, old: String, new: String, ts: u64 },
+    /// v1 旧事件，原样保留
+    Legacy(EventV1),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind { Swap, AddLiq, RemoveLiq, Deposit, Withdraw, ClaimFee, AdminAction, Reconcile, ParamChange }
+
+impl EventV1 {
+    fn kind(&self) -> EventKind {
+        match self {
+            EventV1::Swap { .. } => EventKind::Swap,
+            EventV1::AddLiq { .. } => EventKind::AddLiq,
+            EventV1::RemoveLiq { .. } => EventKind::RemoveLiq,
+            EventV1::Deposit { .. } => EventKind::Deposit,
+            EventV1::Withdraw { .. } => EventKind::Withdraw,
+        }
+    }
+
+    fn who(&self) -> &str {
+        match self {
+            EventV1::Swap { who, .. } | EventV1::AddLiq { who, .. } | EventV1::RemoveLiq { who, .. }
+            | EventV1::Deposit { who, .. } | EventV1::Withdraw { who, .. } => who,
+        }
+    }
+
+    fn ts(&self) -> u64 {
+        match self {
+            EventV1::Swap { ts, .. } | EventV1::AddLiq { ts, .. } | EventV1::RemoveLiq { ts, .. }
+            | EventV1::Deposit { ts, .. } | EventV1::Withdraw { ts, .. } => *ts,
+        }
+    }
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Swap { .. } => EventKind::Swap,
+            Event::AddLiq { .. } => EventKind::AddLiq,
+            Event::RemoveLiq { .. } => EventKind::RemoveLiq,
+            Event::Deposit { .. } => EventKind::Deposit,
+            Event::Withdraw { .. } => EventKind::Withdraw,
+            Event::ClaimFee { .. } => EventKind::ClaimFee,
+            Event::AdminAction { .. } => EventKind::AdminAction,
+            Event::Reconcile { .. } => EventKind::Reconcile,
+            Event::ParamChange { .. } => EventKind::ParamChange,
+            Event::Legacy(v1) => v1.kind(),
+        }
+    }
+
+    pub fn who(&self) -> &str {
+        match self {
+            Event::Swap { who, .. } | Event::AddLiq { who, .. } | Event::RemoveLiq { who, .. }
+            | Event::Deposit { who, .. } | Event::Withdraw { who, .. } | Event::ClaimFee { who, .. }
+            | Event::AdminAction { who, .. } | Event::Reconcile { who, .. } | Event::ParamChange { who, .. } => who,
+            Event::Legacy(v1) => v1.who(),
+        }
+    }
+
+    /// 秒；早期 positions::claim_fee 写入的是纳秒，这里统一折算
+    pub fn ts(&self) -> u64 {
+        let ts = match self {
+            Event::Swap { ts, .. } | Event::AddLiq { ts, .. } | Event::RemoveLiq { ts, .. }
+            | Event::Deposit { ts, .. } | Event::Withdraw { ts, .. } | Event::ClaimFee { ts, .. }
+            | Event::AdminAction { ts, .. } | Event::Reconcile { ts, .. } | Event::ParamChange { ts, .. } => *ts,
+            Event::Legacy(v1) => v1.ts(),
+        };
+        if ts > 1_000_000_000_000 { ts / 1_000_000_000 } else { ts }
+    }
+
+    /// 成交量口径 (dx, dy)；非 swap 返回 None
+    pub fn swap_amounts(&self) -> Option<(AmountE6, AmountE6)> {
+        match self {
+            Event::Swap { dx_e6, dy_e6, .. } | Event::Legacy(EventV1::Swap { dx_e6, dy_e6, .. }) => Some((*dx_e6, *dy_e6)),
+            _ => None,
+        }
+    }
+
+    /// 降级为 v1（旧接口用）；v1 无对应类型的事件返回 None
+    pub fn to_v1(&self) -> Option<EventV1> {
+        Some(match self.clone() {
+            Event::Swap { who, dx_e6, dy_e6, ts, .. } => EventV1::Swap { who, dx_e6, dy_e6, ts },
+            Event::AddLiq { who, usdc, usdt, shares, ts, .. } => EventV1::AddLiq { who, usdc, usdt, shares, ts },
+            Event::RemoveLiq { who, shares, usdc, usdt, ts, .. } => EventV1::RemoveLiq { who, shares, usdc, usdt, ts },
+            Event::Deposit { who, token, amount, ts, .. } => EventV1::Deposit { who, token, amount, ts },
+            Event::Withdraw { who, token, amount, ts, .. } => EventV1::Withdraw { who, token, amount, ts },
+            Event::Legacy(v1) => v1,
+            Event::ClaimFee { .. } | Event::AdminAction { .. } | Event::Reconcile { .. } | Event::ParamChange { .. } => return None,
+        })
+    }
+}
+
+/// stable 中的存储格式；新版本在此追加 V3…，旧版本解码后转换为当前 Event
+#[derive(CandidType, Deserialize)]
+enum StoredEvent { V2(Event) }
 
-/// 统一入口：写入事件并裁剪到 MAX_EVENTS
-pub fn push(ev: Event) {
+impl Storable for Event {
+    fn to_bytes(&self) -> Cow<'_, [u8]> { Cow::Owned(Encode!(&StoredEvent::V2(self.clone())).expect("encode event")) }
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match Decode!(&bytes, StoredEvent) {
+            Ok(StoredEvent::V2(ev)) => ev,
+            // 033 写入的无标签 v1 字节
+            Err(_) => Event::Legacy(Decode!(&bytes, EventV1).expect("decode event")),
+        }
+    }
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+/// 当前池子快照；virtual price = D / total_shares（无份额时为 1e6）
+pub fn pool_snapshot(st: &State) -> PoolSnapshot {
+    let p = &st.pool;
+    let amp = crate::swap::normalize_amp(p.a_amp as u128);
+    let virtual_price_e6 = match stableswap::get_d(amp, p.reserve_usdc, p.reserve_usdt) {
+        Ok(d) if p.total_shares > 0 => d.saturating_mul(1_000_000) / p.total_shares,
+        _ => p.virtual_price_e6,
+    };
+    PoolSnapshot {
+        reserve_usdc: p.reserve_usdc,
+        reserve_usdt: p.reserve_usdt,
+        total_shares: p.total_shares,
+        virtual_price_e6,
+    }
+}
+
+
+/// 单页最多返回条数 / 单次查询最多扫描条数（过滤条件很稀疏时靠 next 续扫）
+pub const MAX_PAGE: u32 = 500;
+pub const MAX_SCAN: u64 = 10_000;
+/// 每次搬到 archive 的条数
+pub const ARCHIVE_CHUNK: u64 = 1_000;
+
+thread_local! {
+    static LOG: RefCell<StableBTreeMap<u64, Event, VMem>> =
+        RefCell::new(StableBTreeMap::init(memory::get(memory::EVENTS)));
+    // 正在向 archive 推送（跨 await，不进 State）
+    static ARCHIVING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EventArchiveRange { pub canister_id: Principal, pub start: u64, pub end: u64 }
+
+/// 挂在 State.event_log 上：编号与保留策略（日志本体在 stable memory）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EventLogMeta {
+    pub next_id: u64,
+    // 本地最多保留条数；None 或未设置 archive 时不搬迁
+    pub max_live: Option<u64>,
+    pub archive_canister: Option<Principal>,
+    pub archives: Vec<EventArchiveRange>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct EventQuery {
+    /// 起始 id（含）；升序默认最旧，降序默认最新
+    pub from_id: Option<u64>,
+    pub limit: Option<u32>,
+    pub who: Option<Principal>,
+    pub kinds: Option<Vec<EventKind>>,
+    /// 时间范围（秒，闭区间）
+    pub from_ts: Option<u64>,
+    pub to_ts: Option<u64>,
+    pub descending: Option<bool>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EventRecord { pub id: u64, pub event: Event }
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct EventPage {
+    pub events: Vec<EventRecord>,
+    /// 下一页的 from_id；None 表示已到尽头
+    pub next: Option<u64>,
+    /// 本地最旧 id；更早的在 get_event_archives 列出的 archive 中
+    pub oldest_local_id: u64,
+    pub next_id: u64,
+}
+
+/* ---------------- 写入 ---------------- */
+
+/// 追加事件并返回 id（可在 STATE 借用期间调用）
+pub fn append(st: &mut State, ev: Event) -> u64 {
+    let meta = st.event_log.get_or_insert_with(Default::default);
+    let id = meta.next_id;
+    meta.next_id += 1;
+    LOG.with(|l| l.borrow_mut().insert(id, ev));
+    id
+}
+
+/// 统一入口：写入事件，必要时触发 archive 搬迁（不可在 STATE 借用期间调用）
+pub fn push(ev: Event) -> u64 {
+    let id = STATE.with(|s| append(&mut s.borrow_mut(), ev));
+    maybe_archive();
+    id
+}
+
+/// 旧版 State.events（Vec，最多 2000 条）迁入 stable 日志，按原顺序编号 0..n
+pub fn migrate_legacy(st: &mut State, events: Vec<EventV1>) {
+    for ev in events { append(st, Event::Legacy(ev)); }
+}
+
+/* ---------------- 读取 ---------------- */
+
+fn oldest_id(st: &State) -> u64 {
+    LOG.with(|l| l.borrow().first_key_value().map(|(k, _)| k))
+        .unwrap_or_else(|| next_id(st))
+}
+
+fn next_id(st: &State) -> u64 { st.event_log.as_ref().map_or(0, |m| m.next_id) }
+
+/// 第一个 ts >= from_ts 的 id（本地 id 连续且 ts 单调不减，二分即可）
+fn lower_bound_ts(st: &State, from_ts: u64) -> u64 {
+    let (mut lo, mut hi) = (oldest_id(st), next_id(st));
+    LOG.with(|l| {
+        let l = l.borrow();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match l.get(&mid) {
+                Some(ev) if ev.ts() < from_ts => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+    });
+    lo
+}
+
+pub fn query(st: &State, q: EventQuery) -> EventPage {
+    let limit = q.limit.unwrap_or(100).clamp(1, MAX_PAGE) as usize;
+    let who = q.who.map(|p| p.to_text());
+    let (from_ts, to_ts) = (q.from_ts.unwrap_or(0), q.to_ts.unwrap_or(u64::MAX));
+    let matches = |ev: &Event| {
+        who.as_deref().is_none_or(|w| ev.who() == w)
+            && q.kinds.as_ref().is_none_or(|ks| ks.contains(&ev.kind()))
+    };
+    let (oldest, end) = (oldest_id(st), next_id(st));
+    let mut events = Vec::new();
+    let mut next = None;
+
+    LOG.with(|l| {
+        let l = l.borrow();
+        let mut scanned = 0u64;
+        if q.descending.unwrap_or(false) {
+            if end == 0 { return; }
+            let start = q.from_id.unwrap_or(u64::MAX).min(end - 1);
+            for (id, ev) in l.range(..=start).rev() {
+                if ev.ts() < from_ts { return; }
+                if scanned == MAX_SCAN || events.len() == limit { next = Some(id); return; }
+                scanned += 1;
+                if ev.ts() <= to_ts && matches(&ev) { events.push(EventRecord { id, event: ev }); }
+            }
+        } else {
+            let start = q.from_id.unwrap_or(0).max(if from_ts > 0 { lower_bound_ts(st, from_ts) } else { 0 });
+            for (id, ev) in l.range(start..) {
+                if ev.ts() > to_ts { return; }
+                if scanned == MAX_SCAN || events.len() == limit { next = Some(id); return; }
+                scanned += 1;
+                if matches(&ev) { events.push(EventRecord { id, event: ev }); }
+            }
+        }
+    });
+
+    EventPage { events, next, oldest_local_id: oldest, next_id: end }
+}
+
+/// 按 id 升序取 [cursor, cursor+limit)
+pub fn range(cursor: u64, limit: u64) -> Vec<Event> {
+    let limit = limit.min(MAX_PAGE as u64) as usize;
+    LOG.with(|l| l.borrow().range(cursor..).take(limit).map(|(_, ev)| ev).collect())
+}
+
+/// 最新 limit 条（升序）
+pub fn latest(limit: u64) -> Vec<Event> {
+    let limit = limit.min(MAX_PAGE as u64) as usize;
+    let mut out: Vec<Event> = LOG.with(|l| l.borrow().iter().rev().take(limit).map(|(_, ev)| ev).collect());
+    out.reverse();
+    out
+}
+
+/// 从最新往回遍历，f 返回 false 即停
+pub fn scan_rev(mut f: impl FnMut(&Event) -> bool) {
+    LOG.with(|l| {
+        for (_, ev) in l.borrow().iter().rev() {
+            if !f(&ev) { break; }
+        }
+    });
+}
+
+pub fn archives(st: &State) -> Vec<EventArchiveRange> {
+    st.event_log.as_ref().map(|m| m.archives.clone()).unwrap_or_default()
+}
+
+/* ---------------- 保留策略：搬到 archive ---------------- */
+
+fn live_len() -> u64 { LOG.with(|l| l.borrow().len()) }
+
+fn maybe_archive() {
+    let due = STATE.with(|s| {
+        s.borrow().event_log.as_ref().is_some_and(|m| {
+            m.archive_canister.is_some() && m.max_live.is_some_and(|n| live_len() > n)
+        })
+    });
+    if due && !ARCHIVING.with(|a| a.get()) {
+        ARCHIVING.with(|a| a.set(true));
+        ic_cdk::spawn(async {
+            if let Err(e) = archive_oldest().await {
+                ic_cdk::print(format!("[events] archive failed: {e}"));
+            }
+            ARCHIVING.with(|a| a.set(false));
+        });
+    }
+}
+
+/// 把最旧的一批事件（超出 max_live 的部分，至多 ARCHIVE_CHUNK 条）推到 archive；成功后才本地删除
+async fn archive_oldest() -> Result<(), String> {
+    let (archive, max_live) = STATE.with(|s| {
+        let st = s.borrow();
+        let m = st.event_log.as_ref().expect("event log");
+        (m.archive_canister, m.max_live.unwrap_or(u64::MAX))
+    });
+    let Some(archive) = archive else { return Ok(()) };
+    let n = live_len().saturating_sub(max_live).min(ARCHIVE_CHUNK);
+    let chunk: Vec<(u64, serde_bytes::ByteBuf)> = LOG.with(|l| {
+        l.borrow().iter().take(n as usize).map(|(id, ev)| (id, serde_bytes::ByteBuf::from(ev.to_bytes().into_owned()))).collect()
+    });
+    let Some(&(start, _)) = chunk.first() else { return Ok(()) };
+    let end = start + chunk.len() as u64 - 1;
+    let blobs: Vec<serde_bytes::ByteBuf> = chunk.into_iter().map(|(_, b)| b).collect();
+
+    let (res,): (Result<(), String>,) = ic_cdk::call(archive, "append_events", (start, blobs))
+        .await
+        .map_err(|(code, msg)| format!("append_events rejected: {:?} {}", code, msg))?;
+    res?;
+
+    LOG.with(|l| {
+        let mut l = l.borrow_mut();
+        // 期间若已被其他路径搬走，则不重复删除
+        if l.first_key_value().map(|(k, _)| k) != Some(start) { return; }
+        for id in start..=end { l.remove(&id); }
+    });
     STATE.with(|s| {
         let mut st = s.borrow_mut();
-        st.events.push(ev);
-        if st.events.len() > MAX_EVENTS {
-            let overflow = st.events.len() - MAX_EVENTS;
-            st.events.drain(0..overflow);
+        let m = st.event_log.get_or_insert_with(Default::default);
+        match m.archives.last_mut() {
+            Some(r) if r.canister_id == archive && r.end + 1 == start => r.end = end,
+            _ => m.archives.push(EventArchiveRange { canister_id: archive, start, end }),
         }
     });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap(who: &str, ts: u64) -> Event {
+        Event::Swap {
+            who: who.into(), sub: None, token_in: Coin::USDC, token_out: Coin::USDT,
+            dx_e6: 1, dy_e6: 1, fee_e6: 0, pool: PoolSnapshot::default(), pool_id: None,
+            ledger_blocks: vec![], icrc3_block: None, ts,
+        }
+    }
+
+    #[test]
+    fn ids_stay_stable_after_old_events_leave() {
+        let mut st = State::default();
+        for i in 0..5 { assert_eq!(append(&mut st, swap("a", 100 + i)), i); }
+        LOG.with(|l| { l.borrow_mut().remove(&0); l.borrow_mut().remove(&1); });
+
+        let page = query(&st, EventQuery { from_id: Some(3), ..Default::default() });
+        assert_eq!(page.events.iter().map(|r| r.id).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!((page.oldest_local_id, page.next_id, page.next), (2, 5, None));
+        assert_eq!(append(&mut st, swap("a", 200)), 5);
+    }
+
+    #[test]
+    fn query_filters_by_principal_kind_and_time() {
+        let mut st = State::default();
+        let alice = Principal::from_slice(&[1; 29]);
+        let bob = Principal::from_slice(&[2; 29]);
+        append(&mut st, swap(&alice.to_text(), 10));
+        append(&mut st, Event::ClaimFee {
+            who: alice.to_text(), sub: None, usdc_e6: 5, usdt_e6: 0, pool_id: None, ledger_blocks: vec![], icrc3_block: None, ts: 20,
+        });
+        append(&mut st, swap(&bob.to_text(), 30));
+        // 旧数据中的纳秒时间戳按秒参与过滤
+        append(&mut st, Event::Legacy(EventV1::Swap { who: alice.to_text(), dx_e6: 1, dy_e6: 1, ts: 40_000_000_000 }));
+
+        let ids = |q: EventQuery| query(&st, q).events.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(EventQuery { who: Some(alice), ..Default::default() }), vec![0, 1, 3]);
+        assert_eq!(ids(EventQuery { kinds: Some(vec![EventKind::Swap]), ..Default::default() }), vec![0, 2, 3]);
+        assert_eq!(ids(EventQuery { from_ts: Some(15), to_ts: Some(35), ..Default::default() }), vec![1, 2]);
+        assert_eq!(ids(EventQuery { who: Some(alice), descending: Some(true), limit: Some(2), ..Default::default() }), vec![3, 1]);
+
+        let page = query(&st, EventQuery { limit: Some(2), ..Default::default() });
+        assert_eq!(page.next, Some(2));
+        let page = query(&st, EventQuery { descending: Some(true), from_id: Some(1), limit: Some(1), ..Default::default() });
+        assert_eq!((page.events[0].id, page.next), (1, Some(0)));
+    }
+
+    #[test]
+    fn untagged_v1_bytes_decode_as_legacy() {
+        let v1 = EventV1::Deposit { who: "a".into(), token: TokenId::USDT, amount: 7, ts: 3 };
+        let ev = Event::from_bytes(Cow::Owned(Encode!(&v1).unwrap()));
+        assert!(matches!(&ev, Event::Legacy(EventV1::Deposit { amount: 7, .. })));
+        assert_eq!((ev.kind(), ev.who(), ev.ts()), (EventKind::Deposit, "a", 3));
+
+        // v2 往返；ClaimFee 在旧接口中不出现
+        let claim = Event::ClaimFee { who: "a".into(), sub: None, usdc_e6: 1, usdt_e6: 2, pool_id: Some(0), ledger_blocks: vec![Nat::from(9u8)], icrc3_block: Some(4), ts: 5 };
+        let back = Event::from_bytes(claim.to_bytes());
+        assert!(matches!(back, Event::ClaimFee { usdt_e6: 2, icrc3_block: Some(4), .. }));
+        assert!(back.to_v1().is_none());
+        assert!(matches!(swap("a", 1).to_v1(), Some(EventV1::Swap { dx_e6: 1, .. })));
+    }
 }
@@ -1,17 +1,49 @@
-use crate::{types::PoolInfo, state::STATE};
+use crate::{types::{PoolInfo, StatsSnapshot}, state::{State, STATE}, stats::RollingStats, pools::{self, PoolId, POOL0}};
 
 pub fn get_pool_info()->PoolInfo{
-    STATE.with(|s| {
-        let p = &s.borrow().pool;
-        PoolInfo{
-            a_amp: p.a_amp,
-            fee_bps: p.fee_bps,
-            reserve_usdc: p.reserve_usdc,
-            reserve_usdt: p.reserve_usdt,
-            total_shares: p.total_shares,
-            virtual_price_e6: p.virtual_price_e6,
-        }
-    })
+    STATE.with(|s| pool_info(&s.borrow()))
+}
+
+pub fn pool_info(st:&State)->PoolInfo{
+    let p = &st.pool;
+    PoolInfo{
+        a_amp: p.a_amp,
+        fee_bps: p.fee_bps,
+        reserve_usdc: p.reserve_usdc,
+        reserve_usdt: p.reserve_usdt,
+        total_shares: p.total_shares,
+        virtual_price_e6: p.virtual_price_e6,
+    }
+}
+
+/// 24h / 7d 统计快照（n = 当前秒）
+pub fn stats_snapshot(st:&State, n:u64)->StatsSnapshot{
+    let tvl = st.pool.reserve_usdc.saturating_add(st.pool.reserve_usdt);
+    snapshot_of(&st.stats, tvl, n)
+}
+
+/// 某池子的统计快照（TVL 按两侧储备 e6 直接相加）；池子不存在返回 None
+pub fn pool_stats_snapshot(st:&State, pool:PoolId, n:u64)->Option<StatsSnapshot>{
+    if pool == POOL0 { return Some(stats_snapshot(st, n)); }
+    let p = pools::load(st, pool).ok()?;
+    let e = st.pools.as_ref()?.pools.get(&pool)?;
+    Some(snapshot_of(&e.stats, p.reserves.iter().sum(), n))
+}
+
+fn snapshot_of(stats:&RollingStats, tvl:u128, n:u64)->StatsSnapshot{
+    let (vol_24, fee_24, swaps_24) = stats.sum_last_hours(n, 24);
+    let (vol_7d, fee_7d, _swaps_7d) = stats.sum_last_hours(n, 168);
+    let apy_bp = (fee_24.saturating_mul(365) * 10_000).checked_div(tvl).unwrap_or(0).min(u128::from(u32::MAX)) as u32;
+    StatsSnapshot {
+        now_sec: n,
+        tvl_e6: tvl,
+        vol_24h_e6: vol_24,
+        vol_7d_e6: vol_7d,
+        fee_24h_e6: fee_24,
+        fee_7d_e6: fee_7d,
+        swaps_24h: swaps_24,
+        apy_24h_bp: apy_bp,
+    }
 }
 
 // 仅用于本地演示：直接写池子储备
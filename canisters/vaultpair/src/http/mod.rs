@@ -0,0 +1,384 @@
+// canisters/vaultpair/src/http/mod.rs
+// http_request 只读路由（看板 / 对账工具用，不走 candid）：
+//   GET /pool                     池子参数与储备（JSON）
+//   GET /stats                    24h / 7d 统计快照（JSON）
+//   GET /stats/series?hours=      小时桶序列（JSON）
+//   GET /events?from=&limit=&who=&kind=&format=csv   事件日志（JSON 或 CSV；亦可 /events.csv）
+//   GET /positions/<principal>    LP 头寸、可领手续费与子账户余额（JSON）
+//   GET /metrics                  Prometheus 文本格式指标（见 metrics.rs）
+// query 响应未认证（请经 raw 域名访问）；带 certified=1 时升级为 update 调用，响应经共识返回。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{self, Event, EventKind, EventQuery, EventRecord},
+    explore, metrics, positions,
+    pools::POOL0,
+    state::{skey, State},
+};
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: serde_bytes::ByteBuf,
+    pub certificate_version: Option<u16>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct HttpResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: serde_bytes::ByteBuf,
+    pub upgrade: Option<bool>,
+}
+
+impl HttpResponse {
+    fn new(status_code: u16, content_type: &str, body: Vec<u8>) -> Self {
+        HttpResponse {
+            status_code,
+            headers: vec![
+                ("Content-Type".into(), content_type.into()),
+                ("Access-Control-Allow-Origin".into(), "*".into()),
+                ("Cache-Control".into(), "no-store".into()),
+            ],
+            body: serde_bytes::ByteBuf::from(body),
+            upgrade: None,
+        }
+    }
+
+    fn json<T: Serialize>(v: &T) -> Self {
+        HttpResponse::new(200, "application/json", serde_json::to_vec(v).expect("serialize json"))
+    }
+
+    fn error(status_code: u16, msg: &str) -> Self {
+        HttpResponse::new(status_code, "application/json", serde_json::to_vec(&serde_json::json!({ "error": msg })).expect("serialize json"))
+    }
+
+    fn upgrade() -> Self {
+        HttpResponse { upgrade: Some(true), ..HttpResponse::new(200, "text/plain", vec![]) }
+    }
+}
+
+/* ---------------- 路由 ---------------- */
+
+fn split_url(url: &str) -> (&str, Vec<(&str, &str)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| kv.split_once('=').unwrap_or((kv, "")))
+        .collect();
+    (path, params)
+}
+
+fn param<'a>(params: &[(&'a str, &'a str)], key: &str) -> Option<&'a str> {
+    params.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+fn parse<T: std::str::FromStr>(params: &[(&str, &str)], key: &str) -> Result<Option<T>, HttpResponse> {
+    match param(params, key) {
+        None | Some("") => Ok(None),
+        Some(v) => v.parse().map(Some).map_err(|_| HttpResponse::error(400, &format!("invalid {}", key))),
+    }
+}
+
+/// in_update：当前是否已处于 http_request_update 中
+pub fn handle(st: &State, now_sec: u64, req: &HttpRequest, in_update: bool) -> HttpResponse {
+    if req.method != "GET" {
+        return HttpResponse::error(405, "only GET is supported");
+    }
+    let (path, params) = split_url(&req.url);
+    if !in_update && matches!(param(&params, "certified"), Some("1") | Some("true")) {
+        return HttpResponse::upgrade();
+    }
+    route(st, now_sec, path.trim_end_matches('/'), &params).unwrap_or_else(|e| e)
+}
+
+fn route(st: &State, now_sec: u64, path: &str, params: &[(&str, &str)]) -> Result<HttpResponse, HttpResponse> {
+    Ok(match path {
+        "/pool" => HttpResponse::json(&explore::pool_info(st)),
+        "/metrics" => HttpResponse::new(200, "text/plain; version=0.0.4", metrics::render(st, &metrics::Runtime::sample()).into_bytes()),
+        "/stats" => HttpResponse::json(&explore::stats_snapshot(st, now_sec)),
+        "/stats/series" => {
+            let hours = parse::<u32>(params, "hours")?.unwrap_or(24);
+            HttpResponse::json(&st.stats.series(now_sec, hours))
+        }
+        "/events" | "/events.csv" => {
+            let csv = path.ends_with(".csv") || param(params, "format") == Some("csv");
+            events_page(st, params, csv)?
+        }
+        _ => match path.strip_prefix("/positions/") {
+            Some(p) => {
+                let owner = Principal::from_text(p).map_err(|_| HttpResponse::error(400, "invalid principal"))?;
+                HttpResponse::json(&position_view(st, owner))
+            }
+            None => HttpResponse::error(404, "not found"),
+        },
+    })
+}
+
+/* ---------------- /events ---------------- */
+
+fn parse_kind(s: &str) -> Option<EventKind> {
+    Some(match s {
+        "Swap" => EventKind::Swap,
+        "AddLiq" => EventKind::AddLiq,
+        "RemoveLiq" => EventKind::RemoveLiq,
+        "Deposit" => EventKind::Deposit,
+        "Withdraw" => EventKind::Withdraw,
+        "ClaimFee" => EventKind::ClaimFee,
+        "AdminAction" => EventKind::AdminAction,
+        "Reconcile" => EventKind::Reconcile,
+        "ParamChange" => EventKind::ParamChange,
+        _ => return None,
+    })
+}
+
+fn events_page(st: &State, params: &[(&str, &str)], csv: bool) -> Result<HttpResponse, HttpResponse> {
+    let who = match param(params, "who") {
+        None | Some("") => None,
+        Some(p) => Some(Principal::from_text(p).map_err(|_| HttpResponse::error(400, "invalid who"))?),
+    };
+    let kinds = match param(params, "kind") {
+        None | Some("") => None,
+        Some(ks) => Some(
+            ks.split(',')
+                .map(|k| parse_kind(k).ok_or_else(|| HttpResponse::error(400, "invalid kind")))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+    let page = events::query(st, EventQuery {
+        from_id: parse(params, "from")?,
+        limit: parse(params, "limit")?,
+        who,
+        kinds,
+        from_ts: parse(params, "from_ts")?,
+        to_ts: parse(params, "to_ts")?,
+        descending: parse(params, "desc")?,
+    });
+    let rows: Vec<EventRow> = page.events.iter().map(EventRow::from).collect();
+
+    if !csv {
+        return Ok(HttpResponse::json(&serde_json::json!({
+            "events": rows,
+            "next": page.next,
+            "oldest_local_id": page.oldest_local_id,
+            "next_id": page.next_id,
+        })));
+    }
+    let mut resp = HttpResponse::new(200, "text/csv; charset=utf-8", to_csv(&rows).into_bytes());
+    if let Some(n) = page.next {
+        resp.headers.push(("X-Next-From".into(), n.to_string()));
+    }
+    Ok(resp)
+}
+
+/// 事件展平为一行（JSON / CSV 共用）；与该类型无关的列留空
+#[derive(Serialize, Default, Debug)]
+struct EventRow {
+    id: u64,
+    kind: String,
+    who: String,
+    ts: u64,
+    token_in: Option<String>,
+    token_out: Option<String>,
+    token: Option<String>,
+    dx_e6: Option<u128>,
+    dy_e6: Option<u128>,
+    fee_e6: Option<u128>,
+    usdc_e6: Option<u128>,
+    usdt_e6: Option<u128>,
+    shares_e6: Option<u128>,
+    amount_e6: Option<u128>,
+    virtual_price_e6: Option<u128>,
+    ledger_blocks: String,
+    icrc3_block: Option<u64>,
+    detail: Option<String>,
+}
+
+const CSV_HEADER: &str = "id,kind,who,ts,token_in,token_out,token,dx_e6,dy_e6,fee_e6,usdc_e6,usdt_e6,shares_e6,amount_e6,virtual_price_e6,ledger_blocks,icrc3_block,detail";
+
+fn join_blocks(bs: &[candid::Nat]) -> String {
+    bs.iter().map(|b| b.0.to_string()).collect::<Vec<_>>().join(";")
+}
+
+impl From<&EventRecord> for EventRow {
+    fn from(r: &EventRecord) -> Self {
+        let ev = &r.event;
+        let mut row = EventRow {
+            id: r.id,
+            kind: format!("{:?}", ev.kind()),
+            who: ev.who().to_string(),
+            ts: ev.ts(),
+            ..Default::default()
+        };
+        let tok = |t: &crate::types::Coin| Some(format!("{:?}", t));
+        match ev {
+            Event::Swap { token_in, token_out, dx_e6, dy_e6, fee_e6, pool, ledger_blocks, icrc3_block, .. } => {
+                row.token_in = tok(token_in);
+                row.token_out = tok(token_out);
+                (row.dx_e6, row.dy_e6, row.fee_e6) = (Some(*dx_e6), Some(*dy_e6), Some(*fee_e6));
+                row.virtual_price_e6 = Some(pool.virtual_price_e6);
+                row.ledger_blocks = join_blocks(ledger_blocks);
+                row.icrc3_block = *icrc3_block;
+            }
+            Event::AddLiq { usdc, usdt, shares, pool, ledger_blocks, icrc3_block, .. }
+            | Event::RemoveLiq { usdc, usdt, shares, pool, ledger_blocks, icrc3_block, .. } => {
+                (row.usdc_e6, row.usdt_e6, row.shares_e6) = (Some(*usdc), Some(*usdt), Some(*shares));
+                row.virtual_price_e6 = Some(pool.virtual_price_e6);
+                row.ledger_blocks = join_blocks(ledger_blocks);
+                row.icrc3_block = *icrc3_block;
+            }
+            Event::Deposit { token, amount, ledger_block, icrc3_block, .. }
+            | Event::Withdraw { token, amount, ledger_block, icrc3_block, .. } => {
+                row.token = tok(&(*token).into());
+                row.amount_e6 = Some(*amount);
+                row.ledger_blocks = ledger_block.as_ref().map(|b| b.0.to_string()).unwrap_or_default();
+                row.icrc3_block = *icrc3_block;
+            }
+            Event::ClaimFee { usdc_e6, usdt_e6, ledger_blocks, icrc3_block, .. } => {
+                (row.usdc_e6, row.usdt_e6) = (Some(*usdc_e6), Some(*usdt_e6));
+                row.ledger_blocks = join_blocks(ledger_blocks);
+                row.icrc3_block = *icrc3_block;
+            }
+            Event::AdminAction { action, detail, .. } => row.detail = Some(format!("{}: {}", action, detail)),
+            Event::Reconcile { after, detail, .. } => {
+                row.virtual_price_e6 = Some(after.virtual_price_e6);
+                row.detail = Some(detail.clone());
+            }
+            Event::ParamChange { param, old, new, .. } => row.detail = Some(format!("{}: {} -> {}", param, old, new)),
+            Event::Legacy(v1) => match v1 {
+                events::EventV1::Swap { dx_e6, dy_e6, .. } => (row.dx_e6, row.dy_e6) = (Some(*dx_e6), Some(*dy_e6)),
+                events::EventV1::AddLiq { usdc, usdt, shares, .. } | events::EventV1::RemoveLiq { usdc, usdt, shares, .. } => {
+                    (row.usdc_e6, row.usdt_e6, row.shares_e6) = (Some(*usdc), Some(*usdt), Some(*shares));
+                }
+                events::EventV1::Deposit { token, amount, .. } | events::EventV1::Withdraw { token, amount, .. } => {
+                    row.token = tok(&(*token).into());
+                    row.amount_e6 = Some(*amount);
+                }
+            },
+        }
+        row
+    }
+}
+
+/// RFC 4180：含逗号 / 引号 / 换行的字段加引号，引号转义为 ""
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+fn opt<T: ToString>(v: &Option<T>) -> String { v.as_ref().map(|x| x.to_string()).unwrap_or_default() }
+
+fn to_csv(rows: &[EventRow]) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push_str("\r\n");
+    for r in rows {
+        let cells = [
+            r.id.to_string(), r.kind.clone(), r.who.clone(), r.ts.to_string(),
+            opt(&r.token_in), opt(&r.token_out), opt(&r.token),
+            opt(&r.dx_e6), opt(&r.dy_e6), opt(&r.fee_e6),
+            opt(&r.usdc_e6), opt(&r.usdt_e6), opt(&r.shares_e6), opt(&r.amount_e6),
+            opt(&r.virtual_price_e6), r.ledger_blocks.clone(), opt(&r.icrc3_block), opt(&r.detail),
+        ];
+        out.push_str(&cells.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/* ---------------- /positions ---------------- */
+
+#[derive(Serialize, Debug)]
+struct PositionView {
+    principal: String,
+    shares_e6: u128,
+    total_shares_e6: u128,
+    /// 占池子份额（bps）
+    share_bps: u128,
+    claimable_usdc_e6: u128,
+    claimable_usdt_e6: u128,
+    sub_usdc_e6: u128,
+    sub_usdt_e6: u128,
+}
+
+fn position_view(st: &State, owner: Principal) -> PositionView {
+    let key = owner.to_text();
+    let shares = *st.user_shares.get(&key).unwrap_or(&0);
+    let total = st.pool.total_shares;
+    let [cu, cv] = positions::claimable(st, POOL0, &key);
+    let sub = skey(&owner);
+    PositionView {
+        principal: key,
+        shares_e6: shares,
+        total_shares_e6: total,
+        share_bps: shares.saturating_mul(10_000).checked_div(total).unwrap_or(0),
+        claimable_usdc_e6: cu,
+        claimable_usdt_e6: cv,
+        sub_usdc_e6: *st.user_sub_usdc.get(&sub).unwrap_or(&0),
+        sub_usdt_e6: *st.user_sub_usdt.get(&sub).unwrap_or(&0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::PoolSnapshot;
+    use crate::types::Coin;
+
+    fn get(st: &State, url: &str) -> HttpResponse {
+        let req = HttpRequest { method: "GET".into(), url: url.into(), headers: vec![], body: Default::default(), certificate_version: None };
+        handle(st, 1_700_000_000, &req, false)
+    }
+
+    fn body(r: &HttpResponse) -> String { String::from_utf8(r.body.to_vec()).unwrap() }
+
+    #[test]
+    fn routes_pool_positions_and_errors() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 5;
+        let alice = Principal::from_slice(&[1; 29]);
+        st.user_shares.insert(alice.to_text(), 25);
+        st.pool.total_shares = 100;
+
+        let pool: serde_json::Value = serde_json::from_slice(&get(&st, "/pool").body).unwrap();
+        assert_eq!(pool["reserve_usdc"], 5);
+        let pos: serde_json::Value = serde_json::from_slice(&get(&st, &format!("/positions/{}", alice)).body).unwrap();
+        assert_eq!((pos["shares_e6"].as_u64(), pos["share_bps"].as_u64()), (Some(25), Some(2500)));
+
+        assert_eq!(get(&st, "/nope").status_code, 404);
+        assert_eq!(get(&st, "/positions/not-a-principal").status_code, 400);
+        assert_eq!(get(&st, "/stats/series?hours=x").status_code, 400);
+        assert_eq!(get(&st, "/stats/series?hours=3").status_code, 200);
+        assert_eq!(get(&st, "/pool?certified=1").upgrade, Some(true));
+        assert!(body(&get(&st, "/metrics")).contains("vaultpair_reserve_e6{token=\"USDC\"} 5"));
+    }
+
+    #[test]
+    fn events_export_as_json_and_csv() {
+        let mut st = State::default();
+        let alice = Principal::from_slice(&[1; 29]);
+        events::append(&mut st, Event::Swap {
+            who: alice.to_text(), sub: None, token_in: Coin::USDC, token_out: Coin::USDT,
+            dx_e6: 10, dy_e6: 9, fee_e6: 1, pool: PoolSnapshot::default(), pool_id: None,
+            ledger_blocks: vec![candid::Nat::from(7u8), candid::Nat::from(8u8)], icrc3_block: Some(3), ts: 100,
+        });
+        events::append(&mut st, Event::ParamChange { who: "ctl".into(), param: "fee".into(), old: "1,2".into(), new: "say \"hi\"".into(), ts: 101 });
+
+        let js: serde_json::Value = serde_json::from_slice(&get(&st, &format!("/events?who={}", alice)).body).unwrap();
+        assert_eq!(js["events"].as_array().unwrap().len(), 1);
+        assert_eq!(js["events"][0]["ledger_blocks"], "7;8");
+
+        let r = get(&st, "/events.csv?limit=1");
+        let csv = body(&r);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with(&format!("0,Swap,{},100,USDC,USDT,,10,9,1,", alice)));
+        assert!(r.headers.contains(&("X-Next-From".into(), "1".into())));
+
+        let csv = body(&get(&st, "/events?format=csv&kind=ParamChange"));
+        assert!(csv.contains(r#""fee: 1,2 -> say ""hi""""#));
+        assert_eq!(get(&st, "/events?kind=Bogus").status_code, 400);
+    }
+}
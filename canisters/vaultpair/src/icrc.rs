@@ -1,8 +1,10 @@
 // canisters/vaultpair/src/icrc.rs
 use candid::{CandidType, Deserialize, Nat, Principal};
+use serde::Serialize;
 use ic_cdk::api::call::call as ic_call;
 
 use crate::types::Account;
+use crate::error::{DepositError, Error, LedgerErrorCode, Result};
 use ic_cdk::api;
 
 
@@ -47,7 +49,7 @@ pub fn derive_subaccount(user: Principal) -> [u8; 32] {
 
 /* ============ ICRC-1 交互 ============ */
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Icrc1TransferArg {
     pub from_subaccount: Option<serde_bytes::ByteBuf>,
     pub to: Account,
@@ -57,7 +59,7 @@ pub struct Icrc1TransferArg {
     pub created_at_time: Option<u64>,
 }
 
-#[derive(CandidType, Deserialize, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum TransferError {
     BadFee { expected_fee: Nat },
     BadBurn { min_burn_amount: Nat },
@@ -69,12 +71,123 @@ pub enum TransferError {
     GenericError { error_code: Nat, message: String },
 }
 
-/// 正确按 ICRC-1 规范解码：返回 Ok(Nat) 或 Err(TransferError)
-pub async fn icrc1_transfer(token: Principal, arg: Icrc1TransferArg) -> Result<Nat, String> {
-    let (res,): (Result<Nat, TransferError>,) = ic_call(token, "icrc1_transfer", (arg,))
+/* ============ ICRC-2 类型（LP 份额代币服务端使用） ============ */
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ApproveArgs {
+    pub from_subaccount: Option<serde_bytes::ByteBuf>,
+    pub spender: Account,
+    pub amount: Nat,
+    pub expected_allowance: Option<Nat>,
+    pub expires_at: Option<u64>,
+    pub fee: Option<Nat>,
+    pub memo: Option<serde_bytes::ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ApproveError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    AllowanceChanged { current_allowance: Nat },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct AllowanceArgs { pub account: Account, pub spender: Account }
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Allowance { pub allowance: Nat, pub expires_at: Option<u64> }
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TransferFromArgs {
+    pub spender_subaccount: Option<serde_bytes::ByteBuf>,
+    pub from: Account,
+    pub to: Account,
+    pub amount: Nat,
+    pub fee: Option<Nat>,
+    pub memo: Option<serde_bytes::ByteBuf>,
+    pub created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// icrc1_metadata 的取值
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum MetadataValue { Nat(Nat), Int(candid::Int), Text(String), Blob(serde_bytes::ByteBuf) }
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StandardRecord { pub name: String, pub url: String }
+
+impl From<TransferError> for LedgerErrorCode {
+    fn from(e: TransferError) -> Self {
+        match e {
+            TransferError::BadFee { expected_fee } => LedgerErrorCode::BadFee { expected_fee },
+            TransferError::BadBurn { min_burn_amount } => LedgerErrorCode::BadBurn { min_burn_amount },
+            TransferError::InsufficientFunds { balance } => LedgerErrorCode::InsufficientFunds { balance },
+            TransferError::TooOld => LedgerErrorCode::TooOld,
+            TransferError::CreatedInFuture { ledger_time } => LedgerErrorCode::CreatedInFuture { ledger_time },
+            TransferError::TemporarilyUnavailable => LedgerErrorCode::TemporarilyUnavailable,
+            TransferError::Duplicate { duplicate_of } => LedgerErrorCode::Duplicate { duplicate_of },
+            TransferError::GenericError { error_code, message } => LedgerErrorCode::GenericError { error_code, message },
+        }
+    }
+}
+
+/// 授权不足在 LedgerErrorCode 里没有对应分支：Err 带回当前授权额，由调用方单独表达（见 DepositError）
+impl TryFrom<TransferFromError> for LedgerErrorCode {
+    type Error = Nat;
+    fn try_from(e: TransferFromError) -> core::result::Result<Self, Nat> {
+        Ok(match e {
+            TransferFromError::BadFee { expected_fee } => LedgerErrorCode::BadFee { expected_fee },
+            TransferFromError::BadBurn { min_burn_amount } => LedgerErrorCode::BadBurn { min_burn_amount },
+            TransferFromError::InsufficientFunds { balance } => LedgerErrorCode::InsufficientFunds { balance },
+            TransferFromError::InsufficientAllowance { allowance } => return Err(allowance),
+            TransferFromError::TooOld => LedgerErrorCode::TooOld,
+            TransferFromError::CreatedInFuture { ledger_time } => LedgerErrorCode::CreatedInFuture { ledger_time },
+            TransferFromError::Duplicate { duplicate_of } => LedgerErrorCode::Duplicate { duplicate_of },
+            TransferFromError::TemporarilyUnavailable => LedgerErrorCode::TemporarilyUnavailable,
+            TransferFromError::GenericError { error_code, message } => LedgerErrorCode::GenericError { error_code, message },
+        })
+    }
+}
+
+/// 正确按 ICRC-1 规范解码：返回 Ok(block index) 或 Err(Error::LedgerError)
+pub async fn icrc1_transfer(token: Principal, arg: Icrc1TransferArg) -> Result<Nat> {
+    let (res,): (core::result::Result<Nat, TransferError>,) = ic_call(token, "icrc1_transfer", (arg,))
         .await
-        .map_err(|e| format!("icrc1_transfer call failed: {:?}", e))?;
-    res.map_err(|e| format!("transfer error: {:?}", e))
+        .map_err(|e| Error::call_rejected(token, e))?;
+    res.map_err(|e| Error::ledger(token, e.into()))
+}
+
+/// ICRC-2 代扣（本 canister 为 spender）：返回 Ok(block index)；授权不足为 DepositError::InsufficientAllowance，其余为 Error::LedgerError
+pub async fn icrc2_transfer_from(token: Principal, arg: TransferFromArgs) -> core::result::Result<Nat, DepositError> {
+    let (res,): (core::result::Result<Nat, TransferFromError>,) = ic_call(token, "icrc2_transfer_from", (arg,))
+        .await
+        .map_err(|e| Error::call_rejected(token, e))?;
+    res.map_err(|e| match LedgerErrorCode::try_from(e) {
+        Ok(code) => Error::ledger(token, code).into(),
+        Err(allowance) => {
+            crate::metrics::ledger_error(token, "InsufficientAllowance");
+            DepositError::InsufficientAllowance { ledger: token, allowance }
+        }
+    })
 }
 
 /// 从【调用者派生子账户】转出到任意目标
@@ -83,7 +196,7 @@ pub async fn transfer_from_user_sub(
     caller: Principal,
     to: Account,
     amount: Nat,
-) -> Result<Nat, String> {
+) -> Result<Nat> {
     let sub = derive_subaccount(caller);
     let arg = Icrc1TransferArg {
         from_subaccount: Some(serde_bytes::ByteBuf::from(sub.to_vec())),
@@ -129,10 +242,18 @@ pub fn to_hex32(bytes: &[u8; 32]) -> String {
     s
 }
 
-/// 查询 ICRC-1 余额：返回 Ok(Nat) 或 Err(String)
-pub async fn icrc1_balance_of(token: Principal, acct: Account) -> Result<Nat, String> {
+/// 查询账本当前转账 fee（最小单位）
+pub async fn icrc1_fee(token: Principal) -> Result<Nat> {
+    let (fee,): (Nat,) = ic_call(token, "icrc1_fee", ())
+        .await
+        .map_err(|e| Error::call_rejected(token, e))?;
+    Ok(fee)
+}
+
+/// 查询 ICRC-1 余额：返回 Ok(Nat) 或 Err(Error::LedgerError)
+pub async fn icrc1_balance_of(token: Principal, acct: Account) -> Result<Nat> {
     let (bal,): (Nat,) = ic_call(token, "icrc1_balance_of", (acct,))
         .await
-        .map_err(|e| format!("icrc1_balance_of call failed: {:?}", e))?;
+        .map_err(|e| Error::call_rejected(token, e))?;
     Ok(bal)
 }
\ No newline at end of file
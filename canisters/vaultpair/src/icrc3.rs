@@ -0,0 +1,395 @@
+// canisters/vaultpair/src/icrc3.rs
+// ICRC-3 区块日志：LP 份额转账 + 池子操作（swap / 流动性 / 充提 / 领取手续费）逐笔上链留痕。
+// 每个区块含上一块的 phash 形成哈希链；tip（last_block_index + last_block_hash）经 set_certified_data 认证（与池子状态共用一棵树，见 certified.rs）。
+// 本地只保留最近的区块，超出 MAX_LOCAL_BLOCKS 后按批溢出到控制者配置的 archive canister。
+use candid::{CandidType, Int, Nat, Principal};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::Cell;
+use num_traits::cast::ToPrimitive;
+
+use crate::{
+    state::{State, STATE},
+    types::{Account, Coin},
+};
+
+pub const MAX_LOCAL_BLOCKS: usize = 10_000;
+pub const ARCHIVE_CHUNK: usize = 2_000;
+pub const MAX_BLOCKS_PER_RESPONSE: u64 = 1_000;
+
+/// 自定义区块类型（ICRC-1/2 的 1xfer / 2approve / 2xfer / 1mint / 1burn 之外）
+pub const BT_SWAP: &str = "vp_swap";
+pub const BT_DEPOSIT: &str = "vp_deposit";
+pub const BT_WITHDRAW: &str = "vp_withdraw";
+pub const BT_CLAIM_FEE: &str = "vp_claim_fee";
+
+/* ---------------- ICRC-3 类型 ---------------- */
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Icrc3Value {
+    Blob(serde_bytes::ByteBuf),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Icrc3Value>),
+    Map(Vec<(String, Icrc3Value)>),
+}
+
+impl Icrc3Value {
+    pub fn nat(n: impl Into<Nat>) -> Self { Icrc3Value::Nat(n.into()) }
+    pub fn text(s: impl Into<String>) -> Self { Icrc3Value::Text(s.into()) }
+    pub fn blob(b: &[u8]) -> Self { Icrc3Value::Blob(serde_bytes::ByteBuf::from(b.to_vec())) }
+    pub fn token(t: impl Into<Coin>) -> Self { Icrc3Value::text(format!("{:?}", t.into())) }
+
+    /// ICRC-3 账户编码：[owner] 或 [owner, subaccount]（默认子账户省略）
+    pub fn account(a: &Account) -> Self {
+        let mut v = vec![Icrc3Value::blob(a.owner.as_slice())];
+        if let Some(sub) = a.subaccount.as_ref().filter(|s| s.iter().any(|x| *x != 0)) {
+            v.push(Icrc3Value::blob(sub));
+        }
+        Icrc3Value::Array(v)
+    }
+
+    /// ICRC-3 表示无关哈希（representation-independent hash）
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            Icrc3Value::Blob(b) => sha256(b),
+            Icrc3Value::Text(t) => sha256(t.as_bytes()),
+            Icrc3Value::Nat(n) => sha256(&leb128_nat(n)),
+            Icrc3Value::Int(i) => sha256(&sleb128_int(i)),
+            Icrc3Value::Array(xs) => {
+                let mut h = Sha256::new();
+                for x in xs { h.update(x.hash()); }
+                h.finalize().into()
+            }
+            Icrc3Value::Map(kvs) => {
+                let mut pairs: Vec<[u8; 64]> = kvs.iter().map(|(k, v)| {
+                    let mut p = [0u8; 64];
+                    p[..32].copy_from_slice(&sha256(k.as_bytes()));
+                    p[32..].copy_from_slice(&v.hash());
+                    p
+                }).collect();
+                pairs.sort();
+                let mut h = Sha256::new();
+                for p in pairs { h.update(p); }
+                h.finalize().into()
+            }
+        }
+    }
+}
+
+fn sha256(b: &[u8]) -> [u8; 32] { Sha256::digest(b).into() }
+
+fn leb128_nat(n: &Nat) -> Vec<u8> {
+    let mut out = Vec::new();
+    n.encode(&mut out).expect("leb128 nat");
+    out
+}
+
+fn sleb128_int(i: &Int) -> Vec<u8> {
+    let mut out = Vec::new();
+    i.encode(&mut out).expect("sleb128 int");
+    out
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GetBlocksArgs { pub start: Nat, pub length: Nat }
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BlockWithId { pub id: Nat, pub block: Icrc3Value }
+
+candid::define_function!(pub GetBlocksCallback : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ArchivedBlocks { pub args: Vec<GetBlocksArgs>, pub callback: GetBlocksCallback }
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct GetArchivesArgs { pub from: Option<Principal> }
+
+/// 一个 archive canister 持有的区块区间 [start, end]（闭区间）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Icrc3ArchiveInfo { pub canister_id: Principal, pub start: Nat, pub end: Nat }
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Icrc3DataCertificate {
+    pub certificate: serde_bytes::ByteBuf,
+    pub hash_tree: serde_bytes::ByteBuf,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SupportedBlockType { pub block_type: String, pub url: String }
+
+/* ---------------- 状态 ---------------- */
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ArchiveRange { pub canister_id: Principal, pub start: u64, pub end: u64 }
+
+/// 挂在 State.icrc3 上
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Icrc3Log {
+    // 本地区块：blocks[i] 的全局编号为 first_index + i
+    pub blocks: Vec<Icrc3Value>,
+    pub first_index: u64,
+    pub last_hash: Option<serde_bytes::ByteBuf>,
+    // 当前溢出目标；None 时本地无限增长
+    pub archive_canister: Option<Principal>,
+    pub archives: Vec<ArchiveRange>,
+}
+
+impl Icrc3Log {
+    pub fn log_length(&self) -> u64 { self.first_index + self.blocks.len() as u64 }
+
+    pub(crate) fn tip(&self) -> Option<(u64, [u8; 32])> {
+        let h = self.last_hash.as_ref()?;
+        let mut out = [0u8; 32];
+        out.copy_from_slice(h);
+        Some((self.log_length() - 1, out))
+    }
+}
+
+fn log_mut(st: &mut State) -> &mut Icrc3Log { st.icrc3.get_or_insert_with(Default::default) }
+
+thread_local! {
+    // 正在向 archive 推送（跨 await，不进 State）
+    static ARCHIVING: Cell<bool> = const { Cell::new(false) };
+}
+
+/* ---------------- 写入 ---------------- */
+
+/// 追加一个区块（只改 State，不做认证）；返回区块编号。调用方随后须执行 commit()
+pub fn append(st: &mut State, btype: &str, ts_ns: u64, tx: Vec<(&str, Icrc3Value)>) -> u64 {
+    let log = log_mut(st);
+    let mut fields = Vec::with_capacity(4);
+    if let Some(p) = &log.last_hash { fields.push(("phash".to_string(), Icrc3Value::Blob(p.clone()))); }
+    fields.push(("btype".to_string(), Icrc3Value::text(btype)));
+    fields.push(("ts".to_string(), Icrc3Value::nat(ts_ns)));
+    fields.push((
+        "tx".to_string(),
+        Icrc3Value::Map(tx.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+    ));
+    let block = Icrc3Value::Map(fields);
+    log.last_hash = Some(serde_bytes::ByteBuf::from(block.hash().to_vec()));
+    log.blocks.push(block);
+    log.log_length() - 1
+}
+
+/// 便捷入口：写一个区块并 commit（不可在 STATE 借用期间调用）
+pub fn log(btype: &str, tx: Vec<(&str, Icrc3Value)>) -> u64 {
+    let idx = STATE.with(|s| append(&mut s.borrow_mut(), btype, ic_cdk::api::time(), tx));
+    commit();
+    idx
+}
+
+/// 认证最新 tip，并在本地区块过多时触发 archive 溢出
+pub fn commit() {
+    crate::certified::certify();
+    let need_archive = STATE.with(|s| {
+        s.borrow().icrc3.as_ref().is_some_and(|l| l.archive_canister.is_some() && l.blocks.len() > MAX_LOCAL_BLOCKS)
+    });
+    if need_archive && !ARCHIVING.with(|a| a.get()) {
+        ARCHIVING.with(|a| a.set(true));
+        ic_cdk::spawn(async {
+            if let Err(e) = archive_oldest().await {
+                ic_cdk::print(format!("[icrc3] archive failed: {e}"));
+            }
+            ARCHIVING.with(|a| a.set(false));
+        });
+    }
+}
+
+/* ---------------- 认证 ---------------- */
+
+pub(crate) fn tip_tree(idx: u64, hash: [u8; 32]) -> ic_certification::HashTree {
+    use ic_certification::{fork, labeled, leaf};
+    let mut idx_leb = Vec::new();
+    Nat::from(idx).encode(&mut idx_leb).expect("leb128");
+    fork(
+        labeled("last_block_hash", leaf(hash.to_vec())),
+        labeled("last_block_index", leaf(idx_leb)),
+    )
+}
+
+/// icrc3_get_tip_certificate：仅 query 调用可拿到证书。hash_tree 中池子一支已剪枝
+pub fn tip_certificate() -> Option<Icrc3DataCertificate> {
+    let certificate = ic_cdk::api::data_certificate()?;
+    let tree = STATE.with(|s| {
+        let st = s.borrow();
+        st.icrc3.as_ref().and_then(|l| l.tip())?;
+        serde_cbor::to_vec(&crate::certified::tip_witness(&st)).ok()
+    })?;
+    Some(Icrc3DataCertificate {
+        certificate: serde_bytes::ByteBuf::from(certificate),
+        hash_tree: serde_bytes::ByteBuf::from(tree),
+    })
+}
+
+/* ---------------- 读取 ---------------- */
+
+fn to_u64(n: &Nat) -> u64 { n.0.to_u64().unwrap_or(u64::MAX) }
+
+pub fn get_blocks(st: &State, args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    let empty = Icrc3Log::default();
+    let log = st.icrc3.as_ref().unwrap_or(&empty);
+    let len = log.log_length();
+    let mut budget = MAX_BLOCKS_PER_RESPONSE;
+    let mut blocks = Vec::new();
+    let mut archived: Vec<ArchivedBlocks> = Vec::new();
+
+    for a in args {
+        let start = to_u64(&a.start).min(len);
+        let end = start.saturating_add(to_u64(&a.length)).min(len);
+
+        // 已溢出到 archive 的部分：按 archive 区间切分，交给回调
+        for r in &log.archives {
+            let (s, e) = (start.max(r.start), end.min(r.end + 1));
+            if s >= e { continue; }
+            let part = GetBlocksArgs { start: Nat::from(s), length: Nat::from(e - s) };
+            match archived.iter_mut().find(|x| x.callback.0.principal == r.canister_id) {
+                Some(x) => x.args.push(part),
+                None => archived.push(ArchivedBlocks {
+                    args: vec![part],
+                    callback: GetBlocksCallback::new(r.canister_id, "icrc3_get_blocks".to_string()),
+                }),
+            }
+        }
+
+        // 本地部分
+        let s = start.max(log.first_index);
+        let e = end.min(s.saturating_add(budget));
+        for id in s..e {
+            blocks.push(BlockWithId { id: Nat::from(id), block: log.blocks[(id - log.first_index) as usize].clone() });
+        }
+        budget -= e.saturating_sub(s);
+    }
+
+    GetBlocksResult { log_length: Nat::from(len), blocks, archived_blocks: archived }
+}
+
+pub fn get_archives(st: &State, args: GetArchivesArgs) -> Vec<Icrc3ArchiveInfo> {
+    let Some(log) = st.icrc3.as_ref() else { return vec![] };
+    let mut out: Vec<Icrc3ArchiveInfo> = Vec::new();
+    for r in &log.archives {
+        match out.iter_mut().find(|x| x.canister_id == r.canister_id) {
+            Some(x) => x.end = Nat::from(r.end),
+            None => out.push(Icrc3ArchiveInfo { canister_id: r.canister_id, start: Nat::from(r.start), end: Nat::from(r.end) }),
+        }
+    }
+    // from：返回排在该 canister 之后的 archive（分页）
+    if let Some(from) = args.from {
+        if let Some(pos) = out.iter().position(|x| x.canister_id == from) { out.drain(..=pos); }
+    }
+    out
+}
+
+pub fn supported_block_types() -> Vec<SupportedBlockType> {
+    const ICRC3: &str = "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3";
+    const ICRC2: &str = "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2";
+    const OWN: &str = "https://github.com/EdenCapital/sss-stableswap#icrc-3-blocks";
+    [("1xfer", ICRC3), ("1mint", ICRC3), ("1burn", ICRC3), ("2approve", ICRC2), ("2xfer", ICRC2),
+     (BT_SWAP, OWN), (BT_DEPOSIT, OWN), (BT_WITHDRAW, OWN), (BT_CLAIM_FEE, OWN)]
+        .into_iter()
+        .map(|(b, u)| SupportedBlockType { block_type: b.into(), url: u.into() })
+        .collect()
+}
+
+/* ---------------- Archive 溢出 ---------------- */
+
+/// 把最旧的 ARCHIVE_CHUNK 个本地区块推到 archive canister；成功后才从本地删除
+async fn archive_oldest() -> Result<(), String> {
+    let (archive, start, chunk) = STATE.with(|s| {
+        let st = s.borrow();
+        let log = st.icrc3.as_ref().expect("icrc3 log");
+        let n = ARCHIVE_CHUNK.min(log.blocks.len());
+        (log.archive_canister, log.first_index, log.blocks[..n].to_vec())
+    });
+    let Some(archive) = archive else { return Ok(()) };
+    let n = chunk.len() as u64;
+
+    let (res,): (Result<(), String>,) = ic_cdk::call(archive, "append_blocks", (start, chunk))
+        .await
+        .map_err(|(code, msg)| format!("append_blocks rejected: {:?} {}", code, msg))?;
+    res?;
+
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let log = log_mut(&mut st);
+        // 期间若已被其他路径推进，则不重复删除
+        if log.first_index != start { return; }
+        log.blocks.drain(..n as usize);
+        log.first_index += n;
+        match log.archives.last_mut() {
+            Some(r) if r.canister_id == archive && r.end + 1 == start => r.end = start + n - 1,
+            _ => log.archives.push(ArchiveRange { canister_id: archive, start, end: start + n - 1 }),
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(b: &[u8]) -> String { b.iter().map(|x| format!("{:02x}", x)).collect() }
+
+    /// ICRC-3 规范中的哈希示例
+    #[test]
+    fn value_hash_matches_spec_examples() {
+        assert_eq!(hex(&Icrc3Value::nat(42u8).hash()), "684888c0ebb17f374298b65ee2807526c066094c701bcc7ebbe1c1095f494fc1");
+        assert_eq!(hex(&Icrc3Value::Int(Int::from(-42)).hash()), "de5a6f78116eca62d7fc5ce159d23ae6b889b365a1739ad2cf36f925a140d0cc");
+        assert_eq!(hex(&Icrc3Value::text("Hello, World!").hash()), "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f");
+        assert_eq!(hex(&Icrc3Value::blob(&[1, 2, 3, 4]).hash()), "9f64a747e1b97f131fabb6b447296c9b6f0201e79fb3c5356e6c77e89b6a806a");
+        let arr = Icrc3Value::Array(vec![Icrc3Value::nat(3u8), Icrc3Value::text("foo"), Icrc3Value::blob(&[5, 6])]);
+        assert_eq!(hex(&arr.hash()), "514a04011caa503990d446b7dec5d79e19c221ae607fb08b2848c67734d468d6");
+        let map = Icrc3Value::Map(vec![
+            ("from".into(), Icrc3Value::blob(&[0, 171, 205, 239, 0, 18, 52, 0, 86, 120, 154, 0, 188, 222, 240, 0, 1, 35, 69, 103, 137, 0, 171, 205, 239, 1])),
+            ("to".into(), Icrc3Value::blob(&[0, 171, 13, 239, 0, 18, 52, 0, 86, 120, 154, 0, 188, 222, 240, 0, 1, 35, 69, 103, 137, 0, 171, 205, 239, 1])),
+            ("amount".into(), Icrc3Value::nat(42u8)),
+            ("created_at".into(), Icrc3Value::nat(1699218263u64)),
+            ("memo".into(), Icrc3Value::nat(0u8)),
+        ]);
+        assert_eq!(hex(&map.hash()), "c56ece650e1de4269c5bdeff7875949e3e2033f85b2d193c2ff4f7f78bdcfc75");
+    }
+
+    #[test]
+    fn blocks_are_hash_chained() {
+        let mut st = State::default();
+        let a = Account { owner: Principal::from_slice(&[1; 29]), subaccount: None };
+        assert_eq!(append(&mut st, BT_DEPOSIT, 1, vec![("acct", Icrc3Value::account(&a)), ("amt", Icrc3Value::nat(5u8))]), 0);
+        assert_eq!(append(&mut st, BT_WITHDRAW, 2, vec![("acct", Icrc3Value::account(&a)), ("amt", Icrc3Value::nat(3u8))]), 1);
+
+        let log = st.icrc3.as_ref().unwrap();
+        let Icrc3Value::Map(second) = &log.blocks[1] else { panic!("block is a map") };
+        let phash = second.iter().find(|(k, _)| k == "phash").map(|(_, v)| v.clone());
+        assert_eq!(phash, Some(Icrc3Value::blob(&log.blocks[0].hash())));
+        let Icrc3Value::Map(first) = &log.blocks[0] else { panic!("block is a map") };
+        assert!(first.iter().all(|(k, _)| k != "phash"));
+        assert_eq!(log.tip().unwrap(), (1, log.blocks[1].hash()));
+    }
+
+    #[test]
+    fn get_blocks_splits_local_and_archived_ranges() {
+        let mut st = State::default();
+        for i in 0..10u64 { append(&mut st, BT_DEPOSIT, i, vec![("amt", Icrc3Value::nat(i))]); }
+        let archive = Principal::from_slice(&[9; 29]);
+        {
+            let log = st.icrc3.as_mut().unwrap();
+            log.blocks.drain(..4);
+            log.first_index = 4;
+            log.archives.push(ArchiveRange { canister_id: archive, start: 0, end: 3 });
+        }
+        let r = get_blocks(&st, vec![GetBlocksArgs { start: Nat::from(2u8), length: Nat::from(5u8) }]);
+        assert_eq!(r.log_length, Nat::from(10u8));
+        assert_eq!(r.blocks.iter().map(|b| b.id.clone()).collect::<Vec<_>>(), vec![Nat::from(4u8), Nat::from(5u8), Nat::from(6u8)]);
+        assert_eq!(r.archived_blocks.len(), 1);
+        assert_eq!(r.archived_blocks[0].args[0].start, Nat::from(2u8));
+        assert_eq!(r.archived_blocks[0].args[0].length, Nat::from(2u8));
+        assert_eq!(get_archives(&st, GetArchivesArgs { from: None })[0].end, Nat::from(3u8));
+    }
+}
@@ -0,0 +1,282 @@
+// canisters/vaultpair/src/jobs.rs
+// 后台定时任务（ic-cdk-timers）：参数存 State.jobs，init / post_upgrade / 修改参数时重新挂载（定时器本身不随升级保存）。
+//   reconcile：读取各池子子账户的 live 余额（0 号池与注册表中的池子），与 内部储备 + 手续费金库 比对，超过阈值写 AdminAction("drift_alert")；
+//              告警或现价脱锚时按 pause.rs 的配置自动暂停
+//   stats：    推进小时桶（无成交时也滚动），并记录当前小时的 virtual price
+//   cycles：   余额跌破 cycles_alert_threshold 时写 AdminAction("low_cycles")（跌破 / 恢复各记一次）
+// 告警事件的 who 为本 canister。
+use candid::CandidType;
+use ic_cdk_timers::TimerId;
+use serde::{Deserialize, Serialize};
+use std::{cell::{Cell, RefCell}, time::Duration};
+
+use crate::{
+    events::{self, Event},
+    pools::{self, PoolId, POOL0},
+    reconcile::LiveToken,
+    state::{now, State, STATE},
+    types::TokenId,
+};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JobsConfig {
+    pub enabled: bool,
+    /// 各任务间隔（秒）；0 = 关闭该任务
+    pub reconcile_interval_sec: u64,
+    pub stats_interval_sec: u64,
+    pub cycles_interval_sec: u64,
+    /// live 与内部账的差额（任一币种，e6）超过此值即告警
+    pub drift_alert_e6: u128,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self { enabled: true, reconcile_interval_sec: 600, stats_interval_sec: 300, cycles_interval_sec: 3600, drift_alert_e6: 10_000 }
+    }
+}
+
+/// 一次漂移检查的结果；drift = live - expected（正数表示账本上多出）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DriftCheck {
+    pub ts: u64,
+    pub live_usdc_e6: u128,
+    pub live_usdt_e6: u128,
+    pub expected_usdc_e6: u128,
+    pub expected_usdt_e6: u128,
+    pub drift_usdc_e6: i128,
+    pub drift_usdt_e6: i128,
+    pub alert: bool,
+    /// 1 号起各池子的逐币漂移；Option 以兼容旧状态
+    pub pools: Option<Vec<PoolDrift>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolDrift {
+    pub pool: PoolId,
+    pub token: TokenId,
+    pub live_e6: u128,
+    pub expected_e6: u128,
+    pub drift_e6: i128,
+}
+
+/// 挂在 State.jobs 上
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct JobsState {
+    pub config: JobsConfig,
+    pub last_drift: Option<DriftCheck>,
+    pub last_stats_roll: u64,
+    pub last_cycles_check: u64,
+    /// 当前是否处于低 cycles 告警中（避免每次检查都写事件）
+    pub low_cycles: bool,
+}
+
+thread_local! {
+    static TIMERS: RefCell<Vec<TimerId>> = const { RefCell::new(Vec::new()) };
+    // 对账读取跨 await，防止上一轮未完成时重入
+    static RECONCILING: Cell<bool> = const { Cell::new(false) };
+}
+
+fn jobs_mut(st: &mut State) -> &mut JobsState { st.jobs.get_or_insert_with(Default::default) }
+
+pub fn config(st: &State) -> JobsConfig { st.jobs.as_ref().map(|j| j.config.clone()).unwrap_or_default() }
+
+fn self_who() -> String { ic_cdk::api::id().to_text() }
+
+/* ---------------- 挂载 ---------------- */
+
+/// 按当前配置（重新）挂载全部定时器
+pub fn start() {
+    TIMERS.with(|t| t.borrow_mut().drain(..).for_each(ic_cdk_timers::clear_timer));
+    let cfg = STATE.with(|s| config(&s.borrow()));
+    if !cfg.enabled { return; }
+    let mut ids = Vec::new();
+    if cfg.reconcile_interval_sec > 0 {
+        ids.push(ic_cdk_timers::set_timer_interval(Duration::from_secs(cfg.reconcile_interval_sec), || {
+            ic_cdk::spawn(run_reconcile())
+        }));
+    }
+    if cfg.stats_interval_sec > 0 {
+        ids.push(ic_cdk_timers::set_timer_interval(Duration::from_secs(cfg.stats_interval_sec), run_stats));
+    }
+    if cfg.cycles_interval_sec > 0 {
+        ids.push(ic_cdk_timers::set_timer_interval(Duration::from_secs(cfg.cycles_interval_sec), run_cycles));
+    }
+    TIMERS.with(|t| *t.borrow_mut() = ids);
+}
+
+/* ---------------- reconcile ---------------- */
+
+/// 对照 live 余额计算漂移（纯函数）。池子子账户里同时存放储备与未领取手续费
+/// live 为 reconcile::fetch_live 的结果（各池子各币一条）
+pub fn evaluate_drift(st: &State, live: &[LiveToken], ts: u64) -> DriftCheck {
+    let live0 = |t: TokenId| live.iter().find(|l| l.pool == POOL0 && l.token == t).map_or(0, |l| l.live_pool_e6);
+    let (live_usdc_e6, live_usdt_e6) = (live0(TokenId::USDC), live0(TokenId::USDT));
+    let expected_usdc_e6 = st.pool.reserve_usdc.saturating_add(st.fee_vault_usdc);
+    let expected_usdt_e6 = st.pool.reserve_usdt.saturating_add(st.fee_vault_usdt);
+    let drift_usdc_e6 = live_usdc_e6 as i128 - expected_usdc_e6 as i128;
+    let drift_usdt_e6 = live_usdt_e6 as i128 - expected_usdt_e6 as i128;
+    let others: Vec<PoolDrift> = live.iter().filter(|l| l.pool != POOL0).map(|l| {
+        let expected_e6 = pools::load(st, l.pool).ok()
+            .and_then(|p| p.index_of(l.token.into()).map(|i| p.reserves[i].saturating_add(p.fee_vault[i])))
+            .unwrap_or(0);
+        PoolDrift { pool: l.pool, token: l.token, live_e6: l.live_pool_e6, expected_e6, drift_e6: l.live_pool_e6 as i128 - expected_e6 as i128 }
+    }).collect();
+    let limit = config(st).drift_alert_e6;
+    let alert = drift_usdc_e6.unsigned_abs() > limit || drift_usdt_e6.unsigned_abs() > limit
+        || others.iter().any(|d| d.drift_e6.unsigned_abs() > limit);
+    DriftCheck {
+        ts, live_usdc_e6, live_usdt_e6, expected_usdc_e6, expected_usdt_e6, drift_usdc_e6, drift_usdt_e6, alert,
+        pools: Some(others),
+    }
+}
+
+/// 各池子逐币的漂移（比较两次告警是否相同用）
+fn drifts(d: &DriftCheck) -> Vec<(PoolId, TokenId, i128)> {
+    let others = d.pools.iter().flatten().map(|p| (p.pool, p.token, p.drift_e6));
+    [(POOL0, TokenId::USDC, d.drift_usdc_e6), (POOL0, TokenId::USDT, d.drift_usdt_e6)].into_iter().chain(others).collect()
+}
+
+/// 记录检查结果；新出现或数值变化的告警返回事件（同一漂移不重复记）
+pub fn record_drift(st: &mut State, check: DriftCheck, who: String) -> Option<Event> {
+    let jobs = jobs_mut(st);
+    let repeat = jobs.last_drift.as_ref().is_some_and(|d| d.alert && drifts(d) == drifts(&check));
+    jobs.last_drift = Some(check.clone());
+    let others = check.pools.iter().flatten()
+        .map(|d| format!("; pool {} {:?} live={} expected={} drift={}", d.pool, d.token, d.live_e6, d.expected_e6, d.drift_e6));
+    (check.alert && !repeat).then(|| Event::AdminAction {
+        who,
+        action: "drift_alert".into(),
+        detail: format!(
+            "usdc live={} expected={} drift={}; usdt live={} expected={} drift={}{}",
+            check.live_usdc_e6, check.expected_usdc_e6, check.drift_usdc_e6,
+            check.live_usdt_e6, check.expected_usdt_e6, check.drift_usdt_e6,
+            others.collect::<String>(),
+        ),
+        ts: check.ts,
+    })
+}
+
+pub async fn run_reconcile() {
+    if RECONCILING.with(|r| r.replace(true)) { return; }
+    let res = reconcile_once().await;
+    RECONCILING.with(|r| r.set(false));
+    if let Err(e) = res {
+        ic_cdk::print(format!("[jobs] reconcile skipped: {e}"));
+    }
+}
+
+async fn reconcile_once() -> Result<(), String> {
+    let live = crate::reconcile::fetch_live().await.map_err(|e| e.to_string())?;
+    let evs = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let check = evaluate_drift(&st, &live, now());
+        let alert = record_drift(&mut st, check, self_who());
+        // 告警 / 脱锚时自动暂停（见 pause.rs）
+        let paused = match &alert {
+            Some(Event::AdminAction { detail, .. }) => crate::pause::on_drift_alert(&mut st, detail, self_who(), now()),
+            _ => None,
+        };
+        let depeg = crate::pause::check_depeg(&mut st, self_who(), now());
+        [alert, paused, depeg]
+    });
+    evs.into_iter().flatten().for_each(|ev| { events::push(ev); });
+    Ok(())
+}
+
+/* ---------------- stats ---------------- */
+
+pub fn roll_stats(st: &mut State, now_sec: u64) {
+    let vp = events::pool_snapshot(st).virtual_price_e6;
+    st.stats.snapshot_virtual_price(now_sec, vp);
+    jobs_mut(st).last_stats_roll = now_sec;
+}
+
+fn run_stats() {
+    STATE.with(|s| roll_stats(&mut s.borrow_mut(), now()));
+}
+
+/* ---------------- cycles ---------------- */
+
+/// 跌破阈值 / 恢复时各返回一条事件
+pub fn check_cycles(st: &mut State, balance: u128, now_sec: u64, who: String) -> Option<Event> {
+    let threshold = st.cycles_alert_threshold;
+    let low = balance < threshold;
+    let jobs = jobs_mut(st);
+    jobs.last_cycles_check = now_sec;
+    if low == jobs.low_cycles { return None; }
+    jobs.low_cycles = low;
+    Some(Event::AdminAction {
+        who,
+        action: if low { "low_cycles" } else { "cycles_recovered" }.into(),
+        detail: format!("balance={} threshold={}", balance, threshold),
+        ts: now_sec,
+    })
+}
+
+fn run_cycles() {
+    let bal = ic_cdk::api::canister_balance128();
+    let ev = STATE.with(|s| check_cycles(&mut s.borrow_mut(), bal, now(), self_who()));
+    if let Some(ev) = ev {
+        ic_cdk::print(format!("[jobs] {:?}", ev));
+        events::push(ev);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live(pool: PoolId, token: TokenId, live_pool_e6: u128) -> LiveToken {
+        LiveToken { pool, token, ledger: candid::Principal::anonymous(), live_pool_e6, fee_e6: 0 }
+    }
+
+    #[test]
+    fn drift_alerts_once_per_distinct_drift() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 1_000_000;
+        st.fee_vault_usdc = 500;
+        st.pool.reserve_usdt = 2_000_000;
+        let pair = |usdt: u128| vec![live(POOL0, TokenId::USDC, 1_000_500), live(POOL0, TokenId::USDT, usdt)];
+
+        let ok = evaluate_drift(&st, &pair(2_000_000), 10);
+        assert_eq!((ok.drift_usdc_e6, ok.drift_usdt_e6, ok.alert), (0, 0, false));
+        assert!(record_drift(&mut st, ok, "c".into()).is_none());
+
+        let bad = evaluate_drift(&st, &pair(1_900_000), 11);
+        assert_eq!((bad.drift_usdt_e6, bad.alert), (-100_000, true));
+        assert!(record_drift(&mut st, bad.clone(), "c".into()).is_some());
+        assert!(record_drift(&mut st, bad, "c".into()).is_none());
+        let worse = evaluate_drift(&st, &pair(1_800_000), 12);
+        assert!(record_drift(&mut st, worse, "c".into()).is_some());
+        assert_eq!(st.jobs.as_ref().unwrap().last_drift.as_ref().unwrap().ts, 12);
+
+        // 其余池子按各自的储备 + 金库比对，0 号池无漂移时也能告警
+        let args = pools::CreatePoolArgs { coins: vec![crate::types::Coin::ICP, crate::types::Coin::USDC], a_amp: 50, fee_bps: 30, kind: None, crypto: None, initial_price_e6: None };
+        let id = pools::create(&mut st, &args, 1_700_000_000).unwrap();
+        let mut p = pools::load(&st, id).unwrap();
+        p.reserves = vec![0, 300_000];
+        pools::store(&mut st, &p).unwrap();
+        let mut all = pair(2_000_000);
+        all.push(live(id, TokenId::USDC, 300_000));
+        assert!(!evaluate_drift(&st, &all, 13).alert);
+        all[2].live_pool_e6 = 250_000;
+        let short = evaluate_drift(&st, &all, 14);
+        assert_eq!((short.drift_usdt_e6, short.alert), (0, true));
+        assert_eq!(short.pools.as_ref().unwrap()[0], PoolDrift { pool: id, token: TokenId::USDC, live_e6: 250_000, expected_e6: 300_000, drift_e6: -50_000 });
+        assert!(matches!(record_drift(&mut st, short, "c".into()), Some(Event::AdminAction { ref detail, .. }) if detail.contains(&format!("pool {id} USDC"))));
+    }
+
+    #[test]
+    fn cycles_warn_on_crossing_and_stats_roll_snapshots_vp() {
+        let mut st = State { cycles_alert_threshold: 100, ..Default::default() };
+        assert!(check_cycles(&mut st, 500, 1, "c".into()).is_none());
+        assert!(matches!(check_cycles(&mut st, 50, 2, "c".into()), Some(Event::AdminAction { ref action, .. }) if action == "low_cycles"));
+        assert!(check_cycles(&mut st, 40, 3, "c".into()).is_none());
+        assert!(matches!(check_cycles(&mut st, 200, 4, "c".into()), Some(Event::AdminAction { ref action, .. }) if action == "cycles_recovered"));
+
+        let t = 1_700_000_000;
+        roll_stats(&mut st, t);
+        let series = st.stats.series(t, 1);
+        assert_eq!((series[0].ts_hour, series[0].virtual_price_e6), (t / 3600, Some(1_000_000)));
+    }
+}
@@ -4,7 +4,8 @@ use serde::{Serialize, Deserialize};
 use std::collections::BTreeMap;
 use num_traits::ToPrimitive;
 
-use crate::state::STATE;
+use crate::math::rates::{self, Rounding};
+use crate::state::{State, STATE};
 use crate::types::TokenId;
 
 // =============== 内部类型（e6 存储口径） ===============
@@ -20,7 +21,7 @@ pub struct LedgerBook {
 }
 
 // =============== Token 注册表（后端常量） ===============
-fn token_principal(tok: &TokenId) -> Principal {
+pub(crate) fn token_principal(tok: &TokenId) -> Principal {
     use candid::Principal as P;
     match tok {
         TokenId::USDC => P::from_text("xevnm-gaaaa-aaaar-qafnq-cai").unwrap(), // ckUSDC
@@ -93,12 +94,34 @@ fn nat_to_u128(n: Nat) -> Result<u128, &'static str> {
     n.0.to_u128().ok_or("overflow u128")
 }
 
-// 把不同 decimals 的最小单位换成 e6：ckUSDC/ckUSDT=6 原样；ICP/BOB=8 需 /1e2
-fn to_e6(tok: TokenId, min_unit: u128) -> u128 {
-    match tok {
-        TokenId::USDC | TokenId::USDT => min_unit,          // 6
-        TokenId::ICP  | TokenId::BOB  => min_unit / 100,    // 8 -> 6
-    }
+// 把不同 decimals 的最小单位换成 e6（向下取整）：ckUSDC/ckUSDT=6 原样；ICP/BOB=8 截掉 2 位。返回 (e6, 截掉的尾数，最小单位)
+fn to_e6(tok: TokenId, min_unit: u128) -> (u128, u128) {
+    let dec = match tok {
+        TokenId::USDC | TokenId::USDT => 6,
+        TokenId::ICP  | TokenId::BOB  => 8,
+    };
+    rates::rescale(min_unit, dec, rates::BOOK_DECIMALS, Rounding::Down)
+}
+
+// =============== 换算尾数（dust） ===============
+/// 换算到 e6 记账口径时向下取整截掉的尾数：只记账、不退还，便于核对链上余额与内账的差额
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct DustBook {
+    // 用户子账户链上余额换算为 e6 时截掉的部分（最小单位；每次对账覆盖）
+    pub user: BTreeMap<(Principal, TokenId), u128>,
+    // 池子付款 e6 → 链上单位时截掉的部分（e6；按账本 canister 累计）
+    pub pool: BTreeMap<Principal, u128>,
+}
+
+pub(crate) fn record_user_dust(st: &mut State, user: Principal, t: TokenId, dust: u128) {
+    let book = st.dust.get_or_insert_with(Default::default);
+    if dust == 0 { book.user.remove(&(user, t)); } else { book.user.insert((user, t), dust); }
+}
+
+pub(crate) fn record_pool_dust(st: &mut State, ledger: Principal, dust: u128) {
+    if dust == 0 { return; }
+    let e = st.dust.get_or_insert_with(Default::default).pool.entry(ledger).or_default();
+    *e = e.saturating_add(dust);
 }
 
 pub async fn sync_user_all(user: Principal) -> Result<(), String> {
@@ -109,10 +132,14 @@ pub async fn sync_user_all(user: Principal) -> Result<(), String> {
         let can = token_principal(&t);
         let (bal_nat,): (Nat,) = ic_cdk::call(
             can, "icrc1_balance_of", (BalReq { owner: vault, subaccount: Some(sub32.clone()) },)
-        ).await.map_err(|e| format!("icrc1_balance_of failed: {:?}", e))?;
+        ).await.map_err(|e| {
+            crate::metrics::ledger_error(can, "CallRejected");
+            format!("icrc1_balance_of failed: {:?}", e)
+        })?;
 
         let min_unit = nat_to_u128(bal_nat).map_err(|e| format!("nat->u128: {e}"))?;
-        let onchain_e6 = to_e6(t, min_unit);
+        let (onchain_e6, dust) = to_e6(t, min_unit);
+        STATE.with(|s| record_user_dust(&mut s.borrow_mut(), user, t, dust));
         set_row(user, t, |r| {
             // 对账策略：以链上余额为准，保留 reserved，不让 avail 变负。
             r.avail = onchain_e6.saturating_sub(r.reserved);
@@ -146,3 +173,27 @@ pub fn set_available(user: Principal, t: TokenId, amt_e6: u128) {
 // pub fn set_reserved(user: Principal, t: TokenId, amt_e6: u128) {
 //     set_row(user, t, |r| { r.reserved = amt_e6; });
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 用户尾数每次对账覆盖（为 0 时清除），池子尾数累计
+    #[test]
+    fn user_dust_is_overwritten_and_pool_dust_accumulates() {
+        let mut st = State::default();
+        let (u, ledger) = (Principal::anonymous(), Principal::management_canister());
+        let (e6, dust) = to_e6(TokenId::ICP, 123_456_789);
+        assert_eq!((e6, dust), (1_234_567, 89));
+        record_user_dust(&mut st, u, TokenId::ICP, dust);
+        record_user_dust(&mut st, u, TokenId::ICP, 12);
+        assert_eq!(st.dust.as_ref().unwrap().user.get(&(u, TokenId::ICP)), Some(&12));
+        record_user_dust(&mut st, u, TokenId::ICP, 0);
+        assert!(st.dust.as_ref().unwrap().user.is_empty());
+
+        record_pool_dust(&mut st, ledger, 3);
+        record_pool_dust(&mut st, ledger, 0);
+        record_pool_dust(&mut st, ledger, 4);
+        assert_eq!(st.dust.unwrap().pool.get(&ledger), Some(&7));
+    }
+}
@@ -1,19 +1,121 @@
-mod types; mod error; mod events; mod state; mod icrc; mod stats; mod ledger_book;
-mod swap; mod positions; mod assets; mod explore; mod activity; mod api;
+mod types; mod error; mod memory; mod events; mod state; mod icrc; mod icrc3; mod certified; mod stats; mod ledger_book;
+mod pools; mod swap; mod router; mod metapool; mod positions; mod reconcile; mod solvency; mod pause; mod oracle; mod assets; mod explore; mod activity; mod lp_token; mod metrics; mod jobs; mod http; mod api;
 
 pub use api::*;
-pub mod math { pub mod stableswap; }
+pub mod math { pub mod stableswap; pub mod invariant; pub mod constant_product; pub mod cryptoswap; pub mod metapool; pub mod rates; pub mod exp; }
 use candid::Principal;
 use candid::Nat;
 use crate::ledger_book::available;
+use crate::ledger_book::DustBook;
 
 use crate::types::{
-    Account, AmountE6, TokenId, PoolInfo, QuoteOut, SwapArgs, SubBalance, Position,
+    Account, AmountE6, TokenId, Coin, PoolInfo, SwapArgs, SubBalance, Position,
     StatsSnapshot, RiskParams, CyclesInfo, Available,
 };
-use crate::events::Event;
+use crate::events::{EventV1, EventQuery, EventPage, EventArchiveRange};
+use crate::http::{HttpRequest, HttpResponse};
+use crate::certified::PoolInfoCertified;
+use crate::jobs::{JobsConfig, JobsState};
+use crate::reconcile::{ReconcileReport, ReconcileCorrection};
+use crate::solvency::SolvencyArgs;
+use crate::pause::{AutoPauseConfig, PauseOp, PauseState};
+use crate::oracle::{OracleConfig, PriceOracle};
+use crate::pools::{PoolId, PoolState, CreatePoolArgs};
+use crate::router::RouteSwapArgs;
+use crate::metapool::UnderlyingSwapArgs;
+use crate::icrc::{
+    Icrc1TransferArg as LpTransferArg, TransferError, ApproveArgs, ApproveError, AllowanceArgs, Allowance,
+    TransferFromArgs, TransferFromError, MetadataValue, StandardRecord,
+};
+use crate::icrc3::{
+    GetBlocksArgs, GetBlocksResult, GetArchivesArgs, Icrc3ArchiveInfo, Icrc3DataCertificate, SupportedBlockType,
+};
 
 use ic_cdk::export_candid;
 export_candid!();
 
 
+
+#[cfg(test)]
+mod candid_tests {
+    use candid::types::subtype::subtype;
+    use candid_parser::utils::{service_equal, CandidSource};
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+    use std::path::PathBuf;
+
+    const HEADER: &str = "// 由 export_candid!() 生成，勿手改：UPDATE_DID=1 cargo test did_matches_code 回写\n";
+
+    fn did_path(rel: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(rel)
+    }
+
+    /// 代码导出的接口必须与仓库内 vaultpair.did 一致（漂移即失败）
+    #[test]
+    fn did_matches_code() {
+        let generated = super::__export_service();
+        let path = did_path("src/vaultpair.did");
+        if std::env::var_os("UPDATE_DID").is_some() {
+            std::fs::write(&path, format!("{HEADER}{generated}")).unwrap();
+        }
+        service_equal(CandidSource::Text(&generated), CandidSource::File(&path)).unwrap_or_else(|e| {
+            panic!("vaultpair.did 与代码不一致（UPDATE_DID=1 cargo test did_matches_code 回写）：{e:?}")
+        });
+    }
+
+    fn parse_version(v: &str) -> (u64, u64, u64) {
+        let n: Vec<u64> = v.trim().split('.').map(|x| x.parse().unwrap()).collect();
+        (n[0], n[1], n[2])
+    }
+
+    /// 快照里的 `// version: x.y.z`：快照对应的已部署版本
+    fn release_version(did: &str) -> (u64, u64, u64) {
+        parse_version(did.lines().find_map(|l| l.strip_prefix("// version:")).expect("快照缺少 // version: 行"))
+    }
+
+    /// 本版有意破坏兼容的方法：每行一个方法名，# 之后为说明
+    fn declared_breaks() -> BTreeSet<String> {
+        let text = std::fs::read_to_string(did_path("candid/vaultpair.breaks")).unwrap_or_default();
+        text.lines().map(|l| l.split('#').next().unwrap().trim()).filter(|l| !l.is_empty()).map(String::from).collect()
+    }
+
+    /// 语义化版本的不兼容升级：0.x 升 minor，1.x 起升 major
+    fn is_breaking_bump(old: (u64, u64, u64), new: (u64, u64, u64)) -> bool {
+        if old.0 == 0 { new.0 > 0 || new.1 > old.1 } else { new.0 > old.0 }
+    }
+
+    /// 对已部署接口（candid/vaultpair.release.did）逐方法做 candid 子类型检查，保证已部署的前端/调用方不被破坏。
+    /// 不兼容或删除的方法必须登记在 candid/vaultpair.breaks，且 Cargo 版本须相对快照版本做不兼容升级；登记了却仍兼容的也报错。
+    /// 快照只在发版时更新（见 README「Releasing」），功能提交不改
+    #[test]
+    fn did_backward_compatible_with_release() {
+        let generated = super::__export_service();
+        let path = did_path("candid/vaultpair.release.did");
+        let (mut env, new) = CandidSource::Text(&generated).load().unwrap();
+        let (old_env, old) = CandidSource::File(&path).load().unwrap();
+        let old = env.merge_type(old_env, old.unwrap());
+        let new = new.unwrap();
+        let new_methods: BTreeMap<_, _> = env.as_service(&new).unwrap().iter().cloned().collect();
+
+        let declared = declared_breaks();
+        let (mut broken, mut stale) = (Vec::new(), Vec::new());
+        for (name, old_ty) in env.as_service(&old).unwrap() {
+            let ok = new_methods.get(name).is_some_and(|t| subtype(&mut HashSet::new(), &env, t, old_ty).is_ok());
+            match (ok, declared.contains(name)) {
+                (false, false) => broken.push(name.clone()),
+                (true, true) => stale.push(name.clone()),
+                _ => {}
+            }
+        }
+        let old_names: BTreeSet<_> = env.as_service(&old).unwrap().iter().map(|(n, _)| n.clone()).collect();
+        stale.extend(declared.iter().filter(|n| !old_names.contains(*n)).cloned());
+        assert!(broken.is_empty(), "以下方法与已部署接口不兼容，未登记在 candid/vaultpair.breaks：{broken:?}");
+        assert!(stale.is_empty(), "candid/vaultpair.breaks 登记的方法实际兼容或不存在：{stale:?}");
+
+        if !declared.is_empty() {
+            let released = release_version(&std::fs::read_to_string(&path).unwrap());
+            let current = parse_version(env!("CARGO_PKG_VERSION"));
+            assert!(is_breaking_bump(released, current),
+                "有登记的不兼容变更，Cargo 版本 {current:?} 须相对已部署版本 {released:?} 做不兼容升级");
+        }
+    }
+}
@@ -0,0 +1,464 @@
+// canisters/vaultpair/src/lp_token/mod.rs
+// LP 份额作为 ICRC-1 / ICRC-2 代币：余额仍记在 State.user_shares，
+// 默认子账户的 key 保持为 principal 文本（与 positions 模块一致），非默认子账户为 "principal.hex(sub)"。
+use candid::{CandidType, Nat, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use num_traits::cast::ToPrimitive;
+
+use crate::{
+    types::Account,
+    state::State,
+    positions::settle_user_fee,
+    icrc3::{self, Icrc3Value},
+    icrc::{
+        Icrc1TransferArg, TransferError, ApproveArgs, ApproveError, AllowanceArgs, Allowance,
+        TransferFromArgs, TransferFromError, MetadataValue, StandardRecord,
+    },
+};
+
+pub const LP_NAME: &str = "SSS StableSwap LP (ckUSDC/ckUSDT)";
+pub const LP_SYMBOL: &str = "SSS-LP";
+pub const LP_DECIMALS: u8 = 6;   // 与内部 shares 的 e6 口径一致
+pub const LP_FEE: u128 = 0;
+
+const MAX_MEMO_LEN: usize = 32;
+const TX_WINDOW_NS: u64 = 24 * 3600 * 1_000_000_000;
+const PERMITTED_DRIFT_NS: u64 = 60 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LpAllowance { pub amount: u128, pub expires_at: Option<u64> }
+
+/// LP 代币附加状态（挂在 State.lp_token 上）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LpToken {
+    // (owner_key, spender_key) -> 授权额度
+    pub allowances: BTreeMap<(String, String), LpAllowance>,
+    // ICRC-1 去重：(created_at_time, 交易哈希) -> 区块号；只记带 created_at_time 的交易，超出窗口即清理（Option 以兼容旧状态）
+    pub dedup: Option<BTreeMap<(u64, String), u64>>,
+}
+
+/// 一次调用的上下文（测试中可直接构造，避免依赖 ic0）
+pub struct TxCtx { pub caller: Principal, pub canister: Principal, pub now: u64 }
+
+impl TxCtx {
+    pub fn current() -> Self {
+        TxCtx { caller: ic_cdk::caller(), canister: ic_cdk::api::id(), now: ic_cdk::api::time() }
+    }
+}
+
+fn lp_mut(st: &mut State) -> &mut LpToken { st.lp_token.get_or_insert_with(Default::default) }
+
+fn is_default_sub(sub: &Option<Vec<u8>>) -> bool {
+    sub.as_ref().is_none_or(|b| b.iter().all(|x| *x == 0))
+}
+
+/// 账户 → user_shares 的 key
+pub fn account_key(acct: &Account) -> String {
+    match &acct.subaccount {
+        Some(b) if !is_default_sub(&acct.subaccount) => {
+            use core::fmt::Write;
+            let mut k = format!("{}.", acct.owner.to_text());
+            for x in b { write!(&mut k, "{:02x}", x).unwrap(); }
+            k
+        }
+        _ => acct.owner.to_text(),
+    }
+}
+
+fn account_of(owner: Principal, sub: &Option<serde_bytes::ByteBuf>) -> Account {
+    Account { owner, subaccount: sub.as_ref().map(|b| b.to_vec()) }
+}
+
+fn bad_sub(acct: &Account) -> bool {
+    acct.subaccount.as_ref().is_some_and(|b| b.len() != 32)
+}
+
+fn shares_of(st: &State, key: &str) -> u128 { *st.user_shares.get(key).unwrap_or(&0) }
+
+pub fn balance_of(st: &State, acct: &Account) -> u128 { shares_of(st, &account_key(acct)) }
+
+/// 转移 shares：先按变动前余额结算双方的手续费指数，再改余额，保证未领手续费不随份额转移
+pub(crate) fn move_shares(st: &mut State, from_key: &str, to_key: &str, amount: u128) {
+    let fb = shares_of(st, from_key);
+    settle_user_fee(st, from_key, fb);
+    if from_key == to_key { return; }
+    let tb = shares_of(st, to_key);
+    settle_user_fee(st, to_key, tb);
+    st.user_shares.insert(from_key.to_string(), fb - amount);
+    st.user_shares.insert(to_key.to_string(), tb.saturating_add(amount));
+}
+
+/// 去重键：调用者 + 操作类型 + 全部参数的哈希；不带 created_at_time 的交易不去重
+fn dedup_id<A: CandidType>(btype: &str, caller: Principal, args: &A, created_at_time: Option<u64>) -> Option<(u64, String)> {
+    use sha2::{Digest, Sha256};
+    use core::fmt::Write;
+    let t = created_at_time?;
+    let bytes = candid::encode_args((btype, caller, args)).expect("encode dedup args");
+    let mut h = String::with_capacity(64);
+    for x in Sha256::digest(&bytes) { write!(&mut h, "{:02x}", x).unwrap(); }
+    Some((t, h))
+}
+
+/// 先清理窗口外的记录，再查重放：命中时返回原交易的区块号
+fn find_duplicate(st: &mut State, id: &(u64, String), now: u64) -> Option<Nat> {
+    let cutoff = now.saturating_sub(TX_WINDOW_NS + PERMITTED_DRIFT_NS);
+    let seen = lp_mut(st).dedup.get_or_insert_with(Default::default);
+    while seen.first_key_value().is_some_and(|((t, _), _)| *t < cutoff) { seen.pop_first(); }
+    seen.get(id).map(|b| Nat::from(*b))
+}
+
+/// 写 ICRC-3 区块并返回其编号；带 created_at_time（即有去重键）时登记到去重表。
+/// 调用方在释放 STATE 后执行 icrc3::commit()
+fn record(
+    st: &mut State, ctx: &TxCtx, btype: &str, mut tx: Vec<(&'static str, Icrc3Value)>,
+    fee: &Option<Nat>, memo: &Option<serde_bytes::ByteBuf>, dedup: Option<(u64, String)>,
+) -> Nat {
+    if let Some(f) = fee { tx.push(("fee", Icrc3Value::Nat(f.clone()))); }
+    if let Some(m) = memo { tx.push(("memo", Icrc3Value::Blob(m.clone()))); }
+    if let Some((t, _)) = &dedup { tx.push(("ts", Icrc3Value::nat(*t))); }
+    let block = icrc3::append(st, btype, ctx.now, tx);
+    if let Some(id) = dedup { lp_mut(st).dedup.get_or_insert_with(Default::default).insert(id, block); }
+    Nat::from(block)
+}
+
+fn generic(message: &str) -> (Nat, String) { (Nat::from(0u8), message.to_string()) }
+
+/// 公共前置校验：fee / memo / created_at_time / 去重；不通过时返回对应的 ICRC 错误分支
+enum Precheck { BadFee, TooOld, CreatedInFuture, Duplicate(Nat), Generic(&'static str) }
+
+fn precheck(
+    st: &mut State, fee: &Option<Nat>, memo: &Option<serde_bytes::ByteBuf>, created_at_time: Option<u64>,
+    dedup: &Option<(u64, String)>, now: u64,
+) -> Option<Precheck> {
+    if fee.as_ref().is_some_and(|f| f.0 != LP_FEE.into()) { return Some(Precheck::BadFee); }
+    if memo.as_ref().is_some_and(|m| m.len() > MAX_MEMO_LEN) { return Some(Precheck::Generic("memo too long")); }
+    if let Some(t) = created_at_time {
+        if t.saturating_add(TX_WINDOW_NS + PERMITTED_DRIFT_NS) < now { return Some(Precheck::TooOld); }
+        if t > now.saturating_add(PERMITTED_DRIFT_NS) { return Some(Precheck::CreatedInFuture); }
+    }
+    dedup.as_ref().and_then(|id| find_duplicate(st, id, now)).map(Precheck::Duplicate)
+}
+
+fn live_allowance(st: &State, key: &(String, String), now: u64) -> LpAllowance {
+    match st.lp_token.as_ref().and_then(|lp| lp.allowances.get(key)) {
+        Some(a) if a.expires_at.is_none_or(|t| t > now) => a.clone(),
+        _ => LpAllowance::default(),
+    }
+}
+
+/* ---------------- ICRC-1 ---------------- */
+
+pub fn metadata() -> Vec<(String, MetadataValue)> {
+    vec![
+        ("icrc1:name".into(), MetadataValue::Text(LP_NAME.into())),
+        ("icrc1:symbol".into(), MetadataValue::Text(LP_SYMBOL.into())),
+        ("icrc1:decimals".into(), MetadataValue::Nat(Nat::from(LP_DECIMALS))),
+        ("icrc1:fee".into(), MetadataValue::Nat(Nat::from(LP_FEE))),
+    ]
+}
+
+pub fn supported_standards() -> Vec<StandardRecord> {
+    vec![
+        StandardRecord { name: "ICRC-1".into(), url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-1".into() },
+        StandardRecord { name: "ICRC-2".into(), url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-2".into() },
+        StandardRecord { name: "ICRC-3".into(), url: "https://github.com/dfinity/ICRC-1/tree/main/standards/ICRC-3".into() },
+    ]
+}
+
+/// 铸币账户 = 本 canister 默认账户；份额只能通过 add/remove_liquidity 铸造/销毁
+pub fn minting_account(canister: Principal) -> Account { Account { owner: canister, subaccount: None } }
+
+fn is_minting(acct: &Account, canister: Principal) -> bool {
+    acct.owner == canister && is_default_sub(&acct.subaccount)
+}
+
+pub fn transfer(st: &mut State, ctx: &TxCtx, arg: Icrc1TransferArg) -> Result<Nat, TransferError> {
+    let from = account_of(ctx.caller, &arg.from_subaccount);
+    let generic_err = |m: &str| { let (error_code, message) = generic(m); TransferError::GenericError { error_code, message } };
+    if bad_sub(&from) || bad_sub(&arg.to) { return Err(generic_err("subaccount must be 32 bytes")); }
+    let dedup = dedup_id("1xfer", ctx.caller, &arg, arg.created_at_time);
+    match precheck(st, &arg.fee, &arg.memo, arg.created_at_time, &dedup, ctx.now) {
+        Some(Precheck::BadFee) => return Err(TransferError::BadFee { expected_fee: Nat::from(LP_FEE) }),
+        Some(Precheck::TooOld) => return Err(TransferError::TooOld),
+        Some(Precheck::CreatedInFuture) => return Err(TransferError::CreatedInFuture { ledger_time: ctx.now }),
+        Some(Precheck::Duplicate(duplicate_of)) => return Err(TransferError::Duplicate { duplicate_of }),
+        Some(Precheck::Generic(m)) => return Err(generic_err(m)),
+        None => {}
+    }
+    if is_minting(&arg.to, ctx.canister) { return Err(generic_err("burn shares via remove_liquidity")); }
+    if arg.to.owner == ctx.canister { return Err(generic_err("cannot transfer shares to canister accounts")); }
+
+    let from_key = account_key(&from);
+    let have = shares_of(st, &from_key);
+    let amount = match arg.amount.0.to_u128() {
+        Some(a) if a <= have => a,
+        _ => return Err(TransferError::InsufficientFunds { balance: Nat::from(have) }),
+    };
+    move_shares(st, &from_key, &account_key(&arg.to), amount);
+    let tx = vec![
+        ("from", Icrc3Value::account(&from)),
+        ("to", Icrc3Value::account(&arg.to)),
+        ("amt", Icrc3Value::nat(amount)),
+    ];
+    Ok(record(st, ctx, "1xfer", tx, &arg.fee, &arg.memo, dedup))
+}
+
+/* ---------------- ICRC-2 ---------------- */
+
+pub fn approve(st: &mut State, ctx: &TxCtx, args: ApproveArgs) -> Result<Nat, ApproveError> {
+    let from = account_of(ctx.caller, &args.from_subaccount);
+    let generic_err = |m: &str| { let (error_code, message) = generic(m); ApproveError::GenericError { error_code, message } };
+    if bad_sub(&from) || bad_sub(&args.spender) { return Err(generic_err("subaccount must be 32 bytes")); }
+    if args.spender.owner == ctx.caller { return Err(generic_err("self approval is not allowed")); }
+    let dedup = dedup_id("2approve", ctx.caller, &args, args.created_at_time);
+    match precheck(st, &args.fee, &args.memo, args.created_at_time, &dedup, ctx.now) {
+        Some(Precheck::BadFee) => return Err(ApproveError::BadFee { expected_fee: Nat::from(LP_FEE) }),
+        Some(Precheck::TooOld) => return Err(ApproveError::TooOld),
+        Some(Precheck::CreatedInFuture) => return Err(ApproveError::CreatedInFuture { ledger_time: ctx.now }),
+        Some(Precheck::Duplicate(duplicate_of)) => return Err(ApproveError::Duplicate { duplicate_of }),
+        Some(Precheck::Generic(m)) => return Err(generic_err(m)),
+        None => {}
+    }
+    if args.expires_at.is_some_and(|t| t <= ctx.now) {
+        return Err(ApproveError::Expired { ledger_time: ctx.now });
+    }
+
+    let key = (account_key(&from), account_key(&args.spender));
+    let current = live_allowance(st, &key, ctx.now).amount;
+    if let Some(expected) = &args.expected_allowance {
+        if expected.0 != current.into() {
+            return Err(ApproveError::AllowanceChanged { current_allowance: Nat::from(current) });
+        }
+    }
+    // 超出 u128 的授权按“无限”截断
+    let amount = args.amount.0.to_u128().unwrap_or(u128::MAX);
+    let lp = lp_mut(st);
+    if amount == 0 {
+        lp.allowances.remove(&key);
+    } else {
+        lp.allowances.insert(key, LpAllowance { amount, expires_at: args.expires_at });
+    }
+    let mut tx = vec![
+        ("from", Icrc3Value::account(&from)),
+        ("spender", Icrc3Value::account(&args.spender)),
+        ("amt", Icrc3Value::nat(amount)),
+    ];
+    if let Some(e) = &args.expected_allowance { tx.push(("expected_allowance", Icrc3Value::Nat(e.clone()))); }
+    if let Some(t) = args.expires_at { tx.push(("expires_at", Icrc3Value::nat(t))); }
+    Ok(record(st, ctx, "2approve", tx, &args.fee, &args.memo, dedup))
+}
+
+pub fn allowance(st: &State, args: &AllowanceArgs, now: u64) -> Allowance {
+    let a = live_allowance(st, &(account_key(&args.account), account_key(&args.spender)), now);
+    Allowance { allowance: Nat::from(a.amount), expires_at: a.expires_at }
+}
+
+pub fn transfer_from(st: &mut State, ctx: &TxCtx, args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    let spender = account_of(ctx.caller, &args.spender_subaccount);
+    let generic_err = |m: &str| { let (error_code, message) = generic(m); TransferFromError::GenericError { error_code, message } };
+    if bad_sub(&spender) || bad_sub(&args.from) || bad_sub(&args.to) {
+        return Err(generic_err("subaccount must be 32 bytes"));
+    }
+    let dedup = dedup_id("2xfer", ctx.caller, &args, args.created_at_time);
+    match precheck(st, &args.fee, &args.memo, args.created_at_time, &dedup, ctx.now) {
+        Some(Precheck::BadFee) => return Err(TransferFromError::BadFee { expected_fee: Nat::from(LP_FEE) }),
+        Some(Precheck::TooOld) => return Err(TransferFromError::TooOld),
+        Some(Precheck::CreatedInFuture) => return Err(TransferFromError::CreatedInFuture { ledger_time: ctx.now }),
+        Some(Precheck::Duplicate(duplicate_of)) => return Err(TransferFromError::Duplicate { duplicate_of }),
+        Some(Precheck::Generic(m)) => return Err(generic_err(m)),
+        None => {}
+    }
+    if is_minting(&args.to, ctx.canister) { return Err(generic_err("burn shares via remove_liquidity")); }
+    if args.to.owner == ctx.canister { return Err(generic_err("cannot transfer shares to canister accounts")); }
+
+    let from_key = account_key(&args.from);
+    let spender_key = account_key(&spender);
+    let amount = args.amount.0.to_u128().unwrap_or(u128::MAX);
+
+    // spender 即 from 本人时不消耗授权
+    let key = (from_key.clone(), spender_key.clone());
+    let allowed = if from_key == spender_key { None } else { Some(live_allowance(st, &key, ctx.now)) };
+    if let Some(a) = &allowed {
+        if a.amount < amount {
+            return Err(TransferFromError::InsufficientAllowance { allowance: Nat::from(a.amount) });
+        }
+    }
+    let have = shares_of(st, &from_key);
+    if amount > have { return Err(TransferFromError::InsufficientFunds { balance: Nat::from(have) }); }
+
+    if let Some(a) = allowed {
+        let lp = lp_mut(st);
+        let left = a.amount - amount;
+        if left == 0 { lp.allowances.remove(&key); } else { lp.allowances.insert(key, LpAllowance { amount: left, ..a }); }
+    }
+    move_shares(st, &from_key, &account_key(&args.to), amount);
+    let tx = vec![
+        ("from", Icrc3Value::account(&args.from)),
+        ("to", Icrc3Value::account(&args.to)),
+        ("spender", Icrc3Value::account(&spender)),
+        ("amt", Icrc3Value::nat(amount)),
+    ];
+    Ok(record(st, ctx, "2xfer", tx, &args.fee, &args.memo, dedup))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::positions::ACC_E18;
+
+    fn p(n: u8) -> Principal { Principal::from_slice(&[n; 29]) }
+    fn acct(n: u8) -> Account { Account { owner: p(n), subaccount: None } }
+    fn ctx(n: u8) -> TxCtx { TxCtx { caller: p(n), canister: p(99), now: 1_000_000_000_000 } }
+    fn xfer(to: Account, amount: u128) -> Icrc1TransferArg {
+        Icrc1TransferArg { from_subaccount: None, to, amount: Nat::from(amount), fee: None, memo: None, created_at_time: None }
+    }
+
+    /// A 持有全部份额、已产生 1e6 USDC 手续费时转给 B：手续费留在 A，B 只享有之后的增长
+    #[test]
+    fn transfer_settles_fee_for_sender_and_receiver() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 1_000_000);
+        st.pool.total_shares = 1_000_000;
+        st.fee_growth_usdc_e18 = ACC_E18; // 每份额 1 单位
+
+        assert_eq!(transfer(&mut st, &ctx(1), xfer(acct(2), 400_000)), Ok(Nat::from(0u8)));
+        assert_eq!(st.user_fee_owed_usdc.get(&p(1).to_text()), Some(&1_000_000));
+        assert_eq!(st.user_fee_owed_usdc.get(&p(2).to_text()), None);
+        assert_eq!(st.user_fee_idx_usdc.get(&p(2).to_text()), Some(&ACC_E18));
+
+        st.fee_growth_usdc_e18 += ACC_E18;
+        let settle = |st: &mut State, n: u8| { let k = p(n).to_text(); let s = shares_of(st, &k); settle_user_fee(st, &k, s); };
+        settle(&mut st, 1);
+        settle(&mut st, 2);
+        assert_eq!(st.user_fee_owed_usdc.get(&p(1).to_text()), Some(&1_600_000));
+        assert_eq!(st.user_fee_owed_usdc.get(&p(2).to_text()), Some(&400_000));
+    }
+
+    #[test]
+    fn transfer_checks_balance_fee_and_minting_account() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 10);
+        assert_eq!(transfer(&mut st, &ctx(1), xfer(acct(2), 11)), Err(TransferError::InsufficientFunds { balance: Nat::from(10u8) }));
+        let mut bad_fee = xfer(acct(2), 1);
+        bad_fee.fee = Some(Nat::from(1u8));
+        assert_eq!(transfer(&mut st, &ctx(1), bad_fee), Err(TransferError::BadFee { expected_fee: Nat::from(0u8) }));
+        assert!(matches!(transfer(&mut st, &ctx(1), xfer(acct(99), 1)), Err(TransferError::GenericError { .. })));
+        // 池子账户等 canister 名下的子账户只能经 swap / 流动性接口划入
+        let pool_acct = Account { owner: p(99), subaccount: Some(vec![7; 32]) };
+        assert!(matches!(transfer(&mut st, &ctx(1), xfer(pool_acct, 1)), Err(TransferError::GenericError { .. })));
+        assert_eq!(shares_of(&st, &p(1).to_text()), 10);
+    }
+
+    #[test]
+    fn subaccounts_are_separate_holders() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 10);
+        let sub = Account { owner: p(1), subaccount: Some(vec![1; 32]) };
+        transfer(&mut st, &ctx(1), xfer(sub.clone(), 3)).unwrap();
+        assert_eq!(balance_of(&st, &acct(1)), 7);
+        assert_eq!(balance_of(&st, &sub), 3);
+        // 全零子账户等同默认账户
+        assert_eq!(balance_of(&st, &Account { owner: p(1), subaccount: Some(vec![0; 32]) }), 7);
+    }
+
+    #[test]
+    fn approve_and_transfer_from_consume_allowance() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 100);
+        let approve_args = |amount: u128, expected: Option<u128>| ApproveArgs {
+            from_subaccount: None, spender: acct(2), amount: Nat::from(amount),
+            expected_allowance: expected.map(Nat::from), expires_at: None, fee: None, memo: None, created_at_time: None,
+        };
+        approve(&mut st, &ctx(1), approve_args(50, None)).unwrap();
+        assert_eq!(
+            approve(&mut st, &ctx(1), approve_args(60, Some(10))),
+            Err(ApproveError::AllowanceChanged { current_allowance: Nat::from(50u8) })
+        );
+
+        let tf = |amount: u128| TransferFromArgs {
+            spender_subaccount: None, from: acct(1), to: acct(3), amount: Nat::from(amount),
+            fee: None, memo: None, created_at_time: None,
+        };
+        assert_eq!(
+            transfer_from(&mut st, &ctx(2), tf(51)),
+            Err(TransferFromError::InsufficientAllowance { allowance: Nat::from(50u8) })
+        );
+        transfer_from(&mut st, &ctx(2), tf(30)).unwrap();
+        let left = allowance(&st, &AllowanceArgs { account: acct(1), spender: acct(2) }, ctx(2).now);
+        assert_eq!(left.allowance, Nat::from(20u8));
+        assert_eq!(balance_of(&st, &acct(1)), 70);
+        assert_eq!(balance_of(&st, &acct(3)), 30);
+    }
+
+    #[test]
+    fn expired_allowance_reads_as_zero() {
+        let mut st = State::default();
+        let c = ctx(1);
+        let args = ApproveArgs {
+            from_subaccount: None, spender: acct(2), amount: Nat::from(5u8), expected_allowance: None,
+            expires_at: Some(c.now + 10), fee: None, memo: None, created_at_time: None,
+        };
+        approve(&mut st, &c, args).unwrap();
+        let q = AllowanceArgs { account: acct(1), spender: acct(2) };
+        assert_eq!(allowance(&st, &q, c.now).allowance, Nat::from(5u8));
+        assert_eq!(allowance(&st, &q, c.now + 10).allowance, Nat::from(0u8));
+    }
+
+    /// 带 created_at_time 的转账在窗口内重放返回原区块号，余额只动一次；窗口外按 TooOld 拒绝
+    #[test]
+    fn replayed_transfer_is_deduplicated_within_window() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 100);
+        let c = ctx(1);
+        let mut arg = xfer(acct(2), 10);
+        arg.created_at_time = Some(c.now);
+
+        let first = transfer(&mut st, &c, arg.clone()).unwrap();
+        assert_eq!(transfer(&mut st, &c, arg.clone()), Err(TransferError::Duplicate { duplicate_of: first.clone() }));
+        assert_eq!(balance_of(&st, &acct(2)), 10);
+
+        // memo 不同即为另一笔交易；不带 created_at_time 的交易不去重
+        let mut other = arg.clone();
+        other.memo = Some(serde_bytes::ByteBuf::from(vec![1]));
+        assert!(transfer(&mut st, &c, other).is_ok());
+        transfer(&mut st, &c, xfer(acct(2), 10)).unwrap();
+        transfer(&mut st, &c, xfer(acct(2), 10)).unwrap();
+        assert_eq!(balance_of(&st, &acct(2)), 40);
+
+        let later = TxCtx { now: c.now + TX_WINDOW_NS + PERMITTED_DRIFT_NS + 1, ..ctx(1) };
+        assert_eq!(transfer(&mut st, &later, arg.clone()), Err(TransferError::TooOld));
+        let mut future = arg;
+        future.created_at_time = Some(c.now + PERMITTED_DRIFT_NS + 1);
+        assert_eq!(transfer(&mut st, &c, future), Err(TransferError::CreatedInFuture { ledger_time: c.now }));
+        // 过期记录在下一次检查时被清理
+        let mut fresh = xfer(acct(2), 1);
+        fresh.created_at_time = Some(later.now);
+        transfer(&mut st, &later, fresh).unwrap();
+        assert_eq!(st.lp_token.as_ref().and_then(|lp| lp.dedup.as_ref()).map(|d| d.len()), Some(1));
+    }
+
+    #[test]
+    fn replayed_approve_and_transfer_from_are_deduplicated() {
+        let mut st = State::default();
+        st.user_shares.insert(p(1).to_text(), 100);
+        let c = ctx(1);
+        let approve_args = ApproveArgs {
+            from_subaccount: None, spender: acct(2), amount: Nat::from(50u8), expected_allowance: None,
+            expires_at: None, fee: None, memo: None, created_at_time: Some(c.now),
+        };
+        let a = approve(&mut st, &c, approve_args.clone()).unwrap();
+        assert_eq!(approve(&mut st, &c, approve_args), Err(ApproveError::Duplicate { duplicate_of: a }));
+
+        let tf = TransferFromArgs {
+            spender_subaccount: None, from: acct(1), to: acct(3), amount: Nat::from(20u8),
+            fee: None, memo: None, created_at_time: Some(c.now),
+        };
+        let b = transfer_from(&mut st, &ctx(2), tf.clone()).unwrap();
+        assert_eq!(transfer_from(&mut st, &ctx(2), tf.clone()), Err(TransferFromError::Duplicate { duplicate_of: b }));
+        // 同样的参数由另一个 spender 发起不算重放
+        assert!(matches!(transfer_from(&mut st, &ctx(3), tf), Err(TransferFromError::InsufficientAllowance { .. })));
+        assert_eq!(balance_of(&st, &acct(3)), 20);
+        assert_eq!(allowance(&st, &AllowanceArgs { account: acct(1), spender: acct(2) }, c.now).allowance, Nat::from(30u8));
+    }
+}
@@ -0,0 +1,112 @@
+// canisters/vaultpair/src/math/constant_product.rs
+// 恒定乘积（x * y = k，Uniswap v2 口径）池型，供 ICP / BOB 等非锚定币对使用。
+// - 手续费在输入侧扣除后净额入池：dy = x_out * dx_net / (x_in + dx_net)，向下取整；k 只增不减。
+// - 首次建池份额 = sqrt(a0 * a1)（与两侧价格无关），之后按比例铸造 / 赎回（见 invariant）。
+// - 中间值用 U256，全部 checked；任一侧余额为 0 返回 ZeroReserve。
+
+use ethnum::U256;
+
+use super::invariant::{Invariant, mint_proportional, burn_proportional, settle_dx};
+use super::stableswap::{gross_up, MathError, MathResult, FEE_DENOMINATOR};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConstantProduct;
+
+#[inline]
+fn u(x: u128) -> U256 { U256::from(x) }
+
+#[inline]
+fn to_u128(x: U256) -> MathResult<u128> { u128::try_from(x).map_err(|_| MathError::Overflow) }
+
+impl Invariant for ConstantProduct {
+    fn get_dy(&self, x_in: u128, x_out: u128, dx: u128, fee: u128) -> MathResult<(u128, u128)> {
+        if x_in == 0 || x_out == 0 { return Err(MathError::ZeroReserve); }
+        if dx == 0 { return Ok((0, 0)); }
+        let fee_in = to_u128(u(dx).checked_mul(u(fee)).ok_or(MathError::Overflow)? / u(FEE_DENOMINATOR))?;
+        let dx_net = dx.saturating_sub(fee_in);
+        let num = u(x_out).checked_mul(u(dx_net)).ok_or(MathError::Overflow)?;
+        let dy = to_u128(num / (u(x_in) + u(dx_net)))?;
+        Ok((dy, fee_in))
+    }
+
+    fn get_dx(&self, x_in: u128, x_out: u128, dy: u128, fee: u128) -> MathResult<u128> {
+        if x_in == 0 || dy >= x_out { return Err(MathError::ZeroReserve); }
+        if dy == 0 { return Ok(0); }
+        // dx_net = ceil(x_in * dy / (x_out - dy))
+        let num = u(x_in).checked_mul(u(dy)).ok_or(MathError::Overflow)?;
+        let den = u(x_out - dy);
+        let dx_net = to_u128((num + den - U256::ONE) / den)?;
+        let dx = gross_up(dx_net, fee)?;
+        settle_dx(self, x_in, x_out, dy, fee, dx)
+    }
+
+    fn mint(&self, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)> {
+        if total_shares == 0 || reserves[0] == 0 || reserves[1] == 0 {
+            let k = amounts[0].checked_mul(amounts[1]).ok_or(MathError::Overflow)?;
+            return Ok((amounts, k.isqrt()));
+        }
+        mint_proportional(reserves, total_shares, amounts)
+    }
+
+    fn burn(&self, reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]> {
+        burn_proportional(reserves, total_shares, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::stableswap::bps_to_fee;
+    use proptest::prelude::*;
+
+    const CP: ConstantProduct = ConstantProduct;
+
+    #[test]
+    fn matches_uniswap_v2_formula_and_mints_geometric_mean() {
+        // 1000 / 4000 池，投入 10（0.3%）：dy = 4000 * 9.97 / 1009.97
+        let (x, y, dx) = (1_000_000_000u128, 4_000_000_000u128, 10_000_000u128);
+        let (dy, fee) = CP.get_dy(x, y, dx, bps_to_fee(30)).unwrap();
+        assert_eq!(fee, 30_000);
+        assert_eq!(dy, y * (dx - fee) / (x + dx - fee));
+        assert_eq!(CP.mint([0, 0], 0, [x, y]).unwrap(), ([x, y], 2_000_000_000));
+        assert_eq!(CP.get_dy(0, y, dx, 0), Err(MathError::ZeroReserve));
+        assert_eq!(CP.get_dx(x, y, y, 0), Err(MathError::ZeroReserve));
+    }
+
+    // ===== 性质测试 =====
+    fn balance() -> impl Strategy<Value = u128> { 1_000u128..=1_000_000_000_000_000 }
+    fn fee_bps() -> impl Strategy<Value = u32> { 0u32..=100 }
+
+    proptest! {
+        /// 收费 swap 后（净额入池、dy 出池），k 不应下降
+        #[test]
+        fn k_never_decreases_after_swap(x0 in balance(), x1 in balance(), frac in 1u128..=10_000, fee in fee_bps()) {
+            let dx = (x0 * frac / 10_000).max(1);
+            let (dy, fee_in) = CP.get_dy(x0, x1, dx, bps_to_fee(fee)).unwrap();
+            let k0 = u(x0) * u(x1);
+            let k1 = u(x0 + dx - fee_in) * u(x1 - dy);
+            prop_assert!(k1 >= k0, "dx={dx} dy={dy}");
+        }
+
+        /// get_dx 给出的投入足以换出 dy，且少 1 就不够
+        #[test]
+        fn get_dx_is_tight_inverse_of_get_dy(x0 in balance(), x1 in balance(), frac in 1u128..=9_000, fee in fee_bps()) {
+            let dy = (x1 * frac / 10_000).max(1);
+            let f = bps_to_fee(fee);
+            let dx = CP.get_dx(x0, x1, dy, f).unwrap();
+            prop_assert!(CP.get_dy(x0, x1, dx, f).unwrap().0 >= dy);
+            prop_assert!(CP.get_dy(x0, x1, dx - 1, f).unwrap().0 < dy, "dx={dx} not minimal");
+        }
+
+        /// 先换出再换回，拿回的数量不会超过最初投入
+        #[test]
+        fn round_trip_swap_never_profits(x0 in balance(), x1 in balance(), frac in 1u128..=10_000, fee in fee_bps()) {
+            let dx = (x0 * frac / 10_000).max(1);
+            let f = bps_to_fee(fee);
+            let (dy, fee_in) = CP.get_dy(x0, x1, dx, f).unwrap();
+            prop_assume!(dy > 0);
+            let (dz, _) = CP.get_dy(x1 - dy, x0 + dx - fee_in, dy, f).unwrap();
+            prop_assert!(dz <= dx, "dx={dx} dy={dy} back={dz}");
+        }
+    }
+}
@@ -0,0 +1,530 @@
+// canisters/vaultpair/src/math/cryptoswap.rs
+// Curve v2（twocrypto，2 币）数学：用于 ckBTC↔BTC 以外的非锚定币对。整数实现，逐行对照 CurveCryptoSwap2.vy。
+// - 余额先按 price_scale（coins[1] 以 coins[0] 计价，1e18）换算成 xp（1e18 精度）：xp = [b0 * 1e12, b1 * 1e12 * price_scale / 1e18]，
+//   在 xp 上解不变量（newton_D / newton_y）；A 取 Curve 口径 A * N^N * A_MULTIPLIER，gamma 为 1e18 口径。
+// - 手续费按池子失衡程度在 mid_fee ~ out_fee 之间变化，从输出侧扣除并留在池内（LP 靠 virtual_price 增长获利）。
+// - price_oracle 为成交价的 EMA（半衰期 ma_half_time）；tweak_price 在利润足够时（virtual_price - 1 > (xcp_profit - 1) / 2 + allowed_extra_profit）
+//   把 price_scale 朝 price_oracle 移动 adjustment_step，即 repeg；移动后 virtual_price 仍需高于利润的一半，否则放弃。
+// - 未收敛、或余额相对 D 超出安全范围（Curve 的 "unsafe values"）返回 NoConvergence；不收管理费（无 xcp_profit_a）。
+
+use candid::CandidType;
+use ethnum::U256;
+use serde::{Deserialize, Serialize};
+
+use super::exp::half_pow_wad;
+use super::invariant::{Invariant, burn_proportional, settle_dx};
+use super::stableswap::{MathError, MathResult, FEE_DENOMINATOR};
+
+const N: u128 = 2;
+pub const A_MULTIPLIER: u128 = 10_000;
+pub const MIN_A: u128 = N * N * A_MULTIPLIER / 10;
+pub const MAX_A: u128 = N * N * A_MULTIPLIER * 100_000;
+pub const MIN_GAMMA: u128 = 10_000_000_000;
+pub const MAX_GAMMA: u128 = 20_000_000_000_000_000;
+
+const E18: u128 = 1_000_000_000_000_000_000;
+/// e6 金额 → 1e18 精度
+const PREC: u128 = 1_000_000_000_000;
+const NOISE_FEE: u128 = 100_000;
+const MAX_ITER: usize = 255;
+
+/// 池子参数（fee 为 FEE_DENOMINATOR 口径，其余 1e18 口径）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CryptoParams {
+    pub a: u128,
+    pub gamma: u128,
+    pub mid_fee: u128,
+    pub out_fee: u128,
+    pub fee_gamma: u128,
+    pub allowed_extra_profit: u128,
+    pub adjustment_step: u128,
+    pub ma_half_time_sec: u64,
+}
+
+impl Default for CryptoParams {
+    /// Curve 主网 twocrypto 的常用参数
+    fn default() -> Self {
+        Self {
+            a: 400_000,
+            gamma: 145_000_000_000_000,
+            mid_fee: 26_000_000,
+            out_fee: 45_000_000,
+            fee_gamma: 230_000_000_000_000,
+            allowed_extra_profit: 2_000_000_000_000,
+            adjustment_step: 146_000_000_000_000,
+            ma_half_time_sec: 600,
+        }
+    }
+}
+
+/// 随成交变化的状态（价格均为 coins[1] 以 coins[0] 计价，1e18）；virtual_price = 0 表示尚未注入流动性
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CryptoState {
+    pub price_scale: u128,
+    pub price_oracle: u128,
+    pub last_price: u128,
+    pub last_ts: u64,
+    pub xcp_profit: u128,
+    pub virtual_price: u128,
+    pub not_adjusted: bool,
+}
+
+impl CryptoState {
+    pub fn new(initial_price: u128, ts: u64) -> Self {
+        Self { price_scale: initial_price, price_oracle: initial_price, last_price: initial_price, last_ts: ts, ..Default::default() }
+    }
+}
+
+pub fn check_params(p: &CryptoParams) -> MathResult<()> {
+    if !(MIN_A..=MAX_A).contains(&p.a) || !(MIN_GAMMA..=MAX_GAMMA).contains(&p.gamma) { return Err(MathError::InvalidAmp); }
+    if p.mid_fee > p.out_fee || p.out_fee > FEE_DENOMINATOR / 2 || p.fee_gamma == 0 || p.fee_gamma > E18 || p.ma_half_time_sec == 0 {
+        return Err(MathError::InvalidAmp);
+    }
+    Ok(())
+}
+
+#[inline]
+fn u(x: u128) -> U256 { U256::from(x) }
+
+#[inline]
+fn add(a: U256, b: U256) -> MathResult<U256> { a.checked_add(b).ok_or(MathError::Overflow) }
+#[inline]
+fn sub(a: U256, b: U256) -> MathResult<U256> { a.checked_sub(b).ok_or(MathError::Overflow) }
+#[inline]
+fn mul(a: U256, b: U256) -> MathResult<U256> { a.checked_mul(b).ok_or(MathError::Overflow) }
+#[inline]
+fn div(a: U256, b: U256) -> MathResult<U256> { a.checked_div(b).ok_or(MathError::Overflow) }
+
+#[inline]
+fn to_u128(x: U256) -> MathResult<u128> { u128::try_from(x).map_err(|_| MathError::Overflow) }
+
+#[inline]
+fn abs_diff(a: U256, b: U256) -> U256 { if a > b { a - b } else { b - a } }
+
+fn check_a_gamma(a: u128, gamma: u128) -> MathResult<()> {
+    if (MIN_A..=MAX_A).contains(&a) && (MIN_GAMMA..=MAX_GAMMA).contains(&gamma) { Ok(()) } else { Err(MathError::InvalidAmp) }
+}
+
+/// 余额（e6，按 coins 顺序）→ xp
+pub fn xp(balances: [u128; 2], price_scale: u128) -> MathResult<[U256; 2]> {
+    Ok([mul(u(balances[0]), u(PREC))?, div(mul(mul(u(balances[1]), u(PREC))?, u(price_scale))?, u(E18))?])
+}
+
+/// xp 上的量换回 e6 余额
+fn from_xp(v: U256, j: usize, price_scale: u128) -> MathResult<u128> {
+    let v = if j == 0 { v } else { div(mul(v, u(E18))?, u(price_scale))? };
+    to_u128(v / u(PREC))
+}
+
+/// 几何平均（Curve 的 geometric_mean，2 币）
+pub fn geometric_mean(x: [U256; 2]) -> MathResult<U256> {
+    let (x0, x1) = if x[0] >= x[1] { (x[0], x[1]) } else { (x[1], x[0]) };
+    if x1 == U256::ZERO { return Ok(U256::ZERO); }
+    let prod = mul(x0, x1)?;
+    let mut d = x0;
+    for _ in 0..MAX_ITER {
+        let d_prev = d;
+        d = (d + prod / d) / u(N);
+        let diff = abs_diff(d, d_prev);
+        if diff <= U256::ONE || mul(diff, u(E18))? < d { return Ok(d); }
+    }
+    Err(MathError::NoConvergence)
+}
+
+/// (1 + gamma - K0) 的绝对值 + 1
+#[inline]
+fn g1k0(gamma: u128, k0: U256) -> U256 {
+    let g = u(gamma) + u(E18);
+    if g > k0 { g - k0 + U256::ONE } else { k0 - g + U256::ONE }
+}
+
+/// D / (A * N^N) * g1k0^2 / gamma^2（1e18 口径）
+fn mul1(a: u128, gamma: u128, d: U256, g1k0: U256) -> MathResult<U256> {
+    let v = div(mul(u(E18), d)?, u(gamma))?;
+    let v = div(mul(v, g1k0)?, u(gamma))?;
+    div(mul(mul(v, g1k0)?, u(A_MULTIPLIER))?, u(a))
+}
+
+#[inline]
+fn safe_frac(x: U256, d: U256) -> MathResult<()> {
+    let frac = div(mul(x, u(E18))?, d)?;
+    if frac > u(10_000_000_000_000_000 - 1) && frac < u(100_000_000_000_000_000_000 + 1) { Ok(()) } else { Err(MathError::NoConvergence) }
+}
+
+/// 不变量 D（xp 口径）
+pub fn newton_d(a: u128, gamma: u128, x_unsorted: [U256; 2]) -> MathResult<U256> {
+    check_a_gamma(a, gamma)?;
+    let x = if x_unsorted[0] >= x_unsorted[1] { x_unsorted } else { [x_unsorted[1], x_unsorted[0]] };
+    if x[1] == U256::ZERO { return Err(MathError::ZeroReserve); }
+    if div(mul(x[1], u(E18))?, x[0])? <= u(100_000_000_000_000) { return Err(MathError::NoConvergence); }
+
+    let mut d = mul(u(N), geometric_mean(x)?)?;
+    let s = add(x[0], x[1])?;
+    for _ in 0..MAX_ITER {
+        let d_prev = d;
+        let k0 = div(mul(div(mul(u(E18 * N * N), x[0])?, d)?, x[1])?, d)?;
+        let g = g1k0(gamma, k0);
+        let m1 = mul1(a, gamma, d, g)?;
+        let m2 = div(mul(u(2 * E18 * N), k0)?, g)?;
+
+        let neg_fprime = sub(
+            add(add(s, div(mul(s, m2)?, u(E18))?)?, div(mul(m1, u(N))?, k0)?)?,
+            div(mul(m2, d)?, u(E18))?,
+        )?;
+        let d_plus = div(mul(d, add(neg_fprime, s)?)?, neg_fprime)?;
+        let mut d_minus = div(mul(d, d)?, neg_fprime)?;
+        let t = div(mul(d, div(m1, neg_fprime)?)?, u(E18))?;
+        if u(E18) > k0 {
+            d_minus = add(d_minus, div(mul(t, u(E18) - k0)?, k0)?)?;
+        } else {
+            d_minus = sub(d_minus, div(mul(t, k0 - u(E18))?, k0)?)?;
+        }
+        d = if d_plus > d_minus { d_plus - d_minus } else { (d_minus - d_plus) / u(2) };
+
+        let diff = abs_diff(d, d_prev);
+        if mul(diff, u(100_000_000_000_000))? < d.max(u(10_000_000_000_000_000)) {
+            for xi in x { safe_frac(xi, d)?; }
+            return Ok(d);
+        }
+    }
+    Err(MathError::NoConvergence)
+}
+
+/// 给定 D 与 x[1 - i]，解出 x[i]（xp 口径）
+pub fn newton_y(a: u128, gamma: u128, x: [U256; 2], d: U256, i: usize) -> MathResult<U256> {
+    check_a_gamma(a, gamma)?;
+    let x_j = x[1 - i];
+    if x_j == U256::ZERO || d == U256::ZERO { return Err(MathError::ZeroReserve); }
+    let mut y = div(mul(d, d)?, mul(x_j, u(N * N))?)?;
+    let k0_i = div(mul(u(E18 * N), x_j)?, d)?;
+    if !(k0_i > u(10_000_000_000_000_000 * N - 1) && k0_i < u(100_000_000_000_000_000_000 * N + 1)) {
+        return Err(MathError::NoConvergence);
+    }
+    let limit = (x_j / u(100_000_000_000_000)).max(d / u(100_000_000_000_000)).max(u(100));
+
+    for _ in 0..MAX_ITER {
+        let y_prev = y;
+        let k0 = div(mul(mul(k0_i, y)?, u(N))?, d)?;
+        let s = add(x_j, y)?;
+        let g = g1k0(gamma, k0);
+        let m1 = mul1(a, gamma, d, g)?;
+        let m2 = add(u(E18), div(mul(u(2 * E18), k0)?, g)?)?;
+
+        let mut yfprime = add(add(mul(u(E18), y)?, mul(s, m2)?)?, m1)?;
+        let dyfprime = mul(d, m2)?;
+        if yfprime < dyfprime { y = y_prev / u(2); continue; }
+        yfprime -= dyfprime;
+        let fprime = div(yfprime, y)?;
+
+        let mut y_minus = div(m1, fprime)?;
+        let y_plus = add(div(add(yfprime, mul(u(E18), d)?)?, fprime)?, div(mul(y_minus, u(E18))?, k0)?)?;
+        y_minus = add(y_minus, div(mul(u(E18), s)?, fprime)?)?;
+        y = if y_plus < y_minus { y_prev / u(2) } else { y_plus - y_minus };
+
+        if abs_diff(y, y_prev) < limit.max(y / u(100_000_000_000_000)) {
+            safe_frac(y, d)?;
+            return Ok(y);
+        }
+    }
+    Err(MathError::NoConvergence)
+}
+
+/// 动态费率（FEE_DENOMINATOR 口径）：平衡时 mid_fee，越失衡越接近 out_fee
+pub fn fee(p: &CryptoParams, xp: [U256; 2]) -> MathResult<u128> {
+    let s = add(xp[0], xp[1])?;
+    let k = div(mul(div(mul(u(E18 * N * N), xp[0])?, s)?, xp[1])?, s)?;
+    let f = div(mul(u(p.fee_gamma), u(E18))?, sub(add(u(p.fee_gamma), u(E18))?, k)?)?;
+    to_u128(div(add(mul(u(p.mid_fee), f)?, mul(u(p.out_fee), sub(u(E18), f)?)?)?, u(E18))?)
+}
+
+/// 不平衡注入的手续费：fee / 2 * sum|x - avg| / sum(x)
+fn calc_token_fee(p: &CryptoParams, amounts: [U256; 2], xp: [U256; 2]) -> MathResult<u128> {
+    let f = u(fee(p, xp)?) * u(N) / u(4 * (N - 1));
+    let s = add(amounts[0], amounts[1])?;
+    if s == U256::ZERO { return Ok(0); }
+    let avg = s / u(N);
+    let sdiff = abs_diff(amounts[0], avg) + abs_diff(amounts[1], avg);
+    to_u128(div(mul(f, sdiff)?, s)? + u(NOISE_FEE))
+}
+
+/// D 对应的“扩展恒定乘积”xcp（xp 口径）
+fn xcp(d: U256, price_scale: u128) -> MathResult<U256> {
+    geometric_mean([d / u(N), div(mul(d, u(E18))?, mul(u(N), u(price_scale))?)?])
+}
+
+/// 投入 dx（coins[i]）换出 coins[1 - i]：返回 (dy, 输出侧手续费)
+pub fn get_dy(p: &CryptoParams, price_scale: u128, balances: [u128; 2], i: usize, dx: u128) -> MathResult<(u128, u128)> {
+    if balances[0] == 0 || balances[1] == 0 { return Err(MathError::ZeroReserve); }
+    if dx == 0 { return Ok((0, 0)); }
+    let j = 1 - i;
+    let mut xp0 = xp(balances, price_scale)?;
+    let d = newton_d(p.a, p.gamma, xp0)?;
+    let mut b = balances;
+    b[i] = b[i].checked_add(dx).ok_or(MathError::Overflow)?;
+    xp0[i] = xp(b, price_scale)?[i];
+    let y = newton_y(p.a, p.gamma, xp0, d, j)?;
+    let dy_xp = sub(xp0[j], y)?;
+    xp0[j] = y;
+    let dy = from_xp(dy_xp.saturating_sub(U256::ONE), j, price_scale)?;
+    let fee_out = to_u128(div(mul(u(fee(p, xp0)?), u(dy))?, u(FEE_DENOMINATOR))?)?;
+    Ok((dy - fee_out, fee_out))
+}
+
+/// 注入 amounts（可不按比例）铸造的份额（e6）：首次 = xcp(D) 换算到 e6；之后 = ts * (D1 / D0 - 1) 再扣不平衡手续费
+pub fn calc_token_amount(p: &CryptoParams, price_scale: u128, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<u128> {
+    let new = [reserves[0].checked_add(amounts[0]).ok_or(MathError::Overflow)?, reserves[1].checked_add(amounts[1]).ok_or(MathError::Overflow)?];
+    let xp1 = xp(new, price_scale)?;
+    let d1 = newton_d(p.a, p.gamma, xp1)?;
+    if total_shares == 0 || reserves[0] == 0 || reserves[1] == 0 {
+        return to_u128(xcp(d1, price_scale)? / u(PREC));
+    }
+    let d0 = newton_d(p.a, p.gamma, xp(reserves, price_scale)?)?;
+    let minted = sub(div(mul(u(total_shares), d1)?, d0)?, u(total_shares))?;
+    let amounts_xp = xp(amounts, price_scale)?;
+    let f = calc_token_fee(p, amounts_xp, xp1)?;
+    let minted = minted.saturating_sub(div(mul(minted, u(f))?, u(FEE_DENOMINATOR))? + U256::ONE);
+    to_u128(minted)
+}
+
+/// 成交（或注入）后调用：更新 price_oracle / last_price / xcp_profit / virtual_price，利润足够时 repeg。
+/// balances / total_shares 为变动之后的值；trade_price 为本笔成交价（None 则按曲线求现价）。
+/// virtual_price 下降（亏损）时返回 MathError::VirtualPriceDecreased，调用方应放弃本次变动
+pub fn tweak_price(p: &CryptoParams, st: &mut CryptoState, balances: [u128; 2], total_shares: u128, trade_price: Option<u128>, now_sec: u64) -> MathResult<()> {
+    if total_shares == 0 { return Ok(()); }
+    if st.last_ts < now_sec {
+        let alpha = half_pow_wad(now_sec - st.last_ts, p.ma_half_time_sec);
+        st.price_oracle = to_u128((u(st.last_price) * u(E18 - alpha) + u(st.price_oracle) * u(alpha)) / u(E18))?;
+        st.last_ts = now_sec;
+    }
+
+    let ps = st.price_scale;
+    let xp0 = xp(balances, ps)?;
+    let d = newton_d(p.a, p.gamma, xp0)?;
+    st.last_price = match trade_price {
+        Some(px) => px,
+        None => {
+            let dx_price = xp0[0] / u(1_000_000);
+            let y = newton_y(p.a, p.gamma, [xp0[0] + dx_price, xp0[1]], d, 1)?;
+            to_u128(div(mul(u(ps), dx_price)?, sub(xp0[1], y)?)?)?
+        }
+    };
+
+    let (old_profit, old_vp) = (st.xcp_profit, st.virtual_price);
+    let (mut profit, mut vp) = (E18, E18);
+    if old_vp > 0 {
+        vp = to_u128(div(mul(xcp(d, ps)?, u(1_000_000))?, u(total_shares))?)?;
+        profit = to_u128(div(mul(u(old_profit), u(vp))?, u(old_vp))?)?;
+        if vp < old_vp { return Err(MathError::VirtualPriceDecreased); }
+    }
+    st.xcp_profit = profit;
+
+    let norm = {
+        let r = st.price_oracle.saturating_mul(E18) / ps;
+        r.abs_diff(E18)
+    };
+    let step = p.adjustment_step.max(norm / 5);
+    let mut needs = st.not_adjusted;
+    if !needs && (vp * 2).saturating_sub(E18) > profit + 2 * p.allowed_extra_profit && norm > step && old_vp > 0 {
+        needs = true;
+        st.not_adjusted = true;
+    }
+
+    if needs && norm > step && old_vp > 0 {
+        let p_new = to_u128((u(ps) * u(norm - step) + u(step) * u(st.price_oracle)) / u(norm))?;
+        let xp1 = [xp0[0], div(mul(xp0[1], u(p_new))?, u(ps))?];
+        let d1 = newton_d(p.a, p.gamma, xp1)?;
+        let vp1 = to_u128(div(mul(xcp(d1, p_new)?, u(1_000_000))?, u(total_shares))?)?;
+        if vp1 > E18 && (2 * vp1).saturating_sub(E18) > profit {
+            st.price_scale = p_new;
+            st.virtual_price = vp1;
+            return Ok(());
+        }
+        st.not_adjusted = false;
+        st.virtual_price = vp;
+        return Ok(());
+    }
+
+    st.virtual_price = vp;
+    if needs { st.not_adjusted = false; }
+    Ok(())
+}
+
+/// 池型入口（见 invariant）。i 为输入下标（价格缩放只作用于 coins[1]，方向不对称）
+#[derive(Clone, Debug)]
+pub struct CryptoSwap { pub params: CryptoParams, pub price_scale: u128, pub i: usize }
+
+impl CryptoSwap {
+    fn balances(&self, x_in: u128, x_out: u128) -> [u128; 2] { if self.i == 0 { [x_in, x_out] } else { [x_out, x_in] } }
+}
+
+impl Invariant for CryptoSwap {
+    /// fee 参数不用（费率由失衡程度决定）；手续费从输出侧扣，这里按成交比例折算成输入侧数量返回
+    fn get_dy(&self, x_in: u128, x_out: u128, dx: u128, _fee: u128) -> MathResult<(u128, u128)> {
+        let (dy, fee_out) = get_dy(&self.params, self.price_scale, self.balances(x_in, x_out), self.i, dx)?;
+        let fee_in = to_u128(div(mul(u(fee_out), u(dx))?, u(dy + fee_out).max(U256::ONE))?)?;
+        Ok((dy, fee_in))
+    }
+
+    fn get_dx(&self, x_in: u128, x_out: u128, dy: u128, fee: u128) -> MathResult<u128> {
+        if dy == 0 { return Ok(0); }
+        if dy >= x_out { return Err(MathError::ZeroReserve); }
+        // 按 price_scale 折算作为起点
+        let guess = if self.i == 0 { dy.saturating_mul(self.price_scale) / E18 } else { dy.saturating_mul(E18) / self.price_scale.max(1) };
+        settle_dx(self, x_in, x_out, dy, fee, guess.max(1))
+    }
+
+    /// 不要求按比例：实际入池量即 amounts
+    fn mint(&self, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)> {
+        Ok((amounts, calc_token_amount(&self.params, self.price_scale, reserves, total_shares, amounts)?))
+    }
+
+    fn burn(&self, reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]> {
+        burn_proportional(reserves, total_shares, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PS: u128 = 2_000 * E18; // 1 coins[1] = 2000 coins[0]
+
+    fn pool(st: &mut CryptoState, b: [u128; 2]) -> u128 {
+        let p = CryptoParams::default();
+        let shares = calc_token_amount(&p, st.price_scale, [0, 0], 0, b).unwrap();
+        tweak_price(&p, st, b, shares, None, st.last_ts).unwrap();
+        shares
+    }
+
+    #[test]
+    fn first_deposit_prices_at_price_scale() {
+        let mut st = CryptoState::new(PS, 1_000);
+        let b = [2_000_000_000_000, 1_000_000_000];
+        let shares = pool(&mut st, b);
+        assert_eq!(st.virtual_price, E18);
+        // 平衡池：现价 ≈ price_scale，小额成交约按 mid_fee 收费
+        assert!(st.last_price.abs_diff(PS) < PS / 10_000, "{}", st.last_price);
+        let (dy, fee_out) = get_dy(&CryptoParams::default(), PS, b, 0, 2_000_000).unwrap();
+        assert!(dy.abs_diff(1_000 - 3) <= 1, "{dy}");
+        assert!(fee_out <= 3);
+        assert!(shares > 0);
+        // 份额不变而储备减半：virtual_price 下降，拒绝
+        let lossy = tweak_price(&CryptoParams::default(), &mut st.clone(), [b[0] / 2, b[1] / 2], shares, None, 1_000);
+        assert_eq!(lossy, Err(MathError::VirtualPriceDecreased));
+    }
+
+    /// 价格持续单边变动后：有足够利润时 price_scale 朝 oracle 移动，且移动后 virtual_price 仍高于利润的一半
+    #[test]
+    fn repegs_toward_oracle_when_profitable() {
+        let p = CryptoParams::default();
+        let mut st = CryptoState::new(PS, 1_000);
+        let mut b = [2_000_000_000_000u128, 1_000_000_000];
+        let shares = pool(&mut st, b);
+        let mut ts = 1_000;
+        // 持续买入 coins[1]（推高其价格），来回成交累积手续费
+        for k in 0..200 {
+            ts += 60;
+            let (i, dx) = if k % 4 == 3 { (1, 4_000_000) } else { (0, 20_000_000_000) };
+            let (dy, _) = get_dy(&p, st.price_scale, b, i, dx).unwrap();
+            b[i] += dx;
+            b[1 - i] -= dy;
+            let px = if i == 0 { dx * E18 / dy } else { dy * E18 / dx };
+            tweak_price(&p, &mut st, b, shares, Some(px), ts).unwrap();
+        }
+        assert!(st.price_oracle > PS);
+        assert!(st.price_scale > PS, "price_scale {} should follow oracle {}", st.price_scale, st.price_oracle);
+        assert!(st.price_scale <= st.price_oracle);
+        assert!(st.virtual_price > E18 && 2 * st.virtual_price - E18 > st.xcp_profit);
+    }
+
+    // ===== 性质测试（对照 stableswap 的 props） =====
+    mod props {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn balance() -> impl Strategy<Value = u128> { 1_000_000u128..=1_000_000_000_000_000 }
+        /// price_scale 1e-3 ~ 1e5
+        fn price() -> impl Strategy<Value = u128> { (1u128..=100_000_000).prop_map(|p| p * 1_000_000_000_000_000) }
+        /// 两侧价值之比 1:5 ~ 5:1（Curve 的安全范围内）
+        fn skew() -> impl Strategy<Value = u128> { 200u128..=5_000 }
+        fn gamma() -> impl Strategy<Value = u128> { 10_000_000_000u128..=20_000_000_000_000_000 }
+        fn a() -> impl Strategy<Value = u128> { MIN_A..=MAX_A / 10 }
+
+        /// 价值比为 skew / 1000 的余额对
+        fn balances(b0: u128, ps: u128, skew: u128) -> [u128; 2] { [b0, (b0 * skew / 1_000).saturating_mul(E18) / ps] }
+
+        fn params(a: u128, gamma: u128) -> CryptoParams { CryptoParams { a, gamma, ..Default::default() } }
+
+        proptest! {
+            /// 成交后（dy 扣除手续费出池），D 不应下降
+            #[test]
+            fn d_never_decreases_after_swap(
+                a in a(), gamma in gamma(), b0 in balance(), ps in price(), skew in skew(), frac in 1u128..=1_000, i in 0usize..2,
+            ) {
+                let p = params(a, gamma);
+                let b = balances(b0, ps, skew);
+                prop_assume!(b[1] >= 1_000_000);
+                let dx = (b[i] * frac / 10_000).max(1);
+                let (dy, _) = get_dy(&p, ps, b, i, dx).unwrap();
+                let d0 = newton_d(a, gamma, xp(b, ps).unwrap()).unwrap();
+                let mut b1 = b;
+                b1[i] += dx;
+                b1[1 - i] -= dy;
+                let d1 = newton_d(a, gamma, xp(b1, ps).unwrap()).unwrap();
+                prop_assert!(d1 >= d0, "d0={d0} d1={d1} dx={dx} dy={dy}");
+            }
+
+            /// dy 关于 dx 单调不减
+            #[test]
+            fn dy_monotone_in_dx(
+                b0 in balance(), ps in price(), skew in skew(), x in 1u128..=1_000, y in 1u128..=1_000,
+            ) {
+                let p = CryptoParams::default();
+                let b = balances(b0, ps, skew);
+                prop_assume!(b[1] >= 1_000_000);
+                let (lo, hi) = if x <= y { (x, y) } else { (y, x) };
+                let (dy_lo, _) = get_dy(&p, ps, b, 0, b[0] * lo / 10_000).unwrap();
+                let (dy_hi, _) = get_dy(&p, ps, b, 0, b[0] * hi / 10_000).unwrap();
+                prop_assert!(dy_lo <= dy_hi);
+            }
+
+            /// newton_y(x0, D(x0, x1)) 应回到 x1（相对误差 ≤ 1e-12）
+            #[test]
+            fn newton_y_round_trips(a in a(), gamma in gamma(), b0 in balance(), ps in price(), skew in skew()) {
+                let b = balances(b0, ps, skew);
+                prop_assume!(b[1] >= 1_000_000);
+                let x = xp(b, ps).unwrap();
+                let d = newton_d(a, gamma, x).unwrap();
+                let y = newton_y(a, gamma, x, d, 1).unwrap();
+                prop_assert!(abs_diff(y, x[1]) <= x[1] / u(1_000_000_000_000) + u(PREC), "x1={} y={y}", x[1]);
+            }
+
+            /// 先换出再换回，拿回的数量不会超过最初投入
+            #[test]
+            fn round_trip_swap_never_profits(b0 in balance(), ps in price(), skew in skew(), frac in 1u128..=1_000) {
+                let p = CryptoParams::default();
+                let b = balances(b0, ps, skew);
+                prop_assume!(b[1] >= 1_000_000);
+                let dx = (b[0] * frac / 10_000).max(1);
+                let (dy, _) = get_dy(&p, ps, b, 0, dx).unwrap();
+                prop_assume!(dy > 0);
+                let b1 = [b[0] + dx, b[1] - dy];
+                let (back, _) = get_dy(&p, ps, b1, 1, dy).unwrap();
+                prop_assert!(back <= dx, "dx={dx} dy={dy} back={back}");
+            }
+
+            /// Invariant::get_dx 是 get_dy 的最小逆
+            #[test]
+            fn get_dx_is_tight_inverse_of_get_dy(b0 in balance(), ps in price(), skew in skew(), frac in 1u128..=500, i in 0usize..2) {
+                let b = balances(b0, ps, skew);
+                prop_assume!(b[1] >= 1_000_000);
+                let cs = CryptoSwap { params: CryptoParams::default(), price_scale: ps, i };
+                let (x_in, x_out) = (b[i], b[1 - i]);
+                let dy = (x_out * frac / 10_000).max(1);
+                let dx = cs.get_dx(x_in, x_out, dy, 0).unwrap();
+                prop_assert!(cs.get_dy(x_in, x_out, dx, 0).unwrap().0 >= dy);
+                prop_assert!(dx == 1 || cs.get_dy(x_in, x_out, dx - 1, 0).map_or(true, |(got, _)| got < dy), "dx={dx} not minimal");
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// canisters/vaultpair/src/math/exp.rs
+// 定点指数（1e18 口径，U256 运算），替代 f64 的 exp / powf：结果确定、跨平台一致，可用于 EMA 衰减系数。
+// - exp(-x) 拆成 x = k·ln2 + r（0 ≤ r < ln2）：e^r 用泰勒级数求到末项为 0，取倒数后再右移 k 位。
+// - 结果向下取整；x 足够大（e^-x < 1e-18）时为 0。
+
+use ethnum::U256;
+
+/// 1.0（1e18）
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+/// ln2 × 1e18
+pub const LN2_WAD: u128 = 693_147_180_559_945_309;
+
+/// e^(-x / 1e18) × 1e18
+pub fn exp_neg_wad(x: u128) -> u128 {
+    let k = x / LN2_WAD;
+    // e^-x < 2^-k；k ≥ 60 时已小于 1e-18
+    if k >= 60 { return 0; }
+    let r = U256::from(x - k * LN2_WAD);
+    let wad = U256::from(WAD);
+    let (mut term, mut sum, mut i) = (wad, wad, 1u32);
+    while term > 0 {
+        term = term * r / (U256::from(i) * wad);
+        sum += term;
+        i += 1;
+    }
+    let inv = wad * wad / sum;
+    (inv >> k).as_u128()
+}
+
+/// 0.5^(dt / half_time) × 1e18；half_time 为 0 时视为瞬间衰减完
+pub fn half_pow_wad(dt: u64, half_time: u64) -> u128 {
+    if half_time == 0 { return 0; }
+    if dt == 0 { return WAD; }
+    let x = U256::from(LN2_WAD) * U256::from(dt) / U256::from(half_time);
+    if x > U256::from(u128::MAX) { return 0; }
+    exp_neg_wad(x.as_u128())
+}
+
+/// e^(-dt / tau) × 1e18（Curve 的 ma_exp_time 口径）；tau 为 0 时视为瞬间衰减完
+pub fn exp_decay_wad(dt: u64, tau: u64) -> u128 {
+    if tau == 0 { return 0; }
+    if dt == 0 { return WAD; }
+    let x = U256::from(dt) * U256::from(WAD) / U256::from(tau);
+    if x > U256::from(u128::MAX) { return 0; }
+    exp_neg_wad(x.as_u128())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn matches_known_values() {
+        assert_eq!(exp_neg_wad(0), WAD);
+        // e^-1 = 0.367879441171442321…
+        assert!(exp_neg_wad(WAD).abs_diff(367_879_441_171_442_321) <= 2);
+        assert!(half_pow_wad(600, 600).abs_diff(WAD / 2) <= 2);
+        assert!(half_pow_wad(1_200, 600).abs_diff(WAD / 4) <= 2);
+        assert_eq!(half_pow_wad(5, 0), 0);
+        assert_eq!(exp_decay_wad(0, 0), 0);
+        assert_eq!(exp_decay_wad(0, 866), WAD);
+        assert_eq!(exp_neg_wad(50 * WAD), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn close_to_float_and_monotone(x in 0u128..45 * WAD, d in 1u128..1_000_000_000_000u128) {
+            let a = exp_neg_wad(x);
+            let f = ((-(x as f64) / WAD as f64).exp() * WAD as f64) as u128;
+            // f64 只有 ~15 位有效数字
+            prop_assert!(a.abs_diff(f) <= f / 1_000_000_000_000 + 1_000);
+            // 逐项截断的误差只有几个最小单位
+            prop_assert!(exp_neg_wad(x + d) <= a + 4);
+        }
+    }
+}
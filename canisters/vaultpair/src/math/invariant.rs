@@ -0,0 +1,68 @@
+// canisters/vaultpair/src/math/invariant.rs
+// 池型的统一接口：swap / positions 经由 Invariant 调用各池型数学（StableSwap、恒定乘积 …），不直接依赖具体公式。
+// - 金额均为 e6；fee 为 FEE_DENOMINATOR 口径，在输入侧扣除（fee_in = dx * fee / 1e10），与 stableswap::quote_dx_to_dy_fee 一致。
+// - 取整：get_dy、mint、burn 向下，get_dx 向上。
+
+use super::stableswap::{MathError, MathResult};
+
+pub trait Invariant {
+    /// 投入 dx（含手续费）可换出的 dy，以及输入侧手续费
+    fn get_dy(&self, x_in: u128, x_out: u128, dx: u128, fee: u128) -> MathResult<(u128, u128)>;
+    /// 换出 dy 所需的最小 dx（含手续费）；dy 不小于对侧储备时返回 ZeroReserve
+    fn get_dx(&self, x_in: u128, x_out: u128, dy: u128, fee: u128) -> MathResult<u128>;
+    /// 按 amounts 入池：返回 (实际入池量, 铸造份额)
+    fn mint(&self, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)>;
+    /// 赎回 shares：返回两侧应得数量
+    fn burn(&self, reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]>;
+}
+
+/// 边际价格（不含手续费，e6 口径 dy/dx）：取输入侧储备的万分之一做报价
+pub fn spot_price_e6(inv: &dyn Invariant, x: u128, y: u128) -> Option<u128> {
+    if x == 0 || y == 0 { return None; }
+    let dx = x / 10_000 + 1;
+    let (dy, _) = inv.get_dy(x, y, dx, 0).ok()?;
+    Some(dy.saturating_mul(1_000_000) / dx)
+}
+
+/// 建池之后各池型共用：按现有比例取两侧能铸份额的较小者，多出的一侧不扣
+pub fn mint_proportional(reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)> {
+    let (r, ts) = (reserves, total_shares);
+    if r[0] == 0 || r[1] == 0 { return Err(MathError::ZeroReserve); }
+    let mint = (amounts[0].saturating_mul(ts) / r[0]).min(amounts[1].saturating_mul(ts) / r[1]);
+    if mint == 0 { return Ok(([0, 0], 0)); }
+    Ok(([mint.saturating_mul(r[0]) / ts, mint.saturating_mul(r[1]) / ts], mint))
+}
+
+pub fn burn_proportional(reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]> {
+    if total_shares == 0 { return Err(MathError::ZeroReserve); }
+    Ok([shares.saturating_mul(reserves[0]) / total_shares, shares.saturating_mul(reserves[1]) / total_shares])
+}
+
+/// 由 get_dy 修正 get_dx 的解析解（取整、或 StableSwap 极端失衡时迭代误差）：以估计值为起点倍增步长夹出区间，
+/// 再二分出满足 get_dy(dx) ≥ dy 的最小 dx
+pub(crate) fn settle_dx(inv: &dyn Invariant, x_in: u128, x_out: u128, dy: u128, fee: u128, guess: u128) -> MathResult<u128> {
+    let enough = |dx: u128| inv.get_dy(x_in, x_out, dx, fee).map(|(got, _)| got >= dy);
+    let (mut lo, mut hi) = (guess, guess); // 不变式：!enough(lo) && enough(hi)
+    let mut step = 1u128;
+    if enough(guess)? {
+        loop {
+            lo = guess.saturating_sub(step);
+            if lo == 0 || !enough(lo)? { break; }
+            hi = lo;
+            step = step.saturating_mul(2);
+        }
+    } else {
+        loop {
+            if step > u128::MAX / 4 { return Err(MathError::NoConvergence); }
+            hi = guess.saturating_add(step);
+            if enough(hi)? { break; }
+            lo = hi;
+            step = step.saturating_mul(2);
+        }
+    }
+    while lo + 1 < hi {
+        let mid = lo + (hi - lo) / 2;
+        if enough(mid)? { hi = mid; } else { lo = mid; }
+    }
+    Ok(hi)
+}
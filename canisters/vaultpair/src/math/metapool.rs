@@ -0,0 +1,148 @@
+// canisters/vaultpair/src/math/metapool.rs
+// Metapool（Curve 元池）：新币与 0 号池（USDC/USDT）的 LP 份额组池，LP 按基础池的 virtual price 折算后走 StableSwap 曲线。
+// - rates（Curve 口径，见 math::rates）：xp = e6 余额 * rate / 1e18，落在 1e18 计算口径；coins[0] 为 BOOK_RATE * 1e18，
+//   LP 为 BOOK_RATE * 基础池 virtual price（D / total_shares，1e18），见 meta_rates。
+// - 手续费与 StableSwap 一致在输入侧扣除，按 rate 换回原币种；xp 折算向下取整，dy 换回向下取整（都偏向池子）。
+// - 首次建池份额 = 两侧折算价值之和，之后按比例铸造 / 赎回（见 invariant）。
+
+use ethnum::U256;
+
+use super::invariant::{Invariant, mint_proportional, burn_proportional, settle_dx};
+use super::rates::BOOK_RATE;
+use super::stableswap::{get_d, quote_dx_to_dy_fee, MathError, MathResult, StableSwap};
+
+pub const RATE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// 元池两个币的 rates：[新币, LP]，base_virtual_price 为 1e18 口径
+pub fn meta_rates(base_virtual_price: u128) -> [u128; 2] {
+    [RATE_PRECISION * BOOK_RATE, base_virtual_price.saturating_mul(BOOK_RATE)]
+}
+
+/// 基础池的 virtual price（1e18）：D / total_shares；尚无份额时为 1
+pub fn base_virtual_price(amp_scaled: u128, reserves: [u128; 2], total_shares: u128) -> MathResult<u128> {
+    if total_shares == 0 { return Ok(RATE_PRECISION); }
+    let d = get_d(amp_scaled, reserves[0], reserves[1])?;
+    let vp = U256::from(d).checked_mul(U256::from(RATE_PRECISION)).ok_or(MathError::Overflow)? / U256::from(total_shares);
+    u128::try_from(vp).map_err(|_| MathError::Overflow)
+}
+
+/// 元池曲线：rates[k] 为 coins[k] 的折算率（见 meta_rates），i 为输入下标（mint / burn 传 0）
+#[derive(Clone, Copy, Debug)]
+pub struct MetaSwap {
+    pub amp_scaled: u128,
+    pub rates: [u128; 2],
+    pub i: usize,
+}
+
+impl MetaSwap {
+    fn rate_in(&self) -> u128 { self.rates[self.i] }
+    fn rate_out(&self) -> u128 { self.rates[1 - self.i] }
+}
+
+/// x * rate / 1e18，向下取整
+fn to_xp(x: u128, rate: u128) -> MathResult<u128> {
+    let v = U256::from(x).checked_mul(U256::from(rate)).ok_or(MathError::Overflow)? / U256::from(RATE_PRECISION);
+    u128::try_from(v).map_err(|_| MathError::Overflow)
+}
+
+/// xp * 1e18 / rate，向下取整
+fn from_xp(xp: u128, rate: u128) -> MathResult<u128> {
+    if rate == 0 { return Err(MathError::ZeroReserve); }
+    let v = U256::from(xp).checked_mul(U256::from(RATE_PRECISION)).ok_or(MathError::Overflow)? / U256::from(rate);
+    u128::try_from(v).map_err(|_| MathError::Overflow)
+}
+
+impl Invariant for MetaSwap {
+    fn get_dy(&self, x_in: u128, x_out: u128, dx: u128, fee: u128) -> MathResult<(u128, u128)> {
+        let (ri, ro) = (self.rate_in(), self.rate_out());
+        let (dyp, feep) = quote_dx_to_dy_fee(self.amp_scaled, to_xp(x_in, ri)?, to_xp(x_out, ro)?, to_xp(dx, ri)?, fee)?;
+        Ok((from_xp(dyp, ro)?, from_xp(feep, ri)?.min(dx)))
+    }
+
+    fn get_dx(&self, x_in: u128, x_out: u128, dy: u128, fee: u128) -> MathResult<u128> {
+        if dy == 0 { return Ok(0); }
+        if dy >= x_out { return Err(MathError::ZeroReserve); }
+        let (ri, ro) = (self.rate_in(), self.rate_out());
+        let ss = StableSwap { amp_scaled: self.amp_scaled };
+        // 先在 xp 口径求解析解作起点，再由 get_dy 修正折算取整
+        let guess = ss
+            .get_dx(to_xp(x_in, ri)?, to_xp(x_out, ro)?, to_xp(dy, ro)?.saturating_add(1), fee)
+            .and_then(|dxp| from_xp(dxp, ri))
+            .unwrap_or(dy)
+            .max(1);
+        settle_dx(self, x_in, x_out, dy, fee, guess)
+    }
+
+    /// 首次建池份额 = 两侧折算价值之和（换回 e6）
+    fn mint(&self, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)> {
+        if total_shares == 0 || reserves[0] == 0 || reserves[1] == 0 {
+            let v = to_xp(amounts[0], self.rates[0])?.checked_add(to_xp(amounts[1], self.rates[1])?).ok_or(MathError::Overflow)?;
+            return Ok((amounts, v / BOOK_RATE));
+        }
+        mint_proportional(reserves, total_shares, amounts)
+    }
+
+    fn burn(&self, reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]> {
+        burn_proportional(reserves, total_shares, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::stableswap::bps_to_fee;
+    use proptest::prelude::*;
+
+    const AMP: u128 = 200 * 1_000_000;
+
+    fn meta(vp: u128, i: usize) -> MetaSwap { MetaSwap { amp_scaled: AMP, rates: meta_rates(vp), i } }
+
+    /// virtual price = 1 时与 StableSwap 相同；vp = 1.1 时按价值平衡的池子里 1 LP ≈ 1.1 个新币
+    #[test]
+    fn lp_is_priced_at_base_virtual_price() {
+        let (x, dx) = (1_000_000_000_000u128, 1_000_000u128);
+        assert_eq!(meta(RATE_PRECISION, 0).get_dy(x, x, dx, 0).unwrap(), StableSwap { amp_scaled: AMP }.get_dy(x, x, dx, 0).unwrap());
+
+        let vp = RATE_PRECISION * 11 / 10;
+        let lp = x * 10 / 11;
+        let (dy, _) = meta(vp, 1).get_dy(lp, x, dx, 0).unwrap();
+        assert!(dy.abs_diff(1_100_000) <= 2, "dy={dy}");
+        let (back, _) = meta(vp, 0).get_dy(x, lp, 1_100_000, 0).unwrap();
+        assert!(back.abs_diff(dx) <= 2 && back <= dx, "back={back}");
+        assert_eq!(meta(vp, 0).mint([0, 0], 0, [1_000, 1_000]).unwrap().1, 2_100);
+
+        // 基础池份额与 D 相等时 vp = 1
+        let d = get_d(AMP, x, x).unwrap();
+        assert_eq!(base_virtual_price(AMP, [x, x], d).unwrap(), RATE_PRECISION);
+        assert_eq!(base_virtual_price(AMP, [0, 0], 0).unwrap(), RATE_PRECISION);
+    }
+
+    // ===== 性质测试 =====
+    fn balance() -> impl Strategy<Value = u128> { 1_000_000u128..=1_000_000_000_000_000 }
+    fn vp() -> impl Strategy<Value = u128> { RATE_PRECISION..=RATE_PRECISION * 2 }
+    fn fee_bps() -> impl Strategy<Value = u32> { 0u32..=100 }
+
+    proptest! {
+        /// 先换出再换回，拿回的数量不会超过最初投入
+        #[test]
+        fn round_trip_swap_never_profits(x0 in balance(), x1 in balance(), vp in vp(), frac in 1u128..=10_000, fee in fee_bps()) {
+            let dx = (x0 * frac / 10_000).max(1);
+            let f = bps_to_fee(fee);
+            let (dy, fee_in) = meta(vp, 0).get_dy(x0, x1, dx, f).unwrap();
+            prop_assume!(dy > 0);
+            let (dz, _) = meta(vp, 1).get_dy(x1 - dy, x0 + dx - fee_in, dy, f).unwrap();
+            prop_assert!(dz <= dx, "dx={dx} dy={dy} back={dz}");
+        }
+
+        /// get_dx 给出的投入足以换出 dy，且少 1 就不够
+        #[test]
+        fn get_dx_is_tight_inverse_of_get_dy(x0 in balance(), x1 in balance(), vp in vp(), frac in 1u128..=9_000, fee in fee_bps(), i in 0usize..=1) {
+            let m = meta(vp, i);
+            let dy = (x1 * frac / 10_000).max(1);
+            let f = bps_to_fee(fee);
+            let dx = m.get_dx(x0, x1, dy, f).unwrap();
+            prop_assert!(m.get_dy(x0, x1, dx, f).unwrap().0 >= dy);
+            prop_assert!(dx == 1 || m.get_dy(x0, x1, dx - 1, f).unwrap().0 < dy, "dx={dx} not minimal");
+        }
+    }
+}
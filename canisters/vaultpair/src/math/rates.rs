@@ -0,0 +1,77 @@
+// canisters/vaultpair/src/math/rates.rs
+// 精度倍数（Curve 的 PRECISION_MUL / rates）：各币金额按自身 decimals 放大到 1e18 的计算口径（xp）后再解曲线。
+// - 内账记账口径仍为 e6（AmountE6）：记账金额 × BOOK_RATE = xp；链上金额 × precision_mul(decimals) = xp。
+// - 口径之间的换算都显式给出取整方向：付给用户向下、向用户收取向上（偏向池子）；向下取整截掉的尾数作为 dust 返回，由调用方记账。
+
+use super::stableswap::{MathError, MathResult};
+
+/// 计算口径：1e18
+pub const PRECISION: u128 = 1_000_000_000_000_000_000;
+/// 内账记账精度
+pub const BOOK_DECIMALS: u8 = 6;
+/// e6 记账金额 → 1e18 计算口径的倍数
+pub const BOOK_RATE: u128 = 1_000_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding { Down, Up }
+
+/// decimals 位小数的币放大到 1e18 的倍数：10^(18 - decimals)；超过 18 位不支持
+pub fn precision_mul(decimals: u8) -> MathResult<u128> {
+    if decimals > 18 { return Err(MathError::Overflow); }
+    Ok(10u128.pow((18 - decimals) as u32))
+}
+
+/// 金额 × 倍数（精确）
+pub fn to_xp(amount: u128, mul: u128) -> MathResult<u128> { amount.checked_mul(mul).ok_or(MathError::Overflow) }
+
+/// xp ÷ 倍数，按 rounding 取整
+pub fn from_xp(xp: u128, mul: u128, rounding: Rounding) -> u128 {
+    if mul <= 1 { return xp; }
+    let q = xp / mul;
+    if rounding == Rounding::Up && !xp.is_multiple_of(mul) { q + 1 } else { q }
+}
+
+/// from_dec 位小数的金额换算为 to_dec 位小数：返回 (结果, 截掉的尾数)。
+/// 尾数以源单位计，仅向下取整且确有截断时非 0；向上取整多收的部分由调用方按需记账
+pub fn rescale(amount: u128, from_dec: u8, to_dec: u8, rounding: Rounding) -> (u128, u128) {
+    if from_dec <= to_dec {
+        let k = 10u128.saturating_pow((to_dec - from_dec) as u32);
+        return (amount.saturating_mul(k), 0);
+    }
+    let k = 10u128.saturating_pow((from_dec - to_dec) as u32);
+    let (q, r) = (amount / k, amount % k);
+    match rounding {
+        Rounding::Down => (q, r),
+        Rounding::Up => (if r == 0 { q } else { q + 1 }, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn rounds_in_the_requested_direction_and_reports_dust() {
+        assert_eq!(precision_mul(6).unwrap(), BOOK_RATE);
+        assert_eq!(precision_mul(8).unwrap(), 10_000_000_000);
+        assert!(precision_mul(19).is_err());
+        // ICP（8 位）→ e6：截掉的 2 位作为 dust；向上取整多收 1
+        assert_eq!(rescale(123_456_789, 8, 6, Rounding::Down), (1_234_567, 89));
+        assert_eq!(rescale(123_456_789, 8, 6, Rounding::Up), (1_234_568, 0));
+        assert_eq!(rescale(1_234_567, 6, 8, Rounding::Down), (123_456_700, 0));
+        assert_eq!((from_xp(BOOK_RATE + 1, BOOK_RATE, Rounding::Down), from_xp(BOOK_RATE + 1, BOOK_RATE, Rounding::Up)), (1, 2));
+    }
+
+    proptest! {
+        /// 向下换算的结果加尾数能精确还原原金额
+        #[test]
+        fn down_plus_dust_restores_amount(amount in 0u128..=u64::MAX as u128, from in 0u8..=18, to in 0u8..=18) {
+            let (q, dust) = rescale(amount, from, to, Rounding::Down);
+            let (back, _) = rescale(q, to, from, Rounding::Down);
+            prop_assert_eq!(back + dust, amount);
+            let (up, _) = rescale(amount, from, to, Rounding::Up);
+            prop_assert!(up >= q && up - q <= 1);
+        }
+    }
+}
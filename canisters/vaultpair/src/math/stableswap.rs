@@ -2,110 +2,347 @@
 // Curve StableSwap (2-coin) math in pure integer arithmetic.
 // - A is stored as amp = A * A_PRECISION (A_PRECISION=1e6)
 // - IMPORTANT: ANN = (amp * n^n) / A_PRECISION   <-- 修复点
-// - 外部金额单位为 e6（与 AmountE6 一致）；内部用 BigUint 迭代。
+// - 外部金额单位为 e6（与 AmountE6 一致）；内部用定长 U256 迭代（全部 checked 运算）。
+// - 迭代上限 256 次；未收敛返回 MathError::NoConvergence，而不是静默使用最后一次迭代值。
+// - 入参先校验：A 超出 [MIN_A, MAX_A] 返回 InvalidAmp，任一侧余额为 0 返回 ZeroReserve。
 
-use num_bigint::BigUint;
-use num_traits::{One, ToPrimitive, Zero};
+use candid::CandidType;
+use ethnum::U256;
+use serde::{Deserialize, Serialize};
+
+use super::invariant::{Invariant, mint_proportional, burn_proportional, settle_dx};
+use super::rates::{self, Rounding, BOOK_RATE};
 
 const N_COINS_U128: u128 = 2;
 const A_PRECISION_U128: u128 = 1_000_000; // 1e6
+const MAX_ITER: usize = 256;
+
+/// 允许的 A 范围（未放大的原始 A）
+pub const MIN_A: u128 = 1;
+pub const MAX_A: u128 = 1_000_000;
+
+/// 费率分母（Curve 口径）：1e10 = 100%，1 bps = 1e6
+pub const FEE_DENOMINATOR: u128 = 10_000_000_000;
+
+/// 数学层错误：由调用方决定如何对外呈现
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathError {
+    /// 256 次迭代内未收敛
+    NoConvergence,
+    /// 中间值超出 256 位（或结果超出 u128）
+    Overflow,
+    /// 池子某一侧余额（或 D）为 0，无法定价
+    ZeroReserve,
+    /// amp 不在 [MIN_A, MAX_A] * A_PRECISION 范围内
+    InvalidAmp,
+    /// CryptoSwap：本次变动会使 virtual_price 下降（亏损），须放弃
+    VirtualPriceDecreased,
+}
+
+pub type MathResult<T> = core::result::Result<T, MathError>;
+
+#[inline]
+fn u(x: u128) -> U256 { U256::from(x) }
+
+#[inline]
+fn add(a: U256, b: U256) -> MathResult<U256> { a.checked_add(b).ok_or(MathError::Overflow) }
+#[inline]
+fn sub(a: U256, b: U256) -> MathResult<U256> { a.checked_sub(b).ok_or(MathError::Overflow) }
+#[inline]
+fn mul(a: U256, b: U256) -> MathResult<U256> { a.checked_mul(b).ok_or(MathError::Overflow) }
+#[inline]
+fn div(a: U256, b: U256) -> MathResult<U256> { a.checked_div(b).ok_or(MathError::Overflow) }
+
+#[inline]
+fn to_u128(x: U256) -> MathResult<u128> { u128::try_from(x).map_err(|_| MathError::Overflow) }
+
+#[inline]
+fn converged(a: U256, b: U256) -> bool {
+    if a > b { a - b <= U256::ONE } else { b - a <= U256::ONE }
+}
 
 #[inline]
-fn bu(x: u128) -> BigUint { BigUint::from(x) }
+fn check_amp(amp_scaled: u128) -> MathResult<()> {
+    if (MIN_A * A_PRECISION_U128..=MAX_A * A_PRECISION_U128).contains(&amp_scaled) { Ok(()) }
+    else { Err(MathError::InvalidAmp) }
+}
 
 #[inline]
-fn ann(amp_scaled: u128) -> BigUint {
+fn ann(amp_scaled: u128) -> MathResult<U256> {
+    check_amp(amp_scaled)?;
     // 修复：ANN = (A * n^n) / A_PRECISION，其中 amp_scaled = A * A_PRECISION
-    let n_pow_n = BigUint::from(N_COINS_U128).pow(N_COINS_U128 as u32); // 4 for n=2
-    (bu(amp_scaled) * n_pow_n) / bu(A_PRECISION_U128)
+    let n_pow_n = u(N_COINS_U128.pow(N_COINS_U128 as u32)); // 4 for n=2
+    div(mul(u(amp_scaled), n_pow_n)?, u(A_PRECISION_U128))
+}
+
+/// floor(d^3 / m)，拆成 d^2 = q*m + r 两段计算：1e18 口径下 d^3 会超出 U256，结果与直接整除逐位一致
+fn cube_div(d: U256, m: U256) -> MathResult<U256> {
+    let d2 = mul(d, d)?;
+    let (q, r) = (div(d2, m)?, d2 % m);
+    add(mul(d, q)?, div(mul(d, r)?, m)?)
 }
 
 /// 计算不变量 D；返回 u128（e6）
-pub fn get_d(amp_scaled: u128, x0: u128, x1: u128) -> u128 {
-    let s = x0.saturating_add(x1);
-    if s == 0 { return 0; }
+pub fn get_d(amp_scaled: u128, x0: u128, x1: u128) -> MathResult<u128> {
+    let ann_v = ann(amp_scaled)?;
+    if x0 == 0 || x1 == 0 { return Err(MathError::ZeroReserve); }
 
-    let mut d = bu(s);
-    let ann_v = ann(amp_scaled);
-    let n = bu(N_COINS_U128);
+    let s = u(x0) + u(x1); // 两个 u128 相加不会超出 U256
+    let n = u(N_COINS_U128);
+    let denom0 = u(x0) * n;
+    let denom1 = u(x1) * n;
 
-    for _ in 0..256 {
-        let denom0 = bu(x0) * &n;
-        let denom1 = bu(x1) * &n;
-        if denom0.is_zero() || denom1.is_zero() { return 0; }
+    // 循环内不变的部分
+    let ann_s = mul(ann_v, s)?;
+    let ann_m1 = sub(ann_v, U256::ONE)?;
+    let n_p1 = n + U256::ONE;
 
-        let mut d_p = d.clone() * &d / denom0;
-        d_p = d_p * &d / denom1;
+    let mut d = s;
+    for _ in 0..MAX_ITER {
+        let mut d_p = div(mul(d, d)?, denom0)?;
+        d_p = div(mul(d_p, d)?, denom1)?;
 
-        let d_prev = d.clone();
+        let d_prev = d;
 
         // D = D * (ANN*S + D_P*n) / ((ANN - 1)*D + (n + 1)*D_P)
-        let numerator   = ann_v.clone() * bu(s) + d_p.clone() * &n;
-        let denominator = (ann_v.clone() - BigUint::one()) * d.clone() + (n.clone() + BigUint::one()) * d_p;
+        let numerator   = add(ann_s, mul(d_p, n)?)?;
+        let denominator = add(mul(ann_m1, d)?, mul(n_p1, d_p)?)?;
 
-        d = d * numerator / denominator;
+        d = div(mul(d, numerator)?, denominator)?;
 
-        if &d > &d_prev {
-            if &d - &d_prev <= BigUint::one() { break; }
-        } else if &d_prev - &d <= BigUint::one() { break; }
+        if converged(d, d_prev) { return to_u128(d); }
     }
 
-    d.to_u128().unwrap_or(u128::MAX)
+    Err(MathError::NoConvergence)
 }
 
 /// 在给定不变量 D 下，输入侧新余额 x_i_new（已含净额）时解出输出侧余额 y（e6）
-pub fn get_y(amp_scaled: u128, x_i_new: u128, d: u128) -> u128 {
-    if x_i_new == 0 { return 0; }
+pub fn get_y(amp_scaled: u128, x_i_new: u128, d: u128) -> MathResult<u128> {
+    let ann_v = ann(amp_scaled)?;
+    if x_i_new == 0 || d == 0 { return Err(MathError::ZeroReserve); }
 
-    let ann_v = ann(amp_scaled);
-    let d_b   = bu(d);
-    let x_b   = bu(x_i_new);
-    let n_pow_n = BigUint::from(N_COINS_U128).pow(N_COINS_U128 as u32); // 4
+    let d_b   = u(d);
+    let x_b   = u(x_i_new);
+    let n_pow_n = u(N_COINS_U128.pow(N_COINS_U128 as u32)); // 4
 
     // 两币池：c = D^(n+1) / (n^n * x * ANN)；b = x + D/ANN
-    let c = d_b.clone().pow((N_COINS_U128 as u32) + 1) / (n_pow_n * x_b.clone() * ann_v.clone());
-    let b_term = x_b.clone() + d_b.clone() / ann_v.clone();
+    let c = cube_div(d_b, mul(mul(n_pow_n, x_b)?, ann_v)?)?;
+    let b_term = add(x_b, div(d_b, ann_v)?)?;
 
-    let two = BigUint::from(2u32);
-    let mut y = d_b.clone();
+    let two = u(2);
+    let mut y = d_b;
 
-    for _ in 0..256 {
-        let y_prev = y.clone();
-        let numerator   = y.clone() * y.clone() + c.clone();
-        let denominator = two.clone() * y.clone() + b_term.clone() - d_b.clone();
-        y = numerator / denominator;
+    for _ in 0..MAX_ITER {
+        let y_prev = y;
+        let numerator   = add(mul(y, y)?, c)?;
+        let denominator = sub(add(mul(two, y)?, b_term)?, d_b)?;
+        y = div(numerator, denominator)?;
 
-        if &y > &y_prev {
-            if &y - &y_prev <= BigUint::one() { break; }
-        } else if &y_prev - &y <= BigUint::one() { break; }
+        if converged(y, y_prev) { return to_u128(y); }
     }
 
-    y.to_u128().unwrap_or(0)
+    Err(MathError::NoConvergence)
 }
 
 /// 报价：给定 (x_in, x_out)、dx（e6）、费率（bps），返回 (dy, fee_in_e6)
+/// dx=0 直接返回 (0, 0)；其余情况下 A 与储备的校验错误原样返回
 pub fn quote_dx_to_dy(
     amp_scaled: u128,
     x_in: u128,
     x_out: u128,
     dx: u128,
     fee_bps: u32,
-) -> (u128, u128) {
-    if dx == 0 { return (0, 0); }
+) -> MathResult<(u128, u128)> {
+    quote_dx_to_dy_fee(amp_scaled, x_in, x_out, dx, bps_to_fee(fee_bps))
+}
+
+#[inline]
+pub fn bps_to_fee(fee_bps: u32) -> u128 { fee_bps as u128 * (FEE_DENOMINATOR / 10_000) }
 
-    let fee_in = (dx as u128) * (fee_bps as u128) / 10_000u128;
+/// 同 quote_dx_to_dy，费率按 FEE_DENOMINATOR 口径
+pub fn quote_dx_to_dy_fee(
+    amp_scaled: u128,
+    x_in: u128,
+    x_out: u128,
+    dx: u128,
+    fee: u128,
+) -> MathResult<(u128, u128)> {
+    if dx == 0 { return Ok((0, 0)); }
+
+    let fee_in = to_u128(mul(u(dx), u(fee))? / u(FEE_DENOMINATOR))?;
     let dx_net = dx.saturating_sub(fee_in);
 
-    let d0 = get_d(amp_scaled, x_in, x_out);
-    if d0 == 0 { return (0, fee_in); }
+    let d0 = get_d(amp_scaled, x_in, x_out)?;
 
     let x_new = x_in.saturating_add(dx_net);
-    let y_new = get_y(amp_scaled, x_new, d0);
+    let y_new = get_y(amp_scaled, x_new, d0)?;
 
     let mut dy = x_out.saturating_sub(y_new);
     if dy > 0 { dy = dy.saturating_sub(1); } // 与 Curve 口径一致，避免过报
 
-    (dy, fee_in)
+    Ok((dy, fee_in))
+}
+
+/// 记账口径（e6）报价：余额与 dx 放大到 1e18（rates::BOOK_RATE）求解，再换回 e6。
+/// dy 向下取整；手续费按 dx 的 e6 口径向下取整，使实际入池的净额不少于求解所用净额（取整都偏向池子）。返回 (dy, fee_in, 实际费率)
+pub fn quote_dx_to_dy_book(
+    amp_scaled: u128,
+    x_in: u128,
+    x_out: u128,
+    dx: u128,
+    fee: u128,
+    offpeg_fee_multiplier: u128,
+) -> MathResult<(u128, u128, u128)> {
+    let xp = |x: u128| rates::to_xp(x, BOOK_RATE);
+    let (dy, fee_in, rate) = quote_dx_to_dy_dynamic(amp_scaled, xp(x_in)?, xp(x_out)?, xp(dx)?, fee, offpeg_fee_multiplier)?;
+    Ok((rates::from_xp(dy, BOOK_RATE, Rounding::Down), rates::from_xp(fee_in, BOOK_RATE, Rounding::Down), rate))
+}
+
+/// Curve NG `_dynamic_fee`：池子越失衡费率越高，完全平衡时等于 fee，极端失衡时趋近 fee * multiplier。
+/// xpi / xpj 为两侧余额（取成交前后的均值）；multiplier ≤ FEE_DENOMINATOR（即 ≤ 1x）时返回原费率
+pub fn dynamic_fee(xpi: u128, xpj: u128, fee: u128, offpeg_fee_multiplier: u128) -> MathResult<u128> {
+    if offpeg_fee_multiplier <= FEE_DENOMINATOR { return Ok(fee); }
+    let xps2 = mul(add(u(xpi), u(xpj))?, add(u(xpi), u(xpj))?)?;
+    if xps2 == U256::ZERO { return Ok(fee); }
+    let skew = div(mul(mul(u(offpeg_fee_multiplier - FEE_DENOMINATOR), u(4))?, mul(u(xpi), u(xpj))?)?, xps2)?;
+    to_u128(div(mul(u(offpeg_fee_multiplier), u(fee))?, add(skew, u(FEE_DENOMINATOR))?)?)
+}
+
+/// 动态费率报价：先按零费率求成交后余额，以成交前后两侧余额的均值计算费率，再按该费率报价。
+/// 返回 (dy, fee_in_e6, 实际费率)
+pub fn quote_dx_to_dy_dynamic(
+    amp_scaled: u128,
+    x_in: u128,
+    x_out: u128,
+    dx: u128,
+    fee: u128,
+    offpeg_fee_multiplier: u128,
+) -> MathResult<(u128, u128, u128)> {
+    if dx == 0 { return Ok((0, 0, fee)); }
+    let rate = if offpeg_fee_multiplier <= FEE_DENOMINATOR { fee } else {
+        let (dy0, _) = quote_dx_to_dy_fee(amp_scaled, x_in, x_out, dx, 0)?;
+        let xpi = x_in.saturating_add(x_in.saturating_add(dx)) / 2;
+        let xpj = x_out.saturating_add(x_out.saturating_sub(dy0)) / 2;
+        dynamic_fee(xpi, xpj, fee, offpeg_fee_multiplier)?
+    };
+    let (dy, fee_in) = quote_dx_to_dy_fee(amp_scaled, x_in, x_out, dx, rate)?;
+    Ok((dy, fee_in, rate))
+}
+
+/// 不平衡手续费率：Curve 的 fee * N / (4 * (N - 1))，两币池即 fee / 2
+#[inline]
+fn imbalance_fee(fee: u128) -> U256 { u(fee / 2) }
+
+/// 不按比例注入（Curve NG add_liquidity）：按各侧偏离理想比例的部分收不平衡手续费，扣除后的余额重算 D 再铸造。
+/// 每侧费率为 dynamic_fee(该侧新旧余额之和, (D0 + D1) / N, fee / 2, multiplier)（口径同 Curve NG），multiplier ≤ 1x 时即 fee / 2。
+/// 返回 (铸造份额, 各侧手续费)；手续费从成交后的余额里扣出（由调用方转入 fee_vault）。池子为空时返回 ZeroReserve
+pub fn calc_token_amount(
+    amp_scaled: u128,
+    fee: u128,
+    offpeg_fee_multiplier: u128,
+    reserves: [u128; 2],
+    total_shares: u128,
+    amounts: [u128; 2],
+) -> MathResult<(u128, [u128; 2])> {
+    if total_shares == 0 { return Err(MathError::ZeroReserve); }
+    let d0 = u(get_d(amp_scaled, reserves[0], reserves[1])?);
+    let new = [add(u(reserves[0]), u(amounts[0]))?, add(u(reserves[1]), u(amounts[1]))?];
+    let d1 = u(get_d(amp_scaled, to_u128(new[0])?, to_u128(new[1])?)?);
+    if d1 <= d0 { return Ok((0, [0, 0])); }
+
+    let ys = to_u128(div(add(d0, d1)?, u(N_COINS_U128))?)?;
+    let mut fees = [0u128; 2];
+    let mut adj = [0u128; 2];
+    for k in 0..2 {
+        let ideal = div(mul(d1, u(reserves[k]))?, d0)?;
+        let diff = if ideal > new[k] { ideal - new[k] } else { new[k] - ideal };
+        let xs = to_u128(add(new[k], u(reserves[k]))?)?;
+        let rate = dynamic_fee(xs, ys, to_u128(imbalance_fee(fee))?, offpeg_fee_multiplier)?;
+        fees[k] = to_u128(div(mul(u(rate), diff)?, u(FEE_DENOMINATOR))?)?;
+        adj[k] = to_u128(sub(new[k], u(fees[k]))?)?;
+    }
+    let d2 = u(get_d(amp_scaled, adj[0], adj[1])?);
+    if d2 <= d0 { return Ok((0, fees)); }
+    // 向下取整，余数留在池内
+    let mint = div(mul(u(total_shares), d2 - d0)?, d0)?;
+    Ok((to_u128(mint)?, fees))
+}
+
+/// 单币赎回（Curve remove_liquidity_one_coin）：D 按 shares 占比下降（向上取整），解出 i 侧新余额；
+/// 不平衡手续费按两侧偏离理想比例的部分计算，以 i 侧币支付；每侧费率同 calc_token_amount 取 dynamic_fee
+/// （i 侧用新旧余额的均值、另一侧用原余额，对 (D0 + D1) / 2N）。返回 (实得 dy, 手续费)，dy + 手续费 = 不收费时应得。
+/// 赎回全部份额时无解，返回 ZeroReserve
+pub fn calc_withdraw_one_coin(
+    amp_scaled: u128,
+    fee: u128,
+    offpeg_fee_multiplier: u128,
+    reserves: [u128; 2],
+    total_shares: u128,
+    shares: u128,
+    i: usize,
+) -> MathResult<(u128, u128)> {
+    if shares >= total_shares || i > 1 { return Err(MathError::ZeroReserve); }
+    let j = 1 - i;
+    let d0 = u(get_d(amp_scaled, reserves[0], reserves[1])?);
+    let ts = u(total_shares);
+    let burnt = div(add(mul(u(shares), d0)?, sub(ts, U256::ONE)?)?, ts)?;
+    let d1 = sub(d0, burnt)?;
+    let d1_u = to_u128(d1)?;
+    let new_y = get_y(amp_scaled, reserves[j], d1_u)?;
+
+    let base = to_u128(imbalance_fee(fee))?;
+    let ys = to_u128(div(add(d0, d1)?, u(2 * N_COINS_U128))?)?;
+    let f_i = u(dynamic_fee(reserves[i].saturating_add(new_y) / 2, ys, base, offpeg_fee_multiplier)?);
+    let f_j = u(dynamic_fee(reserves[j], ys, base, offpeg_fee_multiplier)?);
+    let exp_i = sub(div(mul(u(reserves[i]), d1)?, d0)?, u(new_y)).unwrap_or(U256::ZERO);
+    let exp_j = sub(u(reserves[j]), div(mul(u(reserves[j]), d1)?, d0)?)?;
+    let red_i = to_u128(sub(u(reserves[i]), div(mul(f_i, exp_i)?, u(FEE_DENOMINATOR))?)?)?;
+    let red_j = to_u128(sub(u(reserves[j]), div(mul(f_j, exp_j)?, u(FEE_DENOMINATOR))?)?)?;
+
+    // 与 quote 一致少报 1，避免过付
+    let dy = red_i.saturating_sub(get_y(amp_scaled, red_j, d1_u)?).saturating_sub(1);
+    let dy0 = reserves[i].saturating_sub(new_y);
+    Ok((dy, dy0.saturating_sub(dy)))
+}
+
+/// 池型入口（见 invariant）：amp_scaled = A * A_PRECISION；金额为 e6 记账口径，内部按 1e18 求解（见 quote_dx_to_dy_book）
+#[derive(Clone, Copy, Debug)]
+pub struct StableSwap { pub amp_scaled: u128 }
+
+/// 含手续费的总输入：dx_net / (1 - fee)，向上取整
+pub(crate) fn gross_up(dx_net: u128, fee: u128) -> MathResult<u128> {
+    if fee >= FEE_DENOMINATOR { return Err(MathError::Overflow); }
+    let n = mul(u(dx_net), u(FEE_DENOMINATOR))?;
+    let d = u(FEE_DENOMINATOR - fee);
+    to_u128(div(add(n, sub(d, U256::ONE)?)?, d)?)
+}
+
+impl Invariant for StableSwap {
+    fn get_dy(&self, x_in: u128, x_out: u128, dx: u128, fee: u128) -> MathResult<(u128, u128)> {
+        quote_dx_to_dy_book(self.amp_scaled, x_in, x_out, dx, fee, FEE_DENOMINATOR).map(|(dy, f, _)| (dy, f))
+    }
+
+    fn get_dx(&self, x_in: u128, x_out: u128, dy: u128, fee: u128) -> MathResult<u128> {
+        if dy == 0 { return Ok(0); }
+        // get_dy 少报 1，这里对应多留 1
+        if dy.saturating_add(1) >= x_out { return Err(MathError::ZeroReserve); }
+        let d = get_d(self.amp_scaled, x_in, x_out)?;
+        let x_new = get_y(self.amp_scaled, x_out - dy - 1, d)?;
+        let dx = gross_up(x_new.saturating_sub(x_in).saturating_add(1), fee)?;
+        settle_dx(self, x_in, x_out, dy, fee, dx)
+    }
+
+    /// 首次建池份额与 TVL 对齐（两侧之和）
+    fn mint(&self, reserves: [u128; 2], total_shares: u128, amounts: [u128; 2]) -> MathResult<([u128; 2], u128)> {
+        if total_shares == 0 || reserves[0] == 0 || reserves[1] == 0 {
+            return Ok((amounts, amounts[0].saturating_add(amounts[1])));
+        }
+        mint_proportional(reserves, total_shares, amounts)
+    }
+
+    fn burn(&self, reserves: [u128; 2], total_shares: u128, shares: u128) -> MathResult<[u128; 2]> {
+        burn_proportional(reserves, total_shares, shares)
+    }
 }
 
 #[cfg(test)]
@@ -121,19 +358,48 @@ mod tests {
         let x1 = 10_000 * E6;
         let dx = 1_000 * E6;
 
-        let (dy, fee) = quote_dx_to_dy(amp, x0, x1, dx, 30);
+        let (dy, fee) = quote_dx_to_dy(amp, x0, x1, dx, 30).unwrap();
         assert!(fee > 0);
         assert!(dy >= dx - fee - 2_000);
     }
 
+    #[test]
+    fn dynamic_fee_rises_with_imbalance() {
+        let fee = bps_to_fee(4);
+        let mult = 2 * FEE_DENOMINATOR;
+        // 平衡：等于基础费率；multiplier ≤ 1x：不变
+        assert_eq!(dynamic_fee(1_000 * E6, 1_000 * E6, fee, mult).unwrap(), fee);
+        assert_eq!(dynamic_fee(1_900 * E6, 100 * E6, fee, FEE_DENOMINATOR).unwrap(), fee);
+        // 9:1 失衡：4xy/(x+y)^2 = 0.36 → fee * 2 / 1.36
+        assert_eq!(dynamic_fee(900 * E6, 100 * E6, fee, mult).unwrap(), fee * 2 * 100 / 136);
+
+        let amp = 100 * A_PRECISION_U128;
+        let (dy_flat, fee_flat) = quote_dx_to_dy(amp, 1_000 * E6, 1_000 * E6, 100 * E6, 4).unwrap();
+        assert_eq!(quote_dx_to_dy_dynamic(amp, 1_000 * E6, 1_000 * E6, 100 * E6, fee, FEE_DENOMINATOR).unwrap(), (dy_flat, fee_flat, fee));
+        // 往失衡方向交易的费率高于往平衡方向
+        let (_, _, worse) = quote_dx_to_dy_dynamic(amp, 1_500 * E6, 500 * E6, 100 * E6, fee, mult).unwrap();
+        let (_, _, better) = quote_dx_to_dy_dynamic(amp, 500 * E6, 1_500 * E6, 100 * E6, fee, mult).unwrap();
+        assert!(fee < better && better < worse);
+
+        // 单币注入 / 赎回同样按失衡程度加费（Curve NG）：1x 时与固定 fee / 2 一致，失衡池子里 multiplier 越大手续费越高
+        let (r, ts, add) = ([1_500 * E6, 500 * E6], 2_000 * E6, [200 * E6, 0]);
+        let flat = calc_token_amount(amp, fee, FEE_DENOMINATOR, r, ts, add).unwrap();
+        let dynamic = calc_token_amount(amp, fee, mult, r, ts, add).unwrap();
+        assert!(dynamic.1[0] > flat.1[0] && dynamic.0 < flat.0, "{flat:?} {dynamic:?}");
+        let (dy_flat, f_flat) = calc_withdraw_one_coin(amp, fee, FEE_DENOMINATOR, r, ts, 100 * E6, 0).unwrap();
+        let (dy_dyn, f_dyn) = calc_withdraw_one_coin(amp, fee, mult, r, ts, 100 * E6, 0).unwrap();
+        assert!(f_dyn > f_flat && dy_dyn < dy_flat);
+        assert_eq!(dy_dyn + f_dyn, dy_flat + f_flat);
+    }
+
     #[test]
     fn more_slippage_when_a_small() {
         let x0 = 10_000 * E6;
         let x1 = 10_000 * E6;
         let dx = 1_000 * E6;
 
-        let (dy_small_a, _) = quote_dx_to_dy(10 * A_PRECISION_U128, x0, x1, dx, 30);
-        let (dy_large_a, _) = quote_dx_to_dy(5_000 * A_PRECISION_U128, x0, x1, dx, 30);
+        let (dy_small_a, _) = quote_dx_to_dy(10 * A_PRECISION_U128, x0, x1, dx, 30).unwrap();
+        let (dy_large_a, _) = quote_dx_to_dy(5_000 * A_PRECISION_U128, x0, x1, dx, 30).unwrap();
         assert!(dy_small_a < dy_large_a);
     }
 
@@ -144,10 +410,10 @@ mod tests {
         let x1 = 50_000 * E6;
         let dx = 5_000 * E6;
 
-        let d_before = get_d(amp, x0, x1);
-        let (dy, _fee) = quote_dx_to_dy(amp, x0, x1, dx, 0);
+        let d_before = get_d(amp, x0, x1).unwrap();
+        let (dy, _fee) = quote_dx_to_dy(amp, x0, x1, dx, 0).unwrap();
 
-        let d_after = get_d(amp, x0 + dx, x1 - dy);
+        let d_after = get_d(amp, x0 + dx, x1 - dy).unwrap();
         let diff = if d_after > d_before { d_after - d_before } else { d_before - d_after };
         assert!(diff <= 10);
     }
@@ -162,7 +428,7 @@ mod tests {
         let dxs = [1, 10, 100, 1_000, 5_000, 10_000].map(|v| v as u128 * E6);
         let mut last_dy = 0u128;
         for dx in dxs {
-            let (dy, _fee) = quote_dx_to_dy(amp, x0, x1, dx, fee_bps);
+            let (dy, _fee) = quote_dx_to_dy(amp, x0, x1, dx, fee_bps).unwrap();
             assert!(dy >= last_dy, "non-decreasing: dx={dx}, dy={dy}, last={last_dy}");
             last_dy = dy;
         }
@@ -186,11 +452,291 @@ mod tests {
         let dx = 2_000 * E6;
         let fee_bps = 10;
 
-        let (dy_stable, fee) = quote_dx_to_dy(amp, x0, x1, dx, fee_bps);
+        let (dy_stable, fee) = quote_dx_to_dy(amp, x0, x1, dx, fee_bps).unwrap();
         let (dy_xyk,  _f2 ) = xyk_quote(x0, x1, dx, fee_bps);
         let dy_cs = dx - fee; // constant-sum 上界
 
         assert!(dy_xyk <= dy_stable, "should not be worse than XYK");
         assert!(dy_stable <= dy_cs,  "should not exceed constant-sum");
     }
+
+    #[test]
+    fn rejects_invalid_amp_and_zero_reserve() {
+        let amp = 100 * A_PRECISION_U128;
+        assert_eq!(get_d(0, E6, E6), Err(MathError::InvalidAmp));
+        assert_eq!(get_d(A_PRECISION_U128 / 2, E6, E6), Err(MathError::InvalidAmp));
+        assert_eq!(get_d((MAX_A + 1) * A_PRECISION_U128, E6, E6), Err(MathError::InvalidAmp));
+        assert_eq!(get_d(amp, 0, E6), Err(MathError::ZeroReserve));
+        assert_eq!(get_y(amp, 0, 2 * E6), Err(MathError::ZeroReserve));
+        assert_eq!(get_y(amp, E6, 0), Err(MathError::ZeroReserve));
+        assert_eq!(quote_dx_to_dy(amp, E6, 0, E6, 10), Err(MathError::ZeroReserve));
+        assert_eq!(quote_dx_to_dy(amp, E6, E6, 0, 10), Ok((0, 0)));
+    }
+
+    #[test]
+    fn reports_overflow_instead_of_saturating() {
+        // D^3 超出 256 位：旧实现会返回 0，现在必须报错
+        let amp = 100 * A_PRECISION_U128;
+        let big = u128::MAX / 4;
+        assert_eq!(get_y(amp, big, big), Err(MathError::Overflow));
+        assert_eq!(quote_dx_to_dy(amp, big, big, E6, 10), Err(MathError::Overflow));
+    }
+
+    // ===== 差分测试：对照 Curve Vyper 公式生成的参考向量 =====
+    // 向量由 scripts/gen_stableswap_vectors.py 生成（CSV 列：a,x0,x1,d,dx,y,dy；a 为原始 A）
+    const VYPER_VECTORS: &str = include_str!("testdata/stableswap_vectors.csv");
+
+    fn vyper_vectors() -> Vec<[u128; 7]> {
+        VYPER_VECTORS
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.starts_with("a,") && !l.trim().is_empty())
+            .map(|l| {
+                let mut row = [0u128; 7];
+                for (i, v) in l.split(',').enumerate() { row[i] = v.trim().parse().unwrap(); }
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_vyper_reference_vectors() {
+        let rows = vyper_vectors();
+        assert!(rows.len() >= 100, "reference vectors missing");
+        for [a, x0, x1, d_ref, dx, y_ref, dy_ref] in rows {
+            let amp = a * A_PRECISION_U128;
+            let d = get_d(amp, x0, x1).unwrap();
+            assert_eq!(d, d_ref, "get_d: a={a} x0={x0} x1={x1}");
+
+            // Vyper 的 c 逐项取整，这里一次性整除：允许 y 相差 1
+            let y = get_y(amp, x0 + dx, d).unwrap();
+            assert!(y.abs_diff(y_ref) <= 1, "get_y: a={a} x0={x0} x1={x1} dx={dx} y={y} ref={y_ref}");
+
+            let (dy, fee) = quote_dx_to_dy(amp, x0, x1, dx, 0).unwrap();
+            assert_eq!(fee, 0);
+            assert!(dy.abs_diff(dy_ref) <= 1, "dy: a={a} x0={x0} x1={x1} dx={dx} dy={dy} ref={dy_ref}");
+        }
+    }
+
+    // ===== 性质测试 =====
+    mod props {
+        use super::*;
+        use proptest::prelude::*;
+
+        // 余额 1 ~ 1e15（e6），A 1 ~ 1e6，费率 0 ~ 100 bps
+        fn balance() -> impl Strategy<Value = u128> { 1u128..=1_000_000_000_000_000 }
+        fn amp() -> impl Strategy<Value = u128> { (1u128..=1_000_000).prop_map(|a| a * A_PRECISION_U128) }
+        fn fee_bps() -> impl Strategy<Value = u32> { 0u32..=100 }
+
+        proptest! {
+            /// 收费 swap 后（净额入池、dy 出池），D 不应下降
+            #[test]
+            fn d_never_decreases_after_swap(
+                amp in amp(), x0 in balance(), x1 in balance(), frac in 1u128..=10_000, fee in 1u32..=100,
+            ) {
+                let dx = (x0 * frac / 10_000).max(1);
+                let (dy, fee_in) = quote_dx_to_dy(amp, x0, x1, dx, fee).unwrap();
+                prop_assume!(dy > 0);
+                let d0 = get_d(amp, x0, x1).unwrap();
+                let d1 = get_d(amp, x0 + (dx - fee_in), x1 - dy).unwrap();
+                prop_assert!(d1 >= d0, "d0={d0} d1={d1} dx={dx} dy={dy}");
+            }
+
+            /// dy 关于 dx 单调不减
+            #[test]
+            fn dy_monotone_in_dx(
+                amp in amp(), x0 in balance(), x1 in balance(), fee in fee_bps(),
+                a in 1u128..=1_000_000_000_000_000, b in 1u128..=1_000_000_000_000_000,
+            ) {
+                let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+                let (dy_lo, _) = quote_dx_to_dy(amp, x0, x1, lo, fee).unwrap();
+                let (dy_hi, _) = quote_dx_to_dy(amp, x0, x1, hi, fee).unwrap();
+                prop_assert!(dy_lo <= dy_hi, "dx {lo}->{dy_lo}, dx {hi}->{dy_hi}");
+            }
+
+            /// get_y(x0, D(x0, x1)) 应回到 x1
+            #[test]
+            fn get_y_round_trips(amp in amp(), x0 in balance(), x1 in balance()) {
+                let d = get_d(amp, x0, x1).unwrap();
+                let y = get_y(amp, x0, d).unwrap();
+                prop_assert!(y.abs_diff(x1) <= 2, "x1={x1} y={y} d={d}");
+            }
+
+            /// 先换出再换回，拿回的数量不会超过最初投入
+            #[test]
+            fn round_trip_swap_never_profits(
+                amp in amp(), x0 in balance(), x1 in balance(), frac in 1u128..=10_000, fee in fee_bps(),
+            ) {
+                let dx = (x0 * frac / 10_000).max(1);
+                let (dy, fee_in) = quote_dx_to_dy(amp, x0, x1, dx, fee).unwrap();
+                prop_assume!(dy > 0);
+                let (x0_1, x1_1) = (x0 + (dx - fee_in), x1 - dy);
+                let (dz, _) = quote_dx_to_dy(amp, x1_1, x0_1, dy, fee).unwrap();
+                prop_assert!(dz <= dx, "dx={dx} dy={dy} back={dz}");
+            }
+
+            /// Invariant::get_dx 是 get_dy 的最小逆
+            #[test]
+            fn get_dx_is_tight_inverse_of_get_dy(
+                amp in amp(), x0 in balance(), x1 in balance(), frac in 1u128..=9_000, fee in fee_bps(),
+            ) {
+                let ss = StableSwap { amp_scaled: amp };
+                let dy = (x1 * frac / 10_000).max(1);
+                prop_assume!(dy + 1 < x1);
+                let f = bps_to_fee(fee);
+                let dx = ss.get_dx(x0, x1, dy, f).unwrap();
+                prop_assert!(ss.get_dy(x0, x1, dx, f).unwrap().0 >= dy);
+                prop_assert!(dx == 1 || ss.get_dy(x0, x1, dx - 1, f).unwrap().0 < dy, "dx={dx} not minimal");
+            }
+
+            /// 单币注入后立刻单币赎回，拿回的数量不会超过投入
+            #[test]
+            fn one_coin_add_then_withdraw_never_profits(
+                amp in amp(), x0 in 1_000_000u128..=1_000_000_000_000_000, x1 in 1_000_000u128..=1_000_000_000_000_000,
+                frac in 1u128..=10_000, fee in fee_bps(), i in 0usize..=1, mult in 1u128..=10,
+            ) {
+                let (f, m) = (bps_to_fee(fee), mult * FEE_DENOMINATOR);
+                let r = [x0, x1];
+                let ts = get_d(amp, x0, x1).unwrap();
+                let a = (r[i] * frac / 10_000).max(1);
+                let mut amounts = [0, 0];
+                amounts[i] = a;
+                let (minted, fees) = calc_token_amount(amp, f, m, r, ts, amounts).unwrap();
+                prop_assume!(minted > 0);
+                let r1 = [x0 + amounts[0] - fees[0], x1 + amounts[1] - fees[1]];
+                let (dy, _) = calc_withdraw_one_coin(amp, f, m, r1, ts + minted, minted, i).unwrap();
+                prop_assert!(dy <= a, "in={a} minted={minted} out={dy}");
+            }
+
+            /// U256 实现与原 BigUint 实现逐位一致
+            #[test]
+            fn u256_matches_biguint_reference(
+                amp in amp(), x0 in balance(), x1 in balance(), frac in 1u128..=10_000, fee in fee_bps(),
+            ) {
+                let dx = (x0 * frac / 10_000).max(1);
+                let d = get_d(amp, x0, x1).unwrap();
+                prop_assert_eq!(d, bigint_ref::get_d(amp, x0, x1));
+                prop_assert_eq!(get_y(amp, x0 + dx, d).unwrap(), bigint_ref::get_y(amp, x0 + dx, d));
+                prop_assert_eq!(
+                    quote_dx_to_dy(amp, x0, x1, dx, fee).unwrap(),
+                    bigint_ref::quote_dx_to_dy(amp, x0, x1, dx, fee)
+                );
+            }
+        }
+    }
+
+    // 改写为 U256 之前的 BigUint 版本，仅作为等价性对照
+    mod bigint_ref {
+        use super::{A_PRECISION_U128, N_COINS_U128};
+        use num_bigint::BigUint;
+        use num_traits::{One, ToPrimitive, Zero};
+
+        fn bu(x: u128) -> BigUint { BigUint::from(x) }
+
+        fn ann(amp_scaled: u128) -> BigUint {
+            let n_pow_n = BigUint::from(N_COINS_U128).pow(N_COINS_U128 as u32);
+            (bu(amp_scaled) * n_pow_n) / bu(A_PRECISION_U128)
+        }
+
+        pub fn get_d(amp_scaled: u128, x0: u128, x1: u128) -> u128 {
+            let s = x0.saturating_add(x1);
+            if s == 0 { return 0; }
+            let mut d = bu(s);
+            let ann_v = ann(amp_scaled);
+            let n = bu(N_COINS_U128);
+            for _ in 0..256 {
+                let denom0 = bu(x0) * &n;
+                let denom1 = bu(x1) * &n;
+                if denom0.is_zero() || denom1.is_zero() { return 0; }
+                let mut d_p = d.clone() * &d / denom0;
+                d_p = d_p * &d / denom1;
+                let d_prev = d.clone();
+                let numerator   = ann_v.clone() * bu(s) + d_p.clone() * &n;
+                let denominator = (ann_v.clone() - BigUint::one()) * d.clone() + (n.clone() + BigUint::one()) * d_p;
+                d = d * numerator / denominator;
+                if d > d_prev {
+                    if &d - &d_prev <= BigUint::one() { break; }
+                } else if &d_prev - &d <= BigUint::one() { break; }
+            }
+            d.to_u128().unwrap_or(u128::MAX)
+        }
+
+        pub fn get_y(amp_scaled: u128, x_i_new: u128, d: u128) -> u128 {
+            if x_i_new == 0 { return 0; }
+            let ann_v = ann(amp_scaled);
+            let d_b = bu(d);
+            let x_b = bu(x_i_new);
+            let n_pow_n = BigUint::from(N_COINS_U128).pow(N_COINS_U128 as u32);
+            let c = d_b.clone().pow((N_COINS_U128 as u32) + 1) / (n_pow_n * x_b.clone() * ann_v.clone());
+            let b_term = x_b.clone() + d_b.clone() / ann_v.clone();
+            let two = BigUint::from(2u32);
+            let mut y = d_b.clone();
+            for _ in 0..256 {
+                let y_prev = y.clone();
+                let numerator   = y.clone() * y.clone() + c.clone();
+                let denominator = two.clone() * y.clone() + b_term.clone() - d_b.clone();
+                y = numerator / denominator;
+                if y > y_prev {
+                    if &y - &y_prev <= BigUint::one() { break; }
+                } else if &y_prev - &y <= BigUint::one() { break; }
+            }
+            y.to_u128().unwrap_or(0)
+        }
+
+        pub fn quote_dx_to_dy(amp_scaled: u128, x_in: u128, x_out: u128, dx: u128, fee_bps: u32) -> (u128, u128) {
+            if dx == 0 { return (0, 0); }
+            let fee_in = dx * (fee_bps as u128) / 10_000u128;
+            let dx_net = dx.saturating_sub(fee_in);
+            let d0 = get_d(amp_scaled, x_in, x_out);
+            if d0 == 0 { return (0, fee_in); }
+            let y_new = get_y(amp_scaled, x_in.saturating_add(dx_net), d0);
+            let mut dy = x_out.saturating_sub(y_new);
+            if dy > 0 { dy = dy.saturating_sub(1); }
+            (dy, fee_in)
+        }
+    }
+}
+
+// ===== canbench 基准：cargo build --features canbench-rs 后由 canbench 运行（见 canbench.yml） =====
+#[cfg(feature = "canbench-rs")]
+mod benches {
+    use super::*;
+    use canbench_rs::bench;
+    use std::hint::black_box;
+
+    const E6: u128 = 1_000_000;
+    const AMP: u128 = 100 * A_PRECISION_U128;
+
+    #[bench]
+    fn bench_get_d_balanced() {
+        black_box(get_d(black_box(AMP), black_box(1_000_000 * E6), black_box(1_000_000 * E6)).unwrap());
+    }
+
+    #[bench]
+    fn bench_get_d_imbalanced() {
+        black_box(get_d(black_box(AMP), black_box(9_000_000 * E6), black_box(100_000 * E6)).unwrap());
+    }
+
+    #[bench]
+    fn bench_get_y() {
+        let d = get_d(AMP, 1_000_000 * E6, 1_000_000 * E6).unwrap();
+        black_box(get_y(black_box(AMP), black_box(1_050_000 * E6), black_box(d)).unwrap());
+    }
+
+    #[bench]
+    fn bench_quote_dx_to_dy() {
+        black_box(quote_dx_to_dy(black_box(AMP), 1_000_000 * E6, 1_000_000 * E6, black_box(5_000 * E6), 10).unwrap());
+    }
+
+    /// 模拟 quote_exact_out：约 80 次 quote 的二分搜索
+    #[bench]
+    fn bench_exact_out_search() {
+        let (rin, rout, target) = (1_000_000 * E6, 1_000_000 * E6, 5_000 * E6);
+        let (mut lo, mut hi) = (0u128, target);
+        while quote_dx_to_dy(AMP, rin, rout, hi, 10).unwrap().0 < target { hi = hi * 2 + 1; }
+        while lo + 1 < hi {
+            let mid = (lo + hi) / 2;
+            if quote_dx_to_dy(AMP, rin, rout, mid, 10).unwrap().0 >= target { hi = mid; } else { lo = mid; }
+        }
+        black_box(hi);
+    }
 }
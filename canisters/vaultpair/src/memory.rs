@@ -0,0 +1,53 @@
+// canisters/vaultpair/src/memory.rs
+// stable memory 布局：由 MemoryManager 切分为若干虚拟内存。
+//   0 = 升级时的 State 快照（u64 长度前缀 + candid）
+//   1 = 事件日志（StableBTreeMap<id, Event>，实时写入，不随升级拷贝）
+// 旧版本用 ic_cdk::storage::stable_save 直接写裸 stable memory；post_upgrade 先按旧格式读出再初始化 MemoryManager。
+use candid::{decode_one, encode_one, CandidType};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory};
+use serde::de::DeserializeOwned;
+use std::cell::RefCell;
+
+pub type VMem = VirtualMemory<DefaultMemoryImpl>;
+
+const UPGRADES: MemoryId = MemoryId::new(0);
+pub const EVENTS: MemoryId = MemoryId::new(1);
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+}
+
+pub fn get(id: MemoryId) -> VMem {
+    MEMORY_MANAGER.with(|m| m.borrow().get(id))
+}
+
+/// 裸 stable memory 是否仍是旧版 stable_save 格式（尚未由 MemoryManager 接管）
+/// 必须在首次访问 MEMORY_MANAGER 之前调用：init 会覆盖裸内存头部
+pub fn is_legacy_layout() -> bool {
+    if ic_cdk::api::stable::stable_size() == 0 { return false; }
+    let mut magic = [0u8; 3];
+    ic_cdk::api::stable::stable_read(0, &mut magic);
+    &magic != b"MGR"
+}
+
+/// 升级前写入快照
+pub fn save_upgrade<T: CandidType>(v: &T) {
+    let bytes = encode_one(v).expect("encode upgrade state");
+    let mut mem = get(UPGRADES);
+    let mut w = ic_stable_structures::writer::Writer::new(&mut mem, 0);
+    w.write(&(bytes.len() as u64).to_le_bytes()).expect("grow upgrade memory");
+    w.write(&bytes).expect("grow upgrade memory");
+}
+
+/// 升级后读取快照；全新安装（无快照）返回 None。解码失败直接 trap，让升级整体回滚而不是丢状态
+pub fn load_upgrade<T: CandidType + DeserializeOwned>() -> Option<T> {
+    let mem = get(UPGRADES);
+    if mem.size() == 0 { return None; }
+    let mut len = [0u8; 8];
+    mem.read(0, &mut len);
+    let mut bytes = vec![0u8; u64::from_le_bytes(len) as usize];
+    mem.read(8, &mut bytes);
+    Some(decode_one(&bytes).expect("decode upgrade state"))
+}
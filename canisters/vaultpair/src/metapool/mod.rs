@@ -0,0 +1,295 @@
+// canisters/vaultpair/src/metapool/mod.rs
+// Metapool 的 exchange_underlying：一次调用在新币与基础池（0 号池）的币之间成交，LP 只在中间经过用户内账。
+//   新币 → USDC/USDT：元池 swap 新币 → LP，再把这些 LP 从基础池单币赎回（positions::remove_one_coin）；
+//   USDC/USDT → 新币：先单币注入基础池铸造 LP（positions::add_one_coin），再在元池 swap LP → 新币；
+//   USDC ↔ USDT：直接走基础池 swap。
+//   经过基础池的一步与直接 swap 同样受 0 号池的动态费率与脱锚保护（oracle::guard）约束，并更新预言机。
+//   与 router 相同：先整条报价（注入时用模拟后的基础池刷新 LP 折算率）并做一次 min_dy 校验，再逐步成交，报价与执行确定一致。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    types::{Account, AmountE6, Coin},
+    state::{State, STATE, skey},
+    error::{Error, Result},
+    events::{self, Event},
+    icrc3::{self, Icrc3Value},
+    pools::{self, PoolId, PoolKind, PoolState, POOL0},
+    pause::{self, PauseOp},
+    positions, swap,
+};
+
+const E6: u128 = 1_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UnderlyingSwapArgs {
+    pub account: Account,
+    /// Metapool 编号
+    pub pool: PoolId,
+    pub token_in: Coin,
+    pub token_out: Coin,
+    pub dx_e6: AmountE6,
+    pub min_dy_e6: AmountE6,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnderlyingQuote {
+    pub pool: PoolId,
+    pub token_in: Coin,
+    pub token_out: Coin,
+    pub dx_e6: AmountE6,
+    pub dy_e6: AmountE6,
+    /// 中间经过的 0 号池 LP 数量（USDC ↔ USDT 时为 0）
+    pub lp_e6: AmountE6,
+    /// 基础池手续费（单币注入 / 赎回的不平衡手续费或 swap 手续费，以基础池的币计）
+    pub base_fee_e6: AmountE6,
+    /// 元池 swap 手续费（以元池这一步的输入币计）
+    pub meta_fee_e6: AmountE6,
+    pub price_e6: u128,
+}
+
+/// 成交路径：Out(k) 新币 → 基础池 coins[k]；In(k) 基础池 coins[k] → 新币；Base 基础池内 swap
+#[derive(Clone, Copy)]
+enum Leg { Out(usize), In(usize), Base }
+
+fn plan(st: &State, pool: PoolId, token_in: Coin, token_out: Coin) -> Result<(PoolState, PoolState, Leg)> {
+    let p = pools::load(st, pool)?;
+    if p.kind != PoolKind::Meta { return Err(Error::InvalidInput); }
+    let base = pools::load(st, POOL0)?;
+    let coin = p.coins[0];
+    let leg = match (base.index_of(token_in), base.index_of(token_out)) {
+        (None, Some(k)) if token_in == coin => Leg::Out(k),
+        (Some(k), None) if token_out == coin => Leg::In(k),
+        (Some(a), Some(b)) if a != b => Leg::Base,
+        _ => return Err(Error::unsupported(token_in, token_out)),
+    };
+    Ok((p, base, leg))
+}
+
+fn quote_legs(st: &State, pool: PoolId, token_in: Coin, token_out: Coin, dx: u128, now_sec: u64) -> Result<(UnderlyingQuote, Leg)> {
+    if dx == 0 { return Err(Error::InvalidInput); }
+    let (p, base, leg) = plan(st, pool, token_in, token_out)?;
+    let (dy, lp, base_fee, meta_fee) = match leg {
+        Leg::Base => {
+            let q = swap::quote_in(st, POOL0, token_in, token_out, dx, now_sec)?;
+            (q.dy_e6, 0, q.fee_e6, 0)
+        }
+        Leg::Out(k) => {
+            let q = swap::quote_in(st, pool, token_in, Coin::LP, dx, now_sec)?;
+            if q.dy_e6 == 0 { return Err(Error::InvalidInput); }
+            let (dy, fee) = positions::apply_remove_one(st, &mut base.clone(), q.dy_e6, k, now_sec)?;
+            (dy, q.dy_e6, fee, q.fee_e6)
+        }
+        Leg::In(k) => {
+            // 注入会改变基础池的 virtual price：用注入后的基础池给元池报价，与执行时 load 刷新的结果一致
+            let mut b = base.clone();
+            let (lp, fee) = positions::apply_add_one(st, &mut b, k, dx, now_sec)?;
+            let mut m = p.clone();
+            pools::refresh_meta(&mut m, &b);
+            let (dy, meta_fee, _) = swap::price_swap(st, &m, 1, m.reserves[1], m.reserves[0], lp, now_sec)?;
+            (dy, lp, fee, meta_fee)
+        }
+    };
+    if dy == 0 { return Err(Error::InvalidInput); }
+    let q = UnderlyingQuote {
+        pool, token_in, token_out, dx_e6: dx, dy_e6: dy, lp_e6: lp,
+        base_fee_e6: base_fee, meta_fee_e6: meta_fee, price_e6: dy.saturating_mul(E6) / dx,
+    };
+    Ok((q, leg))
+}
+
+pub fn quote_underlying(pool: PoolId, token_in: Coin, token_out: Coin, dx_e6: AmountE6) -> Result<UnderlyingQuote> {
+    STATE.with(|s| quote_legs(&s.borrow(), pool, token_in, token_out, dx_e6, crate::state::now()).map(|(q, _)| q))
+}
+
+/// 内账成交：调用者校验 → 可用额、基础池一步的暂停开关（注入看 add_liquidity、赎回看 remove_liquidity）与 min_dy 校验 → 逐步成交；
+/// 返回的报价即实际成交（留痕见 exchange_underlying）
+fn execute(st: &mut State, args: &UnderlyingSwapArgs, caller: Principal, now_sec: u64) -> Result<(UnderlyingQuote, Leg)> {
+    swap::require_owner(&args.account, caller)?;
+    let key = skey(&args.account.owner);
+    let lp_acct = base_account(&args.account);
+    let avail = pools::sub_balance(st, &key, args.token_in);
+    if args.dx_e6 > avail { return Err(Error::insufficient(args.token_in, avail, args.dx_e6)); }
+    let (q, leg) = quote_legs(st, args.pool, args.token_in, args.token_out, args.dx_e6, now_sec)?;
+    match leg {
+        Leg::Out(_) => pause::guard(st, PauseOp::RemoveLiquidity)?,
+        Leg::In(_) => pause::guard(st, PauseOp::AddLiquidity)?,
+        Leg::Base => {}
+    }
+    if q.dy_e6 < args.min_dy_e6 { return Err(Error::SlippageExceeded { min: args.min_dy_e6, got: q.dy_e6 }); }
+
+    // 报价已覆盖全部失败情形；若仍失败或结果不同说明状态不一致，panic 让整条消息回滚，避免停在半路
+    let fail = |step: &str, e: &dyn core::fmt::Debug| -> ! { panic!("exchange_underlying {step} failed after quote: {e:?}") };
+    match leg {
+        Leg::Base => {
+            swap::execute(st, POOL0, &key, (q.token_in, q.token_out), q.dx_e6, q.dy_e6, now_sec).unwrap_or_else(|e| fail("base swap", &e));
+        }
+        Leg::Out(k) => {
+            swap::execute(st, q.pool, &key, (q.token_in, Coin::LP), q.dx_e6, q.lp_e6, now_sec).unwrap_or_else(|e| fail("meta swap", &e));
+            let got = positions::remove_one_coin(st, POOL0, &lp_acct, q.lp_e6, k, now_sec).unwrap_or_else(|e| fail("remove_one_coin", &e));
+            if got.0 != q.dy_e6 { fail("remove_one_coin", &got); }
+        }
+        Leg::In(k) => {
+            let got = positions::add_one_coin(st, POOL0, &lp_acct, k, q.dx_e6, now_sec).unwrap_or_else(|e| fail("add_one_coin", &e));
+            if got.0 != q.lp_e6 { fail("add_one_coin", &got); }
+            swap::execute(st, q.pool, &key, (Coin::LP, q.token_out), q.lp_e6, q.dy_e6, now_sec).unwrap_or_else(|e| fail("meta swap", &e));
+        }
+    }
+    Ok((q, leg))
+}
+
+/// 执行并留痕：元池一步写 swap 区块（带 hop 序号），基础池单币注入 / 赎回写 LP 的 1mint / 1burn 区块与 AddLiq / RemoveLiq 事件
+pub fn exchange_underlying(args: UnderlyingSwapArgs, caller: Principal) -> Result<UnderlyingQuote> {
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let (q, leg) = execute(&mut st, &args, caller, crate::state::now())?;
+        let (acct, lp_acct) = (&args.account, &base_account(&args.account));
+        match leg {
+            Leg::Base => {
+                let fill = swap::Fill { pool: POOL0, token_in: q.token_in, token_out: q.token_out, dx: q.dx_e6, dy: q.dy_e6, fee: q.base_fee_e6 };
+                swap::log_fill(&mut st, acct, &fill, None);
+            }
+            Leg::Out(k) => {
+                let fill = swap::Fill { pool: q.pool, token_in: q.token_in, token_out: Coin::LP, dx: q.dx_e6, dy: q.lp_e6, fee: q.meta_fee_e6 };
+                swap::log_fill(&mut st, acct, &fill, Some(0));
+                log_base(&mut st, lp_acct, "1burn", q.lp_e6, k, q.dy_e6);
+            }
+            Leg::In(k) => {
+                log_base(&mut st, lp_acct, "1mint", q.lp_e6, k, q.dx_e6);
+                let fill = swap::Fill { pool: q.pool, token_in: Coin::LP, token_out: q.token_out, dx: q.lp_e6, dy: q.dy_e6, fee: q.meta_fee_e6 };
+                swap::log_fill(&mut st, acct, &fill, Some(1));
+            }
+        }
+        Ok(q)
+    })
+}
+
+/// 基础池一步的 LP 持有账户：元池一步按 skey 走调用者默认账户的内账，铸造 / 赎回的 LP 也落在默认账户
+fn base_account(acct: &Account) -> Account { Account { owner: acct.owner, subaccount: None } }
+
+/// 基础池单币注入 / 赎回的 LP 区块与事件（字段同 add_liquidity / remove_liquidity，另一侧为 0）
+fn log_base(st: &mut State, acct: &Account, btype: &str, shares: u128, k: usize, amount: u128) {
+    let mut two = [0u128; 2];
+    two[k] = amount;
+    let mint = btype == "1mint";
+    let block = icrc3::append(st, btype, ic_cdk::api::time(), vec![
+        (if mint { "to" } else { "from" }, Icrc3Value::account(acct)),
+        ("amt", Icrc3Value::nat(shares)),
+        ("usdc", Icrc3Value::nat(two[0])),
+        ("usdt", Icrc3Value::nat(two[1])),
+    ]);
+    let (who, sub, pool, ts) = (acct.owner.to_text(), acct.subaccount.clone(), pools::snapshot(st, POOL0), crate::state::now());
+    events::append(st, if mint {
+        Event::AddLiq { who, sub, usdc: two[0], usdt: two[1], shares, pool, pool_id: Some(POOL0), ledger_blocks: vec![], icrc3_block: Some(block), ts }
+    } else {
+        Event::RemoveLiq { who, sub, shares, usdc: two[0], usdt: two[1], pool, pool_id: Some(POOL0), ledger_blocks: vec![], icrc3_block: Some(block), ts }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pools::CreatePoolArgs;
+
+    const TS: u64 = 1_700_000_000;
+
+    fn acct(n: u8) -> Account { Account { owner: candid::Principal::from_slice(&[n; 29]), subaccount: None } }
+
+    /// BOB/LP 元池：LP 储备记在池子名下、份额总量守恒；新币 → USDC 与 USDC → 新币各一次调用完成，LP 不留在用户手里
+    #[test]
+    fn exchanges_underlying_through_base_pool_in_one_call() {
+        let mut st = State::default();
+        // 交易者带子账户：内账与基础池一步的 LP 都走其默认账户
+        let (lp, trader) = (acct(1), Account { subaccount: Some(vec![1; 32]), ..acct(2) });
+        let (lp_key, t_key) = (skey(&lp.owner), skey(&trader.owner));
+        st.pool.a_amp = 200;
+        st.pool.fee_bps = 4;
+        st.user_sub_usdc.insert(lp_key.clone(), 2_000_000_000);
+        st.user_sub_usdt.insert(lp_key.clone(), 1_000_000_000);
+        positions::add_liquidity(&mut st, POOL0, &lp, [1_000_000_000, 1_000_000_000], TS).unwrap();
+
+        let args = CreatePoolArgs { coins: vec![Coin::BOB, Coin::LP], a_amp: 200, fee_bps: 4, kind: Some(PoolKind::Meta), crypto: None, initial_price_e6: None };
+        assert!(pools::create(&mut st, &CreatePoolArgs { coins: vec![Coin::USDC, Coin::LP], ..args.clone() }, TS).is_err());
+        assert!(pools::create(&mut st, &CreatePoolArgs { coins: vec![Coin::LP, Coin::BOB], kind: None, ..args.clone() }, TS).is_err());
+        let id = pools::create(&mut st, &args, TS).unwrap();
+        st.user_sub_bob.insert(lp_key.clone(), 500_000_000);
+        positions::add_liquidity(&mut st, id, &lp, [500_000_000, 500_000_000], TS).unwrap();
+        assert_eq!(st.user_shares.get(&pools::meta_lp_key(id)), Some(&500_000_000));
+        assert_eq!(pools::sub_balance(&st, &lp_key, Coin::LP), 1_500_000_000);
+
+        // 新币 → USDC
+        st.user_sub_bob.insert(t_key.clone(), 10_000_000);
+        let q = quote_legs(&st, id, Coin::BOB, Coin::USDC, 10_000_000, TS).unwrap().0;
+        assert!(q.dy_e6 < 10_000_000 && q.dy_e6 > 9_980_000 && q.lp_e6 > 0, "{q:?}");
+        let mut a = UnderlyingSwapArgs { account: trader.clone(), pool: id, token_in: Coin::BOB, token_out: Coin::USDC, dx_e6: 10_000_000, min_dy_e6: q.dy_e6 + 1 };
+        let me = trader.owner;
+        assert!(matches!(execute(&mut st, &a, me, TS), Err(Error::SlippageExceeded { .. })));
+        a.min_dy_e6 = q.dy_e6;
+        // 只能动调用者自己的内账
+        assert!(matches!(execute(&mut st, &a, lp.owner, TS), Err(Error::Unauthorized(p)) if p == lp.owner));
+        assert_eq!(st.user_sub_bob.get(&t_key), Some(&10_000_000));
+        assert_eq!(execute(&mut st, &a, me, TS).unwrap().0, q);
+        assert_eq!(st.user_sub_usdc.get(&t_key), Some(&q.dy_e6));
+        assert_eq!(pools::sub_balance(&st, &t_key, Coin::LP), 0);
+
+        // USDC → 新币
+        let back = UnderlyingSwapArgs { token_in: Coin::USDC, token_out: Coin::BOB, dx_e6: q.dy_e6, min_dy_e6: 0, ..a };
+        let q2 = execute(&mut st, &back, me, TS).unwrap().0;
+        assert!(q2.dy_e6 < 10_000_000, "{q2:?}");
+        assert_eq!(st.user_sub_bob.get(&t_key), Some(&q2.dy_e6));
+        assert_eq!(pools::sub_balance(&st, &t_key, Coin::LP), 0);
+
+        // 元池的 LP 储备 + LP 手续费始终等于池子名下的份额；0 号池份额总量与各账户之和一致
+        let m = pools::load(&st, id).unwrap();
+        assert_eq!(m.reserves[1] + m.fee_vault[1], *st.user_shares.get(&pools::meta_lp_key(id)).unwrap());
+        assert_eq!(st.user_shares.values().sum::<u128>(), st.pool.total_shares);
+        assert!(matches!(quote_legs(&st, id, Coin::BOB, Coin::LP, 1, TS), Err(Error::InvalidInput)));
+
+        // 两次经过基础池产生的 0 号池手续费：池子名下份额不分，全部可由真实 LP 领走，金库不留无主余额
+        let meta_key = pools::meta_lp_key(id);
+        let vault = [st.fee_vault_usdc, st.fee_vault_usdt];
+        assert!(vault[0] + vault[1] > 0);
+        assert_eq!(positions::claimable(&st, POOL0, &meta_key), [0, 0]);
+        let got = positions::claim_fee(&mut st, POOL0, &lp).unwrap();
+        assert_eq!(pools::fee_owed(&st, POOL0, &meta_key), [0, 0]);
+        assert!(vault[0] - got[0] <= 1 && vault[1] - got[1] <= 1, "{vault:?} {got:?}");
+    }
+
+    /// 经基础池的一步受 0 号池的暂停开关与脱锚保护约束：不能绕开 swap 的限制
+    #[test]
+    fn base_pool_step_obeys_pause_flags_and_depeg_guard() {
+        let mut st = State::default();
+        let (lp, trader) = (acct(1), acct(2));
+        let (lp_key, t_key) = (skey(&lp.owner), skey(&trader.owner));
+        st.pool.a_amp = 10;
+        st.pool.fee_bps = 4;
+        st.user_sub_usdc.insert(lp_key.clone(), 1_000_000_000);
+        st.user_sub_usdt.insert(lp_key.clone(), 1_000_000_000);
+        positions::add_liquidity(&mut st, POOL0, &lp, [1_000_000_000, 1_000_000_000], TS).unwrap();
+        let args = CreatePoolArgs { coins: vec![Coin::BOB, Coin::LP], a_amp: 200, fee_bps: 4, kind: Some(PoolKind::Meta), crypto: None, initial_price_e6: None };
+        let id = pools::create(&mut st, &args, TS).unwrap();
+        st.user_sub_bob.insert(lp_key.clone(), 1_000_000_000);
+        positions::add_liquidity(&mut st, id, &lp, [1_000_000_000, 1_000_000_000], TS).unwrap();
+        st.user_sub_bob.insert(t_key.clone(), 1_000_000_000);
+        st.user_sub_usdt.insert(t_key.clone(), 1_000_000_000);
+        let me = trader.owner;
+        let swap = |token_in, token_out, dx_e6| UnderlyingSwapArgs { account: trader.clone(), pool: id, token_in, token_out, dx_e6, min_dy_e6: 0 };
+
+        // 注入一步看 add_liquidity 开关、赎回一步看 remove_liquidity 开关
+        pause::set(&mut st, &[PauseOp::AddLiquidity], true, "p".into(), "t".into(), TS);
+        assert!(matches!(execute(&mut st, &swap(Coin::USDT, Coin::BOB, 1_000_000), me, TS), Err(Error::Paused { op: PauseOp::AddLiquidity })));
+        assert!(execute(&mut st, &swap(Coin::BOB, Coin::USDC, 1_000_000), me, TS).is_ok());
+        pause::set(&mut st, &[PauseOp::AddLiquidity], false, "p".into(), "t".into(), TS);
+        pause::set(&mut st, &[PauseOp::RemoveLiquidity], true, "p".into(), "t".into(), TS);
+        assert!(matches!(execute(&mut st, &swap(Coin::BOB, Coin::USDC, 1_000_000), me, TS), Err(Error::Paused { op: PauseOp::RemoveLiquidity })));
+        pause::set(&mut st, &[PauseOp::RemoveLiquidity], false, "p".into(), "t".into(), TS);
+
+        // 大额单币赎回把 0 号池推离锚定 > 3%：与直接 swap 一样被拒，内账不动
+        let before = (st.user_sub_bob.get(&t_key).copied(), st.pool.reserve_usdc);
+        assert!(matches!(execute(&mut st, &swap(Coin::BOB, Coin::USDC, 600_000_000), me, TS), Err(Error::PriceImpactTooHigh)));
+        assert!(matches!(quote_legs(&st, id, Coin::USDT, Coin::BOB, 800_000_000, TS), Err(Error::PriceImpactTooHigh)));
+        assert_eq!((st.user_sub_bob.get(&t_key).copied(), st.pool.reserve_usdc), before);
+        // 小额成交更新 0 号池预言机
+        assert!(st.oracle.as_ref().is_some_and(|o| o.last_ts == TS));
+    }
+}
@@ -0,0 +1,194 @@
+// canisters/vaultpair/src/metrics.rs
+// Prometheus 文本格式指标（GET /metrics，见 http/mod.rs）。
+// 计数器只放堆内存、不随升级保存：升级后从 0 重新计数，Prometheus 的 rate()/increase() 能识别这种重置。
+// 指令数用 performance_counter(1)（整个调用上下文，跨 await 累计）；query 的状态改动会被丢弃，故只统计 update 端点。
+use candid::Principal;
+use std::{cell::RefCell, collections::BTreeMap, fmt::Debug, fmt::Write};
+
+use crate::{error::Error, state::State};
+
+/// 指令数直方图桶上界
+const BUCKETS: [u64; 10] = [
+    100_000, 1_000_000, 3_000_000, 10_000_000, 30_000_000,
+    100_000_000, 300_000_000, 1_000_000_000, 3_000_000_000, 10_000_000_000,
+];
+
+#[derive(Default, Clone, Debug)]
+struct Histogram {
+    counts: [u64; BUCKETS.len()],
+    count: u64,
+    sum: u128,
+}
+
+impl Histogram {
+    fn observe(&mut self, v: u64) {
+        if let Some(i) = BUCKETS.iter().position(|b| v <= *b) { self.counts[i] += 1; }
+        self.count += 1;
+        self.sum += v as u128;
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// (endpoint, outcome) → 次数；outcome = "ok" 或 Error 变体名
+    swaps: BTreeMap<(String, String), u64>,
+    /// (ledger, kind) → 次数；kind = LedgerErrorCode 变体名
+    ledger_errors: BTreeMap<(String, String), u64>,
+    instructions: BTreeMap<&'static str, Histogram>,
+}
+
+thread_local! {
+    static METRICS: RefCell<Metrics> = RefCell::new(Metrics::default());
+}
+
+/// 枚举值的变体名（取 Debug 输出中第一个 ' ' / '{' / '(' 之前的部分）
+pub fn kind_of<T: Debug>(v: &T) -> String {
+    let s = format!("{:?}", v);
+    s.split([' ', '{', '(']).next().unwrap_or_default().to_string()
+}
+
+/// err = None 表示成交
+pub fn swap_outcome(endpoint: &str, err: Option<&Error>) {
+    let outcome = err.map(kind_of).unwrap_or_else(|| "ok".to_string());
+    METRICS.with(|m| *m.borrow_mut().swaps.entry((endpoint.to_string(), outcome)).or_default() += 1);
+}
+
+pub fn ledger_error(ledger: Principal, kind: &str) {
+    METRICS.with(|m| *m.borrow_mut().ledger_errors.entry((ledger.to_text(), kind.to_string())).or_default() += 1);
+}
+
+pub fn observe_instructions(endpoint: &'static str, n: u64) {
+    METRICS.with(|m| m.borrow_mut().instructions.entry(endpoint).or_default().observe(n));
+}
+
+/// 端点入口 `let _m = metrics::measure("swap");`，返回时记录本次调用上下文的指令数。trap 时不记录（状态本就回滚）
+pub struct Measure(&'static str);
+
+pub fn measure(endpoint: &'static str) -> Measure { Measure(endpoint) }
+
+impl Drop for Measure {
+    fn drop(&mut self) {
+        observe_instructions(self.0, ic_cdk::api::performance_counter(1));
+    }
+}
+
+/// 运行时采样（仅 canister 内可取；测试环境为 0）
+#[derive(Default, Clone, Debug)]
+pub struct Runtime {
+    pub cycles: u128,
+    pub heap_bytes: u64,
+    pub stable_bytes: u64,
+}
+
+impl Runtime {
+    #[cfg(target_arch = "wasm32")]
+    pub fn sample() -> Self {
+        Runtime {
+            cycles: ic_cdk::api::canister_balance128(),
+            heap_bytes: (core::arch::wasm32::memory_size(0) as u64) * 65_536,
+            stable_bytes: ic_cdk::api::stable::stable64_size() * 65_536,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sample() -> Self { Runtime::default() }
+}
+
+/* ---------------- 渲染 ---------------- */
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, v: impl std::fmt::Display) {
+    header(out, name, "gauge", help);
+    let _ = writeln!(out, "{} {}", name, v);
+}
+
+/// 标签值转义：\ " 换行
+fn label(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn render(st: &State, rt: &Runtime) -> String {
+    let mut out = String::new();
+    let snap = crate::events::pool_snapshot(st);
+
+    header(&mut out, "vaultpair_reserve_e6", "gauge", "Pool reserve per token (e6).");
+    let _ = writeln!(out, "vaultpair_reserve_e6{{token=\"USDC\"}} {}", snap.reserve_usdc);
+    let _ = writeln!(out, "vaultpair_reserve_e6{{token=\"USDT\"}} {}", snap.reserve_usdt);
+    gauge(&mut out, "vaultpair_total_shares_e6", "Total LP shares (e6).", snap.total_shares);
+    gauge(&mut out, "vaultpair_virtual_price_e6", "D / total_shares (e6).", snap.virtual_price_e6);
+    header(&mut out, "vaultpair_fee_vault_e6", "gauge", "Unclaimed LP fees held in the fee vault (e6).");
+    let _ = writeln!(out, "vaultpair_fee_vault_e6{{token=\"USDC\"}} {}", st.fee_vault_usdc);
+    let _ = writeln!(out, "vaultpair_fee_vault_e6{{token=\"USDT\"}} {}", st.fee_vault_usdt);
+    gauge(&mut out, "vaultpair_cycles_balance", "Canister cycles balance.", rt.cycles);
+    gauge(&mut out, "vaultpair_heap_memory_bytes", "Wasm heap size.", rt.heap_bytes);
+    gauge(&mut out, "vaultpair_stable_memory_bytes", "Stable memory size.", rt.stable_bytes);
+
+    METRICS.with(|m| {
+        let m = m.borrow();
+        header(&mut out, "vaultpair_swaps_total", "counter", "Swap calls by endpoint and outcome.");
+        for ((ep, outcome), n) in &m.swaps {
+            let _ = writeln!(out, "vaultpair_swaps_total{{endpoint=\"{}\",outcome=\"{}\"}} {}", label(ep), label(outcome), n);
+        }
+        header(&mut out, "vaultpair_ledger_errors_total", "counter", "Failed ledger calls by ledger and error kind.");
+        for ((ledger, kind), n) in &m.ledger_errors {
+            let _ = writeln!(out, "vaultpair_ledger_errors_total{{ledger=\"{}\",kind=\"{}\"}} {}", label(ledger), label(kind), n);
+        }
+        header(&mut out, "vaultpair_instructions", "histogram", "Instructions per update call (whole call context).");
+        for (ep, h) in &m.instructions {
+            let mut acc = 0;
+            for (b, c) in BUCKETS.iter().zip(h.counts.iter()) {
+                acc += c;
+                let _ = writeln!(out, "vaultpair_instructions_bucket{{endpoint=\"{}\",le=\"{}\"}} {}", ep, b, acc);
+            }
+            let _ = writeln!(out, "vaultpair_instructions_bucket{{endpoint=\"{}\",le=\"+Inf\"}} {}", ep, h.count);
+            let _ = writeln!(out, "vaultpair_instructions_sum{{endpoint=\"{}\"}} {}", ep, h.sum);
+            let _ = writeln!(out, "vaultpair_instructions_count{{endpoint=\"{}\"}} {}", ep, h.count);
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LedgerErrorCode;
+
+    #[test]
+    fn renders_counters_and_cumulative_histogram() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 7;
+        st.fee_vault_usdt = 3;
+        swap_outcome("swap", None);
+        swap_outcome("swap", Some(&Error::SlippageExceeded { min: 2, got: 1 }));
+        swap_outcome("swap", Some(&Error::SlippageExceeded { min: 2, got: 1 }));
+        let ledger = Principal::from_slice(&[9; 29]);
+        ledger_error(ledger, &kind_of(&LedgerErrorCode::InsufficientFunds { balance: 0u8.into() }));
+        observe_instructions("swap", 50_000);
+        observe_instructions("swap", 2_000_000);
+        observe_instructions("swap", 20_000_000_000);
+
+        let txt = render(&st, &Runtime { cycles: 42, ..Default::default() });
+        for line in [
+            "vaultpair_reserve_e6{token=\"USDC\"} 7",
+            "vaultpair_fee_vault_e6{token=\"USDT\"} 3",
+            "vaultpair_cycles_balance 42",
+            "vaultpair_swaps_total{endpoint=\"swap\",outcome=\"ok\"} 1",
+            "vaultpair_swaps_total{endpoint=\"swap\",outcome=\"SlippageExceeded\"} 2",
+            &format!("vaultpair_ledger_errors_total{{ledger=\"{}\",kind=\"InsufficientFunds\"}} 1", ledger),
+            "vaultpair_instructions_bucket{endpoint=\"swap\",le=\"100000\"} 1",
+            "vaultpair_instructions_bucket{endpoint=\"swap\",le=\"1000000\"} 1",
+            "vaultpair_instructions_bucket{endpoint=\"swap\",le=\"3000000\"} 2",
+            "vaultpair_instructions_bucket{endpoint=\"swap\",le=\"10000000000\"} 2",
+            "vaultpair_instructions_bucket{endpoint=\"swap\",le=\"+Inf\"} 3",
+            "vaultpair_instructions_count{endpoint=\"swap\"} 3",
+        ] {
+            assert!(txt.lines().any(|l| l == line), "missing: {}", line);
+        }
+        assert_eq!(kind_of(&LedgerErrorCode::TooOld), "TooOld");
+        assert_eq!(kind_of(&Error::Internal("x".into())), "Internal");
+    }
+}
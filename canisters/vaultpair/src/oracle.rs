@@ -0,0 +1,187 @@
+// canisters/vaultpair/src/oracle.rs
+// 链上 EMA 价格预言机（参照 Curve 的 price_oracle / ma_exp_time），State.oracle 保存。价格口径：1 USDC 可换多少 USDT（e6）。
+//   每笔 swap 成交后 record_trade：先用上一笔成交后的现价 last_price 按时间衰减更新 EMA，再把本笔成交后的现价记为 last_price
+//     alpha = exp(-dt / ma_exp_time)（定点计算，见 math/exp.rs），ema = last_price * (1 - alpha) + ema * alpha
+//   脱锚保护 guard：成交后的现价偏离 EMA 超过 max_dev_ema_bps、或偏离 1.0 超过 max_dev_peg_bps，
+//     且该笔交易让偏离变大时，按 action 拒绝（PriceImpactTooHigh）或加收手续费；把价格拉回的交易不受限。
+//   EMA 与 guard 只跟踪 pool 0（USDC/USDT）；其它池不更新 EMA、也不做脱锚检查。
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    math::{exp, stableswap},
+    state::State,
+};
+
+const E6: u128 = 1_000_000;
+const E18: u128 = 1_000_000_000_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum DepegAction {
+    Reject,
+    /// 在 fee_bps 之上加收（bps）
+    ExtraFee(u32),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OracleConfig {
+    /// EMA 时间常数（秒）；866 ≈ 半衰期 10 分钟（Curve 默认）
+    pub ma_exp_time_sec: u64,
+    /// 0 = 不检查
+    pub max_dev_ema_bps: u32,
+    pub max_dev_peg_bps: u32,
+    pub action: DepegAction,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        Self { ma_exp_time_sec: 866, max_dev_ema_bps: 100, max_dev_peg_bps: 300, action: DepegAction::Reject }
+    }
+}
+
+/// 挂在 State.oracle 上；last_ts = 0 表示尚无成交
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OracleState {
+    pub config: OracleConfig,
+    pub ema_price_e6: u128,
+    pub last_price_e6: u128,
+    pub last_ts: u64,
+}
+
+/// get_price_oracle 的返回：ema_price_e6 已衰减到查询时刻
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PriceOracle {
+    pub ema_price_e6: u128,
+    pub last_price_e6: u128,
+    pub last_ts: u64,
+    pub config: OracleConfig,
+}
+
+fn oracle_mut(st: &mut State) -> &mut OracleState { st.oracle.get_or_insert_with(Default::default) }
+
+pub fn config(st: &State) -> OracleConfig { st.oracle.as_ref().map(|o| o.config.clone()).unwrap_or_default() }
+
+/* ---------------- 现价 ---------------- */
+
+/// 给定储备 (usdc, usdt) 的边际价格（不含手续费）；取储备的万分之一做报价
+pub fn spot_price_at(amp_scaled: u128, x: u128, y: u128) -> Option<u128> {
+    if x == 0 || y == 0 { return None; }
+    let dx = x / 10_000 + 1;
+    let (dy, _) = stableswap::quote_dx_to_dy(amp_scaled, x, y, dx, 0).ok()?;
+    Some(dy * E6 / dx)
+}
+
+pub fn spot_price_e6(st: &State) -> Option<u128> {
+    let amp = crate::swap::normalize_amp(st.pool.a_amp as u128);
+    spot_price_at(amp, st.pool.reserve_usdc, st.pool.reserve_usdt)
+}
+
+/* ---------------- EMA ---------------- */
+
+fn ema_at(o: &OracleState, now_sec: u64) -> u128 {
+    let a = exp::exp_decay_wad(now_sec.saturating_sub(o.last_ts), o.config.ma_exp_time_sec);
+    (o.last_price_e6 * (E18 - a) + o.ema_price_e6 * a) / E18
+}
+
+/// 当前 EMA；尚无成交时为 None
+pub fn ema_price_e6(st: &State, now_sec: u64) -> Option<u128> {
+    st.oracle.as_ref().filter(|o| o.last_ts > 0).map(|o| ema_at(o, now_sec))
+}
+
+pub fn price_oracle(st: &State, now_sec: u64) -> PriceOracle {
+    let o = st.oracle.clone().unwrap_or_default();
+    PriceOracle {
+        ema_price_e6: ema_price_e6(st, now_sec).unwrap_or(0),
+        last_price_e6: o.last_price_e6,
+        last_ts: o.last_ts,
+        config: o.config,
+    }
+}
+
+/// swap 更新储备之后调用
+pub fn record_trade(st: &mut State, now_sec: u64) {
+    let Some(spot) = spot_price_e6(st) else { return };
+    let o = oracle_mut(st);
+    o.ema_price_e6 = if o.last_ts == 0 { spot } else { ema_at(o, now_sec) };
+    o.last_price_e6 = spot;
+    o.last_ts = now_sec;
+}
+
+/* ---------------- 脱锚保护 ---------------- */
+
+fn dev_bps(price: u128, reference: u128) -> u128 {
+    if reference == 0 { return 0; }
+    price.abs_diff(reference) * 10_000 / reference
+}
+
+/// 按成交前后的储备检查（口径 (usdc, usdt)，after 为按正常费率成交后的储备）。
+/// 返回需加收的费率（FEE_DENOMINATOR 口径，0 = 不加收）；拒绝时返回 Err(PriceImpactTooHigh)
+pub fn guard(st: &State, amp_scaled: u128, before: (u128, u128), after: (u128, u128), now_sec: u64) -> Result<u128> {
+    let cfg = config(st);
+    let (Some(p0), Some(p1)) = (spot_price_at(amp_scaled, before.0, before.1), spot_price_at(amp_scaled, after.0, after.1)) else {
+        return Ok(0);
+    };
+    let mut refs = vec![(E6, cfg.max_dev_peg_bps)];
+    if let Some(ema) = ema_price_e6(st, now_sec) { refs.push((ema, cfg.max_dev_ema_bps)); }
+    let breach = refs.into_iter().any(|(r, limit)| {
+        limit > 0 && dev_bps(p1, r) > limit as u128 && dev_bps(p1, r) > dev_bps(p0, r)
+    });
+    match (breach, cfg.action) {
+        (false, _) => Ok(0),
+        (true, DepegAction::Reject) => Err(Error::PriceImpactTooHigh),
+        (true, DepegAction::ExtraFee(extra)) => Ok(stableswap::bps_to_fee(extra)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(x: u128, y: u128) -> State {
+        let mut st = State::default();
+        st.pool.a_amp = 100;
+        st.pool.reserve_usdc = x;
+        st.pool.reserve_usdt = y;
+        st
+    }
+
+    #[test]
+    fn ema_decays_toward_last_price() {
+        let mut st = pool(1_000_000_000, 1_000_000_000);
+        record_trade(&mut st, 1_000);
+        let p0 = st.oracle.as_ref().unwrap().last_price_e6;
+        assert_eq!(ema_price_e6(&st, 1_000), Some(p0));
+
+        st.pool.reserve_usdc = 1_500_000_000;
+        st.pool.reserve_usdt = 500_000_000;
+        record_trade(&mut st, 1_000);
+        let p1 = st.oracle.as_ref().unwrap().last_price_e6;
+        assert!(p1 < p0);
+        // 刚成交：EMA 仍在旧价；经过一个时间常数约走完 63%；很久之后趋近新价
+        assert_eq!(ema_price_e6(&st, 1_000), Some(p0));
+        let mid = ema_price_e6(&st, 1_866).unwrap();
+        let expect = p0 - (p0 - p1) * 632 / 1000;
+        assert!(mid.abs_diff(expect) <= (p0 - p1) / 100, "{mid} vs {expect}");
+        assert!(ema_price_e6(&st, 1_000_000).unwrap().abs_diff(p1) <= 1);
+    }
+
+    #[test]
+    fn guard_rejects_or_charges_only_when_worsening() {
+        let amp = crate::swap::normalize_amp(100);
+        let mut st = pool(1_000_000_000, 1_000_000_000);
+        record_trade(&mut st, 10);
+        let even = (1_000_000_000, 1_000_000_000);
+        // 小幅：不触发
+        assert!(matches!(guard(&st, amp, even, (1_001_000_000, 999_000_000), 10), Ok(0)));
+        // 大幅压低 USDC 价格
+        let dumped = (1_900_000_000, 150_000_000);
+        assert!(matches!(guard(&st, amp, even, dumped, 10), Err(Error::PriceImpactTooHigh)));
+
+        oracle_mut(&mut st).config.action = DepegAction::ExtraFee(50);
+        assert_eq!(guard(&st, amp, even, dumped, 10).unwrap(), stableswap::bps_to_fee(50));
+
+        // 已失衡的池子：把价格拉回的交易不受限
+        assert_eq!(guard(&st, amp, dumped, (1_600_000_000, 450_000_000), 10).unwrap(), 0);
+    }
+}
@@ -0,0 +1,217 @@
+// canisters/vaultpair/src/pause.rs
+// 紧急暂停：按操作分别开关（State.pause），各 update 入口先调 check。
+//   暂停角色（pausers）可开关 swap / add / claim / deposit；退出通道（remove_liquidity / withdraw）只有 controller 能关，
+//   pause_all 与自动暂停都不会动它们，保证暂停期间用户仍可撤资、提现。
+//   自动暂停：jobs 对账漂移告警（on_drift），或池子价格（EMA，见 oracle.rs）偏离 1:1 超过 depeg_bps。恢复须人工操作。
+// 暂停期间返回 Error::Paused { op }。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    events::Event,
+    state::{State, STATE},
+};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseOp { Swap, AddLiquidity, RemoveLiquidity, ClaimFees, Deposit, Withdraw }
+
+impl PauseOp {
+    pub const ALL: [PauseOp; 6] = [
+        PauseOp::Swap, PauseOp::AddLiquidity, PauseOp::RemoveLiquidity,
+        PauseOp::ClaimFees, PauseOp::Deposit, PauseOp::Withdraw,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PauseOp::Swap => "swap",
+            PauseOp::AddLiquidity => "add_liquidity",
+            PauseOp::RemoveLiquidity => "remove_liquidity",
+            PauseOp::ClaimFees => "claim_fees",
+            PauseOp::Deposit => "deposit",
+            PauseOp::Withdraw => "withdraw",
+        }
+    }
+
+    /// 退出通道：只有 controller 能关
+    pub fn is_exit(self) -> bool { matches!(self, PauseOp::RemoveLiquidity | PauseOp::Withdraw) }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PauseFlags {
+    pub swap: bool,
+    pub add_liquidity: bool,
+    pub remove_liquidity: bool,
+    pub claim_fees: bool,
+    pub deposit: bool,
+    pub withdraw: bool,
+}
+
+impl PauseFlags {
+    fn slot(&mut self, op: PauseOp) -> &mut bool {
+        match op {
+            PauseOp::Swap => &mut self.swap,
+            PauseOp::AddLiquidity => &mut self.add_liquidity,
+            PauseOp::RemoveLiquidity => &mut self.remove_liquidity,
+            PauseOp::ClaimFees => &mut self.claim_fees,
+            PauseOp::Deposit => &mut self.deposit,
+            PauseOp::Withdraw => &mut self.withdraw,
+        }
+    }
+
+    pub fn get(&self, op: PauseOp) -> bool {
+        match op {
+            PauseOp::Swap => self.swap,
+            PauseOp::AddLiquidity => self.add_liquidity,
+            PauseOp::RemoveLiquidity => self.remove_liquidity,
+            PauseOp::ClaimFees => self.claim_fees,
+            PauseOp::Deposit => self.deposit,
+            PauseOp::Withdraw => self.withdraw,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AutoPauseConfig {
+    /// 对账漂移告警时自动暂停
+    pub on_drift: bool,
+    /// 现价偏离 1:1 超过该值（bps）时自动暂停；0 = 关闭
+    pub depeg_bps: u32,
+}
+
+impl Default for AutoPauseConfig {
+    fn default() -> Self { Self { on_drift: true, depeg_bps: 200 } }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PauseChange { pub ts: u64, pub who: String, pub reason: String }
+
+/// 挂在 State.pause 上
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PauseState {
+    pub flags: PauseFlags,
+    pub pausers: Vec<Principal>,
+    pub auto: AutoPauseConfig,
+    pub last_change: Option<PauseChange>,
+}
+
+fn pause_mut(st: &mut State) -> &mut PauseState { st.pause.get_or_insert_with(Default::default) }
+
+pub fn state(st: &State) -> PauseState { st.pause.clone().unwrap_or_default() }
+
+pub fn guard(st: &State, op: PauseOp) -> Result<()> {
+    match st.pause.as_ref().is_some_and(|p| p.flags.get(op)) {
+        true => Err(Error::Paused { op }),
+        false => Ok(()),
+    }
+}
+
+/// update 入口处调用
+pub fn check(op: PauseOp) -> Result<()> { STATE.with(|s| guard(&s.borrow(), op)) }
+
+pub fn can_set(st: &State, who: Principal, is_controller: bool, op: PauseOp) -> bool {
+    is_controller || (!op.is_exit() && st.pause.as_ref().is_some_and(|p| p.pausers.contains(&who)))
+}
+
+/// 批量开关；有变化时返回 AdminAction 事件
+pub fn set(st: &mut State, ops: &[PauseOp], paused: bool, who: String, reason: String, ts: u64) -> Option<Event> {
+    let p = pause_mut(st);
+    let changed: Vec<&str> = ops.iter().copied()
+        .filter(|&op| std::mem::replace(p.flags.slot(op), paused) != paused)
+        .map(PauseOp::name)
+        .collect();
+    if changed.is_empty() { return None; }
+    p.last_change = Some(PauseChange { ts, who: who.clone(), reason: reason.clone() });
+    Some(Event::AdminAction {
+        who,
+        action: if paused { "pause" } else { "unpause" }.into(),
+        detail: format!("{} ({})", changed.join(","), reason),
+        ts,
+    })
+}
+
+/// 紧急暂停：除退出通道外全部关闭
+pub fn pause_all(st: &mut State, who: String, reason: String, ts: u64) -> Option<Event> {
+    let ops: Vec<PauseOp> = PauseOp::ALL.into_iter().filter(|op| !op.is_exit()).collect();
+    set(st, &ops, true, who, reason, ts)
+}
+
+/* ---------------- 自动暂停 ---------------- */
+
+pub fn depeg_bps(price_e6: u128) -> u128 { price_e6.abs_diff(1_000_000) / 100 }
+
+/// 现价偏离超限时自动暂停（swap 之后与定时任务中调用）
+pub fn check_depeg(st: &mut State, who: String, ts: u64) -> Option<Event> {
+    let limit = state(st).auto.depeg_bps;
+    if limit == 0 { return None; }
+    // 优先用 EMA（单笔交易难以操纵），尚无成交时用现价
+    let price = crate::oracle::ema_price_e6(st, ts).or_else(|| crate::oracle::spot_price_e6(st))?;
+    let dev = depeg_bps(price);
+    if dev <= limit as u128 { return None; }
+    pause_all(st, who, format!("auto: depeg price_e6={} deviation={}bps > {}bps", price, dev, limit), ts)
+}
+
+/// 漂移告警时自动暂停
+pub fn on_drift_alert(st: &mut State, detail: &str, who: String, ts: u64) -> Option<Event> {
+    if !state(st).auto.on_drift { return None; }
+    pause_all(st, who, format!("auto: drift {}", detail), ts)
+}
+
+/// swap 成交后检查脱锚
+pub fn after_swap() {
+    let who = ic_cdk::api::id().to_text();
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        if let Some(ev) = check_depeg(&mut st, who, crate::state::now()) {
+            crate::events::append(&mut st, ev);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pausers_cannot_close_exits_and_pause_all_keeps_them_open() {
+        let mut st = State::default();
+        let pauser = Principal::from_slice(&[7; 29]);
+        pause_mut(&mut st).pausers.push(pauser);
+        assert!(can_set(&st, pauser, false, PauseOp::Swap));
+        assert!(!can_set(&st, pauser, false, PauseOp::Withdraw));
+        assert!(!can_set(&st, Principal::anonymous(), false, PauseOp::Swap));
+        assert!(can_set(&st, Principal::anonymous(), true, PauseOp::RemoveLiquidity));
+
+        assert!(pause_all(&mut st, "p".into(), "test".into(), 1).is_some());
+        assert!(pause_all(&mut st, "p".into(), "test".into(), 2).is_none());
+        assert!(matches!(guard(&st, PauseOp::Swap), Err(Error::Paused { op: PauseOp::Swap })));
+        assert!(guard(&st, PauseOp::Deposit).is_err());
+        assert!(guard(&st, PauseOp::RemoveLiquidity).is_ok());
+        assert!(guard(&st, PauseOp::Withdraw).is_ok());
+
+        set(&mut st, &[PauseOp::Swap], false, "p".into(), "resume".into(), 3);
+        assert!(guard(&st, PauseOp::Swap).is_ok());
+        assert_eq!(st.pause.as_ref().unwrap().last_change.as_ref().unwrap().ts, 3);
+    }
+
+    #[test]
+    fn depeg_and_drift_trigger_auto_pause() {
+        let mut st = State::default();
+        st.pool.a_amp = 100;
+        st.pool.reserve_usdc = 1_000_000_000;
+        st.pool.reserve_usdt = 1_000_000_000;
+        assert!(depeg_bps(crate::oracle::spot_price_e6(&st).unwrap()) < 10);
+        assert!(check_depeg(&mut st, "c".into(), 1).is_none());
+
+        st.pool.reserve_usdc = 9_000_000_000;
+        assert!(check_depeg(&mut st, "c".into(), 2).is_some());
+        assert!(guard(&st, PauseOp::Swap).is_err() && guard(&st, PauseOp::Withdraw).is_ok());
+
+        let mut st = State::default();
+        pause_mut(&mut st).auto.on_drift = false;
+        assert!(on_drift_alert(&mut st, "x", "c".into(), 1).is_none());
+        pause_mut(&mut st).auto.on_drift = true;
+        assert!(on_drift_alert(&mut st, "x", "c".into(), 1).is_some());
+        assert!(guard(&st, PauseOp::AddLiquidity).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+// canisters/vaultpair/src/pools/mod.rs
+// 多池工厂：PoolId → 池子（代币对 / A / 费率 / 储备 / 份额账本 / 统计）。State.pools 保存 1 号及以后的池子。
+// 0 号池即原 USDC/USDT 池：数据仍在 State.pool、fee_*、user_shares / user_fee_* 等旧字段里（LP 代币、ICRC-3、认证数据、
+//   对账 / 偿付报告都指向它），load / store 在旧字段与 PoolState 之间转换，不搬数据，旧状态即视为已迁移。
+// 池子子账户："POOL" + id（u32 大端）+ 补零；0 号池与原 POOL_SUBACCOUNT 相同。
+// 下标约定：PoolState.coins[i] 对应 reserves[i] / fee_vault[i] / fee_growth_e18[i] 及份额账本的 fee_idx / fee_owed 第 i 项。
+// Metapool：coins = [新币, Coin::LP]。池子持有的 0 号池 LP 记在 user_shares[meta_lp_key(id)]（= 该池 LP 储备 + LP 手续费金库），
+//   用户的 LP 内账即其 user_shares；进出都经 pay_in / pay_out 走 lp_token::move_shares，份额总量不变。
+//   池子名下的份额不参与 0 号池手续费分配（没人能替池子领取）：accrue_fee 只按其余份额摊，settle_in 对池子 key 不记 owed。
+//   这些划转在 ICRC-3 日志里记为 1xfer，池子一侧的账户为该池的子账户 account(id)（见 log_lp_move / lp_balance_of），按日志重放可还原各账户 LP 余额。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    error::{Error, Result},
+    events::PoolSnapshot,
+    math::{
+        constant_product::ConstantProduct,
+        cryptoswap::{self, CryptoParams, CryptoState, CryptoSwap},
+        invariant::Invariant,
+        metapool::{self, MetaSwap},
+        stableswap::StableSwap,
+    },
+    state::State,
+    stats::RollingStats,
+    types::{Account, AmountE6, Coin, TokenId},
+};
+
+pub type PoolId = u32;
+pub const POOL0: PoolId = 0;
+
+const E18: u128 = 1_000_000_000_000_000_000;
+
+/// 池型：StableSwap 用于锚定币对（a_amp 生效），ConstantProduct（x*y=k）用于 ICP / BOB 等波动币对（忽略 a_amp），
+/// CryptoSwap（Curve v2，见 math::cryptoswap）用于有参考价的非锚定币对（忽略 a_amp / fee_bps，参数见 PoolState.crypto），
+/// Meta（见 math::metapool）为新稳定币对 0 号池 LP 份额（coins = [新币, LP]）
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolKind { StableSwap, ConstantProduct, CryptoSwap, Meta }
+
+/// CryptoSwap 池子的参数与内部价格状态
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CryptoPool {
+    pub params: CryptoParams,
+    pub state: CryptoState,
+}
+
+/// Metapool 的基础池与其 virtual price（1e18，load 时按基础池当前状态刷新）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MetaPool {
+    pub base: PoolId,
+    pub base_virtual_price: u128,
+}
+
+/// 池子参数与储备（e6）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolState {
+    pub id: PoolId,
+    pub kind: PoolKind,
+    pub coins: Vec<Coin>,
+    pub a_amp: u32,
+    pub fee_bps: u16,
+    pub offpeg_fee_multiplier: Option<u64>,
+    pub reserves: Vec<AmountE6>,
+    pub total_shares: AmountE6,
+    pub virtual_price_e6: u128,
+    /// 待领手续费（不计入储备）与每份额累计（1e18）
+    pub fee_vault: Vec<AmountE6>,
+    pub fee_growth_e18: Vec<u128>,
+    pub created_ts: u64,
+    /// 仅 CryptoSwap 池子
+    pub crypto: Option<CryptoPool>,
+    /// 仅 Metapool；Option 以兼容旧状态
+    pub meta: Option<MetaPool>,
+    /// 仅 0 号池：元池名下的份额（load 时汇总，不参与手续费分配、不落盘）
+    pub fee_exempt_shares: Option<AmountE6>,
+}
+
+impl PoolState {
+    /// token 在本池中的下标
+    pub fn index_of(&self, t: Coin) -> Option<usize> { self.coins.iter().position(|&c| c == t) }
+
+    /// (输入下标, 输出下标)；不是本池的代币对返回 None
+    pub fn orient(&self, token_in: Coin, token_out: Coin) -> Option<(usize, usize)> {
+        match (self.index_of(token_in), self.index_of(token_out)) {
+            (Some(i), Some(j)) if i != j => Some((i, j)),
+            _ => None,
+        }
+    }
+
+    /// 本池的池型数学（swap / positions 经此调用）；i 为输入下标（CryptoSwap 两个方向不对称，mint / burn 传 0）
+    pub fn invariant(&self, i: usize) -> Box<dyn Invariant> {
+        match self.kind {
+            PoolKind::StableSwap => Box::new(StableSwap { amp_scaled: crate::swap::normalize_amp(self.a_amp as u128) }),
+            PoolKind::ConstantProduct => Box::new(ConstantProduct),
+            PoolKind::CryptoSwap => {
+                let c = self.crypto.clone().unwrap_or_else(|| CryptoPool { params: CryptoParams::default(), state: CryptoState::new(E18, 0) });
+                Box::new(CryptoSwap { params: c.params, price_scale: c.state.price_scale, i })
+            }
+            PoolKind::Meta => {
+                let vp = self.meta.as_ref().map_or(metapool::RATE_PRECISION, |m| m.base_virtual_price);
+                let amp_scaled = crate::swap::normalize_amp(self.a_amp as u128);
+                Box::new(MetaSwap { amp_scaled, rates: metapool::meta_rates(vp), i })
+            }
+        }
+    }
+}
+
+/// 份额账本（1 号及以后的池子；0 号池用 State.user_shares / user_fee_*）。key 同 user_shares（principal text）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ShareBook {
+    pub shares: BTreeMap<String, u128>,
+    pub fee_idx: BTreeMap<String, Vec<u128>>,
+    pub fee_owed: BTreeMap<String, Vec<u128>>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PoolEntry {
+    pub pool: PoolState,
+    pub book: ShareBook,
+    pub stats: RollingStats,
+}
+
+/// 挂在 State.pools 上
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PoolRegistry {
+    pub next_id: PoolId,
+    pub pools: BTreeMap<PoolId, PoolEntry>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CreatePoolArgs {
+    pub coins: Vec<Coin>,
+    pub a_amp: u32,
+    pub fee_bps: u16,
+    /// None = StableSwap
+    pub kind: Option<PoolKind>,
+    /// CryptoSwap：参数（None = CryptoParams::default()）与初始价格（coins[1] 以 coins[0] 计价，e6，必填）
+    pub crypto: Option<CryptoParams>,
+    pub initial_price_e6: Option<u128>,
+}
+
+fn registry_mut(st: &mut State) -> &mut PoolRegistry {
+    st.pools.get_or_insert_with(|| PoolRegistry { next_id: 1, pools: BTreeMap::new() })
+}
+
+fn entry(st: &State, id: PoolId) -> Result<&PoolEntry> {
+    st.pools.as_ref().and_then(|r| r.pools.get(&id)).ok_or(Error::PoolNotFound(id))
+}
+
+fn entry_mut(st: &mut State, id: PoolId) -> Result<&mut PoolEntry> {
+    st.pools.as_mut().and_then(|r| r.pools.get_mut(&id)).ok_or(Error::PoolNotFound(id))
+}
+
+/* ---------------- 池子读写 ---------------- */
+
+pub fn load(st: &State, id: PoolId) -> Result<PoolState> {
+    if id != POOL0 {
+        let mut p = entry(st, id)?.pool.clone();
+        if p.meta.is_some() { refresh_meta(&mut p, &load(st, POOL0)?); }
+        return Ok(p);
+    }
+    let p = &st.pool;
+    Ok(PoolState {
+        id: POOL0,
+        kind: PoolKind::StableSwap,
+        coins: vec![Coin::USDC, Coin::USDT],
+        a_amp: p.a_amp,
+        fee_bps: p.fee_bps,
+        offpeg_fee_multiplier: p.offpeg_fee_multiplier,
+        reserves: vec![p.reserve_usdc, p.reserve_usdt],
+        total_shares: p.total_shares,
+        virtual_price_e6: p.virtual_price_e6,
+        fee_vault: vec![st.fee_vault_usdc, st.fee_vault_usdt],
+        fee_growth_e18: vec![st.fee_growth_usdc_e18, st.fee_growth_usdt_e18],
+        created_ts: 0,
+        crypto: None,
+        meta: None,
+        fee_exempt_shares: Some(pool_held_shares(st)),
+    })
+}
+
+/// 按基础池状态刷新 Metapool 的 LP 折算率（exchange_underlying 报价时用模拟后的基础池）
+pub(crate) fn refresh_meta(p: &mut PoolState, base: &PoolState) {
+    let Some(m) = p.meta.as_mut() else { return };
+    let amp = crate::swap::normalize_amp(base.a_amp as u128);
+    if let Ok(vp) = metapool::base_virtual_price(amp, [base.reserves[0], base.reserves[1]], base.total_shares) {
+        m.base_virtual_price = vp;
+    }
+}
+
+/// 写回储备 / 份额总量 / 手续费金库与指数（参数由 admin 接口单独改）
+pub fn store(st: &mut State, p: &PoolState) -> Result<()> {
+    if p.id != POOL0 {
+        let e = entry_mut(st, p.id)?;
+        e.pool = p.clone();
+        return Ok(());
+    }
+    st.pool.reserve_usdc = p.reserves[0];
+    st.pool.reserve_usdt = p.reserves[1];
+    st.pool.total_shares = p.total_shares;
+    st.fee_vault_usdc = p.fee_vault[0];
+    st.fee_vault_usdt = p.fee_vault[1];
+    st.fee_growth_usdc_e18 = p.fee_growth_e18[0];
+    st.fee_growth_usdt_e18 = p.fee_growth_e18[1];
+    Ok(())
+}
+
+/// CryptoSwap 池子储备 / 份额变动后（尚未 store）调用：更新内部价格、利润与 virtual_price，必要时 repeg（见 cryptoswap::tweak_price）。
+/// virtual_price 下降时以 Error::Math(VirtualPriceDecreased) 拒绝本次变动；其余池型不做事
+pub(crate) fn tweak_crypto(p: &mut PoolState, trade_price: Option<u128>, now_sec: u64) -> Result<()> {
+    let Some(c) = p.crypto.as_mut() else { return Ok(()) };
+    let balances = [p.reserves[0], p.reserves[1]];
+    cryptoswap::tweak_price(&c.params, &mut c.state, balances, p.total_shares, trade_price, now_sec)?;
+    p.virtual_price_e6 = c.state.virtual_price / (E18 / 1_000_000);
+    Ok(())
+}
+
+/// 全部池子（0 号在前）
+pub fn list(st: &State) -> Vec<PoolState> {
+    let mut out = vec![load(st, POOL0).expect("pool 0")];
+    if let Some(r) = st.pools.as_ref() { out.extend(r.pools.values().map(|e| e.pool.clone())); }
+    out
+}
+
+/// 某代币对所在的池子
+pub fn find(st: &State, a: Coin, b: Coin) -> Option<PoolId> {
+    list(st).into_iter().find(|p| p.orient(a, b).is_some()).map(|p| p.id)
+}
+
+pub fn create(st: &mut State, args: &CreatePoolArgs, ts: u64) -> Result<PoolId> {
+    let [a, b] = args.coins[..] else { return Err(Error::InvalidInput) };
+    let kind = args.kind.unwrap_or(PoolKind::StableSwap);
+    let needs_amp = matches!(kind, PoolKind::StableSwap | PoolKind::Meta);
+    if a == b || (needs_amp && args.a_amp == 0) || args.fee_bps >= 10_000 { return Err(Error::InvalidInput); }
+    // LP 只能作 Metapool 的第二个币，Metapool 的新币不能是基础池的币
+    let base_coin = |t: Coin| matches!(t, Coin::USDC | Coin::USDT | Coin::LP);
+    let meta_ok = kind == PoolKind::Meta && b == Coin::LP && !base_coin(a);
+    if (kind == PoolKind::Meta || a == Coin::LP || b == Coin::LP) && !meta_ok { return Err(Error::InvalidInput); }
+    if let Some(id) = find(st, a, b) { return Err(Error::PoolExists(id)); }
+    let crypto = match kind {
+        PoolKind::CryptoSwap => {
+            let params = args.crypto.clone().unwrap_or_default();
+            cryptoswap::check_params(&params)?;
+            let price = args.initial_price_e6.filter(|&p| p > 0).ok_or(Error::InvalidInput)?;
+            Some(CryptoPool { params, state: CryptoState::new(price.saturating_mul(E18 / 1_000_000), ts) })
+        }
+        _ => None,
+    };
+    let meta = (kind == PoolKind::Meta).then_some(MetaPool { base: POOL0, base_virtual_price: metapool::RATE_PRECISION });
+    let reg = registry_mut(st);
+    let id = reg.next_id;
+    reg.next_id += 1;
+    reg.pools.insert(id, PoolEntry {
+        pool: PoolState {
+            id,
+            kind,
+            coins: vec![a, b],
+            a_amp: args.a_amp,
+            fee_bps: args.fee_bps,
+            offpeg_fee_multiplier: None,
+            reserves: vec![0, 0],
+            total_shares: 0,
+            virtual_price_e6: 1_000_000,
+            fee_vault: vec![0, 0],
+            fee_growth_e18: vec![0, 0],
+            created_ts: ts,
+            crypto,
+            meta,
+            fee_exempt_shares: None,
+        },
+        book: ShareBook::default(),
+        stats: RollingStats::with_now(ts),
+    });
+    Ok(id)
+}
+
+pub fn snapshot(st: &State, id: PoolId) -> PoolSnapshot {
+    if id == POOL0 { return crate::events::pool_snapshot(st); }
+    let Ok(p) = load(st, id) else { return PoolSnapshot::default() };
+    PoolSnapshot { reserve_usdc: p.reserves[0], reserve_usdt: p.reserves[1], total_shares: p.total_shares, virtual_price_e6: p.virtual_price_e6 }
+}
+
+pub fn stats_mut(st: &mut State, id: PoolId) -> Result<&mut RollingStats> {
+    if id == POOL0 { return Ok(&mut st.stats); }
+    entry_mut(st, id).map(|e| &mut e.stats)
+}
+
+/* ---------------- 份额账本 ---------------- */
+
+pub fn shares(st: &State, id: PoolId, who: &str) -> u128 {
+    if id == POOL0 { return *st.user_shares.get(who).unwrap_or(&0); }
+    entry(st, id).ok().and_then(|e| e.book.shares.get(who).copied()).unwrap_or(0)
+}
+
+pub fn set_shares(st: &mut State, id: PoolId, who: &str, v: u128) -> Result<()> {
+    let book = if id == POOL0 { &mut st.user_shares } else { &mut entry_mut(st, id)?.book.shares };
+    book.insert(who.to_string(), v);
+    Ok(())
+}
+
+pub fn fee_idx(st: &State, id: PoolId, who: &str) -> [u128; 2] {
+    if id == POOL0 {
+        return [*st.user_fee_idx_usdc.get(who).unwrap_or(&0), *st.user_fee_idx_usdt.get(who).unwrap_or(&0)];
+    }
+    pair(entry(st, id).ok().and_then(|e| e.book.fee_idx.get(who)))
+}
+
+pub fn fee_owed(st: &State, id: PoolId, who: &str) -> [u128; 2] {
+    if id == POOL0 {
+        return [*st.user_fee_owed_usdc.get(who).unwrap_or(&0), *st.user_fee_owed_usdt.get(who).unwrap_or(&0)];
+    }
+    pair(entry(st, id).ok().and_then(|e| e.book.fee_owed.get(who)))
+}
+
+pub fn set_fee_idx(st: &mut State, id: PoolId, who: &str, v: [u128; 2]) -> Result<()> {
+    if id == POOL0 {
+        st.user_fee_idx_usdc.insert(who.to_string(), v[0]);
+        st.user_fee_idx_usdt.insert(who.to_string(), v[1]);
+    } else {
+        entry_mut(st, id)?.book.fee_idx.insert(who.to_string(), v.to_vec());
+    }
+    Ok(())
+}
+
+pub fn set_fee_owed(st: &mut State, id: PoolId, who: &str, v: [u128; 2]) -> Result<()> {
+    if id == POOL0 {
+        // 与原 claim_fee 一致：领完即删
+        for (book, x) in [(&mut st.user_fee_owed_usdc, v[0]), (&mut st.user_fee_owed_usdt, v[1])] {
+            if x == 0 { book.remove(who); } else { book.insert(who.to_string(), x); }
+        }
+    } else {
+        let book = &mut entry_mut(st, id)?.book.fee_owed;
+        if v == [0, 0] { book.remove(who); } else { book.insert(who.to_string(), v.to_vec()); }
+    }
+    Ok(())
+}
+
+fn pair(v: Option<&Vec<u128>>) -> [u128; 2] {
+    let v = v.map(Vec::as_slice).unwrap_or(&[]);
+    [v.first().copied().unwrap_or(0), v.get(1).copied().unwrap_or(0)]
+}
+
+/* ---------------- 用户内账（main 子账户，key = skey） ---------------- */
+
+/// LP 没有 user_sub_* 账本（余额即 user_shares），返回 None
+/// 某币全部用户内账之和（LP 为 0）
+pub fn sub_total(st: &State, t: Coin) -> u128 { sub_book(st, t).map_or(0, |b| b.values().sum()) }
+
+fn sub_book(st: &State, t: Coin) -> Option<&BTreeMap<String, u128>> {
+    match t {
+        Coin::USDC => Some(&st.user_sub_usdc),
+        Coin::USDT => Some(&st.user_sub_usdt),
+        Coin::ICP => Some(&st.user_sub_icp),
+        Coin::BOB => Some(&st.user_sub_bob),
+        Coin::LP => None,
+    }
+}
+
+fn sub_book_mut(st: &mut State, t: Coin) -> Option<&mut BTreeMap<String, u128>> {
+    match t {
+        Coin::USDC => Some(&mut st.user_sub_usdc),
+        Coin::USDT => Some(&mut st.user_sub_usdt),
+        Coin::ICP => Some(&mut st.user_sub_icp),
+        Coin::BOB => Some(&mut st.user_sub_bob),
+        Coin::LP => None,
+    }
+}
+
+/// skey（"principal#0"）对应的 user_shares key（principal text）
+fn lp_holder(key: &str) -> &str { key.split('#').next().unwrap_or(key) }
+
+/// Metapool 持有的 0 号池 LP 在 user_shares 中的 key（含 '#'，不会与 principal / 子账户 key 冲突；不是 ICRC-1 账户）
+pub fn meta_lp_key(id: PoolId) -> String { format!("pool#{}", id) }
+
+/// 是否为池子名下的份额 key（见 meta_lp_key）
+pub fn is_pool_key(key: &str) -> bool { key.starts_with("pool#") }
+
+/// 各池子名下的份额之和：池子 key 在 user_shares 中连续（"pool#" ≤ key < "pool$"），只扫这一段
+fn pool_held_shares(st: &State) -> u128 {
+    use std::ops::Bound::{Excluded, Included};
+    st.user_shares.range::<str, _>((Included("pool#"), Excluded("pool$"))).map(|(_, v)| *v).sum()
+}
+
+pub fn sub_balance(st: &State, key: &str, t: Coin) -> u128 {
+    match sub_book(st, t) {
+        Some(book) => *book.get(key).unwrap_or(&0),
+        None => *st.user_shares.get(lp_holder(key)).unwrap_or(&0),
+    }
+}
+
+/// 用户付给池子（调用方已校验可用额）：普通币扣内账；LP 从用户份额转到池子名下
+pub(crate) fn pay_in(st: &mut State, pool: PoolId, key: &str, t: Coin, amount: u128) {
+    if amount == 0 { return; }
+    match sub_book_mut(st, t) {
+        Some(book) => { let e = book.entry(key.to_string()).or_default(); *e = e.saturating_sub(amount); }
+        None => crate::lp_token::move_shares(st, lp_holder(key), &meta_lp_key(pool), amount),
+    }
+}
+
+/// 池子付给用户：普通币加内账；LP 从池子名下转回用户份额
+pub(crate) fn pay_out(st: &mut State, pool: PoolId, key: &str, t: Coin, amount: u128) {
+    if amount == 0 { return; }
+    match sub_book_mut(st, t) {
+        Some(book) => { let e = book.entry(key.to_string()).or_default(); *e = e.saturating_add(amount); }
+        None => crate::lp_token::move_shares(st, &meta_lp_key(pool), lp_holder(key), amount),
+    }
+}
+
+/// pay_in / pay_out 中 LP 一侧的划转写 ICRC-3 `1xfer` 区块：to_pool 时 用户默认账户 → account(pool)，否则反向。
+/// 需要 canister id 与时间，由 api / swap::log_fill 在内账落账后调用（之后需 icrc3::commit）
+pub(crate) fn log_lp_move(st: &mut State, pool: PoolId, owner: Principal, amount: u128, to_pool: bool) {
+    if amount == 0 { return; }
+    let (user, pool) = (Account { owner, subaccount: None }, account(pool));
+    let (from, to) = if to_pool { (user, pool) } else { (pool, user) };
+    crate::icrc3::append(st, "1xfer", ic_cdk::api::time(), vec![
+        ("from", crate::icrc3::Icrc3Value::account(&from)),
+        ("to", crate::icrc3::Icrc3Value::account(&to)),
+        ("amt", crate::icrc3::Icrc3Value::nat(amount)),
+    ]);
+}
+
+/// 按 coins 顺序的数量里 LP 一侧的划转（见 log_lp_move）
+pub(crate) fn log_lp_moves(st: &mut State, p: &PoolState, owner: Principal, amounts: [u128; 2], to_pool: bool) {
+    for (&t, amount) in p.coins.iter().zip(amounts) {
+        if t == Coin::LP { log_lp_move(st, p.id, owner, amount, to_pool); }
+    }
+}
+
+/// LP 余额（icrc1_balance_of）：本 canister 名下、子账户为某个池子的账户即该池名下的份额（meta_lp_key），其余同 lp_token::balance_of
+pub fn lp_balance_of(st: &State, acct: &Account, canister: Principal) -> u128 {
+    let pool = acct.subaccount.as_deref().filter(|_| acct.owner == canister).and_then(|sub| pool_of_subaccount(st, sub));
+    match pool.filter(|&id| id != POOL0) {
+        Some(id) => *st.user_shares.get(&meta_lp_key(id)).unwrap_or(&0),
+        None => crate::lp_token::balance_of(st, acct),
+    }
+}
+
+/* ---------------- 账户与账本 ---------------- */
+
+pub fn subaccount(id: PoolId) -> [u8; 32] {
+    let mut sub = crate::icrc::POOL_SUBACCOUNT;
+    sub[4..8].copy_from_slice(&id.to_be_bytes());
+    sub
+}
+
+pub fn account(id: PoolId) -> Account {
+    Account { owner: crate::icrc::canister_principal(), subaccount: Some(subaccount(id).to_vec()) }
+}
+
+/// 池子子账户 → 池子（含 0 号池）
+pub fn pool_of_subaccount(st: &State, sub: &[u8]) -> Option<PoolId> {
+    let mut ids = std::iter::once(POOL0).chain(st.pools.iter().flat_map(|r| r.pools.keys().copied()));
+    ids.find(|&id| sub == &subaccount(id)[..])
+}
+
+/// 链上托管在池子子账户里的币：(池子, 代币, 账本, 精度)，0 号池在前、按 coins 顺序；LP 只在内账，不列出
+pub fn onchain_coins(st: &State) -> Vec<(PoolId, TokenId, Principal, u8)> {
+    list(st).iter().flat_map(|p| p.coins.iter().filter_map(|&c| {
+        let t = c.token()?;
+        let (ledger, dec) = token_ledger(st, c)?;
+        Some((p.id, t, ledger, dec))
+    })).collect()
+}
+
+/// 某池的全部份额持有者 key（对账汇总可领手续费用）
+pub fn holders(st: &State, id: PoolId) -> Vec<String> {
+    if id == POOL0 { return st.user_shares.keys().cloned().collect(); }
+    entry(st, id).map(|e| e.book.shares.keys().cloned().collect()).unwrap_or_default()
+}
+
+/// 代币账本与精度：USDC / USDT 取 set_token_meta 的值，ICP / BOB 为主网常量（8 位），LP 为本 canister
+pub fn token_ledger(st: &State, t: Coin) -> Option<(Principal, u8)> {
+    match t {
+        Coin::USDC => Some((st.ckusdc?, st.dec_usdc?)),
+        Coin::USDT => Some((st.ckusdt?, st.dec_usdt?)),
+        Coin::ICP | Coin::BOB => Some((crate::ledger_book::token_principal(&t.token()?), 8)),
+        Coin::LP => Some((crate::icrc::canister_principal(), crate::lp_token::LP_DECIMALS)),
+    }
+}
+
+/// 需要链上转账的数量（按 coins 顺序）：LP 在内账里经 pay_in / pay_out 划转，置 0
+pub fn onchain_amounts(p: &PoolState, amounts: [u128; 2]) -> [u128; 2] {
+    let mut out = amounts;
+    for (a, &t) in out.iter_mut().zip(&p.coins) {
+        if t == Coin::LP { *a = 0; }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T: u64 = 1_700_000_000;
+
+    #[test]
+    fn pool0_maps_legacy_fields_and_new_pools_get_own_books() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 10;
+        st.user_shares.insert("a".into(), 5);
+        let mut p0 = load(&st, POOL0).unwrap();
+        assert_eq!((p0.reserves.clone(), p0.coins.clone()), (vec![10, 0], vec![Coin::USDC, Coin::USDT]));
+        p0.reserves[1] = 7;
+        p0.fee_vault[0] = 3;
+        store(&mut st, &p0).unwrap();
+        assert_eq!((st.pool.reserve_usdt, st.fee_vault_usdc), (7, 3));
+        assert_eq!(subaccount(POOL0), crate::icrc::POOL_SUBACCOUNT);
+
+        let args = CreatePoolArgs { coins: vec![Coin::ICP, Coin::USDC], a_amp: 50, fee_bps: 30, kind: None, crypto: None, initial_price_e6: None };
+        let id = create(&mut st, &args, T).unwrap();
+        assert_eq!(id, 1);
+        assert_ne!(subaccount(id), subaccount(POOL0));
+        // 重复的代币对（任意顺序）与相同代币都拒绝
+        let dup = CreatePoolArgs { coins: vec![Coin::USDC, Coin::ICP], ..args.clone() };
+        assert!(matches!(create(&mut st, &dup, T), Err(Error::PoolExists(1))));
+        assert!(matches!(create(&mut st, &CreatePoolArgs { coins: vec![Coin::USDT, Coin::USDC], ..args.clone() }, T), Err(Error::PoolExists(POOL0))));
+        assert!(create(&mut st, &CreatePoolArgs { coins: vec![Coin::BOB, Coin::BOB], ..args }, T).is_err());
+
+        set_shares(&mut st, id, "a", 9).unwrap();
+        assert_eq!((shares(&st, id, "a"), shares(&st, POOL0, "a")), (9, 5));
+        assert_eq!(find(&st, Coin::USDC, Coin::ICP), Some(id));
+        assert!(matches!(load(&st, 9), Err(Error::PoolNotFound(9))));
+
+        // LP 余额查询：本 canister 名下的池子子账户读该池名下的份额，其余照常
+        let me = Principal::from_slice(&[9; 29]);
+        st.user_shares.insert(meta_lp_key(id), 4);
+        let pool_acct = Account { owner: me, subaccount: Some(subaccount(id).to_vec()) };
+        assert_eq!(lp_balance_of(&st, &pool_acct, me), 4);
+        st.user_shares.insert(crate::lp_token::account_key(&pool_acct), 1);
+        assert_eq!(lp_balance_of(&st, &pool_acct, Principal::anonymous()), 1);
+
+        // 0 号池的免分成份额只计池子 key
+        for k in ["pool", "pool$", "pool#12", "poolx"] { st.user_shares.insert(k.into(), 100); }
+        assert_eq!(pool_held_shares(&st), 104);
+        assert_eq!(load(&st, POOL0).unwrap().fee_exempt_shares, Some(104));
+    }
+}
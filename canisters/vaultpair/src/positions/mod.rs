@@ -1,275 +1,379 @@
 // canisters/vaultpair/src/positions/mod.rs
+// LP 仓位：按池子（PoolId，见 pools）铸造 / 赎回份额、结算与领取手续费；只动内账（user_sub_*），链上转账由 api 负责。
+// 份额按 lp_token::account_key(account) 记账：非默认子账户持有的份额（如经 icrc1_transfer 转入）可在该账户下赎回与领取手续费。
 use crate::{
-    types::{Account, AmountE6, TokenId},
+    types::{Account, AmountE6},
     state::{STATE, skey, State},
     error::{Result, Error},
-    events::Event,
+    pools::{self, PoolId, PoolKind, PoolState, POOL0},
+    lp_token::account_key,
+    math::{rates::{self, Rounding, BOOK_RATE}, stableswap::{self, FEE_DENOMINATOR}},
+    oracle,
 };
 
 /// fee 累计指数放大系数（避免精度损失）
-const ACC_E18: u128 = 1_000_000_000_000_000_000;
-
-#[inline]
-fn owner_key_txt(p: &candid::Principal) -> String { p.to_text() }
-
-/// 结算某用户的“未领取手续费”到 owed_*，并把该用户的 fee 指数更新到当前全局值
-fn settle_user_fee(st: &mut State, who: &str, shares: u128) {
-    // USDC
-    let g_u = st.fee_growth_usdc_e18;
-    let i_u = *st.user_fee_idx_usdc.get(who).unwrap_or(&0);
-    let du  = g_u.saturating_sub(i_u);
-    if du > 0 && shares > 0 {
-        let add = shares.saturating_mul(du) / ACC_E18;
-        if add > 0 {
-            let e = st.user_fee_owed_usdc.entry(who.to_string()).or_default();
-            *e = e.saturating_add(add);
-        }
-    }
-    st.user_fee_idx_usdc.insert(who.to_string(), g_u);
-
-    // USDT
-    let g_v = st.fee_growth_usdt_e18;
-    let i_v = *st.user_fee_idx_usdt.get(who).unwrap_or(&0);
-    let dv  = g_v.saturating_sub(i_v);
-    if dv > 0 && shares > 0 {
-        let add = shares.saturating_mul(dv) / ACC_E18;
-        if add > 0 {
-            let e = st.user_fee_owed_usdt.entry(who.to_string()).or_default();
-            *e = e.saturating_add(add);
-        }
+pub(crate) const ACC_E18: u128 = 1_000_000_000_000_000_000;
+
+/// 结算某用户在 p 中的“未领取手续费”到 owed，并把该用户的 fee 指数更新到当前全局值；池子名下的份额不分手续费，只更新指数
+pub(crate) fn settle_in(st: &mut State, p: &PoolState, who: &str, shares: u128) -> Result<()> {
+    let shares = if pools::is_pool_key(who) { 0 } else { shares };
+    let idx = pools::fee_idx(st, p.id, who);
+    let mut owed = pools::fee_owed(st, p.id, who);
+    let growth = [p.fee_growth_e18[0], p.fee_growth_e18[1]];
+    for i in 0..2 {
+        let d = growth[i].saturating_sub(idx[i]);
+        if d > 0 && shares > 0 { owed[i] = owed[i].saturating_add(shares.saturating_mul(d) / ACC_E18); }
     }
-    st.user_fee_idx_usdt.insert(who.to_string(), g_v);
+    pools::set_fee_owed(st, p.id, who, owed)?;
+    pools::set_fee_idx(st, p.id, who, growth)
 }
 
-/// 在 swap 时调用：把“本次输入侧手续费”累加到 fee_vault，并更新增长指数
-pub fn accrue_swap_fee(token_in: TokenId, fee_e6: AmountE6) {
+/// 0 号池（LP 代币转账时用）
+pub(crate) fn settle_user_fee(st: &mut State, who: &str, shares: u128) {
+    let p = pools::load(st, POOL0).expect("pool 0");
+    let _ = settle_in(st, &p, who, shares);
+}
+
+/// swap 时调用：把“本次输入侧手续费”累加到 fee_vault，并按可分配份额（扣除元池名下的）更新增长指数（没有 LP 时先攒在 vault，不分配）
+pub(crate) fn accrue_fee(p: &mut PoolState, i: usize, fee_e6: AmountE6) {
     if fee_e6 == 0 { return; }
-    STATE.with(|cell| {
-        let mut st = cell.borrow_mut();
-        let ts = st.pool.total_shares;
-        // 没有 LP：费用先攒在 vault，指数不增长（不分配）
-        if ts == 0 {
-            match token_in {
-                TokenId::USDC => st.fee_vault_usdc = st.fee_vault_usdc.saturating_add(fee_e6),
-                TokenId::USDT => st.fee_vault_usdt = st.fee_vault_usdt.saturating_add(fee_e6),
-                _ => {}
-            }
-            return;
-        }
-        match token_in {
-            TokenId::USDC => {
-                st.fee_vault_usdc = st.fee_vault_usdc.saturating_add(fee_e6);
-                let inc = fee_e6.saturating_mul(ACC_E18) / ts;
-                st.fee_growth_usdc_e18 = st.fee_growth_usdc_e18.saturating_add(inc);
-            }
-            TokenId::USDT => {
-                st.fee_vault_usdt = st.fee_vault_usdt.saturating_add(fee_e6);
-                let inc = fee_e6.saturating_mul(ACC_E18) / ts;
-                st.fee_growth_usdt_e18 = st.fee_growth_usdt_e18.saturating_add(inc);
-            }
-            _ => {}
-        }
-    });
+    p.fee_vault[i] = p.fee_vault[i].saturating_add(fee_e6);
+    let eligible = p.total_shares.saturating_sub(p.fee_exempt_shares.unwrap_or(0));
+    if let Some(inc) = fee_e6.saturating_mul(ACC_E18).checked_div(eligible) {
+        p.fee_growth_e18[i] = p.fee_growth_e18[i].saturating_add(inc);
+    }
 }
 
 /// 读取“我的 LP 份额”（单位 e6 原值）
-pub fn get_user_position(account: Account) -> u128 {
-    let who_txt = owner_key_txt(&account.owner);
-    STATE.with(|s| {
-        let s = s.borrow();
-        *s.user_shares.get(&who_txt).unwrap_or(&0)
-    })
+pub fn get_user_position(account: Account, pool: PoolId) -> u128 {
+    let who_txt = account_key(&account);
+    STATE.with(|s| pools::shares(&s.borrow(), pool, &who_txt))
 }
 
-/// 添加流动性：按当前池比例铸造 shares（首次可按加权/几何平均）
-pub fn add_liquidity(account: Account, usdc: AmountE6, usdt: AmountE6) -> Result<u128> {
-    if usdc == 0 && usdt == 0 { return Err("amount=0".into()); }
+/// 按池型计算 (实际入池量, 铸造份额)（见 Invariant::mint）：StableSwap 首次建池 shares 与 TVL 对齐（两侧之和），
+/// 恒定乘积取 sqrt(a0 * a1)，之后取 min(s0, s1)，多的一侧不扣；CryptoSwap 可不按比例注入（收不平衡手续费）
+pub fn mint_amounts(p: &PoolState, req: [u128; 2]) -> ([u128; 2], u128) {
+    p.invariant(0)
+        .mint([p.reserves[0], p.reserves[1]], p.total_shares, req)
+        .unwrap_or(([0, 0], 0))
+}
 
-    let who_txt = owner_key_txt(&account.owner);
+/// 添加流动性：扣 main 子账户内账 amounts（按 coins 顺序），按池型铸造 shares
+pub fn add_liquidity(st: &mut State, pool: PoolId, account: &Account, amounts: [AmountE6; 2], now_sec: u64) -> Result<u128> {
+    if amounts == [0, 0] { return Err(Error::InvalidInput); }
+    let who_txt = account_key(account);
     let s_key   = skey(&account.owner);
+    let mut p = pools::load(st, pool)?;
 
-    STATE.with(|cell| {
-        let mut st = cell.borrow_mut();
-
-        // 可用额校验（main 子账户，内账）
-        let avail_u = *st.user_sub_usdc.get(&s_key).unwrap_or(&0);
-        let avail_t = *st.user_sub_usdt.get(&s_key).unwrap_or(&0);
-        if usdc > avail_u { return Err("insufficient USDC in subaccount".into()); }
-        if usdt > avail_t { return Err("insufficient USDT in subaccount".into()); }
-
-        let ru = st.pool.reserve_usdc;
-        let rv = st.pool.reserve_usdt;
-        let ts = st.pool.total_shares;
-
-        // 首次建池：shares 与 TVL 对齐（u+v）
-        let minted = if ts == 0 || ru == 0 || rv == 0 {
-            let base = usdc.saturating_add(usdt);
-            if base == 0 { return Err("minted=0".into()); }
-            base
-        } else {
-            let m1 = usdc.saturating_mul(ts) / ru;
-            let m2 = usdt.saturating_mul(ts) / rv;
-            let m  = m1.min(m2);
-            if m == 0 { return Err("minted=0".into()); }
-            m
-        };
+    // 可用额校验（main 子账户，内账）
+    for (i, &t) in p.coins.iter().enumerate() {
+        let have = pools::sub_balance(st, &s_key, t);
+        if amounts[i] > have { return Err(Error::insufficient(t, have, amounts[i])); }
+    }
 
-        // 扣子账户可用额（按实际扣款）
-        st.user_sub_usdc.insert(s_key.clone(), avail_u.saturating_sub(usdc));
-        st.user_sub_usdt.insert(s_key.clone(), avail_t.saturating_sub(usdt));
+    let (_, minted) = mint_amounts(&p, amounts);
+    if minted == 0 { return Err(Error::InvalidInput); }
 
-        // 更新池储备与总份额
-        st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_add(usdc);
-        st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_add(usdt);
-        st.pool.total_shares = st.pool.total_shares.saturating_add(minted);
+    // 更新池储备与总份额（CryptoSwap 同时更新内部价格，可能拒绝）
+    for (r, a) in p.reserves.iter_mut().zip(amounts) { *r = r.saturating_add(a); }
+    p.total_shares = p.total_shares.saturating_add(minted);
+    pools::tweak_crypto(&mut p, None, now_sec)?;
 
-        // 增加用户份额
-        let cur = *st.user_shares.get(&who_txt).unwrap_or(&0);
-        st.user_shares.insert(who_txt, cur.saturating_add(minted));
+    // 先按变动前份额结算手续费，新份额不分享历史手续费
+    let my = pools::shares(st, pool, &who_txt);
+    settle_in(st, &p, &who_txt, my)?;
 
-        Ok(minted)
-    })
+    // 扣子账户可用额（按实际扣款；Metapool 的 LP 划到池子名下）
+    for (&t, a) in p.coins.clone().iter().zip(amounts) { pools::pay_in(st, pool, &s_key, t, a); }
+    pools::store(st, &p)?;
+    pools::set_shares(st, pool, &who_txt, my.saturating_add(minted))?;
+    Ok(minted)
 }
 
-/// 按份额比例赎回，资产回到 main 子账户（内账）
-pub fn remove_liquidity(account: Account, shares: u128) -> Result<(AmountE6, AmountE6)> {
-    if shares == 0 { return Err("shares=0".into()); }
-
-    let who_txt = owner_key_txt(&account.owner);
+/// 按份额比例赎回，资产回到 main 子账户（内账）；返回按 coins 顺序的数量
+pub fn remove_liquidity(st: &mut State, pool: PoolId, account: &Account, shares: u128) -> Result<[AmountE6; 2]> {
+    if shares == 0 { return Err(Error::InvalidInput); }
+    let who_txt = account_key(account);
     let s_key   = skey(&account.owner);
+    let mut p = pools::load(st, pool)?;
 
-    STATE.with(|cell| {
-        let mut st = cell.borrow_mut();
+    let my = pools::shares(st, pool, &who_txt);
+    if shares > my { return Err(Error::InsufficientShares { have: my, need: shares }); }
+    let ts = p.total_shares;
+    if ts == 0 { return Err(Error::InsufficientLiquidity); }
 
-        let my = *st.user_shares.get(&who_txt).unwrap_or(&0);
-        if shares > my { return Err("insufficient shares".into()); }
+    settle_in(st, &p, &who_txt, my)?;
 
-        let ts = st.pool.total_shares;
-        if ts == 0 { return Err("pool shares=0".into()); }
+    let out = p.invariant(0).burn([p.reserves[0], p.reserves[1]], ts, shares)?;
+    for (i, &t) in p.coins.clone().iter().enumerate() {
+        p.reserves[i] = p.reserves[i].saturating_sub(out[i]);
+        pools::pay_out(st, pool, &s_key, t, out[i]);
+    }
+    p.total_shares = ts.saturating_sub(shares);
+    pools::store(st, &p)?;
+    pools::set_shares(st, pool, &who_txt, my.saturating_sub(shares))?;
+    Ok(out)
+}
 
-        let ru = st.pool.reserve_usdc;
-        let rv = st.pool.reserve_usdt;
+/* ---------------- 单币注入 / 赎回（StableSwap 池，exchange_underlying 经此进出基础池） ---------------- */
 
-        let amt_usdc = shares.saturating_mul(ru) / ts;
-        let amt_usdt = shares.saturating_mul(rv) / ts;
+/// (amp, 基础费率 + 加收, offpeg_fee_multiplier)
+fn stable_params(p: &PoolState, extra: u128) -> Result<(u128, u128, u128)> {
+    if p.kind != PoolKind::StableSwap { return Err(Error::InvalidInput); }
+    let fee = stableswap::bps_to_fee(p.fee_bps as u32).saturating_add(extra).min(FEE_DENOMINATOR);
+    Ok((crate::swap::normalize_amp(p.a_amp as u128), fee, crate::swap::offpeg_multiplier(p)))
+}
 
-        // 更新池储备与总份额
-        st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_sub(amt_usdc);
-        st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_sub(amt_usdt);
-        st.pool.total_shares = st.pool.total_shares.saturating_sub(shares);
+/// e6 记账金额 → 1e18 计算口径（见 math::rates）
+fn xp(v: [u128; 2]) -> Result<[u128; 2]> { Ok([rates::to_xp(v[0], BOOK_RATE)?, rates::to_xp(v[1], BOOK_RATE)?]) }
 
-        // 回收份额
-        st.user_shares.insert(who_txt.clone(), my.saturating_sub(shares));
+/// 0 号池单币注入 / 赎回的脱锚保护（同 swap::price_swap）：按不加收时的结果检查成交前后储备，返回需加收的费率；其它池为 0
+fn depeg_extra(st: &State, p: &PoolState, now_sec: u64, apply: impl Fn(&mut PoolState, u128) -> Result<(u128, u128)>) -> Result<u128> {
+    if p.id != POOL0 { return Ok(0); }
+    let mut after = p.clone();
+    apply(&mut after, 0)?;
+    let amp = crate::swap::normalize_amp(p.a_amp as u128);
+    oracle::guard(st, amp, (p.reserves[0], p.reserves[1]), (after.reserves[0], after.reserves[1]), now_sec)
+}
 
-        // 资产退回到 main 子账户（内账）
-        let cur_u = *st.user_sub_usdc.get(&s_key).unwrap_or(&0);
-        let cur_t = *st.user_sub_usdt.get(&s_key).unwrap_or(&0);
-        st.user_sub_usdc.insert(s_key.clone(), cur_u.saturating_add(amt_usdc));
-        st.user_sub_usdt.insert(s_key,         cur_t.saturating_add(amt_usdt));
+/// 在 p 上单币注入 coins[i]（不落账）：不平衡手续费（按 offpeg_fee_multiplier 随失衡加费，0 号池另加脱锚保护的加收）从储备
+/// 转入 fee_vault 分给既有 LP，再加总份额；脱锚保护拒绝时返回 PriceImpactTooHigh。返回 (铸造份额, i 侧手续费)
+pub(crate) fn apply_add_one(st: &State, p: &mut PoolState, i: usize, amount: u128, now_sec: u64) -> Result<(u128, u128)> {
+    let extra = depeg_extra(st, p, now_sec, |q, x| add_one_at(q, i, amount, x))?;
+    add_one_at(p, i, amount, extra)
+}
 
-        Ok((amt_usdc, amt_usdt))
-    })
+fn add_one_at(p: &mut PoolState, i: usize, amount: u128, extra: u128) -> Result<(u128, u128)> {
+    let (amp, fee, mult) = stable_params(p, extra)?;
+    let mut amounts = [0u128; 2];
+    *amounts.get_mut(i).ok_or(Error::InvalidInput)? = amount;
+    let (minted, fees_xp) = stableswap::calc_token_amount(amp, fee, mult, xp([p.reserves[0], p.reserves[1]])?, p.total_shares, xp(amounts)?)?;
+    if minted == 0 { return Err(Error::InvalidInput); }
+    // 手续费向上取整：储备少记、金库多记，都归 LP
+    let fees = fees_xp.map(|f| rates::from_xp(f, BOOK_RATE, Rounding::Up));
+    for k in 0..2 {
+        p.reserves[k] = p.reserves[k].saturating_add(amounts[k]).saturating_sub(fees[k]);
+        accrue_fee(p, k, fees[k]);
+    }
+    p.total_shares = p.total_shares.saturating_add(minted);
+    Ok((minted, fees[i]))
 }
 
-/// 领取手续费：把 owed_* 打入 main 子账户，并记录事件
-pub fn claim_fee(account: Account) -> Result<(u128, u128)> {
-    let who_txt = owner_key_txt(&account.owner);
+/// 在 p 上单币赎回 shares 为 coins[i]（不落账）：手续费（同 apply_add_one）在份额减少后记入 fee_vault，只分给留下的 LP。返回 (dy, 手续费)
+pub(crate) fn apply_remove_one(st: &State, p: &mut PoolState, shares: u128, i: usize, now_sec: u64) -> Result<(u128, u128)> {
+    let extra = depeg_extra(st, p, now_sec, |q, x| remove_one_at(q, shares, i, x))?;
+    remove_one_at(p, shares, i, extra)
+}
+
+fn remove_one_at(p: &mut PoolState, shares: u128, i: usize, extra: u128) -> Result<(u128, u128)> {
+    let (amp, fee, mult) = stable_params(p, extra)?;
+    let (dy_xp, fee_xp) = stableswap::calc_withdraw_one_coin(amp, fee, mult, xp([p.reserves[0], p.reserves[1]])?, p.total_shares, shares, i)?;
+    // 实付向下取整；手续费取整后与实付之和不超过不收费时应得
+    let dy = rates::from_xp(dy_xp, BOOK_RATE, Rounding::Down);
+    let fee_out = rates::from_xp(dy_xp.saturating_add(fee_xp), BOOK_RATE, Rounding::Down).saturating_sub(dy);
+    if dy == 0 { return Err(Error::InvalidInput); }
+    p.reserves[i] = p.reserves[i].saturating_sub(dy.saturating_add(fee_out));
+    p.total_shares = p.total_shares.saturating_sub(shares);
+    accrue_fee(p, i, fee_out);
+    Ok((dy, fee_out))
+}
+
+/// 单币添加流动性：扣 main 子账户内账 coins[i]，返回 (铸造份额, 手续费)；0 号池同 swap 更新预言机
+pub fn add_one_coin(st: &mut State, pool: PoolId, account: &Account, i: usize, amount: AmountE6, now_sec: u64) -> Result<(u128, u128)> {
+    if amount == 0 { return Err(Error::InvalidInput); }
+    let who_txt = account_key(account);
     let s_key   = skey(&account.owner);
+    let mut p = pools::load(st, pool)?;
+    let t = *p.coins.get(i).ok_or(Error::InvalidInput)?;
+    let have = pools::sub_balance(st, &s_key, t);
+    if amount > have { return Err(Error::insufficient(t, have, amount)); }
+
+    let (minted, fee) = apply_add_one(st, &mut p, i, amount, now_sec)?;
+    // 旧份额结算到本次手续费之后的指数，新份额不分享本次手续费
+    let my = pools::shares(st, pool, &who_txt);
+    settle_in(st, &p, &who_txt, my)?;
+
+    pools::pay_in(st, pool, &s_key, t, amount);
+    pools::store(st, &p)?;
+    pools::set_shares(st, pool, &who_txt, my.saturating_add(minted))?;
+    if pool == POOL0 { oracle::record_trade(st, now_sec); }
+    Ok((minted, fee))
+}
 
-    STATE.with(|cell| {
-        let mut st = cell.borrow_mut();
+/// 单币移除流动性：赎回 shares 为 coins[i] 进 main 子账户内账，返回 (数量, 手续费)；0 号池同 swap 更新预言机
+pub fn remove_one_coin(st: &mut State, pool: PoolId, account: &Account, shares: u128, i: usize, now_sec: u64) -> Result<(u128, u128)> {
+    if shares == 0 { return Err(Error::InvalidInput); }
+    let who_txt = account_key(account);
+    let s_key   = skey(&account.owner);
+    let mut p = pools::load(st, pool)?;
+    let t = *p.coins.get(i).ok_or(Error::InvalidInput)?;
+    let my = pools::shares(st, pool, &who_txt);
+    if shares > my { return Err(Error::InsufficientShares { have: my, need: shares }); }
+
+    settle_in(st, &p, &who_txt, my)?;
+    let (dy, fee) = apply_remove_one(st, &mut p, shares, i, now_sec)?;
+    // 本次手续费只分给留下的份额
+    let rest = my.saturating_sub(shares);
+    settle_in(st, &p, &who_txt, rest)?;
+    pools::store(st, &p)?;
+    pools::set_shares(st, pool, &who_txt, rest)?;
+    pools::pay_out(st, pool, &s_key, t, dy);
+    if pool == POOL0 { oracle::record_trade(st, now_sec); }
+    Ok((dy, fee))
+}
 
-        // 领取前先按当前 shares 再结算一次
-        let my = *st.user_shares.get(&who_txt).unwrap_or(&0);
-        settle_user_fee(&mut st, &who_txt, my);
+/// 领取手续费：把 owed 从金库打入 main 子账户（ClaimFee 事件由 api::claim_fee 在链上转账成功后记录）
+pub fn claim_fee(st: &mut State, pool: PoolId, account: &Account) -> Result<[AmountE6; 2]> {
+    let who_txt = account_key(account);
+    let s_key   = skey(&account.owner);
+    let mut p = pools::load(st, pool)?;
 
-        // 取出 owed
-        let owe_u = st.user_fee_owed_usdc.remove(&who_txt).unwrap_or(0);
-        let owe_v = st.user_fee_owed_usdt.remove(&who_txt).unwrap_or(0);
-        if owe_u == 0 && owe_v == 0 {
-            return Ok((0, 0));
-        }
+    // 领取前先按当前 shares 再结算一次
+    let my = pools::shares(st, pool, &who_txt);
+    settle_in(st, &p, &who_txt, my)?;
+    let owed = pools::fee_owed(st, pool, &who_txt);
+    if owed == [0, 0] { return Ok(owed); }
+    pools::set_fee_owed(st, pool, &who_txt, [0, 0])?;
 
+    for (i, &t) in p.coins.clone().iter().enumerate() {
         // 从 fee_vault 扣减（防御用 saturating）
-        st.fee_vault_usdc = st.fee_vault_usdc.saturating_sub(owe_u);
-        st.fee_vault_usdt = st.fee_vault_usdt.saturating_sub(owe_v);
-
-        // 打进 main 子账户（内账）
-        let su = *st.user_sub_usdc.get(&s_key).unwrap_or(&0);
-        let sv = *st.user_sub_usdt.get(&s_key).unwrap_or(&0);
-        st.user_sub_usdc.insert(s_key.clone(), su.saturating_add(owe_u));
-        st.user_sub_usdt.insert(s_key.clone(), sv.saturating_add(owe_v));
-
-        // 记事件
-        let now = ic_cdk::api::time();
-        if owe_u > 0 {
-            st.events.push(Event::Withdraw {
-                ts: now, who: who_txt.clone(), token: TokenId::USDC, amount: owe_u,
-            });
-        }
-        if owe_v > 0 {
-            st.events.push(Event::Withdraw {
-                ts: now, who: who_txt.clone(), token: TokenId::USDT, amount: owe_v,
-            });
-        }
-
-        Ok((owe_u, owe_v))
-    })
+        p.fee_vault[i] = p.fee_vault[i].saturating_sub(owed[i]);
+        pools::pay_out(st, pool, &s_key, t, owed[i]);
+    }
+    pools::store(st, &p)?;
+    Ok(owed)
 }
 
 /// 只读：预览“此刻可领取手续费”（不落账）
-pub fn preview_claim_fee(account: Account) -> Result<(u128, u128)> {
-    let who_txt = owner_key_txt(&account.owner);
-
-    STATE.with(|cell| {
-        let st = cell.borrow();
-
-        let shares = *st.user_shares.get(&who_txt).unwrap_or(&0);
-        if shares == 0 { return Ok((0, 0)); }
+pub fn preview_claim_fee(account: Account, pool: PoolId) -> [u128; 2] {
+    let who_txt = account_key(&account);
+    STATE.with(|cell| claimable(&cell.borrow(), pool, &who_txt))
+}
 
-        let idx_u_user = *st.user_fee_idx_usdc.get(&who_txt).unwrap_or(&0);
-        let idx_v_user = *st.user_fee_idx_usdt.get(&who_txt).unwrap_or(&0);
+/// 某份额账户（0 号池 key 见 lp_token::account_key）此刻可领的手续费（按 coins 顺序）
+pub fn claimable(st: &State, pool: PoolId, who_txt: &str) -> [u128; 2] {
+    let shares = pools::shares(st, pool, who_txt);
+    let Ok(p) = pools::load(st, pool) else { return [0, 0] };
+    if shares == 0 || pools::is_pool_key(who_txt) { return [0, 0]; }
+    let idx = pools::fee_idx(st, pool, who_txt);
+    let owed = pools::fee_owed(st, pool, who_txt);
+    // 当前可领 = 已累积未领 + shares * (全局增长 - 我上次记录) / 1e18，并与金库余额取 min（防御）
+    let can = |i: usize| {
+        let add = shares.saturating_mul(p.fee_growth_e18[i].saturating_sub(idx[i])) / ACC_E18;
+        owed[i].saturating_add(add).min(p.fee_vault[i])
+    };
+    [can(0), can(1)]
+}
 
-        let owed_u_user = *st.user_fee_owed_usdc.get(&who_txt).unwrap_or(&0);
-        let owed_v_user = *st.user_fee_owed_usdt.get(&who_txt).unwrap_or(&0);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pools::CreatePoolArgs, types::Coin};
+
+    fn acct(n: u8) -> Account { Account { owner: candid::Principal::from_slice(&[n; 29]), subaccount: None } }
+
+    /// 后加入的 LP 不分享加入前的手续费；份额与手续费在各池子间独立
+    #[test]
+    fn late_lp_does_not_share_earlier_fees_and_pools_are_isolated() {
+        let mut st = State::default();
+        let id = pools::create(&mut st, &CreatePoolArgs { coins: vec![Coin::ICP, Coin::USDC], a_amp: 50, fee_bps: 30, kind: None, crypto: None, initial_price_e6: None }, 1_700_000_000).unwrap();
+        for n in [1, 2] {
+            st.user_sub_icp.insert(skey(&acct(n).owner), 1_000);
+            st.user_sub_usdc.insert(skey(&acct(n).owner), 1_000);
+        }
+        assert_eq!(add_liquidity(&mut st, id, &acct(1), [500, 500], 1_700_000_000).unwrap(), 1_000);
 
-        // 额外可领 = shares * (全局增长 - 我上次记录) / 1e18
-        let add_u = if st.fee_growth_usdc_e18 > idx_u_user {
-            shares.saturating_mul(st.fee_growth_usdc_e18 - idx_u_user) / ACC_E18
-        } else { 0 };
-        let add_v = if st.fee_growth_usdt_e18 > idx_v_user {
-            shares.saturating_mul(st.fee_growth_usdt_e18 - idx_v_user) / ACC_E18
-        } else { 0 };
+        let mut p = pools::load(&st, id).unwrap();
+        accrue_fee(&mut p, 0, 100);
+        pools::store(&mut st, &p).unwrap();
 
-        // 当前可领 = 已累积未领 + 额外可领，并与金库余额取 min（防御）
-        let can_u = (owed_u_user.saturating_add(add_u)).min(st.fee_vault_usdc);
-        let can_v = (owed_v_user.saturating_add(add_v)).min(st.fee_vault_usdt);
+        assert_eq!(add_liquidity(&mut st, id, &acct(2), [500, 500], 1_700_000_000).unwrap(), 1_000);
+        assert_eq!(claimable(&st, id, &acct(2).owner.to_text()), [0, 0]);
+        assert_eq!(claim_fee(&mut st, id, &acct(1)).unwrap(), [100, 0]);
+        assert_eq!(st.user_sub_icp.get(&skey(&acct(1).owner)), Some(&600));
+        assert_eq!((st.pool.total_shares, st.user_shares.len()), (0, 0));
 
-        Ok((can_u, can_v))
-    })
-}
+        assert_eq!(remove_liquidity(&mut st, id, &acct(2), 1_000).unwrap(), [500, 500]);
+        assert!(matches!(remove_liquidity(&mut st, POOL0, &acct(1), 1), Err(Error::InsufficientShares { .. })));
+    }
 
-/// 等比缩放所有用户 shares 到 new_total_e6；
-/// **分母采用“当前全部用户 shares 之和”**，而不是 `st.pool.total_shares`，避免被演示函数改写后的基准失真。
-pub fn admin_rescale_all_shares(new_total_e6: u128) {
-    STATE.with(|s| {
-        let mut st = s.borrow_mut();
+    /// 恒定乘积池：首次铸造 sqrt(a0 * a1)，swap 走同一条内账路径，赎回按比例拿回含手续费后的储备
+    #[test]
+    fn constant_product_pool_reuses_swap_and_position_paths() {
+        let mut st = State::default();
+        let args = CreatePoolArgs { coins: vec![Coin::BOB, Coin::ICP], a_amp: 0, fee_bps: 30, kind: Some(pools::PoolKind::ConstantProduct), crypto: None, initial_price_e6: None };
+        let id = pools::create(&mut st, &args, 1_700_000_000).unwrap();
+        let key = skey(&acct(1).owner);
+        st.user_sub_bob.insert(key.clone(), 1_000_000_000);
+        st.user_sub_icp.insert(key.clone(), 250_000_000);
+        assert_eq!(add_liquidity(&mut st, id, &acct(1), [400_000_000, 100_000_000], 1_700_000_000).unwrap(), 200_000_000);
+
+        let (dy, fee) = crate::swap::execute(&mut st, id, &key, (Coin::BOB, Coin::ICP), 4_000_000, 0, 1_700_000_000).unwrap();
+        assert_eq!(fee, 12_000);
+        assert_eq!(dy, 100_000_000 * 3_988_000 / 403_988_000);
+        let p = pools::load(&st, id).unwrap();
+        assert!(p.reserves[0] * p.reserves[1] >= 400_000_000 * 100_000_000);
+
+        let out = remove_liquidity(&mut st, id, &acct(1), 200_000_000).unwrap();
+        assert_eq!(out, [403_988_000, 100_000_000 - dy]);
+    }
 
-        // 以“实际用户份额之和”做旧分母
-        let mut old_total: u128 = 0;
-        for v in st.user_shares.values() {
-            old_total = old_total.saturating_add(*v);
-        }
+    /// CryptoSwap 池：可不按比例注入；手续费留在池内推高 virtual_price，不进 fee_vault
+    #[test]
+    fn crypto_pool_keeps_fees_in_reserves() {
+        let mut st = State::default();
+        let args = CreatePoolArgs {
+            coins: vec![Coin::USDC, Coin::ICP], a_amp: 0, fee_bps: 0,
+            kind: Some(pools::PoolKind::CryptoSwap), crypto: None, initial_price_e6: Some(10_000_000),
+        };
+        assert!(pools::create(&mut st, &CreatePoolArgs { initial_price_e6: None, ..args.clone() }, 1_700_000_000).is_err());
+        let id = pools::create(&mut st, &args, 1_700_000_000).unwrap();
+        let key = skey(&acct(1).owner);
+        st.user_sub_usdc.insert(key.clone(), 20_000_000_000);
+        st.user_sub_icp.insert(key.clone(), 2_000_000_000);
+        let minted = add_liquidity(&mut st, id, &acct(1), [10_000_000_000, 1_000_000_000], 1_700_000_000).unwrap();
+        assert!(add_liquidity(&mut st, id, &acct(1), [1_000_000_000, 0], 1_700_000_000).unwrap() < minted / 10);
+
+        let (dy, fee) = crate::swap::execute(&mut st, id, &key, (Coin::USDC, Coin::ICP), 100_000_000, 0, 1_700_000_060).unwrap();
+        assert!(dy < 10_000_000 && fee > 0);
+        let p = pools::load(&st, id).unwrap();
+        assert_eq!(p.fee_vault, vec![0, 0]);
+        assert!(p.virtual_price_e6 > 1_000_000);
+        assert_eq!(claimable(&st, id, &acct(1).owner.to_text()), [0, 0]);
+    }
 
-        if old_total == 0 {
-            // 没有 LP：仅更新池总份额（保持用户份额为0）
-            st.pool.total_shares = new_total_e6;
-            return;
-        }
+    /// 转到非默认子账户的份额：手续费在该账户下累积、可在该账户下领取与赎回，不留无主余额
+    #[test]
+    fn subaccount_holder_can_claim_and_remove() {
+        use crate::lp_token::{self, TxCtx};
+        use crate::icrc::Icrc1TransferArg;
+        let mut st = State::default();
+        st.pool.a_amp = 100;
+        let owner = acct(1);
+        let sub = Account { owner: owner.owner, subaccount: Some(vec![7; 32]) };
+        st.user_sub_usdc.insert(skey(&owner.owner), 1_000_000);
+        st.user_sub_usdt.insert(skey(&owner.owner), 1_000_000);
+        assert_eq!(add_liquidity(&mut st, POOL0, &owner, [1_000_000, 1_000_000], 1_700_000_000).unwrap(), 2_000_000);
+
+        let ctx = TxCtx { caller: owner.owner, canister: candid::Principal::from_slice(&[99; 29]), now: 1 };
+        let arg = Icrc1TransferArg { from_subaccount: None, to: sub.clone(), amount: candid::Nat::from(500_000u32), fee: None, memo: None, created_at_time: None };
+        lp_token::transfer(&mut st, &ctx, arg).unwrap();
+        assert_eq!(get_shares(&st, &sub), 500_000);
+
+        let mut p = pools::load(&st, POOL0).unwrap();
+        accrue_fee(&mut p, 0, 2_000);
+        pools::store(&mut st, &p).unwrap();
+        assert_eq!(claimable(&st, POOL0, &lp_token::account_key(&sub)), [500, 0]);
+        assert_eq!(claim_fee(&mut st, POOL0, &sub).unwrap(), [500, 0]);
+        assert_eq!(claim_fee(&mut st, POOL0, &owner).unwrap(), [1_500, 0]);
+        assert_eq!(st.fee_vault_usdc, 0);
+
+        assert_eq!(remove_liquidity(&mut st, POOL0, &sub, 500_000).unwrap(), [250_000, 250_000]);
+        assert_eq!(get_shares(&st, &sub), 0);
+        assert_eq!(get_shares(&st, &owner), 1_500_000);
+        // 赎回与手续费都进 owner 的内账（派生子账户按 owner 计）
+        assert_eq!(st.user_sub_usdc.get(&skey(&owner.owner)), Some(&(250_000 + 2_000)));
+    }
 
-        for (_who, shares) in st.user_shares.iter_mut() {
-            *shares = (*shares).saturating_mul(new_total_e6) / old_total;
-        }
-        st.pool.total_shares = new_total_e6;
-    });
+    fn get_shares(st: &State, a: &Account) -> u128 { pools::shares(st, POOL0, &crate::lp_token::account_key(a)) }
 }
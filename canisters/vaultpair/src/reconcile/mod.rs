@@ -0,0 +1,339 @@
+// canisters/vaultpair/src/reconcile/mod.rs
+// 对账（只读 + 显式更正），取代旧的“按 live 余额覆盖储备并等比缩放全部 LP 份额”。
+// 逐个池子（0 号池与注册表中的池子）逐币对账，池子子账户应持有：储备 + 手续费金库（未领取手续费）。差额按类别拆解：
+//   live - reserve = unclaimed_fees(fee_vault) - ledger_fees + unaccounted_deposits + unexplained
+//   ledger_fees：池子每笔转出由账本另扣一次 fee，内部账不记；按“转出笔数 × 当前 fee - 已核销”估算
+//   unaccounted_deposits：直接打进池子子账户、未经任何入口记账的资金
+//   unexplained：扣除 ledger_fees 后仍然短缺的部分（需人工排查）
+// Metapool 的 LP 一侧只在内账（pool#<id> 名下的份额），不对账。
+// 用户内账余额（user_sub_*）托管在各自的派生子账户，不在池子子账户，仅列出供参考（全量偿付见 solvency）。
+// 报告只读；唯一的改账入口是 apply_correction：须以最近一份报告为上限，逐笔写 Reconcile 事件留痕。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{
+    error::{Error, Result},
+    events::{self, Event},
+    positions,
+    pools::{self, PoolId, POOL0},
+    state::{State, STATE},
+    types::{AmountE6, TokenId},
+};
+
+/// 更正须基于不超过该时长的报告（秒）
+pub const REPORT_MAX_AGE_SEC: u64 = 3600;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenReconcile {
+    /// None = 0 号池（旧报告）
+    pub pool: Option<PoolId>,
+    pub token: TokenId,
+    pub ledger: Principal,
+    pub live_pool_e6: AmountE6,
+    pub reserve_e6: AmountE6,
+    /// 手续费金库 = 未领取手续费（应在池子子账户里）
+    pub unclaimed_fees_e6: AmountE6,
+    /// 全部 LP 当前可领之和；金库减去它为取整留下的尘埃
+    pub claimable_fees_e6: AmountE6,
+    /// 用户内账余额之和（托管在派生子账户，不参与本差额）
+    pub user_balances_e6: AmountE6,
+    /// live - (reserve + unclaimed_fees)
+    pub diff_e6: i128,
+    /// 估算的账本转出手续费（尚未核销部分）
+    pub ledger_fees_e6: AmountE6,
+    pub unaccounted_deposits_e6: AmountE6,
+    /// 负数 = 扣除 ledger_fees 后仍短缺
+    pub unexplained_e6: i128,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub ts: u64,
+    pub tokens: Vec<TokenReconcile>,
+    pub total_shares: AmountE6,
+    pub virtual_price_e6: u128,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CorrectionKind {
+    /// 把未记账的入金并入储备（全体 LP 按份额受益）
+    AbsorbDepositToReserve,
+    /// 核销账本转出手续费：储备减少相应数额
+    WriteOffLedgerFees,
+    /// 核销无法解释的短缺：储备减少相应数额（损失由全体 LP 分摊）
+    WriteOffShortfall,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ReconcileCorrection {
+    /// None = 0 号池
+    pub pool: Option<PoolId>,
+    pub token: TokenId,
+    pub kind: CorrectionKind,
+    pub amount_e6: AmountE6,
+    /// 审计备注（必填）
+    pub memo: String,
+}
+
+/// 挂在 State.reconcile 上；Option 以兼容旧状态
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ReconcileState {
+    /// 池子子账户成功转出的笔数（key 见 out_key）
+    pub pool_out_transfers: BTreeMap<String, u64>,
+    /// 已核销的账本手续费（key 同上，e6）
+    pub ledger_fees_written_off_e6: BTreeMap<String, u128>,
+    pub last_report: Option<ReconcileReport>,
+}
+
+fn rec_mut(st: &mut State) -> &mut ReconcileState { st.reconcile.get_or_insert_with(Default::default) }
+
+/// 转出计数 / 核销的 key：0 号池为账本 principal text（兼容旧状态），其余池子为 "<账本>@<池子>"
+fn out_key(pool: PoolId, ledger: Principal) -> String {
+    if pool == POOL0 { ledger.to_text() } else { format!("{}@{}", ledger.to_text(), pool) }
+}
+
+/// 池子子账户每成功转出一笔调用一次（ledger 另扣的 fee 不进内部账）
+pub fn note_pool_outgoing(pool: PoolId, ledger: Principal) {
+    STATE.with(|s| *rec_mut(&mut s.borrow_mut()).pool_out_transfers.entry(out_key(pool, ledger)).or_default() += 1);
+}
+
+/// 一个池子一个币种的 live 输入：账本、池子子账户余额与当前转账 fee（均为 e6）
+#[derive(Clone, Debug)]
+pub struct LiveToken { pub pool: PoolId, pub token: TokenId, pub ledger: Principal, pub live_pool_e6: u128, pub fee_e6: u128 }
+
+/// 某池某币的内部账：(储备, 手续费金库, 全部 LP 可领之和, 用户内账之和)；池子或币不存在时为 0
+fn books(st: &State, pool: PoolId, token: TokenId) -> (u128, u128, u128, u128) {
+    let Ok(p) = pools::load(st, pool) else { return (0, 0, 0, 0) };
+    let Some(i) = p.index_of(token.into()) else { return (0, 0, 0, 0) };
+    let claim = pools::holders(st, pool).iter().fold(0u128, |acc, who| acc.saturating_add(positions::claimable(st, pool, who)[i]));
+    (p.reserves[i], p.fee_vault[i], claim, pools::sub_total(st, token.into()))
+}
+
+/// 生成报告（纯函数，不改状态）
+pub fn build_report(st: &State, live: &[LiveToken], ts: u64) -> ReconcileReport {
+    let rec = st.reconcile.clone().unwrap_or_default();
+    let tokens = live.iter().map(|l| {
+        let (reserve, vault, claim, users) = books(st, l.pool, l.token);
+        let key = out_key(l.pool, l.ledger);
+        let outs = *rec.pool_out_transfers.get(&key).unwrap_or(&0) as u128;
+        let written_off = *rec.ledger_fees_written_off_e6.get(&key).unwrap_or(&0);
+        let ledger_fees = outs.saturating_mul(l.fee_e6).saturating_sub(written_off);
+
+        let diff = l.live_pool_e6 as i128 - reserve.saturating_add(vault) as i128;
+        let rest = diff + ledger_fees as i128;
+        TokenReconcile {
+            pool: Some(l.pool),
+            token: l.token,
+            ledger: l.ledger,
+            live_pool_e6: l.live_pool_e6,
+            reserve_e6: reserve,
+            unclaimed_fees_e6: vault,
+            claimable_fees_e6: claim,
+            user_balances_e6: users,
+            diff_e6: diff,
+            ledger_fees_e6: ledger_fees,
+            unaccounted_deposits_e6: rest.max(0) as u128,
+            unexplained_e6: rest.min(0),
+        }
+    }).collect();
+    let snap = events::pool_snapshot(st);
+    ReconcileReport { ts, tokens, total_shares: snap.total_shares, virtual_price_e6: snap.virtual_price_e6 }
+}
+
+/// 一行摘要（事件 detail / 文本返回用）
+pub fn summary(r: &ReconcileReport) -> String {
+    r.tokens.iter().map(|t| format!(
+        "pool {} {:?}: live={} reserve={} unclaimed_fees={} diff={} ledger_fees={} unaccounted={} unexplained={}",
+        t.pool.unwrap_or(POOL0), t.token, t.live_pool_e6, t.reserve_e6, t.unclaimed_fees_e6, t.diff_e6,
+        t.ledger_fees_e6, t.unaccounted_deposits_e6, t.unexplained_e6,
+    )).collect::<Vec<_>>().join("; ")
+}
+
+/// 按最近一份报告校验并执行一笔更正；成功后从报告中扣减对应额度，防止重复执行
+pub fn apply_correction(st: &mut State, c: &ReconcileCorrection, now_sec: u64) -> Result<()> {
+    if c.amount_e6 == 0 || c.memo.trim().is_empty() { return Err(Error::InvalidInput); }
+    let report = st.reconcile.as_ref().and_then(|r| r.last_report.clone())
+        .ok_or(Error::NoReconcileReport)?;
+    if now_sec.saturating_sub(report.ts) > REPORT_MAX_AGE_SEC {
+        return Err(Error::StaleReconcileReport { report_ts: report.ts });
+    }
+    let pool = c.pool.unwrap_or(POOL0);
+    let idx = report.tokens.iter().position(|t| t.token == c.token && t.pool.unwrap_or(POOL0) == pool)
+        .ok_or(Error::UnsupportedPair { token_in: c.token, token_out: c.token })?;
+    let t = &report.tokens[idx];
+    let cap = match c.kind {
+        CorrectionKind::AbsorbDepositToReserve => t.unaccounted_deposits_e6,
+        CorrectionKind::WriteOffLedgerFees => t.ledger_fees_e6,
+        CorrectionKind::WriteOffShortfall => t.unexplained_e6.unsigned_abs(),
+    };
+    if c.amount_e6 > cap {
+        return Err(Error::InsufficientBalance { token: c.token, have: cap, need: c.amount_e6 });
+    }
+
+    let mut p = pools::load(st, pool)?;
+    let i = p.index_of(c.token.into()).ok_or(Error::InvalidInput)?;
+    let reserve = &mut p.reserves[i];
+    match c.kind {
+        CorrectionKind::AbsorbDepositToReserve => *reserve = reserve.saturating_add(c.amount_e6),
+        CorrectionKind::WriteOffLedgerFees | CorrectionKind::WriteOffShortfall => {
+            if *reserve < c.amount_e6 {
+                return Err(Error::InsufficientLiquidity);
+            }
+            *reserve -= c.amount_e6;
+        }
+    }
+    pools::store(st, &p)?;
+    let ledger_key = out_key(pool, t.ledger);
+    let rec = rec_mut(st);
+    if c.kind == CorrectionKind::WriteOffLedgerFees {
+        *rec.ledger_fees_written_off_e6.entry(ledger_key).or_default() += c.amount_e6;
+    }
+    if let Some(t) = rec.last_report.as_mut().map(|r| &mut r.tokens[idx]) {
+        match c.kind {
+            CorrectionKind::AbsorbDepositToReserve => t.unaccounted_deposits_e6 -= c.amount_e6,
+            CorrectionKind::WriteOffLedgerFees => t.ledger_fees_e6 -= c.amount_e6,
+            CorrectionKind::WriteOffShortfall => t.unexplained_e6 += c.amount_e6 as i128,
+        }
+    }
+    Ok(())
+}
+
+/* ---------------- live 读取 ---------------- */
+
+async fn live_token(pool: PoolId, token: TokenId, ledger: Principal, dec: u8) -> Result<LiveToken> {
+    let (bal, fee) = futures::future::join(
+        crate::icrc::icrc1_balance_of(ledger, pools::account(pool)),
+        crate::icrc::icrc1_fee(ledger),
+    ).await;
+    Ok(LiveToken {
+        pool,
+        token,
+        ledger,
+        live_pool_e6: crate::api::ext_to_e6(&bal?, dec),
+        fee_e6: crate::api::ext_to_e6(&fee?, dec),
+    })
+}
+
+/// 读取各池子子账户的 live 余额与转账 fee（顺序同 pools::onchain_coins，0 号池的 USDC / USDT 在前）
+pub async fn fetch_live() -> Result<Vec<LiveToken>> {
+    crate::api::get_token_meta().ok_or(Error::TokenMetaNotSet)?;
+    let coins = STATE.with(|s| pools::onchain_coins(&s.borrow()));
+    let reads = coins.into_iter().map(|(pool, token, ledger, dec)| live_token(pool, token, ledger, dec));
+    futures::future::join_all(reads).await.into_iter().collect()
+}
+
+/// 读取 live → 生成报告 → 保存为 last_report 并写 Reconcile 事件（储备不变，before == after）
+pub async fn run_report(who: String, ts: u64) -> Result<ReconcileReport> {
+    let live = fetch_live().await?;
+    let (report, snap) = STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let report = build_report(&st, &live, ts);
+        rec_mut(&mut st).last_report = Some(report.clone());
+        (report, events::pool_snapshot(&st))
+    });
+    events::push(Event::Reconcile { who, before: snap.clone(), after: snap, detail: format!("report: {}", summary(&report)), ts });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Coin;
+
+    fn ledger(b: u8) -> Principal { Principal::from_slice(&[b; 10]) }
+
+    fn setup() -> State {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 1_000_000;
+        st.pool.reserve_usdt = 1_000_000;
+        st.fee_vault_usdc = 300;
+        st.user_sub_usdc.insert("u#main".into(), 42);
+        let rec = rec_mut(&mut st);
+        rec.pool_out_transfers.insert(ledger(1).to_text(), 5);
+        st
+    }
+
+    #[test]
+    fn report_splits_diff_into_categories() {
+        let st = setup();
+        let live = [
+            // 5 笔转出 × fee 10 = 50 的账本手续费；另有 20 未记账入金
+            LiveToken { pool: POOL0, token: TokenId::USDC, ledger: ledger(1), live_pool_e6: 1_000_300 - 50 + 20, fee_e6: 10 },
+            // 短缺 7，没有转出记录可解释
+            LiveToken { pool: POOL0, token: TokenId::USDT, ledger: ledger(2), live_pool_e6: 999_993, fee_e6: 10 },
+        ];
+        let r = build_report(&st, &live, 100);
+        let u = &r.tokens[0];
+        assert_eq!((u.diff_e6, u.ledger_fees_e6, u.unaccounted_deposits_e6, u.unexplained_e6), (-30, 50, 20, 0));
+        assert_eq!((u.unclaimed_fees_e6, u.user_balances_e6), (300, 42));
+        let v = &r.tokens[1];
+        assert_eq!((v.diff_e6, v.ledger_fees_e6, v.unaccounted_deposits_e6, v.unexplained_e6), (-7, 0, 0, -7));
+        // 报告本身不改任何账
+        assert_eq!(st.pool.reserve_usdc, 1_000_000);
+    }
+
+    #[test]
+    fn corrections_are_capped_by_the_latest_report() {
+        let mut st = setup();
+        let c = |token, kind, amount_e6| ReconcileCorrection { pool: None, token, kind, amount_e6, memo: "audit #1".into() };
+        assert!(matches!(apply_correction(&mut st, &c(TokenId::USDC, CorrectionKind::WriteOffLedgerFees, 1), 100), Err(Error::NoReconcileReport)));
+
+        let live = [
+            LiveToken { pool: POOL0, token: TokenId::USDC, ledger: ledger(1), live_pool_e6: 1_000_300 - 50 + 20, fee_e6: 10 },
+            LiveToken { pool: POOL0, token: TokenId::USDT, ledger: ledger(2), live_pool_e6: 999_993, fee_e6: 10 },
+        ];
+        let report = build_report(&st, &live, 100);
+        rec_mut(&mut st).last_report = Some(report);
+
+        assert!(apply_correction(&mut st, &c(TokenId::USDC, CorrectionKind::AbsorbDepositToReserve, 21), 100).is_err());
+        apply_correction(&mut st, &c(TokenId::USDC, CorrectionKind::AbsorbDepositToReserve, 20), 100).unwrap();
+        assert!(apply_correction(&mut st, &c(TokenId::USDC, CorrectionKind::AbsorbDepositToReserve, 1), 100).is_err());
+        apply_correction(&mut st, &c(TokenId::USDC, CorrectionKind::WriteOffLedgerFees, 50), 100).unwrap();
+        apply_correction(&mut st, &c(TokenId::USDT, CorrectionKind::WriteOffShortfall, 7), 100).unwrap();
+        assert_eq!((st.pool.reserve_usdc, st.pool.reserve_usdt), (1_000_000 + 20 - 50, 999_993));
+        assert!(apply_correction(&mut st, &c(TokenId::USDT, CorrectionKind::WriteOffShortfall, 1), 100).is_err());
+
+        // 核销后重新对账：差额已被吸收，ledger_fees 不再重复计入
+        let r = build_report(&st, &live, 200);
+        assert!(r.tokens.iter().all(|t| t.diff_e6 == 0 && t.ledger_fees_e6 == 0 && t.unexplained_e6 == 0));
+        // 过期报告不可用
+        assert!(matches!(apply_correction(&mut st, &c(TokenId::USDT, CorrectionKind::WriteOffShortfall, 1), 100 + REPORT_MAX_AGE_SEC + 1), Err(Error::StaleReconcileReport { .. })));
+        // 备注必填
+        let mut no_memo = c(TokenId::USDC, CorrectionKind::AbsorbDepositToReserve, 1);
+        no_memo.memo = " ".into();
+        assert!(matches!(apply_correction(&mut st, &no_memo, 100), Err(Error::InvalidInput)));
+    }
+
+    /// 注册表中的池子按自己的储备 / 金库 / 转出笔数对账，更正只改该池
+    #[test]
+    fn other_pools_reconcile_against_their_own_books() {
+        let mut st = setup();
+        let args = pools::CreatePoolArgs { coins: vec![Coin::ICP, Coin::USDC], a_amp: 50, fee_bps: 30, kind: None, crypto: None, initial_price_e6: None };
+        let id = pools::create(&mut st, &args, 1_700_000_000).unwrap();
+        let mut p = pools::load(&st, id).unwrap();
+        p.reserves = vec![500, 800];
+        p.fee_vault = vec![0, 40];
+        pools::store(&mut st, &p).unwrap();
+        // 同一账本，0 号池已有 5 笔转出；该池 2 笔
+        rec_mut(&mut st).pool_out_transfers.insert(out_key(id, ledger(1)), 2);
+
+        let live = [
+            LiveToken { pool: POOL0, token: TokenId::USDC, ledger: ledger(1), live_pool_e6: 1_000_300 - 50, fee_e6: 10 },
+            LiveToken { pool: id, token: TokenId::USDC, ledger: ledger(1), live_pool_e6: 840 - 20 + 5, fee_e6: 10 },
+        ];
+        let r = build_report(&st, &live, 100);
+        let u = &r.tokens[0];
+        assert_eq!((u.pool, u.diff_e6, u.ledger_fees_e6, u.unaccounted_deposits_e6), (Some(POOL0), -50, 50, 0));
+        let t = &r.tokens[1];
+        assert_eq!((t.pool, t.reserve_e6, t.unclaimed_fees_e6), (Some(id), 800, 40));
+        assert_eq!((t.diff_e6, t.ledger_fees_e6, t.unaccounted_deposits_e6, t.unexplained_e6), (-15, 20, 5, 0));
+
+        rec_mut(&mut st).last_report = Some(r);
+        let c = ReconcileCorrection { pool: Some(id), token: TokenId::USDC, kind: CorrectionKind::AbsorbDepositToReserve, amount_e6: 5, memo: "audit #2".into() };
+        apply_correction(&mut st, &c, 100).unwrap();
+        assert_eq!((pools::load(&st, id).unwrap().reserves, st.pool.reserve_usdc), (vec![500, 805], 1_000_000));
+        // 额度按池子分开：0 号池没有未记账入金
+        assert!(apply_correction(&mut st, &ReconcileCorrection { pool: None, amount_e6: 1, ..c }, 100).is_err());
+    }
+}
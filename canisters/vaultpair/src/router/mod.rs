@@ -0,0 +1,206 @@
+// canisters/vaultpair/src/router/mod.rs
+// 多跳路由：在 pools 的代币图上枚举不重复经过同一代币的路径（至多 max_hops 跳），逐跳按 swap::quote_in 报价（含各池手续费），取输出最多者。
+//   执行只动内账：先整条报价并做一次 min_out 校验，再逐跳 swap::execute（单跳不设滑点）；各跳经过的池子互不相同，报价与执行确定一致。
+//   价格冲击 = 1 - 成交价 / 各跳边际价之积（含手续费）。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    types::{Account, AmountE6, Coin},
+    state::{State, STATE, skey},
+    error::{Error, Result},
+    pools::{self, PoolId, PoolState},
+    swap,
+};
+
+const E6: u128 = 1_000_000;
+
+pub const DEFAULT_MAX_HOPS: u8 = 3;
+/// 跳数上限（防止枚举爆炸）
+pub const MAX_HOPS: u8 = 4;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RouteHop {
+    pub pool: PoolId,
+    pub token_in: Coin,
+    pub token_out: Coin,
+    pub dx_e6: AmountE6,
+    pub dy_e6: AmountE6,
+    /// 输入侧手续费（token_in 计）
+    pub fee_e6: AmountE6,
+    pub fee_rate_e10: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RouteQuote {
+    pub hops: Vec<RouteHop>,
+    pub dx_e6: AmountE6,
+    pub dy_e6: AmountE6,
+    /// 成交价（dy / dx）
+    pub price_e6: u128,
+    /// 各跳边际价之积（不含手续费）
+    pub spot_price_e6: u128,
+    /// 相对边际价的价格冲击（含手续费）
+    pub price_impact_bps: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RouteSwapArgs {
+    pub account: Account,
+    pub token_in: Coin,
+    pub token_out: Coin,
+    pub dx_e6: AmountE6,
+    /// 整条路径的最小接收量（只校验这一次）
+    pub min_out_e6: AmountE6,
+    /// None = DEFAULT_MAX_HOPS
+    pub max_hops: Option<u8>,
+}
+
+/// 一条路径：(池子, 输入币, 输出币)
+type Path = Vec<(PoolId, Coin, Coin)>;
+
+/// 枚举 from → to 的全部简单路径（不重复经过同一代币）
+fn paths(pools: &[PoolState], from: Coin, to: Coin, max_hops: u8) -> Vec<Path> {
+    fn dfs(pools: &[PoolState], at: Coin, to: Coin, left: u8, seen: &mut Vec<Coin>, cur: &mut Path, out: &mut Vec<Path>) {
+        if at == to { out.push(cur.clone()); return; }
+        if left == 0 { return; }
+        for p in pools {
+            let Some(i) = p.index_of(at) else { continue };
+            let next = p.coins[1 - i];
+            if seen.contains(&next) { continue; }
+            seen.push(next);
+            cur.push((p.id, at, next));
+            dfs(pools, next, to, left - 1, seen, cur, out);
+            cur.pop();
+            seen.pop();
+        }
+    }
+    let mut out = Vec::new();
+    dfs(pools, from, to, max_hops, &mut vec![from], &mut Vec::new(), &mut out);
+    out
+}
+
+/// 沿给定路径逐跳报价；任何一跳失败或输出取整为 0 即整条失败
+fn quote_path(st: &State, path: &Path, dx: u128, now_sec: u64) -> Result<RouteQuote> {
+    let (mut amt, mut spot) = (dx, E6);
+    let mut hops = Vec::with_capacity(path.len());
+    for &(pool, tin, tout) in path {
+        let p = pools::load(st, pool)?;
+        let (i, j) = p.orient(tin, tout).ok_or_else(|| Error::unsupported(tin, tout))?;
+        let q = swap::quote_in(st, pool, tin, tout, amt, now_sec)?;
+        if q.dy_e6 == 0 { return Err(Error::InvalidInput); }
+        spot = spot.saturating_mul(swap::spot_price_e6(&p, i, j).ok_or(Error::InsufficientLiquidity)?) / E6;
+        hops.push(RouteHop { pool, token_in: tin, token_out: tout, dx_e6: amt, dy_e6: q.dy_e6, fee_e6: q.fee_e6, fee_rate_e10: q.fee_rate_e10 });
+        amt = q.dy_e6;
+    }
+    let price = amt.saturating_mul(E6) / dx;
+    let impact = spot.saturating_sub(price).saturating_mul(10_000).checked_div(spot).unwrap_or(0);
+    Ok(RouteQuote { hops, dx_e6: dx, dy_e6: amt, price_e6: price, spot_price_e6: spot, price_impact_bps: impact.min(10_000) as u32 })
+}
+
+/// 最优路径：输出最多者，同等输出取跳数少者。无路可走 → UnsupportedPair；路径都报价失败 → 最后一个错误
+pub fn best_route(st: &State, token_in: Coin, token_out: Coin, dx_e6: AmountE6, max_hops: Option<u8>, now_sec: u64) -> Result<RouteQuote> {
+    if dx_e6 == 0 || token_in == token_out { return Err(Error::InvalidInput); }
+    let max_hops = max_hops.unwrap_or(DEFAULT_MAX_HOPS).clamp(1, MAX_HOPS);
+    let all = paths(&pools::list(st), token_in, token_out, max_hops);
+    let mut best: Option<RouteQuote> = None;
+    let mut last_err = Error::unsupported(token_in, token_out);
+    for path in &all {
+        match quote_path(st, path, dx_e6, now_sec) {
+            Ok(q) => {
+                let better = best.as_ref().is_none_or(|b| q.dy_e6 > b.dy_e6 || (q.dy_e6 == b.dy_e6 && q.hops.len() < b.hops.len()));
+                if better { best = Some(q); }
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    best.ok_or(last_err)
+}
+
+pub fn quote_route(token_in: Coin, token_out: Coin, dx_e6: AmountE6, max_hops: Option<u8>) -> Result<RouteQuote> {
+    STATE.with(|s| best_route(&s.borrow(), token_in, token_out, dx_e6, max_hops, crate::state::now()))
+}
+
+/// 内账执行最优路径：调用者校验 → 整条报价 → 可用额与 min_out 校验 → 逐跳成交；返回的 hops 即实际成交（留痕见 swap_route）
+pub fn execute_route(st: &mut State, args: &RouteSwapArgs, caller: Principal, now_sec: u64) -> Result<RouteQuote> {
+    swap::require_owner(&args.account, caller)?;
+    let key = skey(&args.account.owner);
+    let avail = pools::sub_balance(st, &key, args.token_in);
+    if args.dx_e6 > avail { return Err(Error::insufficient(args.token_in, avail, args.dx_e6)); }
+    let q = best_route(st, args.token_in, args.token_out, args.dx_e6, args.max_hops, now_sec)?;
+    if q.dy_e6 < args.min_out_e6 { return Err(Error::SlippageExceeded { min: args.min_out_e6, got: q.dy_e6 }); }
+
+    for (n, h) in q.hops.iter().enumerate() {
+        // 报价已覆盖全部失败情形；若仍失败说明状态不一致，panic 让整条消息回滚，避免停在半路
+        if let Err(e) = swap::execute(st, h.pool, &key, (h.token_in, h.token_out), h.dx_e6, h.dy_e6, now_sec) {
+            panic!("route hop {n} failed after quote: {e:?}");
+        }
+    }
+    Ok(q)
+}
+
+/// 执行并逐跳写 ICRC-3 区块（带 hop 序号）与 Swap 事件
+pub fn swap_route(args: RouteSwapArgs, caller: Principal) -> Result<RouteQuote> {
+    STATE.with(|s| {
+        let mut st = s.borrow_mut();
+        let q = execute_route(&mut st, &args, caller, crate::state::now())?;
+        for (n, h) in q.hops.iter().enumerate() {
+            let fill = swap::Fill { pool: h.pool, token_in: h.token_in, token_out: h.token_out, dx: h.dx_e6, dy: h.dy_e6, fee: h.fee_e6 };
+            swap::log_fill(&mut st, &args.account, &fill, Some(n));
+        }
+        Ok(q)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pools::CreatePoolArgs;
+
+    const TS: u64 = 1_700_000_000;
+
+    fn acct() -> Account { Account { owner: candid::Principal::from_slice(&[7; 29]), subaccount: None } }
+
+    fn seed(st: &mut State, coins: [Coin; 2], amount: u128) -> PoolId {
+        let id = pools::create(st, &CreatePoolArgs { coins: coins.to_vec(), a_amp: 100, fee_bps: 30, kind: None, crypto: None, initial_price_e6: None }, TS).unwrap();
+        let mut p = pools::load(st, id).unwrap();
+        p.reserves = vec![amount, amount];
+        p.total_shares = amount * 2;
+        pools::store(st, &p).unwrap();
+        id
+    }
+
+    /// BOB → ICP → USDC 两跳：每跳都收手续费，min_out 只在终点校验一次，中间币不留在内账
+    #[test]
+    fn routes_through_intermediate_pool_and_checks_min_out_once() {
+        let mut st = State::default();
+        let bob_icp = seed(&mut st, [Coin::BOB, Coin::ICP], 1_000_000_000);
+        let icp_usdc = seed(&mut st, [Coin::ICP, Coin::USDC], 1_000_000_000);
+        let key = skey(&acct().owner);
+        st.user_sub_bob.insert(key.clone(), 10_000_000);
+
+        let q = best_route(&st, Coin::BOB, Coin::USDC, 10_000_000, None, TS).unwrap();
+        assert_eq!(q.hops.iter().map(|h| h.pool).collect::<Vec<_>>(), vec![bob_icp, icp_usdc]);
+        assert!(q.hops.iter().all(|h| h.fee_e6 > 0));
+        assert!(q.dy_e6 < 10_000_000 && q.price_impact_bps >= 60, "{q:?}");
+        assert!(matches!(best_route(&st, Coin::BOB, Coin::USDC, 10_000_000, Some(1), TS), Err(Error::UnsupportedPair { .. })));
+
+        let mut args = RouteSwapArgs {
+            account: acct(), token_in: Coin::BOB, token_out: Coin::USDC,
+            dx_e6: 10_000_000, min_out_e6: q.dy_e6 + 1, max_hops: None,
+        };
+        let owner = acct().owner;
+        assert!(matches!(execute_route(&mut st, &args, owner, TS), Err(Error::SlippageExceeded { .. })));
+        assert_eq!(st.user_sub_bob.get(&key), Some(&10_000_000));
+        // 不能替别人的账户下单
+        let other = Principal::from_slice(&[8; 29]);
+        assert!(matches!(execute_route(&mut st, &RouteSwapArgs { min_out_e6: 0, ..args.clone() }, other, TS), Err(Error::Unauthorized(p)) if p == other));
+        assert_eq!(st.user_sub_bob.get(&key), Some(&10_000_000));
+
+        args.min_out_e6 = q.dy_e6;
+        assert_eq!(execute_route(&mut st, &args, owner, TS).unwrap(), q);
+        assert_eq!(st.user_sub_bob.get(&key), Some(&0));
+        assert_eq!(st.user_sub_icp.get(&key), Some(&0));
+        assert_eq!(st.user_sub_usdc.get(&key), Some(&q.dy_e6));
+    }
+}
@@ -0,0 +1,217 @@
+// canisters/vaultpair/src/solvency/mod.rs
+// 偿付能力报告：内部负债（用户内账 + 储备 + 手续费金库）对照托管资产（池子子账户 + 用户派生子账户的 live 余额）。
+// 用户较多时按 key 分页抽查：每页读取该页用户派生子账户的 live 余额，池子子账户每页都读。
+// coverage = (池子 live + 本页用户 live) / (储备 + 金库 + 本页用户内账)，单位 bps；
+// 逐页翻完（complete = true）即覆盖全部用户。用户内账本期仅 USDC / USDT，计入 0 号池那一行。
+// 注册表中的其余池子各币单列一行，只比对池子子账户：coverage = 池子 live / (储备 + 金库)；Metapool 的 LP 只在内账，不列出。
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    error::{Error, Result},
+    events::{self, Event},
+    pools::{self, PoolId, POOL0},
+    state::{State, STATE},
+    types::{AmountE6, TokenId},
+};
+
+pub const DEFAULT_PAGE: u32 = 50;
+pub const MAX_PAGE: u32 = 200;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SolvencyArgs {
+    /// 从该用户 key（"owner#sub"）之后开始；None = 从头
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenSolvency {
+    /// None = 0 号池（旧报告）
+    pub pool: Option<PoolId>,
+    pub token: TokenId,
+    pub ledger: Principal,
+    pub reserve_e6: AmountE6,
+    pub fee_vault_e6: AmountE6,
+    /// 全部用户内账之和
+    pub users_total_e6: AmountE6,
+    pub pool_live_e6: AmountE6,
+    /// 本页抽查用户的内账之和 / live 余额之和
+    pub users_checked_e6: AmountE6,
+    pub users_checked_live_e6: AmountE6,
+    /// 池子子账户：live / (储备 + 金库)
+    pub pool_coverage_bps: u64,
+    /// 抽查范围：(池子 live + 本页用户 live) / (储备 + 金库 + 本页用户内账)
+    pub coverage_bps: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SolvencyReport {
+    pub ts: u64,
+    pub tokens: Vec<TokenSolvency>,
+    pub users_total: u64,
+    /// 本页抽查的用户 key
+    pub users_checked: Vec<String>,
+    pub next_cursor: Option<String>,
+    pub complete: bool,
+}
+
+/// 有 USDC / USDT 内账记录的用户 key（有序，去重）
+fn user_keys(st: &State) -> BTreeSet<&String> {
+    st.user_sub_usdc.keys().chain(st.user_sub_usdt.keys()).collect()
+}
+
+/// 选出本页用户；返回 (本页 key, 下一页游标)
+pub fn page(st: &State, args: &SolvencyArgs) -> (Vec<String>, Option<String>) {
+    let limit = args.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE) as usize;
+    let keys = user_keys(st);
+    let mut it = keys.into_iter()
+        .filter(|k| args.cursor.as_ref().is_none_or(|c| k.as_str() > c.as_str()))
+        .cloned();
+    let out: Vec<String> = it.by_ref().take(limit).collect();
+    let next = if it.next().is_some() { out.last().cloned() } else { None };
+    (out, next)
+}
+
+fn bps(num: u128, den: u128) -> u64 {
+    if den == 0 { return 10_000; }
+    (num.saturating_mul(10_000) / den).min(u64::MAX as u128) as u64
+}
+
+/// 汇总（纯函数）。pool_live: (池子, token, ledger, 池子 live e6)；users_live: (用户 key, token) → live e6
+pub fn build(
+    st: &State,
+    users: Vec<String>,
+    next_cursor: Option<String>,
+    pool_live: &[(PoolId, TokenId, Principal, u128)],
+    users_live: &BTreeMap<(String, TokenId), u128>,
+    ts: u64,
+) -> SolvencyReport {
+    let empty = BTreeMap::new();
+    let tokens = pool_live.iter().map(|&(pool, token, ledger, pool_live_e6)| {
+        let (reserve, vault) = pools::load(st, pool).ok()
+            .and_then(|p| p.index_of(token.into()).map(|i| (p.reserves[i], p.fee_vault[i])))
+            .unwrap_or_default();
+        let book = match (pool, token) {
+            (POOL0, TokenId::USDC) => &st.user_sub_usdc,
+            (POOL0, TokenId::USDT) => &st.user_sub_usdt,
+            _ => &empty,
+        };
+        let users_checked_e6: u128 = users.iter().map(|k| *book.get(k).unwrap_or(&0)).sum();
+        let users_checked_live_e6: u128 = if pool == POOL0 {
+            users.iter().map(|k| *users_live.get(&(k.clone(), token)).unwrap_or(&0)).sum()
+        } else { 0 };
+        let pool_liab = reserve.saturating_add(vault);
+        TokenSolvency {
+            pool: Some(pool),
+            token,
+            ledger,
+            reserve_e6: reserve,
+            fee_vault_e6: vault,
+            users_total_e6: book.values().sum(),
+            pool_live_e6,
+            users_checked_e6,
+            users_checked_live_e6,
+            pool_coverage_bps: bps(pool_live_e6, pool_liab),
+            coverage_bps: bps(pool_live_e6.saturating_add(users_checked_live_e6), pool_liab.saturating_add(users_checked_e6)),
+        }
+    }).collect();
+    SolvencyReport {
+        ts,
+        tokens,
+        users_total: user_keys(st).len() as u64,
+        users_checked: users,
+        complete: next_cursor.is_none(),
+        next_cursor,
+    }
+}
+
+pub fn summary(r: &SolvencyReport) -> String {
+    let toks = r.tokens.iter().map(|t| format!(
+        "pool {} {:?}: liabilities reserve={} fee_vault={} users_checked={} | live pool={} users_checked={} | coverage={}bps pool={}bps",
+        t.pool.unwrap_or(POOL0), t.token, t.reserve_e6, t.fee_vault_e6, t.users_checked_e6, t.pool_live_e6, t.users_checked_live_e6,
+        t.coverage_bps, t.pool_coverage_bps,
+    )).collect::<Vec<_>>().join("; ");
+    format!("{} (users {}/{}, complete={})", toks, r.users_checked.len(), r.users_total, r.complete)
+}
+
+/* ---------------- live 读取（composite query 与 update 通用） ---------------- */
+
+/// 用户 key "owner#sub" → owner
+fn owner_of(key: &str) -> Option<Principal> {
+    Principal::from_text(key.split('#').next()?).ok()
+}
+
+pub async fn report(args: SolvencyArgs, ts: u64) -> Result<SolvencyReport> {
+    let meta = crate::api::get_token_meta().ok_or(Error::TokenMetaNotSet)?;
+    let (users, next) = STATE.with(|s| page(&s.borrow(), &args));
+    let ledgers = [(TokenId::USDC, meta.ckusdc, meta.dec_usdc), (TokenId::USDT, meta.ckusdt, meta.dec_usdt)];
+
+    let coins = STATE.with(|s| pools::onchain_coins(&s.borrow()));
+    let pool_calls = coins.iter().map(|&(pool, _, l, _)| crate::icrc::icrc1_balance_of(l, pools::account(pool)));
+    let pool_bals = futures::future::join_all(pool_calls).await;
+    let mut pool_live = Vec::new();
+    for ((pool, token, ledger, dec), bal) in coins.into_iter().zip(pool_bals) {
+        pool_live.push((pool, token, ledger, crate::api::ext_to_e6(&bal?, dec)));
+    }
+
+    let me = crate::icrc::canister_principal();
+    let mut jobs = Vec::new();
+    for k in &users {
+        let Some(owner) = owner_of(k) else { continue };
+        let acct = crate::types::Account { owner: me, subaccount: Some(crate::icrc::derive_subaccount(owner).to_vec()) };
+        for &(token, ledger, dec) in &ledgers {
+            let (k, acct) = (k.clone(), acct.clone());
+            jobs.push(async move {
+                crate::icrc::icrc1_balance_of(ledger, acct).await.map(|b| ((k, token), crate::api::ext_to_e6(&b, dec)))
+            });
+        }
+    }
+    let users_live = futures::future::join_all(jobs).await.into_iter().collect::<Result<BTreeMap<_, _>>>()?;
+
+    Ok(STATE.with(|s| build(&s.borrow(), users, next, &pool_live, &users_live, ts)))
+}
+
+/// update 版本：生成报告并写 Reconcile 事件（储备不变，before == after），便于长期审计
+pub async fn record(who: String, args: SolvencyArgs, ts: u64) -> Result<SolvencyReport> {
+    let r = report(args, ts).await?;
+    let snap = STATE.with(|s| events::pool_snapshot(&s.borrow()));
+    events::push(Event::Reconcile { who, before: snap.clone(), after: snap, detail: format!("solvency: {}", summary(&r)), ts });
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(b: u8) -> String { format!("{}#main", Principal::from_slice(&[b; 29]).to_text()) }
+
+    #[test]
+    fn pages_and_coverage() {
+        let mut st = State::default();
+        st.pool.reserve_usdc = 1_000;
+        st.fee_vault_usdc = 100;
+        for b in 1..=3u8 { st.user_sub_usdc.insert(key(b), 100); }
+        st.user_sub_usdt.insert(key(4), 50);
+        st.user_sub_usdc.insert(key(4), 100);
+        let (p1, next) = page(&st, &SolvencyArgs { cursor: None, limit: Some(2) });
+        assert_eq!(p1.len(), 2);
+        let (p2, end) = page(&st, &SolvencyArgs { cursor: next.clone(), limit: Some(2) });
+        assert_eq!((p2.len(), end), (2, None));
+        assert!(p1.iter().all(|k| !p2.contains(k)));
+        assert_eq!(owner_of(&p1[0]), Some(Principal::from_text(p1[0].split('#').next().unwrap()).unwrap()));
+
+        let ledger = Principal::from_slice(&[9; 10]);
+        let mut live = BTreeMap::new();
+        // 第一页两位用户：一位足额、一位只剩 50
+        live.insert((p1[0].clone(), TokenId::USDC), 100);
+        live.insert((p1[1].clone(), TokenId::USDC), 50);
+        let r = build(&st, p1, next, &[(POOL0, TokenId::USDC, ledger, 1_100)], &live, 7);
+        let t = &r.tokens[0];
+        assert_eq!((t.users_total_e6, t.users_checked_e6, t.users_checked_live_e6), (400, 200, 150));
+        assert_eq!(t.pool_coverage_bps, 10_000);
+        assert_eq!(t.coverage_bps, (1_250u128 * 10_000 / 1_300) as u64);
+        assert_eq!((r.users_total, r.complete), (4, false));
+    }
+}
@@ -2,10 +2,17 @@
 use candid::{CandidType, Principal};
 use serde::{Serialize,Deserialize};
 use std::{cell::RefCell, collections::BTreeMap};
-use crate::events::{Event,MAX_EVENTS};
+use crate::events::{self,Event,EventV1,EventLogMeta};
 use crate::stats::RollingStats;
 use crate::types::RiskParams;
-use crate::ledger_book::LedgerBook;
+use crate::ledger_book::{DustBook, LedgerBook};
+use crate::lp_token::LpToken;
+use crate::icrc3::Icrc3Log;
+use crate::jobs::JobsState;
+use crate::reconcile::ReconcileState;
+use crate::pause::PauseState;
+use crate::oracle::OracleState;
+use crate::pools::PoolRegistry;
 
 
 pub const DEFAULT_SUB_ID:&str = "main";            // 统一子账户ID
@@ -21,18 +28,33 @@ pub struct Pool{
   pub reserve_usdt:u128,
   pub total_shares:u128,
   pub virtual_price_e6:u128,
+  // 动态费率倍数（Curve NG offpeg_fee_multiplier，1e10 = 1x）；None = 固定费率。Option 以兼容旧状态
+  pub offpeg_fee_multiplier:Option<u64>,
 }
 
 #[derive(CandidType,Serialize,Deserialize,Clone,Debug,Default)]
 pub struct State{
   pub pool:Pool,
-  pub events:Vec<Event>,
+  // 事件日志编号/保留策略（日志本体在 stable memory，见 events.rs）；Option 以兼容旧状态
+  pub event_log: Option<EventLogMeta>,
 
   // === 统计与风控 ===
   pub stats: RollingStats,
   pub risk: RiskParams,
   pub cycles_alert_threshold: u128,  
+  // 后台定时任务参数与最近结果；Option 以兼容旧状态
+  pub jobs: Option<JobsState>,
+  // 对账：池子转出计数、已核销手续费与最近一份报告；Option 以兼容旧状态
+  pub reconcile: Option<ReconcileState>,
+  // 紧急暂停开关、暂停角色与自动暂停阈值；Option 以兼容旧状态
+  pub pause: Option<PauseState>,
+  // EMA 价格预言机与脱锚保护参数；Option 以兼容旧状态
+  pub oracle: Option<OracleState>,
+  // 1 号及以后的池子（0 号池仍用 pool / fee_* / user_shares 等字段，见 pools）；Option 以兼容旧状态
+  pub pools: Option<PoolRegistry>,
   pub ledger_book: LedgerBook,
+  // 换算到 e6 记账口径时截掉的尾数（见 ledger_book::DustBook）；Option 以兼容旧状态
+  pub dust: Option<DustBook>,
   
 
   // 主账户余额（key = Principal text）
@@ -47,8 +69,12 @@ pub struct State{
   pub user_sub_bob :BTreeMap<String,u128>,
   pub user_sub_icp :BTreeMap<String,u128>,
 
-  // LP 份额
+  // LP 份额（key = principal text；非默认子账户为 "principal.hex(sub)"，见 lp_token::account_key）
   pub user_shares:BTreeMap<String,u128>,
+  // LP 份额代币（ICRC-1/2）授权与交易计数；Option 以兼容旧状态
+  pub lp_token: Option<LpToken>,
+  // ICRC-3 哈希链区块日志；Option 以兼容旧状态
+  pub icrc3: Option<Icrc3Log>,
 
   // ===== 手续费累计（新增） =====
   // fee 暂存金库（swap 时累加到这里；不计入池子储备）
@@ -84,6 +110,7 @@ impl Default for Pool{
       reserve_usdt:0,
       total_shares:0,
       virtual_price_e6:1_000_000,
+      offpeg_fee_multiplier:None,
     }
   }
 }
@@ -91,12 +118,18 @@ impl Default for Pool{
 thread_local!{
   pub static STATE:RefCell<State>=RefCell::new(State{
     pool:Pool::default(),
-    events:Vec::new(),
+    event_log: None,
 
     stats: RollingStats::with_now(now()),
     risk: RiskParams { max_price_impact_bps: 3000, d_tolerance_e6: 50 },
     cycles_alert_threshold: 50_000_000_000_000u128, // 示例阈值
+    jobs: None,
+    reconcile: None,
+    pause: None,
+    oracle: None,
+    pools: None,
     ledger_book: LedgerBook::default(),    
+    dust: None,
         
     user_usdc:BTreeMap::new(),
     user_usdt:BTreeMap::new(),
@@ -107,6 +140,8 @@ thread_local!{
     user_sub_bob :BTreeMap::new(),
     user_sub_icp :BTreeMap::new(),
     user_shares:BTreeMap::new(),
+    lp_token: None,
+    icrc3: None,
 
     // 新增字段初始化
     fee_vault_usdc:0,
@@ -128,26 +163,45 @@ thread_local!{
   });
 }
 
-pub fn push_event(ev:Event){
-  STATE.with(|s|{
-    let mut s=s.borrow_mut();
-    s.events.push(ev);
-    if s.events.len()>MAX_EVENTS { let over=s.events.len()-MAX_EVENTS; s.events.drain(0..over); }
-  });
-}
+pub fn push_event(ev:Event){ events::push(ev); }
 
 // 用 ic_cdk::api::time() 防止 wasm panic
 pub fn now()->u64{ (ic_cdk::api::time()/1_000_000_000) as u64 }
 // 纳秒（给 ledger_book 用）
 pub fn now_ns() -> u64 { ic_cdk::api::time() }
 
+// 旧版快照里的事件 Vec（新 State 已无此字段，candid 解码时忽略多余字段）
+#[derive(Deserialize,CandidType)]
+struct LegacyEvents{ events:Vec<EventV1> }
+
 #[ic_cdk::pre_upgrade]
 fn pre_upgrade(){
-  STATE.with(|s| ic_cdk::storage::stable_save((s.borrow().clone(),)).expect("stable_save"));
+  STATE.with(|s| crate::memory::save_upgrade(&*s.borrow()));
 }
 
 #[ic_cdk::post_upgrade]
 fn post_upgrade(){
-  if let Ok((loaded,))=ic_cdk::storage::stable_restore::<(State,)>(){ STATE.with(|s| *s.borrow_mut()=loaded ); }
+  if crate::memory::is_legacy_layout() {
+    // 从 stable_save 裸格式升级：先读出 State 与旧事件，再交给 MemoryManager（会覆盖裸内存）
+    let (loaded,)=ic_cdk::storage::stable_restore::<(State,)>().expect("stable_restore");
+    let (legacy,)=ic_cdk::storage::stable_restore::<(LegacyEvents,)>().expect("stable_restore events");
+    STATE.with(|s|{
+      let mut s=s.borrow_mut();
+      *s=loaded;
+      events::migrate_legacy(&mut s, legacy.events);
+    });
+  } else if let Some(loaded)=crate::memory::load_upgrade::<State>() {
+    STATE.with(|s| *s.borrow_mut()=loaded );
+  }
+  // 升级后 certified data 被清空，需重新认证（ICRC-3 tip + 池子状态）
+  crate::certified::certify();
+  // 定时器不随升级保存，按 State.jobs 重新挂载
+  crate::jobs::start();
+}
+
+#[ic_cdk::init]
+fn init(){
+  crate::certified::certify();
+  crate::jobs::start();
 }
 
@@ -9,6 +9,7 @@ pub struct HourBucket {
     pub volume_e6: u128,  // 成交量近似 (dx+dy)/2
     pub fee_e6: u128,     // 手续费（输入侧 fee）
     pub swaps: u32,       // 成交笔数
+    pub virtual_price_e6: Option<u128>, // 该小时最近一次 virtual price 快照（定时任务写入）；Option 以兼容旧状态
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -32,7 +33,7 @@ impl RollingStats {
     fn reset_all(&mut self, hour_now: u64) {
         self.base_hour = hour_now - (HOURS_RING as u64) + 1;
         for i in 0..HOURS_RING {
-            self.buckets[i] = HourBucket { ts_hour: self.base_hour + i as u64, volume_e6: 0, fee_e6: 0, swaps: 0 };
+            self.buckets[i] = HourBucket { ts_hour: self.base_hour + i as u64, volume_e6: 0, fee_e6: 0, swaps: 0, virtual_price_e6: None };
         }
     }
     fn ensure_advanced(&mut self, hour_now: u64) {
@@ -45,7 +46,7 @@ impl RollingStats {
         for i in 0..HOURS_RING {
             let h = self.base_hour + i as u64;
             if self.buckets[i].ts_hour != h {
-                self.buckets[i] = HourBucket { ts_hour: h, volume_e6: 0, fee_e6: 0, swaps: 0 };
+                self.buckets[i] = HourBucket { ts_hour: h, volume_e6: 0, fee_e6: 0, swaps: 0, virtual_price_e6: None };
             }
         }
     }
@@ -59,6 +60,14 @@ impl RollingStats {
         self.buckets[idx].fee_e6 = self.buckets[idx].fee_e6.saturating_add(fee_e6);
         self.buckets[idx].swaps = self.buckets[idx].swaps.saturating_add(1);
     }
+    /// 推进到当前小时（无成交时由定时任务调用）并记录 virtual price
+    pub fn snapshot_virtual_price(&mut self, now_sec: u64, vp_e6: u128) {
+        let hour_now = now_sec / 3600;
+        self.ensure_advanced(hour_now);
+        let idx = (hour_now - self.base_hour) as usize;
+        if idx >= HOURS_RING { return; }
+        self.buckets[idx].virtual_price_e6 = Some(vp_e6);
+    }
     pub fn sum_last_hours(&self, now_sec: u64, hours: u32) -> (u128, u128, u32) {
         let hour_now = now_sec / 3600;
         let start_hour = hour_now.saturating_sub(hours as u64 - 1);
@@ -82,7 +91,7 @@ impl RollingStats {
             if let Some(ii) = idx {
                 if ii < HOURS_RING { out.push(self.buckets[ii].clone()); continue; }
             }
-            out.push(HourBucket { ts_hour: h, volume_e6: 0, fee_e6: 0, swaps: 0 });
+            out.push(HourBucket { ts_hour: h, volume_e6: 0, fee_e6: 0, swaps: 0, virtual_price_e6: None });
         }
         out
     }
@@ -1,9 +1,13 @@
 // canisters/vaultpair/src/swap/mod.rs
 use crate::{
-    types::{TokenId, AmountE6, QuoteOut, SwapArgs},
-    state::{STATE, skey},
+    types::{Account, Coin, AmountE6, QuoteOut, SwapArgs},
+    state::{State, STATE, skey},
+    math::{invariant, stableswap::{self, FEE_DENOMINATOR}},
     error::{Result, Error},
-    math::stableswap,
+    oracle,
+    icrc3::{self, Icrc3Value},
+    events::{self, Event},
+    pools::{self, PoolId, PoolKind, PoolState, POOL0},
     positions, // 手续费入金库/指数
 };
 use num_bigint::BigUint;
@@ -12,106 +16,154 @@ const E6: u128 = 1_000_000;
 const A_PRECISION: u128 = 1_000_000;
 
 #[inline]
-fn orient(token_in: &TokenId, token_out: &TokenId, usdc: u128, usdt: u128)
-    -> Option<(bool /*in=USDC?*/, u128 /*rin*/, u128 /*rout*/)>
-{
-    match (token_in, token_out) {
-        (TokenId::USDC, TokenId::USDT) => Some((true,  usdc, usdt)),
-        (TokenId::USDT, TokenId::USDC) => Some((false, usdt, usdc)),
-        _ => None,
-    }
+pub(crate) fn normalize_amp(a_raw: u128) -> u128 {
+    if a_raw < A_PRECISION { a_raw.saturating_mul(A_PRECISION) } else { a_raw }
 }
 
-#[inline]
-fn normalize_amp(a_raw: u128) -> u128 {
-    if a_raw < A_PRECISION { a_raw.saturating_mul(A_PRECISION) } else { a_raw }
+/// 池子的 offpeg_fee_multiplier（FEE_DENOMINATOR 口径；未设置 = 1x，即固定费率）
+pub fn offpeg_multiplier(p: &PoolState) -> u128 {
+    p.offpeg_fee_multiplier.map(|m| m as u128).unwrap_or(FEE_DENOMINATOR)
 }
 
-pub fn quote(token_in: TokenId, token_out: TokenId, dx_e6: AmountE6) -> QuoteOut {
-    let (usdc, usdt, a_amp_raw, fee_bps) = STATE.with(|s| {
-        let s = s.borrow();
-        (s.pool.reserve_usdc, s.pool.reserve_usdt, s.pool.a_amp as u128, s.pool.fee_bps)
-    });
+/// 统一计价（quote / swap / swap_live 共用）：按池型报价（StableSwap 用动态费率，见 stableswap::dynamic_fee；其余池型用固定费率）+ 脱锚保护（oracle::guard，仅 0 号池）。
+/// i 为输入币下标，rin / rout 为本次计价所用储备（swap_live 用 live 余额）。返回 (dy, fee_in, 实际费率)
+pub fn price_swap(st: &State, p: &PoolState, i: usize, rin: u128, rout: u128, dx: u128, now_sec: u64) -> Result<(u128, u128, u128)> {
+    let amp = normalize_amp(p.a_amp as u128);
+    let base = stableswap::bps_to_fee(p.fee_bps as u32);
+    let (dy, fee, rate) = match p.kind {
+        PoolKind::StableSwap => stableswap::quote_dx_to_dy_book(amp, rin, rout, dx, base, offpeg_multiplier(p))?,
+        _ => {
+            let (dy, fee) = p.invariant(i).get_dy(rin, rout, dx, base)?;
+            // CryptoSwap 的费率随失衡程度变化，按折算后的输入侧手续费反推
+            let rate = if p.kind == PoolKind::CryptoSwap { fee.saturating_mul(FEE_DENOMINATOR) / dx.max(1) } else { base };
+            (dy, fee, rate)
+        }
+    };
+    // 预言机与脱锚保护只覆盖 pool 0；其它池只收基础费率
+    if p.id != POOL0 { return Ok((dy, fee, rate)); }
+    let (rin1, rout1) = (rin.saturating_add(dx.saturating_sub(fee)), rout.saturating_sub(dy));
+    let (before, after) = if i == 0 { ((rin, rout), (rin1, rout1)) } else { ((rout, rin), (rout1, rin1)) };
+    let extra = oracle::guard(st, amp, before, after, now_sec)?;
+    if extra == 0 { return Ok((dy, fee, rate)); }
+    let rate = rate.saturating_add(extra).min(FEE_DENOMINATOR);
+    let (dy, fee, _) = stableswap::quote_dx_to_dy_book(amp, rin, rout, dx, rate, FEE_DENOMINATOR)?;
+    Ok((dy, fee, rate))
+}
+
+/// 池内边际价格（不含手续费，e6 口径：1 个 coins[i] 可换多少 coins[j]）
+pub fn spot_price_e6(p: &PoolState, i: usize, j: usize) -> Option<u128> {
+    invariant::spot_price_e6(&*p.invariant(i), p.reserves[i], p.reserves[j])
+}
 
+pub fn quote_in(st: &State, pool: PoolId, token_in: Coin, token_out: Coin, dx_e6: AmountE6, now_sec: u64) -> Result<QuoteOut> {
+    let p = pools::load(st, pool)?;
+    let (i, j) = p.orient(token_in, token_out).ok_or(Error::InvalidInput)?;
     if dx_e6 == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 };
+        let fee_rate_e10 = stableswap::bps_to_fee(p.fee_bps as u32) as u64;
+        return Ok(QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6, fee_rate_e10 });
     }
+    // 与 swap 同口径：动态费率 + 脱锚保护（可能拒绝或加收手续费）
+    let (dy, fee_e6, rate) = price_swap(st, &p, i, p.reserves[i], p.reserves[j], dx_e6, now_sec)?;
+    let price_e6 = dy.saturating_mul(E6) / dx_e6;
+    Ok(QuoteOut { dy_e6: dy, fee_e6, price_e6, fee_rate_e10: rate as u64 })
+}
 
-    let (is_usdc_in, rin, rout) = match orient(&token_in, &token_out, usdc, usdt) {
-        Some(x) => x,
-        None => return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 },
-    };
-    if rin == 0 || rout == 0 {
-        return QuoteOut { dy_e6: 0, fee_e6: 0, price_e6: E6 };
+pub fn quote(pool: PoolId, token_in: Coin, token_out: Coin, dx_e6: AmountE6) -> Result<QuoteOut> {
+    STATE.with(|s| quote_in(&s.borrow(), pool, token_in, token_out, dx_e6, crate::state::now()))
+}
+
+/// 成交落账：手续费记入 fee_vault（不进储备），净投入进储备，写回池子并记统计（0 号池同时更新预言机）。
+/// CryptoSwap 的手续费留在池内（计入 xcp_profit 供 repeg），投入全额进储备；其内部价格更新失败时不写回
+pub(crate) fn apply_trade(st: &mut State, p: &mut PoolState, (i, j): (usize, usize), dx: u128, dy: u128, fee_e6: u128, now_sec: u64) -> Result<()> {
+    if p.kind == PoolKind::CryptoSwap {
+        p.reserves[i] = p.reserves[i].saturating_add(dx);
+        p.reserves[j] = p.reserves[j].saturating_sub(dy);
+        pools::tweak_crypto(p, trade_price(i, dx, dy), now_sec)?;
+    } else {
+        positions::accrue_fee(p, i, fee_e6);
+        p.reserves[i] = p.reserves[i].saturating_add(dx.saturating_sub(fee_e6));
+        p.reserves[j] = p.reserves[j].saturating_sub(dy);
     }
+    pools::store(st, p)?;
+    pools::stats_mut(st, p.id)?.record_swap(now_sec, dx, dy, fee_e6);
+    if p.id == POOL0 { oracle::record_trade(st, now_sec); }
+    Ok(())
+}
+
+/// 成交价（coins[1] 以 coins[0] 计价，1e18）；数额太小时取整噪声大，返回 None 由曲线求现价
+fn trade_price(i: usize, dx: u128, dy: u128) -> Option<u128> {
+    const E18: u128 = 1_000_000_000_000_000_000;
+    if dx < 1_000 || dy < 1_000 { return None; }
+    Some(if i == 0 { dx.saturating_mul(E18) / dy } else { dy.saturating_mul(E18) / dx })
+}
+
+/// 路由 / 元池成交入口：只能动调用者自己的内账
+pub(crate) fn require_owner(account: &Account, caller: candid::Principal) -> Result<()> {
+    if account.owner == caller { Ok(()) } else { Err(Error::Unauthorized(caller)) }
+}
+
+/// 内账成交（swap 与后续路由共用）：校验可用额与滑点，扣输入、加输出、落账；不写事件。返回 (dy, fee_in)
+pub(crate) fn execute(st: &mut State, pool: PoolId, key: &str, (token_in, token_out): (Coin, Coin), dx: u128, min_dy: u128, now_sec: u64)
+    -> Result<(u128, u128)>
+{
+    if dx == 0 { return Err(Error::InvalidInput); }
+    let mut p = pools::load(st, pool)?;
+    let (i, j) = p.orient(token_in, token_out).ok_or_else(|| Error::unsupported(token_in, token_out))?;
+
+    // 可用额校验
+    let avail = pools::sub_balance(st, key, token_in);
+    if dx > avail { return Err(Error::insufficient(token_in, avail, dx)); }
+
+    // 计价：得到 dy 与“输入侧手续费”（空池/A 非法/未收敛均以 Error::Math 返回；动态费率与脱锚保护见 price_swap）
+    let (dy, fee_e6, _rate) = price_swap(st, &p, i, p.reserves[i], p.reserves[j], dx, now_sec)?;
+    if dy == 0 { return Err(Error::InvalidInput); } // 输入过小，输出取整为 0
+    // 最小接收量保护
+    if dy < min_dy { return Err(Error::SlippageExceeded { min: min_dy, got: dy }); }
+
+    // 先落账池子（可能被 CryptoSwap 拒绝），再扣输入可用额、加输出
+    apply_trade(st, &mut p, (i, j), dx, dy, fee_e6, now_sec)?;
+    pools::pay_in(st, pool, key, token_in, dx);
+    pools::pay_out(st, pool, key, token_out, dy);
+    Ok((dy, fee_e6))
+}
 
-    let amp = normalize_amp(a_amp_raw);
-    let (dy, fee_e6) = stableswap::quote_dx_to_dy(amp, rin, rout, dx_e6 as u128, fee_bps as u32);
-    let price_e6 = if dx_e6 > 0 { dy.saturating_mul(E6) / (dx_e6 as u128) } else { E6 };
-    let _ = is_usdc_in;
-    QuoteOut { dy_e6: dy, fee_e6, price_e6 }
+/// 一笔已成交的内账 swap（写 ICRC-3 / 事件用）
+pub(crate) struct Fill { pub pool: PoolId, pub token_in: Coin, pub token_out: Coin, pub dx: u128, pub dy: u128, pub fee: u128 }
+
+/// ICRC-3 留痕 + Swap 事件（认证与溢出由调用方在释放借用后 icrc3::commit）；hop = 路由中的第几跳
+pub(crate) fn log_fill(st: &mut State, account: &Account, f: &Fill, hop: Option<usize>) {
+    let mut tx = vec![
+        ("acct", Icrc3Value::account(account)),
+        ("tin", Icrc3Value::token(f.token_in)),
+        ("tout", Icrc3Value::token(f.token_out)),
+        ("dx", Icrc3Value::nat(f.dx)),
+        ("dy", Icrc3Value::nat(f.dy)),
+        ("fee", Icrc3Value::nat(f.fee)),
+    ];
+    if f.pool != POOL0 { tx.push(("pool", Icrc3Value::nat(f.pool))); }
+    if let Some(h) = hop { tx.push(("hop", Icrc3Value::nat(h as u64))); }
+    let block = icrc3::append(st, icrc3::BT_SWAP, ic_cdk::api::time(), tx);
+    // Metapool 的 LP 一侧同时记 1xfer，按日志重放可还原 LP 余额
+    if f.token_in == Coin::LP { pools::log_lp_move(st, f.pool, account.owner, f.dx, true); }
+    if f.token_out == Coin::LP { pools::log_lp_move(st, f.pool, account.owner, f.dy, false); }
+    let snap = pools::snapshot(st, f.pool);
+    events::append(st, Event::Swap {
+        who: account.owner.to_text(), sub: account.subaccount.clone(),
+        token_in: f.token_in, token_out: f.token_out,
+        dx_e6: f.dx, dy_e6: f.dy, fee_e6: f.fee,
+        pool: snap, pool_id: Some(f.pool),
+        ledger_blocks: vec![], icrc3_block: Some(block), ts: crate::state::now(),
+    });
 }
 
 pub fn swap(args: SwapArgs) -> Result<BigUint> {
     STATE.with(|cell| {
         let mut st = cell.borrow_mut();
-
-        // 入参与方向
+        let pool = args.pool.unwrap_or(POOL0);
         let key = skey(&args.account.owner);
-        let dx  = args.dx_e6 as u128;
-        if dx == 0 { return Err("amountIn=0".into()); }
-
-        let (is_usdc_in, rin, rout) = match orient(&args.token_in, &args.token_out,
-                                                   st.pool.reserve_usdc, st.pool.reserve_usdt) {
-            Some(x) => x,
-            None => return Err("unsupported token pair".into()),
-        };
-
-        // 可用额校验
-        if is_usdc_in {
-            let avail = *st.user_sub_usdc.get(&key).unwrap_or(&0);
-            if dx > avail { return Err("insufficient USDC in subaccount".into()); }
-        } else {
-            let avail = *st.user_sub_usdt.get(&key).unwrap_or(&0);
-            if dx > avail { return Err("insufficient USDT in subaccount".into()); }
-        }
-        if rin == 0 || rout == 0 { return Err("pool empty".into()); }
-
-        // 计价：得到 dy 与“输入侧手续费” fee_e6
-        let amp = normalize_amp(st.pool.a_amp as u128);
-        let (dy, fee_e6) = stableswap::quote_dx_to_dy(amp, rin, rout, dx, st.pool.fee_bps as u32);
-        if dy == 0 { return Err("dy=0".into()); }
-
-        // 最小接收量保护
-        let min_dy = args.min_dy_e6 as u128;
-        if dy < min_dy { return Err("slippage".into()); }
-
-        // 手续费记入 fee_vault（不进储备）
-        positions::accrue_swap_fee(args.token_in.clone(), fee_e6);
-
-        // 净投入（进入池储备）
-        let dx_net = dx.saturating_sub(fee_e6);
-
-        if is_usdc_in {
-            // 扣 USDC，可用额；加 USDT
-            let u0 = *st.user_sub_usdc.get(&key).unwrap_or(&0);
-            let v0 = *st.user_sub_usdt.get(&key).unwrap_or(&0);
-            st.user_sub_usdc.insert(key.clone(), u0.saturating_sub(dx));
-            st.user_sub_usdt.insert(key.clone(), v0.saturating_add(dy));
-
-            // 储备：只加净投入
-            st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_add(dx_net);
-            st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_sub(dy);
-        } else {
-            let u0 = *st.user_sub_usdt.get(&key).unwrap_or(&0);
-            let v0 = *st.user_sub_usdc.get(&key).unwrap_or(&0);
-            st.user_sub_usdt.insert(key.clone(), u0.saturating_sub(dx));
-            st.user_sub_usdc.insert(key.clone(), v0.saturating_add(dy));
-
-            st.pool.reserve_usdt = st.pool.reserve_usdt.saturating_add(dx_net);
-            st.pool.reserve_usdc = st.pool.reserve_usdc.saturating_sub(dy);
-        }
-
+        let dx = args.dx_e6 as u128;
+        let (dy, fee) = execute(&mut st, pool, &key, (args.token_in, args.token_out), dx, args.min_dy_e6, crate::state::now())?;
+        log_fill(&mut st, &args.account, &Fill { pool, token_in: args.token_in, token_out: args.token_out, dx, dy, fee }, None);
         Ok(BigUint::from(dy))
     })
 }
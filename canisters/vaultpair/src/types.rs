@@ -13,8 +13,42 @@ pub struct Available {
     CandidType, Serialize, Deserialize,
     Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash
 )]
+/// 外部账本上的代币（已发布接口里的 TokenId）
 pub enum TokenId { USDC, USDT, ICP, BOB }
 
+#[derive(
+    CandidType, Serialize, Deserialize,
+    Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash
+)]
+/// 池子里的币：外部代币，或 LP = 0 号池（USDC/USDT）的 LP 份额（只作 metapool 的第二个币；余额即 user_shares，见 pools::sub_balance）。
+/// 与 TokenId 分开，已发布接口里的 TokenId（如 get_events）不因元池多出分支
+#[allow(clippy::upper_case_acronyms)] // 分支名与 TokenId 保持一致
+pub enum Coin { USDC, USDT, ICP, BOB, LP }
+
+impl From<TokenId> for Coin {
+    fn from(t: TokenId) -> Self {
+        match t {
+            TokenId::USDC => Coin::USDC,
+            TokenId::USDT => Coin::USDT,
+            TokenId::ICP => Coin::ICP,
+            TokenId::BOB => Coin::BOB,
+        }
+    }
+}
+
+impl Coin {
+    /// 对应的外部代币；LP 为 None
+    pub fn token(self) -> Option<TokenId> {
+        match self {
+            Coin::USDC => Some(TokenId::USDC),
+            Coin::USDT => Some(TokenId::USDT),
+            Coin::ICP => Some(TokenId::ICP),
+            Coin::BOB => Some(TokenId::BOB),
+            Coin::LP => None,
+        }
+    }
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Account { pub owner: Principal, pub subaccount: Option<Vec<u8>> }
 
@@ -29,15 +63,23 @@ pub struct PoolInfo {
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
-pub struct QuoteOut { pub dy_e6: AmountE6, pub fee_e6: AmountE6, pub price_e6: u128 }
+pub struct QuoteOut {
+    pub dy_e6: AmountE6,
+    pub fee_e6: AmountE6,
+    pub price_e6: u128,
+    /// 实际费率（含动态费率与脱锚加收），1e10 = 100%
+    pub fee_rate_e10: u64,
+}
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct SwapArgs {
     pub account: Account,
-    pub token_in: TokenId,
-    pub token_out: TokenId,
+    pub token_in: Coin,
+    pub token_out: Coin,
     pub dx_e6: AmountE6,
     pub min_dy_e6: AmountE6,
+    /// 池子（见 pools）；None = 0 号 USDC/USDT 池
+    pub pool: Option<crate::pools::PoolId>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
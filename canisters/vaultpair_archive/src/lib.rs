@@ -0,0 +1,153 @@
+// canisters/vaultpair_archive/src/lib.rs
+// vaultpair 的 archive：只接受 vaultpair 按序推送的旧 ICRC-3 区块与旧事件，并提供查询。
+use candid::{CandidType, Int, Nat, Principal};
+use num_traits::cast::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+const MAX_BLOCKS_PER_RESPONSE: u64 = 1_000;
+
+/// 与 vaultpair::icrc3::Icrc3Value 同构（candid 层面一致即可）
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum Icrc3Value {
+    Blob(serde_bytes::ByteBuf),
+    Text(String),
+    Nat(Nat),
+    Int(Int),
+    Array(Vec<Icrc3Value>),
+    Map(Vec<(String, Icrc3Value)>),
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetBlocksArgs { pub start: Nat, pub length: Nat }
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BlockWithId { pub id: Nat, pub block: Icrc3Value }
+
+candid::define_function!(pub GetBlocksCallback : (Vec<GetBlocksArgs>) -> (GetBlocksResult) query);
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ArchivedBlocks { pub args: Vec<GetBlocksArgs>, pub callback: GetBlocksCallback }
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct GetBlocksResult {
+    pub log_length: Nat,
+    pub blocks: Vec<BlockWithId>,
+    pub archived_blocks: Vec<ArchivedBlocks>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct State {
+    // 唯一允许写入的 vaultpair canister
+    ledger: Principal,
+    // blocks[i] 的全局编号为 first_index + i
+    first_index: u64,
+    blocks: Vec<Icrc3Value>,
+    // 事件：vaultpair 的 stable 存储字节（带版本标签的 candid，见 vaultpair events::StoredEvent），原样保存；Option 以兼容旧状态
+    events: Option<EventStore>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct EventStore {
+    first_id: u64,
+    events: Vec<serde_bytes::ByteBuf>,
+}
+
+thread_local! {
+    static STATE: RefCell<Option<State>> = const { RefCell::new(None) };
+}
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    STATE.with(|s| f(s.borrow_mut().as_mut().expect("archive not initialized")))
+}
+
+#[ic_cdk::init]
+fn init(ledger: Principal) {
+    STATE.with(|s| *s.borrow_mut() = Some(State { ledger, first_index: 0, blocks: vec![], events: None }));
+}
+
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    let st = STATE.with(|s| s.borrow().clone());
+    ic_cdk::storage::stable_save((st,)).expect("stable_save");
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    let (st,): (Option<State>,) = ic_cdk::storage::stable_restore().expect("stable_restore");
+    STATE.with(|s| *s.borrow_mut() = st);
+}
+
+/// 仅 ledger 可调；start 必须紧接已存区块（首批可从任意编号开始）
+#[ic_cdk::update]
+fn append_blocks(start: u64, blocks: Vec<Icrc3Value>) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    with_state(|st| {
+        if caller != st.ledger {
+            return Err(format!("caller {} is not the ledger", caller));
+        }
+        if st.blocks.is_empty() {
+            st.first_index = start;
+        }
+        let next = st.first_index + st.blocks.len() as u64;
+        if start != next {
+            return Err(format!("expected start {}, got {}", next, start));
+        }
+        st.blocks.extend(blocks);
+        Ok(())
+    })
+}
+
+/// 仅 ledger 可调；start 为首条事件 id，须紧接已存事件（首批可从任意 id 开始）
+#[ic_cdk::update]
+fn append_events(start: u64, events: Vec<serde_bytes::ByteBuf>) -> Result<(), String> {
+    let caller = ic_cdk::caller();
+    with_state(|st| {
+        if caller != st.ledger {
+            return Err(format!("caller {} is not the ledger", caller));
+        }
+        let store = st.events.get_or_insert_with(Default::default);
+        if store.events.is_empty() {
+            store.first_id = start;
+        }
+        let next = store.first_id + store.events.len() as u64;
+        if start != next {
+            return Err(format!("expected start {}, got {}", next, start));
+        }
+        store.events.extend(events);
+        Ok(())
+    })
+}
+
+/// 按 id 读取事件：返回 (id, 事件存储字节)
+#[ic_cdk::query]
+fn get_events(start: u64, length: u64) -> Vec<(u64, serde_bytes::ByteBuf)> {
+    with_state(|st| {
+        let Some(store) = st.events.as_ref() else { return vec![] };
+        let end = store.first_id + store.events.len() as u64;
+        let s = start.max(store.first_id);
+        let e = s.saturating_add(length.min(MAX_BLOCKS_PER_RESPONSE)).min(end);
+        (s..e).map(|id| (id, store.events[(id - store.first_id) as usize].clone())).collect()
+    })
+}
+
+#[ic_cdk::query]
+fn icrc3_get_blocks(args: Vec<GetBlocksArgs>) -> GetBlocksResult {
+    with_state(|st| {
+        let end_all = st.first_index + st.blocks.len() as u64;
+        let mut budget = MAX_BLOCKS_PER_RESPONSE;
+        let mut blocks = Vec::new();
+        for a in args {
+            let s = a.start.0.to_u64().unwrap_or(u64::MAX).max(st.first_index);
+            let len = a.length.0.to_u64().unwrap_or(u64::MAX).min(budget);
+            let e = s.saturating_add(len).min(end_all);
+            for id in s..e {
+                blocks.push(BlockWithId { id: Nat::from(id), block: st.blocks[(id - st.first_index) as usize].clone() });
+            }
+            budget -= e.saturating_sub(s);
+        }
+        GetBlocksResult { log_length: Nat::from(end_all), blocks, archived_blocks: vec![] }
+    })
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,123 @@
+// canisters/vaultpair_verify/src/lib.rs
+// 客户端校验 vaultpair 的认证读接口（get_pool_info_certified）：
+//   1) 证书签名（含子网 delegation）与时效；
+//   2) 证书中 canister/<id>/certified_data == witness 根摘要；
+//   3) 从 witness 的 pool/* 叶子还原池子字段，并与响应正文逐项比对。
+// 树结构见 canisters/vaultpair/src/certified.rs。
+use candid::{CandidType, Nat, Principal};
+use ic_cbor::{CertificateToCbor, HashTreeToCbor};
+use ic_certificate_verification::VerifyCertificate;
+use ic_certification::{Certificate, HashTree, LookupResult};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+/// 主网根公钥（DER）；本地 dfx 网络请改用 `dfx ping` 返回的 root_key
+pub const IC_ROOT_KEY_DER: &str = "308182301d060d2b0601040182dc7c0503010201060c2b0601040182dc7c05030201036100814c0e6ec71fab583b08bd81373c255c3c371b2e84863c98a4f1e08b74235d14fb5d9c0cd546d9685f913a0c0b2cc5341583bf4b4392e467db96d65b9bb4cb717112f8472e0d5a4d14505ffd7484b01291091c5f87b98883463f98091a0baaae";
+
+/// 默认允许的证书时延：5 分钟
+pub const DEFAULT_MAX_AGE_NS: u128 = 5 * 60 * 1_000_000_000;
+
+/// 与 vaultpair.did 中 PoolInfo 一致
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolInfo {
+    pub a_amp: u32,
+    pub fee_bps: u16,
+    pub reserve_usdc: u128,
+    pub reserve_usdt: u128,
+    pub total_shares: u128,
+    pub virtual_price_e6: u128,
+}
+
+/// 与 vaultpair.did 中 PoolInfoCertified 一致
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PoolInfoCertified {
+    pub pool: PoolInfo,
+    pub certificate: serde_bytes::ByteBuf,
+    pub witness: serde_bytes::ByteBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// 证书 / witness 无法解析
+    Decode(String),
+    /// 签名、delegation 或时效校验失败
+    Certificate(String),
+    /// 证书中没有该 canister 的 certified_data
+    MissingCertifiedData,
+    /// witness 根摘要与 certified_data 不符
+    RootMismatch,
+    /// witness 缺少某个 pool 字段，或叶子不是合法的 LEB128
+    BadLeaf(&'static str),
+    /// 响应正文与已认证的字段不一致
+    FieldMismatch(&'static str),
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{:?}", self) }
+}
+
+impl std::error::Error for VerifyError {}
+
+pub fn root_key_der() -> Vec<u8> {
+    (0..IC_ROOT_KEY_DER.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&IC_ROOT_KEY_DER[i..i + 2], 16).expect("hex"))
+        .collect()
+}
+
+fn nat_leaf(tree: &HashTree, name: &'static str) -> Result<u128, VerifyError> {
+    let bytes = match tree.lookup_path([b"pool".as_slice(), name.as_bytes()]) {
+        LookupResult::Found(b) => b,
+        _ => return Err(VerifyError::BadLeaf(name)),
+    };
+    Nat::decode(&mut &bytes[..]).ok().and_then(|n| n.0.to_u128()).ok_or(VerifyError::BadLeaf(name))
+}
+
+/// 只校验 witness：根摘要须等于 certified_data（来自已验证的证书），返回其中认证的池子字段
+pub fn verify_witness(certified_data: &[u8], witness_cbor: &[u8]) -> Result<PoolInfo, VerifyError> {
+    let tree = HashTree::from_cbor(witness_cbor).map_err(|e| VerifyError::Decode(e.to_string()))?;
+    if tree.digest().as_slice() != certified_data {
+        return Err(VerifyError::RootMismatch);
+    }
+    let small = |name: &'static str| nat_leaf(&tree, name);
+    Ok(PoolInfo {
+        a_amp: small("a_amp")?.try_into().map_err(|_| VerifyError::BadLeaf("a_amp"))?,
+        fee_bps: small("fee_bps")?.try_into().map_err(|_| VerifyError::BadLeaf("fee_bps"))?,
+        reserve_usdc: small("reserve_usdc")?,
+        reserve_usdt: small("reserve_usdt")?,
+        total_shares: small("total_shares")?,
+        virtual_price_e6: small("virtual_price_e6")?,
+    })
+}
+
+/// 完整校验 get_pool_info_certified 的响应。now_ns 为本地当前时间（ns），max_age_ns 为允许的证书时延
+pub fn verify_pool_info(
+    resp: &PoolInfoCertified,
+    canister_id: Principal,
+    root_key_der: &[u8],
+    now_ns: u128,
+    max_age_ns: u128,
+) -> Result<PoolInfo, VerifyError> {
+    let cert = Certificate::from_cbor(&resp.certificate).map_err(|e| VerifyError::Decode(e.to_string()))?;
+    cert.verify(canister_id.as_slice(), root_key_der, &now_ns, &max_age_ns)
+        .map_err(|e| VerifyError::Certificate(e.to_string()))?;
+    let certified_data = match cert.tree.lookup_path([b"canister".as_slice(), canister_id.as_slice(), b"certified_data"]) {
+        LookupResult::Found(d) => d,
+        _ => return Err(VerifyError::MissingCertifiedData),
+    };
+    let pool = verify_witness(certified_data, &resp.witness)?;
+
+    let p = &resp.pool;
+    let checks: [(&'static str, bool); 6] = [
+        ("a_amp", p.a_amp == pool.a_amp),
+        ("fee_bps", p.fee_bps == pool.fee_bps),
+        ("reserve_usdc", p.reserve_usdc == pool.reserve_usdc),
+        ("reserve_usdt", p.reserve_usdt == pool.reserve_usdt),
+        ("total_shares", p.total_shares == pool.total_shares),
+        ("virtual_price_e6", p.virtual_price_e6 == pool.virtual_price_e6),
+    ];
+    match checks.iter().find(|(_, ok)| !ok) {
+        Some((name, _)) => Err(VerifyError::FieldMismatch(name)),
+        None => Ok(pool),
+    }
+}
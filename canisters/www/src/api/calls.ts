@@ -124,7 +124,7 @@ export async function sync_user_ledger_all(identity?: any): Promise<string> {
   const res = await (a as any).sync_user_ledger_all();
   if (typeof res === "string") return res;
   if (res && "ok" in res) return String(res.ok);
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   return String(res);
 }
 
@@ -137,7 +137,7 @@ export async function refresh_available_for(
   const a = await makeActor(identity);
   const res = await (a as any).refresh_available_for(user);
   if (res && "ok" in res) return "scheduled";
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   return "scheduled";
 }
 
@@ -215,10 +215,16 @@ export async function ensure_allowance_for_user(
   return Boolean(ok);
 }
 
+// 池子编号（candid opt nat32）；不传 = 0 号 USDC/USDT 池
+const optPool = (pool?: number) => (pool === undefined ? [] : [pool]);
+
 /* ---------------- Swap：改用 quote_live / swap_live ---------------- */
-export async function quote(token_in: TokenId, token_out: TokenId, dxNat: number) {
+export async function quote(token_in: TokenId, token_out: TokenId, dxNat: number, pool?: number) {
   const a = await makeActor();
-  return (a as any).quote_live(token_in, token_out, toE6(dxNat));
+  const res = await (a as any).quote_live(token_in, token_out, toE6(dxNat), optPool(pool));
+  if (res && "ok" in res) return res.ok;
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
+  return res;
 }
 
 export async function swap(args: {
@@ -227,6 +233,7 @@ export async function swap(args: {
   token_out: TokenId;
   dx_e6: number;      // 自然数口径，例如 10.5
   min_dy_e6?: number; // 自然数口径
+  pool?: number;
 }) {
   const a = await makeActor();
   const acc = accountOf(args.account.owner);
@@ -236,9 +243,10 @@ export async function swap(args: {
     token_out: args.token_out,
     dx_e6: toE6(args.dx_e6),
     min_dy_e6: toE6(args.min_dy_e6 ?? 0),
+    pool: optPool(args.pool),
   });
   if (res && "ok" in res) return res.ok;
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   return res;
 }
 
@@ -262,18 +270,18 @@ export async function get_pool_info(): Promise<PoolInfo> {
   };
 }
 
-export async function get_user_position(account: Account): Promise<number> {
+export async function get_user_position(account: Account, pool?: number): Promise<number> {
   const a = await makeActor();
   const acc = accountOf(account.owner);
-  const r = await (a as any).get_user_position(acc);
+  const r = await (a as any).get_user_position(acc, optPool(pool));
   const shares = (r && (r.shares ?? r)) || 0;
   return Number(shares) / 1_000_000;
 }
 
-export async function get_unclaimed_fee(account: Account): Promise<{ usdc: number; usdt: number }> {
+export async function get_unclaimed_fee(account: Account, pool?: number): Promise<{ usdc: number; usdt: number }> {
   const a = await makeActor();
   const acc = accountOf(account.owner);
-  const r = await (a as any).get_unclaimed_fee(acc);
+  const r = await (a as any).get_unclaimed_fee(acc, optPool(pool));
   const toNum = (x: any) => Number(typeof x === "bigint" ? x : x ?? 0) / 1_000_000;
   return { usdc: toNum(r.usdc), usdt: toNum(r.usdt) };
 }
@@ -282,11 +290,13 @@ export async function add_liquidity(
   account: Account,
   usdcNat: number,
   usdtNat: number,
-  identity?: any
+  identity?: any,
+  pool?: number
 ): Promise<number> {
   const a = await makeActor(identity);
   const acc = accountOf(account.owner);
-  const r = await (a as any).add_liquidity(acc, toE6(usdcNat), toE6(usdtNat));
+  const r = await (a as any).add_liquidity(acc, toE6(usdcNat), toE6(usdtNat), optPool(pool));
+  if (r && "err" in r) throw new Error(candidErrText(r.err));
   const ok = (r && "ok" in r) ? r.ok : r;
   const shares = (ok && ok.shares) || ok || 0n;
   return Number(shares) / 1_000_000;
@@ -295,11 +305,13 @@ export async function add_liquidity(
 export async function remove_liquidity(
   account: Account,
   sharesNat: number,
-  identity?: any
+  identity?: any,
+  pool?: number
 ): Promise<{ usdc: number; usdt: number }> {
   const a = await makeActor(identity);
   const acc = accountOf(account.owner);
-  const r = await (a as any).remove_liquidity(acc, toE6(sharesNat));
+  const r = await (a as any).remove_liquidity(acc, toE6(sharesNat), optPool(pool));
+  if (r && "err" in r) throw new Error(candidErrText(r.err));
   const ok = (r && "ok" in r) ? r.ok : r;
   const toNum = (x: any) => Number(typeof x === "bigint" ? x : x ?? 0) / 1_000_000;
   return { usdc: toNum(ok.usdc), usdt: toNum(ok.usdt) };
@@ -308,7 +320,8 @@ export async function remove_liquidity(
 // 领取手续费
 export async function claim_fee(
   account: Account,
-  identity?: any
+  identity?: any,
+  pool?: number
 ): Promise<{ usdc: number; usdt: number }> {
   const a = await makeActor(identity);
   const acc = {
@@ -317,7 +330,8 @@ export async function claim_fee(
       : account.owner,
     subaccount: [] as any,
   };
-  const r = await (a as any).claim_fee(acc);
+  const r = await (a as any).claim_fee(acc, optPool(pool));
+  if (r && "err" in r) throw new Error(candidErrText(r.err));
   const ok = (r && "ok" in r) ? r.ok : r;
   const toNum = (x: any) => Number(typeof x === "bigint" ? x : x ?? 0) / 1_000_000;
   return { usdc: toNum(ok.usdc), usdt: toNum(ok.usdt) };
@@ -475,7 +489,7 @@ export async function admin_reconcile_pool_from_live(identity?: any): Promise<st
   const r = await (a as any).admin_reconcile_pool_from_live();
   if (typeof r === "string") return r;
   if (r && "ok" in r) return String(r.ok);
-  if (r && "err" in r) throw new Error(String(r.err));
+  if (r && "err" in r) throw new Error(candidErrText(r.err));
   return String(r);
 }
 
@@ -486,7 +500,7 @@ export type EventUI =
   | { kind: "RemoveLiq";  who: string; ts: number; usdc: number; usdt: number; shares: number }
   | { kind: "Deposit";    who: string; ts: number; token: "ckUSDC" | "ckUSDT"; amount: number }
   | { kind: "Withdraw";   who: string; ts: number; token: "ckUSDC" | "ckUSDT"; amount: number }
-  | { kind: "ClaimFee";   who: string; ts: number; usdc: number; usdt: number }; // 若后端已加
+  | { kind: "ClaimFee";   who: string; ts: number; usdc: number; usdt: number };
 
 function tokenNameOf(t: any): "ckUSDC" | "ckUSDT" {
   if (t && typeof t === "object") {
@@ -530,7 +544,8 @@ function normalizeEvent(e: any): EventUI | null {
     }
   }
 
-  // candid variant 形态
+  // candid variant 形态（v1 旧事件包在 Legacy 中；AdminAction 等运维事件不展示）
+  if (e.Legacy) return normalizeEvent(e.Legacy);
   if (e.Swap) {
     const v = e.Swap;
     return { kind: "Swap", who: String(v.who), ts: normalizeTsMs(v.ts), dx: fromE6(v.dx_e6), dy: fromE6(v.dy_e6) };
@@ -578,6 +593,26 @@ export async function get_events(
     .sort((a, b) => b.ts - a.ts);
 }
 
+// 按事件 id 从新到旧分页；cursor 传上一页的 next（首页传 null）
+export async function query_events(
+  cursor: bigint | null,
+  limit: number,
+  identity?: any
+): Promise<{ events: EventUI[]; next: bigint | null }> {
+  const a = await makeActor(identity);
+  const res = await (a as any).query_events({
+    from_id: cursor == null ? [] : [cursor],
+    limit: [limit],
+    who: [], kinds: [], from_ts: [], to_ts: [],
+    descending: [true],
+  });
+  const events = (res?.events ?? [])
+    .map((r: any) => normalizeEvent(r.event))
+    .filter(Boolean) as EventUI[];
+  const next = res?.next?.length ? BigInt(res.next[0]) : null;
+  return { events, next };
+}
+
 export async function get_events_latest(
   limit: bigint | number | string,
   identity?: any
@@ -608,7 +643,7 @@ export async function transfer_from_user_sub_to_pool(
   const nat = typeof amount_e6 === "bigint" ? amount_e6 : BigInt(String(amount_e6));
   const res = await (a as any).transfer_from_user_sub_to_pool(tokenLedgerId, userPrin, nat);
   if (res && "ok" in res) return BigInt(res.ok);
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   throw new Error("unexpected result from transfer_from_user_sub_to_pool");
 }
 
@@ -624,7 +659,7 @@ export async function transfer_from_pool_to_user_sub(
   const nat = typeof amount_e6 === "bigint" ? amount_e6 : BigInt(String(amount_e6));
   const res = await (a as any).transfer_from_pool_to_user_sub(tokenLedgerId, userPrin, nat);
   if (res && "ok" in res) return BigInt(res.ok);
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   throw new Error("unexpected result from transfer_from_pool_to_user_sub");
 }
 
@@ -649,7 +684,7 @@ export async function withdraw_from_sub(
     typeof amount_e0 === "bigint" ? amount_e0 : BigInt(String(amount_e0));
   const res = await (a as any).withdraw_from_sub(tokenCanisterId, toAcc, nat);
   if (res && "ok" in res) return String(res.ok);
-  if (res && "err" in res) throw new Error(String(res.err));
+  if (res && "err" in res) throw new Error(candidErrText(res.err));
   return String(res);
 }
 
@@ -692,6 +727,22 @@ export async function get_stats_snapshot(): Promise<StatsSnapshotUI> {
   };
 }
 
+// candid 变体错误（如 { Math: { ZeroReserve: null } } / { Internal: "..." }）→ 可读文本
+export function candidErrText(err: unknown): string {
+  if (err == null) return '未知错误';
+  if (typeof err !== 'object') return String(err);
+  const any = err as any;
+  if (typeof any.toText === 'function') return any.toText();     // Principal
+  const entries = Object.entries(any as Record<string, unknown>);
+  // 单键对象视为 candid variant：{ SlippageExceeded: { min, got } } → "SlippageExceeded(min=…, got=…)"
+  if (entries.length === 1) {
+    const [k, v] = entries[0];
+    if (v == null) return k;
+    return `${k}(${candidErrText(v)})`;
+  }
+  return entries.map(([k, v]) => `${k}=${candidErrText(v)}`).join(', ');
+}
+
 export function toErrMsg(e: unknown): string {
   try {
     if (e == null) return '未知错误';
@@ -700,7 +751,8 @@ export function toErrMsg(e: unknown): string {
     if (typeof any.message === 'string') return any.message;
     if (typeof any.Text === 'string') return any.Text;        // candid 常见
     if (any?.Err && typeof any.Err === 'string') return any.Err;
-    return JSON.stringify(any);
+    if (any?.err != null) return candidErrText(any.err);
+    return candidErrText(any);
   } catch {
     return String(e);
   }
@@ -1,5 +1,5 @@
 import React from "react";
-import { get_pool_info, fromE6, get_events, type PoolInfo, type EventUI } from "../../api/calls";
+import { get_pool_info, fromE6, query_events, type PoolInfo, type EventUI } from "../../api/calls";
 
 const fmt = (n: number) =>
   n.toLocaleString(undefined, { maximumFractionDigits: 6 });
@@ -15,7 +15,7 @@ export default function Explore() {
 
   // Recent Activity（分页）
   const [events, setEvents] = React.useState<EventUI[]>([]);
-  const [cursor, setCursor] = React.useState(0);
+  const [cursor, setCursor] = React.useState<bigint | null>(null);
   const [hasMore, setHasMore] = React.useState(true);
   const [loadingMore, setLoadingMore] = React.useState(false);
 
@@ -32,10 +32,10 @@ export default function Explore() {
     (async () => {
       try {
         setLoadingMore(true);
-        const first = await get_events(0, 20);
-        setEvents(first);
-        setCursor(first.length);
-        setHasMore(first.length >= 20);
+        const first = await query_events(null, 20);
+        setEvents(first.events);
+        setCursor(first.next);
+        setHasMore(first.next != null);
       } finally {
         setLoadingMore(false);
       }
@@ -46,10 +46,10 @@ export default function Explore() {
     if (loadingMore || !hasMore) return;
     try {
       setLoadingMore(true);
-      const batch = await get_events(cursor, 20);
-      setEvents((old) => [...old, ...batch]);
-      setCursor((c) => c + batch.length);
-      setHasMore(batch.length >= 20);
+      const batch = await query_events(cursor, 20);
+      setEvents((old) => [...old, ...batch.events]);
+      setCursor(batch.next);
+      setHasMore(batch.next != null);
     } finally {
       setLoadingMore(false);
     }
@@ -507,7 +507,7 @@ export default function LiquidityPage() {
                     onClick={async () => {
                       try {
                         const msg = await admin_reconcile_pool_from_live();
-                        alert("✅ Reconciled: " + msg);
+                        alert("✅ Reconcile report: " + msg);
                         await refresh();
                       } catch (e: any) {
                         alert("❌ Reconcile failed: " + (e?.message ?? String(e)));
@@ -515,10 +515,10 @@ export default function LiquidityPage() {
                     }}
                     className="bg-zinc-800 hover:bg-zinc-700 rounded px-3 py-2 text-sm"
                   >
-                    Reconcile Internal From Live
+                    Reconcile Report (Live vs Internal)
                   </button>
                   <span className="ml-2 text-xs text-zinc-500">
-                    (Admin only; writes back to internal reserves for alignment display)
+                    (Admin only; read-only diff by category, corrections are applied separately)
                   </span>
                 </div>
               </div>
@@ -11,6 +11,7 @@ import {
   deposit_ckusdc_to_my_sub,
   deposit_ckusdt_to_my_sub,
   get_pool_reserves_live, 
+  toErrMsg,
   type TokenId,
 } from "../../api/calls";
 import { useAuth } from "../../auth/AuthContext";
@@ -123,7 +124,14 @@ export default function SwapPage() {
 
   /* 单次报价：给定 dx（自然数）→ {dy, fee, p} */
   async function quoteDy(dxNat: number): Promise<{ dy: number; feeNat: number; p: number }> {
-    const r = await quote(sellTokenId, buyTokenId, dxNat);
+    let r: any;
+    try {
+      r = await quote(sellTokenId, buyTokenId, dxNat);
+    } catch (e) {
+      // 报价失败（空池 / 未收敛 / 溢出等）：按 0 报价展示，避免 UI 抛错
+      console.warn("[quote]", toErrMsg(e));
+      return { dy: 0, feeNat: 0, p: 0 };
+    }
     return {
       dy: Number(fromE6(r.dy_e6)),
       feeNat: Number(fromE6(r.fee_e6)),
@@ -0,0 +1,124 @@
+#!/usr/bin/env python3
+# scripts/gen_stableswap_vectors.py
+# 按 Curve 原版 Vyper 公式（StableSwap 2-coin：get_D / get_y / get_dy）生成差分测试向量，
+# 输出到 canisters/vaultpair/src/math/testdata/stableswap_vectors.csv。
+#
+# 口径换算：
+#   - Vyper 中存储的 A 实为 A * n^(n-1)，Ann = A_vyper * n = A * n^n
+#   - Rust 侧 amp_scaled = A * A_PRECISION(1e6)，ANN = amp_scaled * n^n / A_PRECISION
+#   - 因此两边对同一个 A 得到相同的 Ann；CSV 中记录的是“原始 A”
+#
+# 用法：python3 scripts/gen_stableswap_vectors.py [--seed 20240601] [--count 400]
+
+import argparse
+import csv
+import os
+import random
+
+N_COINS = 2
+
+
+def vy_get_D(xp, amp_vyper):
+    S = sum(xp)
+    if S == 0:
+        return 0
+    D = S
+    Ann = amp_vyper * N_COINS
+    for _ in range(255):
+        D_P = D
+        for x in xp:
+            D_P = D_P * D // (x * N_COINS)
+        Dprev = D
+        D = (Ann * S + D_P * N_COINS) * D // ((Ann - 1) * D + (N_COINS + 1) * D_P)
+        if D > Dprev:
+            if D - Dprev <= 1:
+                return D
+        elif Dprev - D <= 1:
+            return D
+    raise ArithmeticError("get_D did not converge")
+
+
+def vy_get_y(i, j, x, xp, amp_vyper):
+    D = vy_get_D(xp, amp_vyper)
+    c = D
+    S_ = 0
+    Ann = amp_vyper * N_COINS
+    for k in range(N_COINS):
+        if k == i:
+            _x = x
+        elif k != j:
+            _x = xp[k]
+        else:
+            continue
+        S_ += _x
+        c = c * D // (_x * N_COINS)
+    c = c * D // (Ann * N_COINS)
+    b = S_ + D // Ann
+    y = D
+    for _ in range(255):
+        y_prev = y
+        den = 2 * y + b - D
+        if den <= 0:
+            raise ArithmeticError("uint256 underflow")  # Vyper 中会直接 revert
+        y = (y * y + c) // den
+        if y > y_prev:
+            if y - y_prev <= 1:
+                return y
+        elif y_prev - y <= 1:
+            return y
+    raise ArithmeticError("get_y did not converge")
+
+
+def vy_get_dy(i, j, dx, xp, amp_vyper):
+    # fee=0 的 get_dy：dy = xp[j] - y - 1
+    y = vy_get_y(i, j, xp[i] + dx, xp, amp_vyper)
+    return xp[j] - y - 1
+
+
+def log_uniform(rng, lo, hi):
+    return max(lo, min(hi, int(10 ** rng.uniform(len(str(lo)) - 1, len(str(hi)) - 1))))
+
+
+def main():
+    ap = argparse.ArgumentParser()
+    ap.add_argument("--seed", type=int, default=20240601)
+    ap.add_argument("--count", type=int, default=400)
+    ap.add_argument(
+        "--out",
+        default=os.path.join(
+            os.path.dirname(__file__), "..", "canisters", "vaultpair", "src", "math", "testdata",
+            "stableswap_vectors.csv",
+        ),
+    )
+    args = ap.parse_args()
+
+    rng = random.Random(args.seed)
+    rows = []
+    while len(rows) < args.count:
+        a = log_uniform(rng, 1, 1_000_000)
+        x0 = log_uniform(rng, 1_000, 10 ** 15)
+        # 余额比例限制在 1:1000 以内，覆盖常见与偏斜池子
+        x1 = max(1_000, min(10 ** 15, x0 * rng.randint(1, 1000) // rng.randint(1, 1000)))
+        dx = max(1, x0 * rng.randint(1, 10_000) // 10_000)
+        amp_vyper = a * N_COINS ** (N_COINS - 1)
+        try:
+            d = vy_get_D([x0, x1], amp_vyper)
+            y = vy_get_y(0, 1, x0 + dx, [x0, x1], amp_vyper)
+        except ArithmeticError:
+            continue
+        dy = x1 - y - 1
+        if dy <= 0:
+            continue
+        rows.append((a, x0, x1, d, dx, y, dy))
+
+    os.makedirs(os.path.dirname(args.out), exist_ok=True)
+    with open(args.out, "w", newline="") as f:
+        f.write(f"# generated by scripts/gen_stableswap_vectors.py --seed {args.seed} --count {args.count}\n")
+        w = csv.writer(f, lineterminator="\n")
+        w.writerow(["a", "x0", "x1", "d", "dx", "y", "dy"])
+        w.writerows(rows)
+    print(f"[gen_stableswap_vectors] wrote {len(rows)} rows -> {args.out}")
+
+
+if __name__ == "__main__":
+    main()
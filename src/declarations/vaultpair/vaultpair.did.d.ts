@@ -2,98 +2,561 @@ import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 import type { IDL } from '@dfinity/candid';
 
+/**
+ * 由 export_candid!() 生成，勿手改：UPDATE_DID=1 cargo test did_matches_code 回写
+ */
 export interface Account {
   'owner' : Principal,
   'subaccount' : [] | [Uint8Array | number[]],
 }
-export type AmountE6 = bigint;
-export interface Available { 'usdc' : bigint, 'usdt' : bigint }
+export interface Allowance {
+  'allowance' : bigint,
+  'expires_at' : [] | [bigint],
+}
+export interface AllowanceArgs { 'account' : Account, 'spender' : Account }
+export interface ApproveArgs {
+  'fee' : [] | [bigint],
+  'memo' : [] | [Uint8Array | number[]],
+  'from_subaccount' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+  'expected_allowance' : [] | [bigint],
+  'expires_at' : [] | [bigint],
+  'spender' : Account,
+}
+export type ApproveError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'AllowanceChanged' : { 'current_allowance' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'Expired' : { 'ledger_time' : bigint } } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
+export interface ArchivedBlocks {
+  'args' : Array<GetBlocksArgs>,
+  'callback' : [Principal, string],
+}
+export interface AutoPauseConfig { 'depeg_bps' : number, 'on_drift' : boolean }
+export interface BlockWithId { 'id' : bigint, 'block' : Icrc3Value }
+export type Coin = { 'LP' : null } |
+  { 'BOB' : null } |
+  { 'ICP' : null } |
+  { 'USDC' : null } |
+  { 'USDT' : null };
+export type CorrectionKind = { 'WriteOffLedgerFees' : null } |
+  { 'WriteOffShortfall' : null } |
+  { 'AbsorbDepositToReserve' : null };
+export interface CreatePoolArgs {
+  'a_amp' : number,
+  'initial_price_e6' : [] | [bigint],
+  'kind' : [] | [PoolKind],
+  'coins' : Array<Coin>,
+  'fee_bps' : number,
+  'crypto' : [] | [CryptoParams],
+}
+export type CreatePoolResult = { 'ok' : number } |
+  { 'err' : Error };
+export interface CryptoParams {
+  'a' : bigint,
+  'adjustment_step' : bigint,
+  'mid_fee' : bigint,
+  'gamma' : bigint,
+  'fee_gamma' : bigint,
+  'out_fee' : bigint,
+  'ma_half_time_sec' : bigint,
+  'allowed_extra_profit' : bigint,
+}
+export interface CryptoPool { 'state' : CryptoState, 'params' : CryptoParams }
+export interface CryptoState {
+  'virtual_price' : bigint,
+  'last_price' : bigint,
+  'not_adjusted' : boolean,
+  'price_scale' : bigint,
+  'last_ts' : bigint,
+  'price_oracle' : bigint,
+  'xcp_profit' : bigint,
+}
 export interface CyclesInfo {
   'low' : boolean,
   'balance' : bigint,
   'alert_threshold' : bigint,
 }
+export type DepegAction = { 'ExtraFee' : number } |
+  { 'Reject' : null };
+export type DepositError = { 'Failed' : Error } |
+  { 'InsufficientAllowance' : { 'ledger' : Principal, 'allowance' : bigint } };
+export type DepositResult = { 'ok' : bigint } |
+  { 'err' : DepositError };
 export interface DepositTarget {
   'sub' : Uint8Array | number[],
   'owner' : Principal,
   'ai_hex' : string,
 }
+export interface DriftCheck {
+  'ts' : bigint,
+  'alert' : boolean,
+  'expected_usdc_e6' : bigint,
+  'expected_usdt_e6' : bigint,
+  'live_usdc_e6' : bigint,
+  'live_usdt_e6' : bigint,
+  'drift_usdc_e6' : bigint,
+  'drift_usdt_e6' : bigint,
+  'pools' : [] | [Array<PoolDrift>],
+}
+export interface DustBook {
+  'pool' : Array<[Principal, bigint]>,
+  'user' : Array<[[Principal, TokenId], bigint]>,
+}
+export type Error = { 'Internal' : string } |
+  { 'DInvariantBroken' : null } |
+  { 'InsufficientShares' : { 'have' : bigint, 'need' : bigint } } |
+  { 'Paused' : { 'op' : PauseOp } } |
+  { 'InvalidInput' : null } |
+  { 'Math' : MathError } |
+  { 'PoolNotFound' : number } |
+  { 'NoReconcileReport' : null } |
+  { 'StaleReconcileReport' : { 'report_ts' : bigint } } |
+  {
+    'InsufficientBalance' : {
+      'token' : TokenId,
+      'have' : bigint,
+      'need' : bigint,
+    }
+  } |
+  { 'InvalidPrincipal' : string } |
+  { 'LedgerError' : { 'code' : LedgerErrorCode, 'ledger' : Principal } } |
+  { 'Unauthorized' : Principal } |
+  { 'InsufficientLiquidity' : null } |
+  { 'PoolExists' : number } |
+  { 'SlippageExceeded' : { 'got' : bigint, 'min' : bigint } } |
+  { 'TokenMetaNotSet' : null } |
+  { 'PriceImpactTooHigh' : null } |
+  { 'UnsupportedPair' : { 'token_in' : TokenId, 'token_out' : TokenId } };
 export type Event = {
     'Withdraw' : {
       'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
       'who' : string,
       'token' : TokenId,
-      'amount' : AmountE6,
+      'icrc3_block' : [] | [bigint],
+      'ledger_block' : [] | [bigint],
+      'amount' : bigint,
+    }
+  } |
+  {
+    'ClaimFee' : {
+      'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
+      'who' : string,
+      'icrc3_block' : [] | [bigint],
+      'pool_id' : [] | [number],
+      'usdc_e6' : bigint,
+      'ledger_blocks' : Array<bigint>,
+      'usdt_e6' : bigint,
+    }
+  } |
+  {
+    'Reconcile' : {
+      'ts' : bigint,
+      'who' : string,
+      'after' : PoolSnapshot,
+      'detail' : string,
+      'before' : PoolSnapshot,
     }
   } |
   {
     'Deposit' : {
       'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
       'who' : string,
       'token' : TokenId,
-      'amount' : AmountE6,
+      'icrc3_block' : [] | [bigint],
+      'ledger_block' : [] | [bigint],
+      'amount' : bigint,
     }
   } |
   {
     'Swap' : {
+      'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
+      'who' : string,
+      'token_in' : Coin,
+      'fee_e6' : bigint,
+      'icrc3_block' : [] | [bigint],
+      'pool' : PoolSnapshot,
+      'token_out' : Coin,
+      'pool_id' : [] | [number],
+      'ledger_blocks' : Array<bigint>,
+      'dx_e6' : bigint,
+      'dy_e6' : bigint,
+    }
+  } |
+  {
+    'AdminAction' : {
       'ts' : bigint,
       'who' : string,
-      'dx_e6' : AmountE6,
-      'dy_e6' : AmountE6,
+      'action' : string,
+      'detail' : string,
+    }
+  } |
+  {
+    'ParamChange' : {
+      'ts' : bigint,
+      'new' : string,
+      'old' : string,
+      'who' : string,
+      'param' : string,
     }
   } |
   {
     'RemoveLiq' : {
       'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
       'who' : string,
-      'shares' : AmountE6,
-      'usdc' : AmountE6,
-      'usdt' : AmountE6,
+      'shares' : bigint,
+      'icrc3_block' : [] | [bigint],
+      'pool' : PoolSnapshot,
+      'usdc' : bigint,
+      'usdt' : bigint,
+      'pool_id' : [] | [number],
+      'ledger_blocks' : Array<bigint>,
     }
   } |
+  { 'Legacy' : EventV1 } |
   {
     'AddLiq' : {
       'ts' : bigint,
+      'sub' : [] | [Uint8Array | number[]],
       'who' : string,
-      'shares' : AmountE6,
-      'usdc' : AmountE6,
-      'usdt' : AmountE6,
+      'shares' : bigint,
+      'icrc3_block' : [] | [bigint],
+      'pool' : PoolSnapshot,
+      'usdc' : bigint,
+      'usdt' : bigint,
+      'pool_id' : [] | [number],
+      'ledger_blocks' : Array<bigint>,
     }
   };
+export interface EventArchiveRange {
+  'end' : bigint,
+  'canister_id' : Principal,
+  'start' : bigint,
+}
+export type EventKind = { 'Withdraw' : null } |
+  { 'ClaimFee' : null } |
+  { 'Reconcile' : null } |
+  { 'Deposit' : null } |
+  { 'Swap' : null } |
+  { 'AdminAction' : null } |
+  { 'ParamChange' : null } |
+  { 'RemoveLiq' : null } |
+  { 'AddLiq' : null };
+export interface EventPage {
+  'oldest_local_id' : bigint,
+  'next' : [] | [bigint],
+  'events' : Array<EventRecord>,
+  'next_id' : bigint,
+}
+export interface EventQuery {
+  'who' : [] | [Principal],
+  'to_ts' : [] | [bigint],
+  'descending' : [] | [boolean],
+  'from_id' : [] | [bigint],
+  'from_ts' : [] | [bigint],
+  'limit' : [] | [number],
+  'kinds' : [] | [Array<EventKind>],
+}
+export interface EventRecord { 'id' : bigint, 'event' : Event }
+export type EventV1 = {
+    'Withdraw' : {
+      'ts' : bigint,
+      'who' : string,
+      'token' : TokenId,
+      'amount' : bigint,
+    }
+  } |
+  {
+    'Deposit' : {
+      'ts' : bigint,
+      'who' : string,
+      'token' : TokenId,
+      'amount' : bigint,
+    }
+  } |
+  {
+    'Swap' : {
+      'ts' : bigint,
+      'who' : string,
+      'dx_e6' : bigint,
+      'dy_e6' : bigint,
+    }
+  } |
+  {
+    'RemoveLiq' : {
+      'ts' : bigint,
+      'who' : string,
+      'shares' : bigint,
+      'usdc' : bigint,
+      'usdt' : bigint,
+    }
+  } |
+  {
+    'AddLiq' : {
+      'ts' : bigint,
+      'who' : string,
+      'shares' : bigint,
+      'usdc' : bigint,
+      'usdt' : bigint,
+    }
+  };
+export interface GetArchivesArgs { 'from' : [] | [Principal] }
+export interface GetBlocksArgs { 'start' : bigint, 'length' : bigint }
+export interface GetBlocksResult {
+  'log_length' : bigint,
+  'blocks' : Array<BlockWithId>,
+  'archived_blocks' : Array<ArchivedBlocks>,
+}
 export interface HourBucket {
-  /**
-   * (dx+dy)/2
-   */
   'fee_e6' : bigint,
-  /**
-   * 小时整点（秒 / 3600）
-   */
+  'virtual_price_e6' : [] | [bigint],
   'volume_e6' : bigint,
-  /**
-   * 输入侧手续费
-   */
   'swaps' : number,
   'ts_hour' : bigint,
 }
+export interface HttpRequest {
+  'url' : string,
+  'method' : string,
+  'body' : Uint8Array | number[],
+  'headers' : Array<[string, string]>,
+  'certificate_version' : [] | [number],
+}
+export interface HttpResponse {
+  'body' : Uint8Array | number[],
+  'headers' : Array<[string, string]>,
+  'upgrade' : [] | [boolean],
+  'status_code' : number,
+}
+export interface Icrc1TransferArg {
+  'to' : Account,
+  'fee' : [] | [bigint],
+  'memo' : [] | [Uint8Array | number[]],
+  'from_subaccount' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+}
+export interface Icrc3ArchiveInfo {
+  'end' : bigint,
+  'canister_id' : Principal,
+  'start' : bigint,
+}
+export interface Icrc3DataCertificate {
+  'certificate' : Uint8Array | number[],
+  'hash_tree' : Uint8Array | number[],
+}
+export type Icrc3Value = { 'Int' : bigint } |
+  { 'Map' : Array<[string, Icrc3Value]> } |
+  { 'Nat' : bigint } |
+  { 'Blob' : Uint8Array | number[] } |
+  { 'Text' : string } |
+  { 'Array' : Array<Icrc3Value> };
+export interface JobsConfig {
+  'reconcile_interval_sec' : bigint,
+  'drift_alert_e6' : bigint,
+  'stats_interval_sec' : bigint,
+  'enabled' : boolean,
+  'cycles_interval_sec' : bigint,
+}
+export interface JobsState {
+  'last_stats_roll' : bigint,
+  'low_cycles' : boolean,
+  'last_drift' : [] | [DriftCheck],
+  'config' : JobsConfig,
+  'last_cycles_check' : bigint,
+}
+export type LedgerErrorCode = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'BadBurn' : { 'min_burn_amount' : bigint } } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'CallRejected' : { 'code' : number, 'message' : string } } |
+  { 'TooOld' : null } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
+export type MathError = { 'ZeroReserve' : null } |
+  { 'Overflow' : null } |
+  { 'NoConvergence' : null } |
+  { 'InvalidAmp' : null } |
+  { 'VirtualPriceDecreased' : null };
+export interface MetaPool { 'base_virtual_price' : bigint, 'base' : number }
+export type MetadataValue = { 'Int' : bigint } |
+  { 'Nat' : bigint } |
+  { 'Blob' : Uint8Array | number[] } |
+  { 'Text' : string };
+export interface OracleConfig {
+  'max_dev_ema_bps' : number,
+  'max_dev_peg_bps' : number,
+  'action' : DepegAction,
+  'ma_exp_time_sec' : bigint,
+}
+export interface PauseChange {
+  'ts' : bigint,
+  'who' : string,
+  'reason' : string,
+}
+export interface PauseFlags {
+  'add_liquidity' : boolean,
+  'withdraw' : boolean,
+  'swap' : boolean,
+  'deposit' : boolean,
+  'remove_liquidity' : boolean,
+  'claim_fees' : boolean,
+}
+export type PauseOp = { 'Withdraw' : null } |
+  { 'ClaimFees' : null } |
+  { 'AddLiquidity' : null } |
+  { 'Deposit' : null } |
+  { 'Swap' : null } |
+  { 'RemoveLiquidity' : null };
+export interface PauseState {
+  'flags' : PauseFlags,
+  'last_change' : [] | [PauseChange],
+  'auto' : AutoPauseConfig,
+  'pausers' : Array<Principal>,
+}
+export interface PoolDrift {
+  'token' : TokenId,
+  'pool' : number,
+  'expected_e6' : bigint,
+  'live_e6' : bigint,
+  'drift_e6' : bigint,
+}
 export interface PoolInfo {
   'a_amp' : number,
   'virtual_price_e6' : bigint,
   'fee_bps' : number,
-  'total_shares' : AmountE6,
-  'reserve_usdc' : AmountE6,
-  'reserve_usdt' : AmountE6,
+  'total_shares' : bigint,
+  'reserve_usdc' : bigint,
+  'reserve_usdt' : bigint,
+}
+export interface PoolInfoCertified {
+  'certificate' : Uint8Array | number[],
+  'pool' : PoolInfo,
+  'witness' : Uint8Array | number[],
+}
+export type PoolKind = { 'StableSwap' : null } |
+  { 'Meta' : null } |
+  { 'ConstantProduct' : null } |
+  { 'CryptoSwap' : null };
+export interface PoolSnapshot {
+  'virtual_price_e6' : bigint,
+  'total_shares' : bigint,
+  'reserve_usdc' : bigint,
+  'reserve_usdt' : bigint,
+}
+export interface PoolState {
+  'id' : number,
+  'a_amp' : number,
+  'fee_vault' : Array<bigint>,
+  'kind' : PoolKind,
+  'meta' : [] | [MetaPool],
+  'virtual_price_e6' : bigint,
+  'coins' : Array<Coin>,
+  'reserves' : Array<bigint>,
+  'offpeg_fee_multiplier' : [] | [bigint],
+  'fee_bps' : number,
+  'total_shares' : bigint,
+  'created_ts' : bigint,
+  'crypto' : [] | [CryptoPool],
+  'fee_exempt_shares' : [] | [bigint],
+  'fee_growth_e18' : Array<bigint>,
+}
+export interface Position { 'shares' : bigint }
+export type PositionResult = { 'ok' : Position } |
+  { 'err' : Error };
+export interface PriceOracle {
+  'last_price_e6' : bigint,
+  'ema_price_e6' : bigint,
+  'config' : OracleConfig,
+  'last_ts' : bigint,
 }
-export interface PoolReserves { 'usdc' : bigint, 'usdt' : bigint }
-export interface Position { 'shares' : AmountE6 }
 export interface QuoteOut {
-  'fee_e6' : AmountE6,
+  'fee_rate_e10' : bigint,
+  'fee_e6' : bigint,
   'price_e6' : bigint,
-  'dy_e6' : AmountE6,
+  'dy_e6' : bigint,
+}
+export type QuoteResult = { 'ok' : QuoteOut } |
+  { 'err' : Error };
+export interface ReconcileCorrection {
+  'token' : TokenId,
+  'kind' : CorrectionKind,
+  'memo' : string,
+  'pool' : [] | [number],
+  'amount_e6' : bigint,
 }
+export interface ReconcileReport {
+  'ts' : bigint,
+  'virtual_price_e6' : bigint,
+  'total_shares' : bigint,
+  'tokens' : Array<TokenReconcile>,
+}
+export type Result = { 'Ok' : bigint } |
+  { 'Err' : TransferError };
+export type Result_1 = { 'Ok' : bigint } |
+  { 'Err' : ApproveError };
+export type Result_2 = { 'Ok' : bigint } |
+  { 'Err' : TransferFromError };
 export interface RiskParams {
   'd_tolerance_e6' : bigint,
   'max_price_impact_bps' : number,
 }
+export interface RouteHop {
+  'token_in' : Coin,
+  'fee_rate_e10' : bigint,
+  'fee_e6' : bigint,
+  'pool' : number,
+  'token_out' : Coin,
+  'dx_e6' : bigint,
+  'dy_e6' : bigint,
+}
+export interface RouteQuote {
+  'price_impact_bps' : number,
+  'spot_price_e6' : bigint,
+  'hops' : Array<RouteHop>,
+  'price_e6' : bigint,
+  'dx_e6' : bigint,
+  'dy_e6' : bigint,
+}
+export type RouteResult = { 'ok' : RouteQuote } |
+  { 'err' : Error };
+export interface RouteSwapArgs {
+  'max_hops' : [] | [number],
+  'token_in' : Coin,
+  'min_out_e6' : bigint,
+  'account' : Account,
+  'token_out' : Coin,
+  'dx_e6' : bigint,
+}
+export interface SolvencyArgs {
+  'cursor' : [] | [string],
+  'limit' : [] | [number],
+}
+export interface SolvencyReport {
+  'ts' : bigint,
+  'complete' : boolean,
+  'tokens' : Array<TokenSolvency>,
+  'next_cursor' : [] | [string],
+  'users_total' : bigint,
+  'users_checked' : Array<string>,
+}
+export type SolvencyReportResult = { 'ok' : SolvencyReport } |
+  { 'err' : Error };
+export interface StandardRecord { 'url' : string, 'name' : string }
 export interface StatsSnapshot {
   'now_sec' : bigint,
   'fee_24h_e6' : bigint,
@@ -104,22 +567,27 @@ export interface StatsSnapshot {
   'vol_24h_e6' : bigint,
   'fee_7d_e6' : bigint,
 }
+export type StdResultSwap = { 'ok' : SwapOk } |
+  { 'err' : Error };
 export interface SubBalance {
   'id' : string,
-  'bob' : AmountE6,
-  'icp' : AmountE6,
-  'usdc' : AmountE6,
-  'usdt' : AmountE6,
+  'bob' : bigint,
+  'icp' : bigint,
+  'usdc' : bigint,
+  'usdt' : bigint,
 }
+export interface SupportedBlockType { 'url' : string, 'block_type' : string }
 export interface SwapArgs {
-  'min_dy_e6' : AmountE6,
-  'token_in' : TokenId,
+  'min_dy_e6' : bigint,
+  'token_in' : Coin,
+  'pool' : [] | [number],
   'account' : Account,
-  'token_out' : TokenId,
-  'dx_e6' : AmountE6,
+  'token_out' : Coin,
+  'dx_e6' : bigint,
 }
+export interface SwapOk { 'dy_e6' : bigint }
 export type TextResult = { 'ok' : string } |
-  { 'err' : string };
+  { 'err' : Error };
 export type TokenId = { 'BOB' : null } |
   { 'ICP' : null } |
   { 'USDC' : null } |
@@ -130,117 +598,344 @@ export interface TokenMeta {
   'ckusdc' : Principal,
   'ckusdt' : Principal,
 }
+export interface TokenReconcile {
+  'token' : TokenId,
+  'diff_e6' : bigint,
+  'pool' : [] | [number],
+  'unclaimed_fees_e6' : bigint,
+  'live_pool_e6' : bigint,
+  'unexplained_e6' : bigint,
+  'ledger' : Principal,
+  'ledger_fees_e6' : bigint,
+  'unaccounted_deposits_e6' : bigint,
+  'reserve_e6' : bigint,
+  'claimable_fees_e6' : bigint,
+  'user_balances_e6' : bigint,
+}
+export interface TokenSolvency {
+  'token' : TokenId,
+  'users_checked_e6' : bigint,
+  'pool_coverage_bps' : bigint,
+  'pool' : [] | [number],
+  'users_checked_live_e6' : bigint,
+  'fee_vault_e6' : bigint,
+  'ledger' : Principal,
+  'reserve_e6' : bigint,
+  'users_total_e6' : bigint,
+  'pool_live_e6' : bigint,
+  'coverage_bps' : bigint,
+}
+export type TransferError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'BadBurn' : { 'min_burn_amount' : bigint } } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
+export interface TransferFromArgs {
+  'to' : Account,
+  'fee' : [] | [bigint],
+  'spender_subaccount' : [] | [Uint8Array | number[]],
+  'from' : Account,
+  'memo' : [] | [Uint8Array | number[]],
+  'created_at_time' : [] | [bigint],
+  'amount' : bigint,
+}
+export type TransferFromError = {
+    'GenericError' : { 'message' : string, 'error_code' : bigint }
+  } |
+  { 'TemporarilyUnavailable' : null } |
+  { 'InsufficientAllowance' : { 'allowance' : bigint } } |
+  { 'BadBurn' : { 'min_burn_amount' : bigint } } |
+  { 'Duplicate' : { 'duplicate_of' : bigint } } |
+  { 'BadFee' : { 'expected_fee' : bigint } } |
+  { 'CreatedInFuture' : { 'ledger_time' : bigint } } |
+  { 'TooOld' : null } |
+  { 'InsufficientFunds' : { 'balance' : bigint } };
 export interface TwoAmounts { 'usdc' : bigint, 'usdt' : bigint }
-export type TxResult = { 'ok' : bigint } |
-  { 'err' : string };
+export type TwoAmountsResult = { 'ok' : TwoAmounts } |
+  { 'err' : Error };
+export type TxResultNat = { 'ok' : bigint } |
+  { 'err' : Error };
+export interface UnderlyingQuote {
+  'base_fee_e6' : bigint,
+  'token_in' : Coin,
+  'pool' : number,
+  'lp_e6' : bigint,
+  'meta_fee_e6' : bigint,
+  'token_out' : Coin,
+  'price_e6' : bigint,
+  'dx_e6' : bigint,
+  'dy_e6' : bigint,
+}
+export type UnderlyingResult = { 'ok' : UnderlyingQuote } |
+  { 'err' : Error };
+export interface UnderlyingSwapArgs {
+  'min_dy_e6' : bigint,
+  'token_in' : Coin,
+  'pool' : number,
+  'account' : Account,
+  'token_out' : Coin,
+  'dx_e6' : bigint,
+}
 export interface _SERVICE {
-  '__get_candid_interface_tmp_hack' : ActorMethod<[], string>,
+  'add_liquidity' : ActorMethod<
+    [Account, bigint, bigint, [] | [number]],
+    PositionResult
+  >,
   /**
-   * Positions
+   * 管理员：按最近一份报告执行一笔更正（额度以报告为上限），写 Reconcile 事件留痕
    */
-  'add_liquidity' : ActorMethod<
-    [Account, AmountE6, AmountE6],
-    { 'ok' : { 'shares' : AmountE6 } } |
-      { 'err' : string }
+  'admin_apply_reconcile_correction' : ActorMethod<
+    [ReconcileCorrection],
+    TextResult
   >,
+  /**
+   * 暂停角色 / 控制者：紧急暂停（除退出通道外全部关闭）
+   */
+  'admin_pause_all' : ActorMethod<[string], TextResult>,
+  /**
+   * 已停用：旧实现会把 total_shares 重置为 usdc+usdt 并等比缩放全部 LP。请改用对账报告 + 显式更正
+   */
   'admin_reconcile_from_internal' : ActorMethod<[], TextResult>,
+  /**
+   * 管理员：读取 Ledger 实时余额，生成对账报告（只读，不改任何账），保存为最近一份报告并写 Reconcile 事件
+   */
   'admin_reconcile_pool_from_live' : ActorMethod<[], TextResult>,
-  'claim_fee' : ActorMethod<
-    [Account],
-    { 'ok' : { 'usdc' : AmountE6, 'usdt' : AmountE6 } } |
-      { 'err' : string }
+  /**
+   * 管理员：同上，并把报告写成 Reconcile 事件留档
+   */
+  'admin_record_solvency_report' : ActorMethod<
+    [SolvencyArgs],
+    SolvencyReportResult
   >,
+  /**
+   * 控制者：立即执行一次对账漂移检查（不等定时器）
+   */
+  'admin_run_reconcile_check' : ActorMethod<[], TextResult>,
+  /**
+   * 控制者：本地最多保留 max_live 条事件，超出部分搬到 archive；任一为 None 则不搬迁（绝不删除）
+   */
+  'admin_set_event_retention' : ActorMethod<
+    [[] | [bigint], [] | [Principal]],
+    TextResult
+  >,
+  /**
+   * 控制者：设置 ICRC-3 溢出目标 archive canister（见 canisters/vaultpair_archive）；None 停止溢出
+   */
+  'admin_set_icrc3_archive' : ActorMethod<[[] | [Principal]], TextResult>,
+  /**
+   * 控制者：修改定时任务参数并立即重新挂载
+   */
+  'admin_set_jobs' : ActorMethod<[JobsConfig], TextResult>,
+  /**
+   * 控制者：设置 offpeg_fee_multiplier（1e10 = 1x；≤ 1x 即固定费率，None 清除）
+   */
+  'admin_set_offpeg_fee_multiplier' : ActorMethod<[[] | [bigint]], TextResult>,
+  /**
+   * 控制者：修改 EMA 时间常数与脱锚保护阈值
+   */
+  'admin_set_oracle_config' : ActorMethod<[OracleConfig], TextResult>,
+  /**
+   * 暂停角色 / 控制者：开关单项操作；退出通道（remove_liquidity / withdraw）仅控制者可关
+   */
+  'admin_set_pause' : ActorMethod<[PauseOp, boolean, string], TextResult>,
+  /**
+   * 控制者：设置暂停角色与自动暂停阈值
+   */
+  'admin_set_pause_config' : ActorMethod<
+    [Array<Principal>, AutoPauseConfig],
+    TextResult
+  >,
+  'claim_fee' : ActorMethod<[Account, [] | [number]], TwoAmountsResult>,
+  /**
+   * 控制者：新建池子（同一代币对只能有一个池子）；池子子账户见 pools::subaccount
+   */
+  'create_pool' : ActorMethod<[CreatePoolArgs], CreatePoolResult>,
+  /**
+   * ICRC-2 入金：按调用者对本 canister 的授权，从其默认账户代扣到派生子账户，再按 live 余额刷新内账，返回账本块号（见 assets::deposit）
+   */
+  'deposit' : ActorMethod<[TokenId, bigint], DepositResult>,
+  /**
+   * ICRC-2 授权（保留：提现/代扣前置）
+   */
   'ensure_allowance_for_user' : ActorMethod<[Principal, bigint], boolean>,
-  'get_available_balances' : ActorMethod<[Account], Available>,
-  'get_available_balances_live_for' : ActorMethod<[Principal], Available>,
   /**
-   * ===== ICRC 辅助：canister principal / 用户子账户 =====
+   * 经基础池一次成交：中间的 LP 只在内账经过，min_dy 只在终点校验；任何一步不成则整笔不成。
+   * 基础池一步另受 add_liquidity / remove_liquidity 暂停开关与脱锚保护约束（见 metapool::execute）
+   */
+  'exchange_underlying' : ActorMethod<[UnderlyingSwapArgs], UnderlyingResult>,
+  /**
+   * 供资产/限额等读取：从 ledger_book 汇总（保持历史接口）
+   */
+  'get_available_balances' : ActorMethod<[Account], TwoAmounts>,
+  /**
+   * Liquidity 页“指定用户 live 可用额”（**query**）：读取本地缓存（内部 e6→外部 Nat）
    */
+  'get_available_balances_live_for' : ActorMethod<[Principal], TwoAmounts>,
   'get_canister_principal' : ActorMethod<[], Principal>,
   'get_cycles_info' : ActorMethod<[], CyclesInfo>,
+  'get_deposit_target_for' : ActorMethod<[Principal], DepositTarget>,
   /**
-   * / 计算“指定用户 principal”的存款目标（owner=本 canister、sub=该用户派生子账户、ai_hex=ICP AccountIdentifier 16进制）
+   * 换算到 e6 记账口径时截掉的尾数（见 ledger_book::DustBook）
    */
-  'get_deposit_target_for' : ActorMethod<[Principal], DepositTarget>,
+  'get_dust' : ActorMethod<[], DustBook>,
+  'get_estimated_24h_volume' : ActorMethod<[number], bigint>,
+  /**
+   * 已搬到 archive 的事件区间（id 闭区间）；archive 上用 get_events(start, length) 读取
+   */
+  'get_event_archives' : ActorMethod<[], Array<EventArchiveRange>>,
+  'get_events' : ActorMethod<[bigint, bigint], Array<EventV1>>,
+  'get_events_latest' : ActorMethod<[bigint], Array<EventV1>>,
+  'get_jobs_status' : ActorMethod<[], JobsState>,
+  'get_last_reconcile_report' : ActorMethod<[], [] | [ReconcileReport]>,
   /**
-   * Activity
+   * Liquidity 页“我的 live 可用额”（**query**）：读取本地缓存（内部 e6→外部 Nat）
    */
-  'get_events' : ActorMethod<[bigint, bigint], Array<Event>>,
-  'get_events_latest' : ActorMethod<[bigint], Array<Event>>,
-  'get_my_available_balances_live' : ActorMethod<[], Available>,
+  'get_my_available_balances_live' : ActorMethod<[], TwoAmounts>,
   'get_my_deposit_target' : ActorMethod<[], DepositTarget>,
   'get_my_icp_account_id_hex' : ActorMethod<[], string>,
   'get_my_subaccount' : ActorMethod<[], Uint8Array | number[]>,
-  'get_pool_account' : ActorMethod<[string], Account>,
+  'get_pause_state' : ActorMethod<[], PauseState>,
+  'get_pool' : ActorMethod<[number], [] | [PoolState]>,
   /**
-   * Explore
+   * 返回池子的 ICRC 账户（owner=本 canister；sub=固定 POOL_SUB）
+   * 入参 token_id_or_symbol 目前仅占位，保留未来多池/多路由扩展空间
    */
+  'get_pool_account' : ActorMethod<[string], Account>,
   'get_pool_info' : ActorMethod<[], PoolInfo>,
-  'get_pool_reserves_live' : ActorMethod<[], PoolReserves>,
+  /**
+   * 带证书与 witness 的池子信息（客户端用 vaultpair_verify 校验）；非 query 调用返回 null
+   */
+  'get_pool_info_certified' : ActorMethod<[], [] | [PoolInfoCertified]>,
+  'get_pool_reserves_live' : ActorMethod<[], TwoAmounts>,
+  /**
+   * 某池子 24h / 7d 统计；不存在返回 null
+   */
+  'get_pool_stats' : ActorMethod<[number], [] | [StatsSnapshot]>,
+  'get_pools' : ActorMethod<[], Array<PoolState>>,
+  /**
+   * pool 0（USDC/USDT）的 EMA 价格；其它池没有预言机，也不受脱锚保护
+   */
+  'get_price_oracle' : ActorMethod<[], PriceOracle>,
   'get_risk_params' : ActorMethod<[], RiskParams>,
+  /**
+   * 复合查询：内部负债 vs 池子子账户 + 本页用户派生子账户的 live 余额；翻页用 next_cursor
+   */
+  'get_solvency_report' : ActorMethod<[SolvencyArgs], SolvencyReportResult>,
+  /**
+   * pool 0 内部储备下的现价（1 USDC 可换 USDT，e6，不含手续费）；空池为 None
+   */
+  'get_spot_price' : ActorMethod<[], [] | [bigint]>,
   'get_stats_series' : ActorMethod<[number], Array<HourBucket>>,
   'get_stats_snapshot' : ActorMethod<[], StatsSnapshot>,
   'get_token_meta' : ActorMethod<[], [] | [TokenMeta]>,
+  'get_tvl_e6' : ActorMethod<[], bigint>,
   /**
-   * ===== 权威统计 / 风控 / Cycles =====
+   * 只读预览：可领取手续费
    */
-  'get_tvl_e6' : ActorMethod<[], bigint>,
-  'get_unclaimed_fee' : ActorMethod<
-    [Account],
-    { 'usdc' : AmountE6, 'usdt' : AmountE6 }
-  >,
+  'get_unclaimed_fee' : ActorMethod<[Account, [] | [number]], TwoAmounts>,
   /**
-   * Assets（主/子账户 + 演示划转）
+   * 资产页：返回“main 子账户”的内账 e6 余额（演示阶段口径）
    */
   'get_user_balances' : ActorMethod<
     [Account],
-    { 'bob' : AmountE6, 'icp' : AmountE6, 'usdc' : AmountE6, 'usdt' : AmountE6 }
+    [bigint, bigint, bigint, bigint]
   >,
-  'get_user_position' : ActorMethod<[Account], Position>,
+  'get_user_position' : ActorMethod<[Account, [] | [number]], Position>,
+  /**
+   * 资产页：子账户余额明细（保留）
+   */
   'get_user_sub_balances' : ActorMethod<[Account], Array<SubBalance>>,
+  'http_request' : ActorMethod<[HttpRequest], HttpResponse>,
   /**
-   * Swap
+   * certified=1 的请求由网关升级到这里：响应经共识返回
    */
-  'quote' : ActorMethod<[TokenId, TokenId, AmountE6], QuoteOut>,
-  'quote_exact_out' : ActorMethod<[TokenId, TokenId, AmountE6], QuoteOut>,
-  'quote_live' : ActorMethod<[TokenId, TokenId, AmountE6], QuoteOut>,
-  'quote_live_exact_out' : ActorMethod<[TokenId, TokenId, AmountE6], QuoteOut>,
-  'refresh_available_for' : ActorMethod<
-    [Principal],
-    { 'ok' : string } |
-      { 'err' : string }
+  'http_request_update' : ActorMethod<[HttpRequest], HttpResponse>,
+  'icrc1_balance_of' : ActorMethod<[Account], bigint>,
+  'icrc1_decimals' : ActorMethod<[], number>,
+  'icrc1_fee' : ActorMethod<[], bigint>,
+  'icrc1_metadata' : ActorMethod<[], Array<[string, MetadataValue]>>,
+  'icrc1_minting_account' : ActorMethod<[], [] | [Account]>,
+  'icrc1_name' : ActorMethod<[], string>,
+  'icrc1_supported_standards' : ActorMethod<[], Array<StandardRecord>>,
+  'icrc1_symbol' : ActorMethod<[], string>,
+  'icrc1_total_supply' : ActorMethod<[], bigint>,
+  'icrc1_transfer' : ActorMethod<[Icrc1TransferArg], Result>,
+  'icrc2_allowance' : ActorMethod<[AllowanceArgs], Allowance>,
+  'icrc2_approve' : ActorMethod<[ApproveArgs], Result_1>,
+  'icrc2_transfer_from' : ActorMethod<[TransferFromArgs], Result_2>,
+  'icrc3_get_archives' : ActorMethod<
+    [GetArchivesArgs],
+    Array<Icrc3ArchiveInfo>
   >,
-  'remove_liquidity' : ActorMethod<
-    [Account, AmountE6],
-    { 'ok' : { 'usdc' : AmountE6, 'usdt' : AmountE6 } } |
-      { 'err' : string }
+  'icrc3_get_blocks' : ActorMethod<[Array<GetBlocksArgs>], GetBlocksResult>,
+  'icrc3_get_tip_certificate' : ActorMethod<[], [] | [Icrc3DataCertificate]>,
+  'icrc3_supported_block_types' : ActorMethod<[], Array<SupportedBlockType>>,
+  /**
+   * 按 id 游标分页，可按 principal / 事件类型 / 时间范围过滤；next 为下一页 from_id
+   */
+  'query_events' : ActorMethod<[EventQuery], EventPage>,
+  /**
+   * pool 缺省 = 0 号 USDC/USDT 池（swap / swap_live 用 SwapArgs.pool）
+   */
+  'quote' : ActorMethod<[Coin, Coin, bigint, [] | [number]], QuoteResult>,
+  /**
+   * —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于内部储备）——
+   * 返回：dx_e6（最小需要投入），fee_e6（基于 dx 的输入侧手续费），price_e6 = dy/dx * 1e6
+   */
+  'quote_exact_out' : ActorMethod<
+    [Coin, Coin, bigint, [] | [number]],
+    QuoteResult
   >,
-  'set_token_meta' : ActorMethod<[TokenMeta], undefined>,
-  'swap' : ActorMethod<
-    [SwapArgs],
-    { 'ok' : { 'dy_e6' : AmountE6 } } |
-      { 'err' : string }
+  'quote_live' : ActorMethod<[Coin, Coin, bigint, [] | [number]], QuoteResult>,
+  /**
+   * —— 反向报价：给定目标 dy_e6，计算最小 dx_e6（基于实时 live 储备）——
+   */
+  'quote_live_exact_out' : ActorMethod<
+    [Coin, Coin, bigint, [] | [number]],
+    QuoteResult
   >,
-  'swap_live' : ActorMethod<
-    [SwapArgs],
-    { 'ok' : { 'dy_e6' : AmountE6 } } |
-      { 'err' : string }
+  /**
+   * 多跳路由报价（见 router）：max_hops 缺省 3
+   */
+  'quote_route' : ActorMethod<[Coin, Coin, bigint, [] | [number]], RouteResult>,
+  /**
+   * Metapool 与基础池（0 号池）币之间的报价（见 metapool）：新币 ↔ USDC / USDT，或 USDC ↔ USDT
+   */
+  'quote_underlying' : ActorMethod<
+    [number, Coin, Coin, bigint],
+    UnderlyingResult
+  >,
+  'refresh_available_for' : ActorMethod<[Principal], TextResult>,
+  'refresh_available_for_blocking' : ActorMethod<[Principal], TextResult>,
+  'remove_liquidity' : ActorMethod<
+    [Account, bigint, [] | [number]],
+    TwoAmountsResult
   >,
+  /**
+   * 演示/灌池入口 —— 为避免线上干扰，这里改为“无副作用”的空实现：返回当前池信息，不再改写任何状态。
+   */
+  'seed_pool_demo' : ActorMethod<[bigint, bigint], PoolInfo>,
+  'set_token_meta' : ActorMethod<[TokenMeta], undefined>,
+  'swap' : ActorMethod<[SwapArgs], StdResultSwap>,
+  'swap_live' : ActorMethod<[SwapArgs], StdResultSwap>,
+  /**
+   * 按最优路径在内账逐跳成交，只在终点校验 min_out；任何一跳不成则整笔不成
+   */
+  'swap_route' : ActorMethod<[RouteSwapArgs], RouteResult>,
   'transfer_from_pool_to_user_sub' : ActorMethod<
     [string, Principal, bigint],
-    { 'ok' : bigint } |
-      { 'err' : string }
+    TxResultNat
   >,
   'transfer_from_user_sub_to_pool' : ActorMethod<
     [string, Principal, bigint],
-    { 'ok' : bigint } |
-      { 'err' : string }
-  >,
-  'withdraw_from_sub' : ActorMethod<
-    [string, Account, bigint],
-    { 'ok' : string } |
-      { 'err' : string }
+    TxResultNat
   >,
+  'withdraw_from_sub' : ActorMethod<[string, Account, bigint], TextResult>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
 export declare const init: (args: { IDL: typeof IDL }) => IDL.Type[];
@@ -1,11 +1,206 @@
 export const idlFactory = ({ IDL }) => {
+  const GetBlocksResult = IDL.Rec();
+  const Icrc3Value = IDL.Rec();
   const Account = IDL.Record({
     'owner' : IDL.Principal,
     'subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
   });
-  const AmountE6 = IDL.Nat;
-  const TextResult = IDL.Variant({ 'ok' : IDL.Text, 'err' : IDL.Text });
-  const Available = IDL.Record({ 'usdc' : IDL.Nat, 'usdt' : IDL.Nat });
+  const Position = IDL.Record({ 'shares' : IDL.Nat });
+  const PauseOp = IDL.Variant({
+    'Withdraw' : IDL.Null,
+    'ClaimFees' : IDL.Null,
+    'AddLiquidity' : IDL.Null,
+    'Deposit' : IDL.Null,
+    'Swap' : IDL.Null,
+    'RemoveLiquidity' : IDL.Null,
+  });
+  const MathError = IDL.Variant({
+    'ZeroReserve' : IDL.Null,
+    'Overflow' : IDL.Null,
+    'NoConvergence' : IDL.Null,
+    'InvalidAmp' : IDL.Null,
+    'VirtualPriceDecreased' : IDL.Null,
+  });
+  const TokenId = IDL.Variant({
+    'BOB' : IDL.Null,
+    'ICP' : IDL.Null,
+    'USDC' : IDL.Null,
+    'USDT' : IDL.Null,
+  });
+  const LedgerErrorCode = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'BadBurn' : IDL.Record({ 'min_burn_amount' : IDL.Nat }),
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'CallRejected' : IDL.Record({ 'code' : IDL.Int32, 'message' : IDL.Text }),
+    'TooOld' : IDL.Null,
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Error = IDL.Variant({
+    'Internal' : IDL.Text,
+    'DInvariantBroken' : IDL.Null,
+    'InsufficientShares' : IDL.Record({ 'have' : IDL.Nat, 'need' : IDL.Nat }),
+    'Paused' : IDL.Record({ 'op' : PauseOp }),
+    'InvalidInput' : IDL.Null,
+    'Math' : MathError,
+    'PoolNotFound' : IDL.Nat32,
+    'NoReconcileReport' : IDL.Null,
+    'StaleReconcileReport' : IDL.Record({ 'report_ts' : IDL.Nat64 }),
+    'InsufficientBalance' : IDL.Record({
+      'token' : TokenId,
+      'have' : IDL.Nat,
+      'need' : IDL.Nat,
+    }),
+    'InvalidPrincipal' : IDL.Text,
+    'LedgerError' : IDL.Record({
+      'code' : LedgerErrorCode,
+      'ledger' : IDL.Principal,
+    }),
+    'Unauthorized' : IDL.Principal,
+    'InsufficientLiquidity' : IDL.Null,
+    'PoolExists' : IDL.Nat32,
+    'SlippageExceeded' : IDL.Record({ 'got' : IDL.Nat, 'min' : IDL.Nat }),
+    'TokenMetaNotSet' : IDL.Null,
+    'PriceImpactTooHigh' : IDL.Null,
+    'UnsupportedPair' : IDL.Record({
+      'token_in' : TokenId,
+      'token_out' : TokenId,
+    }),
+  });
+  const PositionResult = IDL.Variant({ 'ok' : Position, 'err' : Error });
+  const CorrectionKind = IDL.Variant({
+    'WriteOffLedgerFees' : IDL.Null,
+    'WriteOffShortfall' : IDL.Null,
+    'AbsorbDepositToReserve' : IDL.Null,
+  });
+  const ReconcileCorrection = IDL.Record({
+    'token' : TokenId,
+    'kind' : CorrectionKind,
+    'memo' : IDL.Text,
+    'pool' : IDL.Opt(IDL.Nat32),
+    'amount_e6' : IDL.Nat,
+  });
+  const TextResult = IDL.Variant({ 'ok' : IDL.Text, 'err' : Error });
+  const SolvencyArgs = IDL.Record({
+    'cursor' : IDL.Opt(IDL.Text),
+    'limit' : IDL.Opt(IDL.Nat32),
+  });
+  const TokenSolvency = IDL.Record({
+    'token' : TokenId,
+    'users_checked_e6' : IDL.Nat,
+    'pool_coverage_bps' : IDL.Nat64,
+    'pool' : IDL.Opt(IDL.Nat32),
+    'users_checked_live_e6' : IDL.Nat,
+    'fee_vault_e6' : IDL.Nat,
+    'ledger' : IDL.Principal,
+    'reserve_e6' : IDL.Nat,
+    'users_total_e6' : IDL.Nat,
+    'pool_live_e6' : IDL.Nat,
+    'coverage_bps' : IDL.Nat64,
+  });
+  const SolvencyReport = IDL.Record({
+    'ts' : IDL.Nat64,
+    'complete' : IDL.Bool,
+    'tokens' : IDL.Vec(TokenSolvency),
+    'next_cursor' : IDL.Opt(IDL.Text),
+    'users_total' : IDL.Nat64,
+    'users_checked' : IDL.Vec(IDL.Text),
+  });
+  const SolvencyReportResult = IDL.Variant({
+    'ok' : SolvencyReport,
+    'err' : Error,
+  });
+  const JobsConfig = IDL.Record({
+    'reconcile_interval_sec' : IDL.Nat64,
+    'drift_alert_e6' : IDL.Nat,
+    'stats_interval_sec' : IDL.Nat64,
+    'enabled' : IDL.Bool,
+    'cycles_interval_sec' : IDL.Nat64,
+  });
+  const DepegAction = IDL.Variant({
+    'ExtraFee' : IDL.Nat32,
+    'Reject' : IDL.Null,
+  });
+  const OracleConfig = IDL.Record({
+    'max_dev_ema_bps' : IDL.Nat32,
+    'max_dev_peg_bps' : IDL.Nat32,
+    'action' : DepegAction,
+    'ma_exp_time_sec' : IDL.Nat64,
+  });
+  const AutoPauseConfig = IDL.Record({
+    'depeg_bps' : IDL.Nat32,
+    'on_drift' : IDL.Bool,
+  });
+  const TwoAmounts = IDL.Record({ 'usdc' : IDL.Nat, 'usdt' : IDL.Nat });
+  const TwoAmountsResult = IDL.Variant({ 'ok' : TwoAmounts, 'err' : Error });
+  const PoolKind = IDL.Variant({
+    'StableSwap' : IDL.Null,
+    'Meta' : IDL.Null,
+    'ConstantProduct' : IDL.Null,
+    'CryptoSwap' : IDL.Null,
+  });
+  const Coin = IDL.Variant({
+    'LP' : IDL.Null,
+    'BOB' : IDL.Null,
+    'ICP' : IDL.Null,
+    'USDC' : IDL.Null,
+    'USDT' : IDL.Null,
+  });
+  const CryptoParams = IDL.Record({
+    'a' : IDL.Nat,
+    'adjustment_step' : IDL.Nat,
+    'mid_fee' : IDL.Nat,
+    'gamma' : IDL.Nat,
+    'fee_gamma' : IDL.Nat,
+    'out_fee' : IDL.Nat,
+    'ma_half_time_sec' : IDL.Nat64,
+    'allowed_extra_profit' : IDL.Nat,
+  });
+  const CreatePoolArgs = IDL.Record({
+    'a_amp' : IDL.Nat32,
+    'initial_price_e6' : IDL.Opt(IDL.Nat),
+    'kind' : IDL.Opt(PoolKind),
+    'coins' : IDL.Vec(Coin),
+    'fee_bps' : IDL.Nat16,
+    'crypto' : IDL.Opt(CryptoParams),
+  });
+  const CreatePoolResult = IDL.Variant({ 'ok' : IDL.Nat32, 'err' : Error });
+  const DepositError = IDL.Variant({
+    'Failed' : Error,
+    'InsufficientAllowance' : IDL.Record({
+      'ledger' : IDL.Principal,
+      'allowance' : IDL.Nat,
+    }),
+  });
+  const DepositResult = IDL.Variant({ 'ok' : IDL.Nat, 'err' : DepositError });
+  const UnderlyingSwapArgs = IDL.Record({
+    'min_dy_e6' : IDL.Nat,
+    'token_in' : Coin,
+    'pool' : IDL.Nat32,
+    'account' : Account,
+    'token_out' : Coin,
+    'dx_e6' : IDL.Nat,
+  });
+  const UnderlyingQuote = IDL.Record({
+    'base_fee_e6' : IDL.Nat,
+    'token_in' : Coin,
+    'pool' : IDL.Nat32,
+    'lp_e6' : IDL.Nat,
+    'meta_fee_e6' : IDL.Nat,
+    'token_out' : Coin,
+    'price_e6' : IDL.Nat,
+    'dx_e6' : IDL.Nat,
+    'dy_e6' : IDL.Nat,
+  });
+  const UnderlyingResult = IDL.Variant({
+    'ok' : UnderlyingQuote,
+    'err' : Error,
+  });
   const CyclesInfo = IDL.Record({
     'low' : IDL.Bool,
     'balance' : IDL.Nat,
@@ -16,64 +211,159 @@ export const idlFactory = ({ IDL }) => {
     'owner' : IDL.Principal,
     'ai_hex' : IDL.Text,
   });
-  const TokenId = IDL.Variant({
-    'BOB' : IDL.Null,
-    'ICP' : IDL.Null,
-    'USDC' : IDL.Null,
-    'USDT' : IDL.Null,
+  const DustBook = IDL.Record({
+    'pool' : IDL.Vec(IDL.Tuple(IDL.Principal, IDL.Nat)),
+    'user' : IDL.Vec(IDL.Tuple(IDL.Tuple(IDL.Principal, TokenId), IDL.Nat)),
   });
-  const Event = IDL.Variant({
+  const EventArchiveRange = IDL.Record({
+    'end' : IDL.Nat64,
+    'canister_id' : IDL.Principal,
+    'start' : IDL.Nat64,
+  });
+  const EventV1 = IDL.Variant({
     'Withdraw' : IDL.Record({
       'ts' : IDL.Nat64,
       'who' : IDL.Text,
       'token' : TokenId,
-      'amount' : AmountE6,
+      'amount' : IDL.Nat,
     }),
     'Deposit' : IDL.Record({
       'ts' : IDL.Nat64,
       'who' : IDL.Text,
       'token' : TokenId,
-      'amount' : AmountE6,
+      'amount' : IDL.Nat,
     }),
     'Swap' : IDL.Record({
       'ts' : IDL.Nat64,
       'who' : IDL.Text,
-      'dx_e6' : AmountE6,
-      'dy_e6' : AmountE6,
+      'dx_e6' : IDL.Nat,
+      'dy_e6' : IDL.Nat,
     }),
     'RemoveLiq' : IDL.Record({
       'ts' : IDL.Nat64,
       'who' : IDL.Text,
-      'shares' : AmountE6,
-      'usdc' : AmountE6,
-      'usdt' : AmountE6,
+      'shares' : IDL.Nat,
+      'usdc' : IDL.Nat,
+      'usdt' : IDL.Nat,
     }),
     'AddLiq' : IDL.Record({
       'ts' : IDL.Nat64,
       'who' : IDL.Text,
-      'shares' : AmountE6,
-      'usdc' : AmountE6,
-      'usdt' : AmountE6,
+      'shares' : IDL.Nat,
+      'usdc' : IDL.Nat,
+      'usdt' : IDL.Nat,
     }),
   });
-  const PoolInfo = IDL.Record({
+  const PoolDrift = IDL.Record({
+    'token' : TokenId,
+    'pool' : IDL.Nat32,
+    'expected_e6' : IDL.Nat,
+    'live_e6' : IDL.Nat,
+    'drift_e6' : IDL.Int,
+  });
+  const DriftCheck = IDL.Record({
+    'ts' : IDL.Nat64,
+    'alert' : IDL.Bool,
+    'expected_usdc_e6' : IDL.Nat,
+    'expected_usdt_e6' : IDL.Nat,
+    'live_usdc_e6' : IDL.Nat,
+    'live_usdt_e6' : IDL.Nat,
+    'drift_usdc_e6' : IDL.Int,
+    'drift_usdt_e6' : IDL.Int,
+    'pools' : IDL.Opt(IDL.Vec(PoolDrift)),
+  });
+  const JobsState = IDL.Record({
+    'last_stats_roll' : IDL.Nat64,
+    'low_cycles' : IDL.Bool,
+    'last_drift' : IDL.Opt(DriftCheck),
+    'config' : JobsConfig,
+    'last_cycles_check' : IDL.Nat64,
+  });
+  const TokenReconcile = IDL.Record({
+    'token' : TokenId,
+    'diff_e6' : IDL.Int,
+    'pool' : IDL.Opt(IDL.Nat32),
+    'unclaimed_fees_e6' : IDL.Nat,
+    'live_pool_e6' : IDL.Nat,
+    'unexplained_e6' : IDL.Int,
+    'ledger' : IDL.Principal,
+    'ledger_fees_e6' : IDL.Nat,
+    'unaccounted_deposits_e6' : IDL.Nat,
+    'reserve_e6' : IDL.Nat,
+    'claimable_fees_e6' : IDL.Nat,
+    'user_balances_e6' : IDL.Nat,
+  });
+  const ReconcileReport = IDL.Record({
+    'ts' : IDL.Nat64,
+    'virtual_price_e6' : IDL.Nat,
+    'total_shares' : IDL.Nat,
+    'tokens' : IDL.Vec(TokenReconcile),
+  });
+  const PauseFlags = IDL.Record({
+    'add_liquidity' : IDL.Bool,
+    'withdraw' : IDL.Bool,
+    'swap' : IDL.Bool,
+    'deposit' : IDL.Bool,
+    'remove_liquidity' : IDL.Bool,
+    'claim_fees' : IDL.Bool,
+  });
+  const PauseChange = IDL.Record({
+    'ts' : IDL.Nat64,
+    'who' : IDL.Text,
+    'reason' : IDL.Text,
+  });
+  const PauseState = IDL.Record({
+    'flags' : PauseFlags,
+    'last_change' : IDL.Opt(PauseChange),
+    'auto' : AutoPauseConfig,
+    'pausers' : IDL.Vec(IDL.Principal),
+  });
+  const MetaPool = IDL.Record({
+    'base_virtual_price' : IDL.Nat,
+    'base' : IDL.Nat32,
+  });
+  const CryptoState = IDL.Record({
+    'virtual_price' : IDL.Nat,
+    'last_price' : IDL.Nat,
+    'not_adjusted' : IDL.Bool,
+    'price_scale' : IDL.Nat,
+    'last_ts' : IDL.Nat64,
+    'price_oracle' : IDL.Nat,
+    'xcp_profit' : IDL.Nat,
+  });
+  const CryptoPool = IDL.Record({
+    'state' : CryptoState,
+    'params' : CryptoParams,
+  });
+  const PoolState = IDL.Record({
+    'id' : IDL.Nat32,
     'a_amp' : IDL.Nat32,
+    'fee_vault' : IDL.Vec(IDL.Nat),
+    'kind' : PoolKind,
+    'meta' : IDL.Opt(MetaPool),
     'virtual_price_e6' : IDL.Nat,
+    'coins' : IDL.Vec(Coin),
+    'reserves' : IDL.Vec(IDL.Nat),
+    'offpeg_fee_multiplier' : IDL.Opt(IDL.Nat64),
     'fee_bps' : IDL.Nat16,
-    'total_shares' : AmountE6,
-    'reserve_usdc' : AmountE6,
-    'reserve_usdt' : AmountE6,
+    'total_shares' : IDL.Nat,
+    'created_ts' : IDL.Nat64,
+    'crypto' : IDL.Opt(CryptoPool),
+    'fee_exempt_shares' : IDL.Opt(IDL.Nat),
+    'fee_growth_e18' : IDL.Vec(IDL.Nat),
   });
-  const PoolReserves = IDL.Record({ 'usdc' : IDL.Nat, 'usdt' : IDL.Nat });
-  const RiskParams = IDL.Record({
-    'd_tolerance_e6' : IDL.Nat64,
-    'max_price_impact_bps' : IDL.Nat32,
+  const PoolInfo = IDL.Record({
+    'a_amp' : IDL.Nat32,
+    'virtual_price_e6' : IDL.Nat,
+    'fee_bps' : IDL.Nat16,
+    'total_shares' : IDL.Nat,
+    'reserve_usdc' : IDL.Nat,
+    'reserve_usdt' : IDL.Nat,
   });
-  const HourBucket = IDL.Record({
-    'fee_e6' : IDL.Nat,
-    'volume_e6' : IDL.Nat,
-    'swaps' : IDL.Nat32,
-    'ts_hour' : IDL.Nat64,
+  const PoolInfoCertified = IDL.Record({
+    'certificate' : IDL.Vec(IDL.Nat8),
+    'pool' : PoolInfo,
+    'witness' : IDL.Vec(IDL.Nat8),
   });
   const StatsSnapshot = IDL.Record({
     'now_sec' : IDL.Nat64,
@@ -85,65 +375,410 @@ export const idlFactory = ({ IDL }) => {
     'vol_24h_e6' : IDL.Nat,
     'fee_7d_e6' : IDL.Nat,
   });
+  const PriceOracle = IDL.Record({
+    'last_price_e6' : IDL.Nat,
+    'ema_price_e6' : IDL.Nat,
+    'config' : OracleConfig,
+    'last_ts' : IDL.Nat64,
+  });
+  const RiskParams = IDL.Record({
+    'd_tolerance_e6' : IDL.Nat64,
+    'max_price_impact_bps' : IDL.Nat32,
+  });
+  const HourBucket = IDL.Record({
+    'fee_e6' : IDL.Nat,
+    'virtual_price_e6' : IDL.Opt(IDL.Nat),
+    'volume_e6' : IDL.Nat,
+    'swaps' : IDL.Nat32,
+    'ts_hour' : IDL.Nat64,
+  });
   const TokenMeta = IDL.Record({
     'dec_usdc' : IDL.Nat8,
     'dec_usdt' : IDL.Nat8,
     'ckusdc' : IDL.Principal,
     'ckusdt' : IDL.Principal,
   });
-  const Position = IDL.Record({ 'shares' : AmountE6 });
   const SubBalance = IDL.Record({
     'id' : IDL.Text,
-    'bob' : AmountE6,
-    'icp' : AmountE6,
-    'usdc' : AmountE6,
-    'usdt' : AmountE6,
+    'bob' : IDL.Nat,
+    'icp' : IDL.Nat,
+    'usdc' : IDL.Nat,
+    'usdt' : IDL.Nat,
+  });
+  const HttpRequest = IDL.Record({
+    'url' : IDL.Text,
+    'method' : IDL.Text,
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    'certificate_version' : IDL.Opt(IDL.Nat16),
+  });
+  const HttpResponse = IDL.Record({
+    'body' : IDL.Vec(IDL.Nat8),
+    'headers' : IDL.Vec(IDL.Tuple(IDL.Text, IDL.Text)),
+    'upgrade' : IDL.Opt(IDL.Bool),
+    'status_code' : IDL.Nat16,
+  });
+  const MetadataValue = IDL.Variant({
+    'Int' : IDL.Int,
+    'Nat' : IDL.Nat,
+    'Blob' : IDL.Vec(IDL.Nat8),
+    'Text' : IDL.Text,
+  });
+  const StandardRecord = IDL.Record({ 'url' : IDL.Text, 'name' : IDL.Text });
+  const Icrc1TransferArg = IDL.Record({
+    'to' : Account,
+    'fee' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+  });
+  const TransferError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'BadBurn' : IDL.Record({ 'min_burn_amount' : IDL.Nat }),
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : TransferError });
+  const AllowanceArgs = IDL.Record({
+    'account' : Account,
+    'spender' : Account,
+  });
+  const Allowance = IDL.Record({
+    'allowance' : IDL.Nat,
+    'expires_at' : IDL.Opt(IDL.Nat64),
+  });
+  const ApproveArgs = IDL.Record({
+    'fee' : IDL.Opt(IDL.Nat),
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+    'expected_allowance' : IDL.Opt(IDL.Nat),
+    'expires_at' : IDL.Opt(IDL.Nat64),
+    'spender' : Account,
+  });
+  const ApproveError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'AllowanceChanged' : IDL.Record({ 'current_allowance' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'Expired' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result_1 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : ApproveError });
+  const TransferFromArgs = IDL.Record({
+    'to' : Account,
+    'fee' : IDL.Opt(IDL.Nat),
+    'spender_subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'from' : Account,
+    'memo' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+    'created_at_time' : IDL.Opt(IDL.Nat64),
+    'amount' : IDL.Nat,
+  });
+  const TransferFromError = IDL.Variant({
+    'GenericError' : IDL.Record({
+      'message' : IDL.Text,
+      'error_code' : IDL.Nat,
+    }),
+    'TemporarilyUnavailable' : IDL.Null,
+    'InsufficientAllowance' : IDL.Record({ 'allowance' : IDL.Nat }),
+    'BadBurn' : IDL.Record({ 'min_burn_amount' : IDL.Nat }),
+    'Duplicate' : IDL.Record({ 'duplicate_of' : IDL.Nat }),
+    'BadFee' : IDL.Record({ 'expected_fee' : IDL.Nat }),
+    'CreatedInFuture' : IDL.Record({ 'ledger_time' : IDL.Nat64 }),
+    'TooOld' : IDL.Null,
+    'InsufficientFunds' : IDL.Record({ 'balance' : IDL.Nat }),
+  });
+  const Result_2 = IDL.Variant({ 'Ok' : IDL.Nat, 'Err' : TransferFromError });
+  const GetArchivesArgs = IDL.Record({ 'from' : IDL.Opt(IDL.Principal) });
+  const Icrc3ArchiveInfo = IDL.Record({
+    'end' : IDL.Nat,
+    'canister_id' : IDL.Principal,
+    'start' : IDL.Nat,
+  });
+  const GetBlocksArgs = IDL.Record({ 'start' : IDL.Nat, 'length' : IDL.Nat });
+  Icrc3Value.fill(
+    IDL.Variant({
+      'Int' : IDL.Int,
+      'Map' : IDL.Vec(IDL.Tuple(IDL.Text, Icrc3Value)),
+      'Nat' : IDL.Nat,
+      'Blob' : IDL.Vec(IDL.Nat8),
+      'Text' : IDL.Text,
+      'Array' : IDL.Vec(Icrc3Value),
+    })
+  );
+  const BlockWithId = IDL.Record({ 'id' : IDL.Nat, 'block' : Icrc3Value });
+  const ArchivedBlocks = IDL.Record({
+    'args' : IDL.Vec(GetBlocksArgs),
+    'callback' : IDL.Func(
+        [IDL.Vec(GetBlocksArgs)],
+        [GetBlocksResult],
+        ['query'],
+      ),
+  });
+  GetBlocksResult.fill(
+    IDL.Record({
+      'log_length' : IDL.Nat,
+      'blocks' : IDL.Vec(BlockWithId),
+      'archived_blocks' : IDL.Vec(ArchivedBlocks),
+    })
+  );
+  const Icrc3DataCertificate = IDL.Record({
+    'certificate' : IDL.Vec(IDL.Nat8),
+    'hash_tree' : IDL.Vec(IDL.Nat8),
+  });
+  const SupportedBlockType = IDL.Record({
+    'url' : IDL.Text,
+    'block_type' : IDL.Text,
+  });
+  const EventKind = IDL.Variant({
+    'Withdraw' : IDL.Null,
+    'ClaimFee' : IDL.Null,
+    'Reconcile' : IDL.Null,
+    'Deposit' : IDL.Null,
+    'Swap' : IDL.Null,
+    'AdminAction' : IDL.Null,
+    'ParamChange' : IDL.Null,
+    'RemoveLiq' : IDL.Null,
+    'AddLiq' : IDL.Null,
+  });
+  const EventQuery = IDL.Record({
+    'who' : IDL.Opt(IDL.Principal),
+    'to_ts' : IDL.Opt(IDL.Nat64),
+    'descending' : IDL.Opt(IDL.Bool),
+    'from_id' : IDL.Opt(IDL.Nat64),
+    'from_ts' : IDL.Opt(IDL.Nat64),
+    'limit' : IDL.Opt(IDL.Nat32),
+    'kinds' : IDL.Opt(IDL.Vec(EventKind)),
+  });
+  const PoolSnapshot = IDL.Record({
+    'virtual_price_e6' : IDL.Nat,
+    'total_shares' : IDL.Nat,
+    'reserve_usdc' : IDL.Nat,
+    'reserve_usdt' : IDL.Nat,
+  });
+  const Event = IDL.Variant({
+    'Withdraw' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'token' : TokenId,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'ledger_block' : IDL.Opt(IDL.Nat),
+      'amount' : IDL.Nat,
+    }),
+    'ClaimFee' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'pool_id' : IDL.Opt(IDL.Nat32),
+      'usdc_e6' : IDL.Nat,
+      'ledger_blocks' : IDL.Vec(IDL.Nat),
+      'usdt_e6' : IDL.Nat,
+    }),
+    'Reconcile' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'who' : IDL.Text,
+      'after' : PoolSnapshot,
+      'detail' : IDL.Text,
+      'before' : PoolSnapshot,
+    }),
+    'Deposit' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'token' : TokenId,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'ledger_block' : IDL.Opt(IDL.Nat),
+      'amount' : IDL.Nat,
+    }),
+    'Swap' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'token_in' : Coin,
+      'fee_e6' : IDL.Nat,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'pool' : PoolSnapshot,
+      'token_out' : Coin,
+      'pool_id' : IDL.Opt(IDL.Nat32),
+      'ledger_blocks' : IDL.Vec(IDL.Nat),
+      'dx_e6' : IDL.Nat,
+      'dy_e6' : IDL.Nat,
+    }),
+    'AdminAction' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'who' : IDL.Text,
+      'action' : IDL.Text,
+      'detail' : IDL.Text,
+    }),
+    'ParamChange' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'new' : IDL.Text,
+      'old' : IDL.Text,
+      'who' : IDL.Text,
+      'param' : IDL.Text,
+    }),
+    'RemoveLiq' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'shares' : IDL.Nat,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'pool' : PoolSnapshot,
+      'usdc' : IDL.Nat,
+      'usdt' : IDL.Nat,
+      'pool_id' : IDL.Opt(IDL.Nat32),
+      'ledger_blocks' : IDL.Vec(IDL.Nat),
+    }),
+    'Legacy' : EventV1,
+    'AddLiq' : IDL.Record({
+      'ts' : IDL.Nat64,
+      'sub' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+      'who' : IDL.Text,
+      'shares' : IDL.Nat,
+      'icrc3_block' : IDL.Opt(IDL.Nat64),
+      'pool' : PoolSnapshot,
+      'usdc' : IDL.Nat,
+      'usdt' : IDL.Nat,
+      'pool_id' : IDL.Opt(IDL.Nat32),
+      'ledger_blocks' : IDL.Vec(IDL.Nat),
+    }),
+  });
+  const EventRecord = IDL.Record({ 'id' : IDL.Nat64, 'event' : Event });
+  const EventPage = IDL.Record({
+    'oldest_local_id' : IDL.Nat64,
+    'next' : IDL.Opt(IDL.Nat64),
+    'events' : IDL.Vec(EventRecord),
+    'next_id' : IDL.Nat64,
   });
   const QuoteOut = IDL.Record({
-    'fee_e6' : AmountE6,
+    'fee_rate_e10' : IDL.Nat64,
+    'fee_e6' : IDL.Nat,
     'price_e6' : IDL.Nat,
-    'dy_e6' : AmountE6,
+    'dy_e6' : IDL.Nat,
+  });
+  const QuoteResult = IDL.Variant({ 'ok' : QuoteOut, 'err' : Error });
+  const RouteHop = IDL.Record({
+    'token_in' : Coin,
+    'fee_rate_e10' : IDL.Nat64,
+    'fee_e6' : IDL.Nat,
+    'pool' : IDL.Nat32,
+    'token_out' : Coin,
+    'dx_e6' : IDL.Nat,
+    'dy_e6' : IDL.Nat,
   });
+  const RouteQuote = IDL.Record({
+    'price_impact_bps' : IDL.Nat32,
+    'spot_price_e6' : IDL.Nat,
+    'hops' : IDL.Vec(RouteHop),
+    'price_e6' : IDL.Nat,
+    'dx_e6' : IDL.Nat,
+    'dy_e6' : IDL.Nat,
+  });
+  const RouteResult = IDL.Variant({ 'ok' : RouteQuote, 'err' : Error });
   const SwapArgs = IDL.Record({
-    'min_dy_e6' : AmountE6,
-    'token_in' : TokenId,
+    'min_dy_e6' : IDL.Nat,
+    'token_in' : Coin,
+    'pool' : IDL.Opt(IDL.Nat32),
     'account' : Account,
-    'token_out' : TokenId,
-    'dx_e6' : AmountE6,
+    'token_out' : Coin,
+    'dx_e6' : IDL.Nat,
   });
+  const SwapOk = IDL.Record({ 'dy_e6' : IDL.Nat });
+  const StdResultSwap = IDL.Variant({ 'ok' : SwapOk, 'err' : Error });
+  const RouteSwapArgs = IDL.Record({
+    'max_hops' : IDL.Opt(IDL.Nat8),
+    'token_in' : Coin,
+    'min_out_e6' : IDL.Nat,
+    'account' : Account,
+    'token_out' : Coin,
+    'dx_e6' : IDL.Nat,
+  });
+  const TxResultNat = IDL.Variant({ 'ok' : IDL.Nat, 'err' : Error });
   return IDL.Service({
-    '__get_candid_interface_tmp_hack' : IDL.Func([], [IDL.Text], ['query']),
     'add_liquidity' : IDL.Func(
-        [Account, AmountE6, AmountE6],
-        [
-          IDL.Variant({
-            'ok' : IDL.Record({ 'shares' : AmountE6 }),
-            'err' : IDL.Text,
-          }),
-        ],
+        [Account, IDL.Nat, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [PositionResult],
+        [],
+      ),
+    'admin_apply_reconcile_correction' : IDL.Func(
+        [ReconcileCorrection],
+        [TextResult],
         [],
       ),
+    'admin_pause_all' : IDL.Func([IDL.Text], [TextResult], []),
     'admin_reconcile_from_internal' : IDL.Func([], [TextResult], []),
     'admin_reconcile_pool_from_live' : IDL.Func([], [TextResult], []),
+    'admin_record_solvency_report' : IDL.Func(
+        [SolvencyArgs],
+        [SolvencyReportResult],
+        [],
+      ),
+    'admin_run_reconcile_check' : IDL.Func([], [TextResult], []),
+    'admin_set_event_retention' : IDL.Func(
+        [IDL.Opt(IDL.Nat64), IDL.Opt(IDL.Principal)],
+        [TextResult],
+        [],
+      ),
+    'admin_set_icrc3_archive' : IDL.Func(
+        [IDL.Opt(IDL.Principal)],
+        [TextResult],
+        [],
+      ),
+    'admin_set_jobs' : IDL.Func([JobsConfig], [TextResult], []),
+    'admin_set_offpeg_fee_multiplier' : IDL.Func(
+        [IDL.Opt(IDL.Nat64)],
+        [TextResult],
+        [],
+      ),
+    'admin_set_oracle_config' : IDL.Func([OracleConfig], [TextResult], []),
+    'admin_set_pause' : IDL.Func(
+        [PauseOp, IDL.Bool, IDL.Text],
+        [TextResult],
+        [],
+      ),
+    'admin_set_pause_config' : IDL.Func(
+        [IDL.Vec(IDL.Principal), AutoPauseConfig],
+        [TextResult],
+        [],
+      ),
     'claim_fee' : IDL.Func(
-        [Account],
-        [
-          IDL.Variant({
-            'ok' : IDL.Record({ 'usdc' : AmountE6, 'usdt' : AmountE6 }),
-            'err' : IDL.Text,
-          }),
-        ],
+        [Account, IDL.Opt(IDL.Nat32)],
+        [TwoAmountsResult],
         [],
       ),
+    'create_pool' : IDL.Func([CreatePoolArgs], [CreatePoolResult], []),
+    'deposit' : IDL.Func([TokenId, IDL.Nat], [DepositResult], []),
     'ensure_allowance_for_user' : IDL.Func(
         [IDL.Principal, IDL.Nat],
         [IDL.Bool],
         [],
       ),
-    'get_available_balances' : IDL.Func([Account], [Available], ['query']),
+    'exchange_underlying' : IDL.Func(
+        [UnderlyingSwapArgs],
+        [UnderlyingResult],
+        [],
+      ),
+    'get_available_balances' : IDL.Func([Account], [TwoAmounts], ['query']),
     'get_available_balances_live_for' : IDL.Func(
         [IDL.Principal],
-        [Available],
+        [TwoAmounts],
         ['query'],
       ),
     'get_canister_principal' : IDL.Func([], [IDL.Principal], ['query']),
@@ -153,16 +788,49 @@ export const idlFactory = ({ IDL }) => {
         [DepositTarget],
         ['query'],
       ),
-    'get_events' : IDL.Func([IDL.Nat, IDL.Nat], [IDL.Vec(Event)], ['query']),
-    'get_events_latest' : IDL.Func([IDL.Nat], [IDL.Vec(Event)], ['query']),
-    'get_my_available_balances_live' : IDL.Func([], [Available], ['query']),
+    'get_dust' : IDL.Func([], [DustBook], ['query']),
+    'get_estimated_24h_volume' : IDL.Func([IDL.Nat32], [IDL.Nat], ['query']),
+    'get_event_archives' : IDL.Func(
+        [],
+        [IDL.Vec(EventArchiveRange)],
+        ['query'],
+      ),
+    'get_events' : IDL.Func([IDL.Nat, IDL.Nat], [IDL.Vec(EventV1)], ['query']),
+    'get_events_latest' : IDL.Func([IDL.Nat], [IDL.Vec(EventV1)], ['query']),
+    'get_jobs_status' : IDL.Func([], [JobsState], ['query']),
+    'get_last_reconcile_report' : IDL.Func(
+        [],
+        [IDL.Opt(ReconcileReport)],
+        ['query'],
+      ),
+    'get_my_available_balances_live' : IDL.Func([], [TwoAmounts], ['query']),
     'get_my_deposit_target' : IDL.Func([], [DepositTarget], ['query']),
     'get_my_icp_account_id_hex' : IDL.Func([], [IDL.Text], ['query']),
     'get_my_subaccount' : IDL.Func([], [IDL.Vec(IDL.Nat8)], ['query']),
+    'get_pause_state' : IDL.Func([], [PauseState], ['query']),
+    'get_pool' : IDL.Func([IDL.Nat32], [IDL.Opt(PoolState)], ['query']),
     'get_pool_account' : IDL.Func([IDL.Text], [Account], ['query']),
     'get_pool_info' : IDL.Func([], [PoolInfo], ['query']),
-    'get_pool_reserves_live' : IDL.Func([], [PoolReserves], ['query']),
+    'get_pool_info_certified' : IDL.Func(
+        [],
+        [IDL.Opt(PoolInfoCertified)],
+        ['query'],
+      ),
+    'get_pool_reserves_live' : IDL.Func([], [TwoAmounts], ['composite_query']),
+    'get_pool_stats' : IDL.Func(
+        [IDL.Nat32],
+        [IDL.Opt(StatsSnapshot)],
+        ['query'],
+      ),
+    'get_pools' : IDL.Func([], [IDL.Vec(PoolState)], ['query']),
+    'get_price_oracle' : IDL.Func([], [PriceOracle], ['query']),
     'get_risk_params' : IDL.Func([], [RiskParams], ['query']),
+    'get_solvency_report' : IDL.Func(
+        [SolvencyArgs],
+        [SolvencyReportResult],
+        ['composite_query'],
+      ),
+    'get_spot_price' : IDL.Func([], [IDL.Opt(IDL.Nat)], ['query']),
     'get_stats_series' : IDL.Func(
         [IDL.Nat32],
         [IDL.Vec(HourBucket)],
@@ -172,93 +840,128 @@ export const idlFactory = ({ IDL }) => {
     'get_token_meta' : IDL.Func([], [IDL.Opt(TokenMeta)], ['query']),
     'get_tvl_e6' : IDL.Func([], [IDL.Nat], ['query']),
     'get_unclaimed_fee' : IDL.Func(
-        [Account],
-        [IDL.Record({ 'usdc' : AmountE6, 'usdt' : AmountE6 })],
+        [Account, IDL.Opt(IDL.Nat32)],
+        [TwoAmounts],
         ['query'],
       ),
     'get_user_balances' : IDL.Func(
         [Account],
-        [
-          IDL.Record({
-            'bob' : AmountE6,
-            'icp' : AmountE6,
-            'usdc' : AmountE6,
-            'usdt' : AmountE6,
-          }),
-        ],
+        [IDL.Nat, IDL.Nat, IDL.Nat, IDL.Nat],
+        ['query'],
+      ),
+    'get_user_position' : IDL.Func(
+        [Account, IDL.Opt(IDL.Nat32)],
+        [Position],
         ['query'],
       ),
-    'get_user_position' : IDL.Func([Account], [Position], ['query']),
     'get_user_sub_balances' : IDL.Func(
         [Account],
         [IDL.Vec(SubBalance)],
         ['query'],
       ),
-    'quote' : IDL.Func([TokenId, TokenId, AmountE6], [QuoteOut], ['query']),
+    'http_request' : IDL.Func([HttpRequest], [HttpResponse], ['query']),
+    'http_request_update' : IDL.Func([HttpRequest], [HttpResponse], []),
+    'icrc1_balance_of' : IDL.Func([Account], [IDL.Nat], ['query']),
+    'icrc1_decimals' : IDL.Func([], [IDL.Nat8], ['query']),
+    'icrc1_fee' : IDL.Func([], [IDL.Nat], ['query']),
+    'icrc1_metadata' : IDL.Func(
+        [],
+        [IDL.Vec(IDL.Tuple(IDL.Text, MetadataValue))],
+        ['query'],
+      ),
+    'icrc1_minting_account' : IDL.Func([], [IDL.Opt(Account)], ['query']),
+    'icrc1_name' : IDL.Func([], [IDL.Text], ['query']),
+    'icrc1_supported_standards' : IDL.Func(
+        [],
+        [IDL.Vec(StandardRecord)],
+        ['query'],
+      ),
+    'icrc1_symbol' : IDL.Func([], [IDL.Text], ['query']),
+    'icrc1_total_supply' : IDL.Func([], [IDL.Nat], ['query']),
+    'icrc1_transfer' : IDL.Func([Icrc1TransferArg], [Result], []),
+    'icrc2_allowance' : IDL.Func([AllowanceArgs], [Allowance], ['query']),
+    'icrc2_approve' : IDL.Func([ApproveArgs], [Result_1], []),
+    'icrc2_transfer_from' : IDL.Func([TransferFromArgs], [Result_2], []),
+    'icrc3_get_archives' : IDL.Func(
+        [GetArchivesArgs],
+        [IDL.Vec(Icrc3ArchiveInfo)],
+        ['query'],
+      ),
+    'icrc3_get_blocks' : IDL.Func(
+        [IDL.Vec(GetBlocksArgs)],
+        [GetBlocksResult],
+        ['query'],
+      ),
+    'icrc3_get_tip_certificate' : IDL.Func(
+        [],
+        [IDL.Opt(Icrc3DataCertificate)],
+        ['query'],
+      ),
+    'icrc3_supported_block_types' : IDL.Func(
+        [],
+        [IDL.Vec(SupportedBlockType)],
+        ['query'],
+      ),
+    'query_events' : IDL.Func([EventQuery], [EventPage], ['query']),
+    'quote' : IDL.Func(
+        [Coin, Coin, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [QuoteResult],
+        ['query'],
+      ),
     'quote_exact_out' : IDL.Func(
-        [TokenId, TokenId, AmountE6],
-        [QuoteOut],
+        [Coin, Coin, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [QuoteResult],
         ['query'],
       ),
     'quote_live' : IDL.Func(
-        [TokenId, TokenId, AmountE6],
-        [QuoteOut],
+        [Coin, Coin, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [QuoteResult],
         ['query'],
       ),
     'quote_live_exact_out' : IDL.Func(
-        [TokenId, TokenId, AmountE6],
-        [QuoteOut],
+        [Coin, Coin, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [QuoteResult],
+        ['composite_query'],
+      ),
+    'quote_route' : IDL.Func(
+        [Coin, Coin, IDL.Nat, IDL.Opt(IDL.Nat8)],
+        [RouteResult],
         ['query'],
       ),
-    'refresh_available_for' : IDL.Func(
+    'quote_underlying' : IDL.Func(
+        [IDL.Nat32, Coin, Coin, IDL.Nat],
+        [UnderlyingResult],
+        ['query'],
+      ),
+    'refresh_available_for' : IDL.Func([IDL.Principal], [TextResult], []),
+    'refresh_available_for_blocking' : IDL.Func(
         [IDL.Principal],
-        [IDL.Variant({ 'ok' : IDL.Text, 'err' : IDL.Text })],
+        [TextResult],
         [],
       ),
     'remove_liquidity' : IDL.Func(
-        [Account, AmountE6],
-        [
-          IDL.Variant({
-            'ok' : IDL.Record({ 'usdc' : AmountE6, 'usdt' : AmountE6 }),
-            'err' : IDL.Text,
-          }),
-        ],
+        [Account, IDL.Nat, IDL.Opt(IDL.Nat32)],
+        [TwoAmountsResult],
         [],
       ),
+    'seed_pool_demo' : IDL.Func([IDL.Nat, IDL.Nat], [PoolInfo], []),
     'set_token_meta' : IDL.Func([TokenMeta], [], []),
-    'swap' : IDL.Func(
-        [SwapArgs],
-        [
-          IDL.Variant({
-            'ok' : IDL.Record({ 'dy_e6' : AmountE6 }),
-            'err' : IDL.Text,
-          }),
-        ],
-        [],
-      ),
-    'swap_live' : IDL.Func(
-        [SwapArgs],
-        [
-          IDL.Variant({
-            'ok' : IDL.Record({ 'dy_e6' : AmountE6 }),
-            'err' : IDL.Text,
-          }),
-        ],
-        [],
-      ),
+    'swap' : IDL.Func([SwapArgs], [StdResultSwap], []),
+    'swap_live' : IDL.Func([SwapArgs], [StdResultSwap], []),
+    'swap_route' : IDL.Func([RouteSwapArgs], [RouteResult], []),
     'transfer_from_pool_to_user_sub' : IDL.Func(
         [IDL.Text, IDL.Principal, IDL.Nat],
-        [IDL.Variant({ 'ok' : IDL.Nat, 'err' : IDL.Text })],
+        [TxResultNat],
         [],
       ),
     'transfer_from_user_sub_to_pool' : IDL.Func(
         [IDL.Text, IDL.Principal, IDL.Nat],
-        [IDL.Variant({ 'ok' : IDL.Nat, 'err' : IDL.Text })],
+        [TxResultNat],
         [],
       ),
     'withdraw_from_sub' : IDL.Func(
         [IDL.Text, Account, IDL.Nat],
-        [IDL.Variant({ 'ok' : IDL.Text, 'err' : IDL.Text })],
+        [TextResult],
         [],
       ),
   });